@@ -0,0 +1,774 @@
+// What the zip, tar and gzip files among the results hold, their largest members first, to tell
+// what's really inside an 80 GB backup.tar without unpacking it. Zip files are read from their
+// central directory and plain tar files header by header, skipping over the content. Compressed tar
+// files are inflated as they are read, which takes as long as their size
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::{EntryKind, FileEntry};
+
+// Members listed for every archive
+pub const PEEK_MEMBERS: usize = 10;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArchiveMember {
+    pub path: String,
+    // Uncompressed
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArchivePeek {
+    // zip, tar, tar.gz, gzip or git
+    pub format: &'static str,
+    // Every file in the archive
+    pub members: u64,
+    // The largest of them, biggest first
+    pub largest: Vec<ArchiveMember>,
+}
+
+impl ArchivePeek {
+    pub(crate) fn new(format: &'static str, mut members: Vec<ArchiveMember>) -> ArchivePeek {
+        let count = members.len() as u64;
+        members.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+        members.truncate(PEEK_MEMBERS);
+        ArchivePeek { format, members: count, largest: members }
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+// Zip
+
+const END_OF_DIRECTORY: u32 = 0x06054b50;
+const ZIP64_LOCATOR: u32 = 0x07064b50;
+const ZIP64_END_OF_DIRECTORY: u32 = 0x06064b50;
+const DIRECTORY_HEADER: u32 = 0x02014b50;
+// The end record and the longest comment it can be followed by
+const END_SEARCH: u64 = 22 + 0xffff;
+
+fn read_at(file: &mut File, offset: u64, length: usize) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![0; length];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+// Where the central directory starts, from the end record or its zip64 twin for the big ones
+fn central_directory(file: &mut File, size: u64) -> io::Result<u64> {
+    let tail_start = size.saturating_sub(END_SEARCH);
+    let tail = read_at(file, tail_start, (size - tail_start) as usize)?;
+    let end = (0..tail.len().saturating_sub(21)).rev()
+        .find(|offset| u32_at(&tail, *offset) == END_OF_DIRECTORY)
+        .ok_or_else(|| invalid("no end of central directory"))?;
+    let offset = u32_at(&tail, end + 16);
+    if offset != u32::MAX {
+        return Ok(offset as u64);
+    }
+    if end < 20 || u32_at(&tail, end - 20) != ZIP64_LOCATOR {
+        return Err(invalid("no zip64 end of central directory"));
+    }
+    let zip64_end = read_at(file, u64_at(&tail, end - 20 + 8), 56)?;
+    if u32_at(&zip64_end, 0) != ZIP64_END_OF_DIRECTORY {
+        return Err(invalid("no zip64 end of central directory"));
+    }
+    Ok(u64_at(&zip64_end, 48))
+}
+
+fn peek_zip(path: &Path, size: u64) -> io::Result<ArchivePeek> {
+    let mut file = File::open(path)?;
+    let start = central_directory(&mut file, size)?;
+    file.seek(SeekFrom::Start(start))?;
+    let mut reader = BufReader::new(file);
+    let mut members = Vec::new();
+    let mut header = [0u8; 46];
+    loop {
+        reader.read_exact(&mut header[..4])?;
+        if u32_at(&header, 0) != DIRECTORY_HEADER {
+            break;
+        }
+        reader.read_exact(&mut header[4..])?;
+        let mut variable = vec![0; u16_at(&header, 28) as usize + u16_at(&header, 30) as usize + u16_at(&header, 32) as usize];
+        reader.read_exact(&mut variable)?;
+        let (name, mut extra) = variable.split_at(u16_at(&header, 28) as usize);
+        extra = &extra[..u16_at(&header, 30) as usize];
+        let mut member_size = u32_at(&header, 24) as u64;
+        // Sizes past 4 GiB are in the zip64 extra field, the uncompressed one first
+        if member_size == u32::MAX as u64 {
+            while extra.len() >= 4 {
+                let (id, length) = (u16_at(extra, 0), u16_at(extra, 2) as usize);
+                if id == 1 && length >= 8 && extra.len() >= 12 {
+                    member_size = u64_at(extra, 4);
+                }
+                extra = &extra[(4 + length).min(extra.len())..];
+            }
+        }
+        let name = String::from_utf8_lossy(name).into_owned();
+        if !name.ends_with('/') {
+            members.push(ArchiveMember { path: name, size: member_size });
+        }
+    }
+    Ok(ArchivePeek::new("zip", members))
+}
+
+// Tar
+
+const TAR_BLOCK: usize = 512;
+// Long names and pax headers are read whole, past this they are taken for garbage
+const TAR_HEADER_DATA: u64 = 1024 * 1024;
+
+// Readers the content of members can be skipped over in, by seeking or by reading through
+trait Skip: Read {
+    fn skip(&mut self, bytes: u64) -> io::Result<()>;
+}
+
+impl Skip for BufReader<File> {
+    fn skip(&mut self, bytes: u64) -> io::Result<()> {
+        self.seek_relative(i64::try_from(bytes).map_err(|_| invalid("member too large"))?)
+    }
+}
+
+impl<R: Read> Skip for Inflate<R> {
+    fn skip(&mut self, bytes: u64) -> io::Result<()> {
+        let skipped = io::copy(&mut self.take(bytes), &mut io::sink())?;
+        if skipped < bytes { Err(io::ErrorKind::UnexpectedEof.into()) } else { Ok(()) }
+    }
+}
+
+fn is_tar_header(block: &[u8]) -> bool {
+    block.len() >= TAR_BLOCK && &block[257..262] == b"ustar"
+}
+
+// A text field, cut at its first NUL
+fn tar_text(field: &[u8]) -> String {
+    let end = field.iter().position(|byte| *byte == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+// Octal, or big endian base 256 when the top bit is set for sizes past 8 GiB
+fn tar_number(field: &[u8]) -> u64 {
+    if field[0] & 0x80 != 0 {
+        return field[1..].iter().fold(0, |number, byte| (number << 8) | *byte as u64);
+    }
+    let text = tar_text(field);
+    u64::from_str_radix(text.trim(), 8).unwrap_or(0)
+}
+
+// The path and size records of a pax header, "<length> <key>=<value>\n" each
+fn pax_records(data: &[u8]) -> (Option<String>, Option<u64>) {
+    let (mut path, mut size) = (None, None);
+    for record in String::from_utf8_lossy(data).lines() {
+        if let Some((_, pair)) = record.split_once(' ') {
+            match pair.split_once('=') {
+                Some(("path", value)) => path = Some(value.to_string()),
+                Some(("size", value)) => size = value.parse().ok(),
+                _ => {}
+            }
+        }
+    }
+    (path, size)
+}
+
+fn read_tar(reader: &mut impl Skip) -> io::Result<Vec<ArchiveMember>> {
+    let mut members = Vec::new();
+    let mut block = [0u8; TAR_BLOCK];
+    // What a GNU long name or a pax header said about the next member
+    let (mut next_path, mut next_size): (Option<String>, Option<u64>) = (None, None);
+    loop {
+        // Archives cut short list what they hold up to there
+        match reader.read_exact(&mut block) {
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+            result => result?,
+        }
+        if block.iter().all(|byte| *byte == 0) {
+            break;
+        }
+        // A pax size stands for the header one, too small for the member
+        let size = match block[156] {
+            b'L' | b'x' | b'g' => tar_number(&block[124..136]),
+            _ => next_size.take().unwrap_or_else(|| tar_number(&block[124..136])),
+        };
+        let padded = size.checked_next_multiple_of(TAR_BLOCK as u64).ok_or_else(|| invalid("member too large"))?;
+        match block[156] {
+            b'L' | b'x' if size > TAR_HEADER_DATA => return Err(invalid("header data too large")),
+            b'L' | b'x' => {
+                let mut data = vec![0; size as usize];
+                reader.read_exact(&mut data)?;
+                reader.skip(padded - size)?;
+                if block[156] == b'L' {
+                    next_path = Some(tar_text(&data));
+                } else {
+                    let (path, size) = pax_records(&data);
+                    next_path = path.or(next_path);
+                    next_size = size.or(next_size);
+                }
+                continue;
+            }
+            // Regular and contiguous files
+            b'0' | b'\0' | b'7' => {
+                let mut name = tar_text(&block[0..100]);
+                let prefix = tar_text(&block[345..500]);
+                if is_tar_header(&block) && !prefix.is_empty() {
+                    name = format!("{}/{}", prefix, name);
+                }
+                let path = next_path.take().unwrap_or(name);
+                members.push(ArchiveMember { path, size });
+            }
+            _ => next_path = None,
+        }
+        reader.skip(padded)?;
+    }
+    Ok(members)
+}
+
+// Gzip and deflate, RFC 1952 and RFC 1951
+
+const WINDOW_SIZE: usize = 32 * 1024;
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+const FIXED_DISTANCES: [u8; 30] = [5; 30];
+
+// A canonical Huffman code, by the number of codes of every length and the symbols in code order
+#[derive(Default)]
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0u16; 16];
+        for length in lengths {
+            counts[*length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0u16; 16];
+        for length in 1..15 {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, length) in lengths.iter().enumerate().filter(|(_, length)| **length > 0) {
+            symbols[offsets[*length as usize] as usize] = symbol as u16;
+            offsets[*length as usize] += 1;
+        }
+        Huffman { counts, symbols }
+    }
+
+    fn fixed_literals() -> Huffman {
+        let lengths: Vec<u8> = (0..288).map(|symbol| match symbol {
+            0..=143 => 8,
+            144..=255 => 9,
+            256..=279 => 7,
+            _ => 8,
+        }).collect();
+        Huffman::new(&lengths)
+    }
+}
+
+// Bits from the least significant one of every byte, as deflate packs them
+struct Bits<R: Read> {
+    input: BufReader<R>,
+    buffer: u64,
+    count: u32,
+}
+
+impl<R: Read> Bits<R> {
+    // The next byte when there is one, only at a byte boundary
+    fn next_byte(&mut self) -> io::Result<Option<u8>> {
+        if self.count >= 8 {
+            return self.bits(8).map(|byte| Some(byte as u8));
+        }
+        let mut byte = [0u8];
+        match self.input.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+
+    fn byte(&mut self) -> io::Result<u8> {
+        self.next_byte()?.ok_or_else(|| io::ErrorKind::UnexpectedEof.into())
+    }
+
+    fn bits(&mut self, wanted: u32) -> io::Result<u32> {
+        while self.count < wanted {
+            let mut byte = [0u8];
+            self.input.read_exact(&mut byte)?;
+            self.buffer |= (byte[0] as u64) << self.count;
+            self.count += 8;
+        }
+        let value = (self.buffer & ((1 << wanted) - 1)) as u32;
+        self.buffer >>= wanted;
+        self.count -= wanted;
+        Ok(value)
+    }
+
+    fn align(&mut self) {
+        let dropped = self.count % 8;
+        self.buffer >>= dropped;
+        self.count -= dropped;
+    }
+
+    fn decode(&mut self, huffman: &Huffman) -> io::Result<u16> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for length in 1..16 {
+            code |= self.bits(1)? as i32;
+            let count = huffman.counts[length] as i32;
+            if code - first < count {
+                return huffman.symbols.get((index + code - first) as usize).copied().ok_or_else(|| invalid("invalid Huffman code"));
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid("invalid Huffman code"))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    // Before the header of a gzip member
+    Member,
+    // Before the two bytes starting a zlib stream
+    Zlib,
+    // Before the header of a deflate block
+    Block,
+    Stored(u32),
+    Codes,
+    // After the last block of a member, before its trailer
+    Trailer,
+    Done,
+}
+
+// A gzip stream inflated as it's read, members following each other read as one, or a zlib one
+pub(crate) struct Inflate<R: Read> {
+    bits: Bits<R>,
+    state: State,
+    last_block: bool,
+    literals: Huffman,
+    distances: Huffman,
+    window: Vec<u8>,
+    position: usize,
+    pending: Vec<u8>,
+    pending_read: usize,
+    members: u64,
+    zlib: bool,
+    // The original name in the header of the first member
+    name: Option<String>,
+}
+
+impl<R: Read> Inflate<R> {
+    fn new(input: R) -> Inflate<R> {
+        Inflate {
+            bits: Bits { input: BufReader::new(input), buffer: 0, count: 0 },
+            state: State::Member,
+            last_block: false,
+            literals: Huffman::default(),
+            distances: Huffman::default(),
+            window: vec![0; WINDOW_SIZE],
+            position: 0,
+            pending: Vec::with_capacity(WINDOW_SIZE),
+            pending_read: 0,
+            members: 0,
+            zlib: false,
+            name: None,
+        }
+    }
+
+    // A zlib stream, as git keeps its loose objects, the checksum at its end left unread
+    pub(crate) fn zlib(input: R) -> Inflate<R> {
+        Inflate { state: State::Zlib, zlib: true, ..Inflate::new(input) }
+    }
+
+    fn output(&mut self, byte: u8) {
+        self.window[self.position % WINDOW_SIZE] = byte;
+        self.position += 1;
+        self.pending.push(byte);
+    }
+
+    // The header of the next member, false at the end of the stream
+    fn member_header(&mut self) -> io::Result<bool> {
+        let Some(first) = self.bits.next_byte()? else {
+            if self.members == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            return Ok(false);
+        };
+        let mut header = [first, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        for byte in &mut header[1..] {
+            *byte = self.bits.byte()?;
+        }
+        if header[..3] != [0x1f, 0x8b, 8] {
+            // Padding after the last member is left alone, like gzip does
+            if self.members > 0 {
+                return Ok(false);
+            }
+            return Err(invalid("not a gzip file"));
+        }
+        let flags = header[3];
+        if flags & 4 != 0 {
+            let length = self.bits.byte()? as usize | (self.bits.byte()? as usize) << 8;
+            for _ in 0..length {
+                self.bits.byte()?;
+            }
+        }
+        for flag in [8, 16] {
+            if flags & flag != 0 {
+                let mut text = Vec::new();
+                loop {
+                    match self.bits.byte()? {
+                        0 => break,
+                        byte => text.push(byte),
+                    }
+                }
+                if flag == 8 && self.members == 0 {
+                    self.name = Some(String::from_utf8_lossy(&text).into_owned());
+                }
+            }
+        }
+        if flags & 2 != 0 {
+            self.bits.byte()?;
+            self.bits.byte()?;
+        }
+        self.members += 1;
+        self.last_block = false;
+        Ok(true)
+    }
+
+    fn block_header(&mut self) -> io::Result<State> {
+        if self.last_block {
+            return Ok(State::Trailer);
+        }
+        self.last_block = self.bits.bits(1)? == 1;
+        match self.bits.bits(2)? {
+            0 => {
+                self.bits.align();
+                let length = self.bits.bits(16)?;
+                let complement = self.bits.bits(16)?;
+                if length != !complement & 0xffff {
+                    return Err(invalid("invalid stored block length"));
+                }
+                Ok(State::Stored(length))
+            }
+            1 => {
+                self.literals = Huffman::fixed_literals();
+                self.distances = Huffman::new(&FIXED_DISTANCES);
+                Ok(State::Codes)
+            }
+            2 => {
+                self.dynamic_codes()?;
+                Ok(State::Codes)
+            }
+            _ => Err(invalid("invalid block type")),
+        }
+    }
+
+    fn dynamic_codes(&mut self) -> io::Result<()> {
+        let literal_count = self.bits.bits(5)? as usize + 257;
+        let distance_count = self.bits.bits(5)? as usize + 1;
+        let code_length_count = self.bits.bits(4)? as usize + 4;
+        let mut code_lengths = [0u8; 19];
+        for index in CODE_LENGTH_ORDER.iter().take(code_length_count) {
+            code_lengths[*index] = self.bits.bits(3)? as u8;
+        }
+        let code_lengths = Huffman::new(&code_lengths);
+        let mut lengths: Vec<u8> = Vec::with_capacity(literal_count + distance_count);
+        while lengths.len() < literal_count + distance_count {
+            let (length, repeat) = match self.bits.decode(&code_lengths)? {
+                symbol @ 0..=15 => (symbol as u8, 1),
+                16 => (*lengths.last().ok_or_else(|| invalid("repeat without a length"))?, 3 + self.bits.bits(2)?),
+                17 => (0, 3 + self.bits.bits(3)?),
+                _ => (0, 11 + self.bits.bits(7)?),
+            };
+            lengths.extend(std::iter::repeat_n(length, repeat as usize));
+        }
+        if lengths.len() > literal_count + distance_count {
+            return Err(invalid("too many code lengths"));
+        }
+        self.literals = Huffman::new(&lengths[..literal_count]);
+        self.distances = Huffman::new(&lengths[literal_count..]);
+        Ok(())
+    }
+
+    // Inflate about a window more, false at the end of the stream
+    fn fill(&mut self) -> io::Result<bool> {
+        self.pending.clear();
+        self.pending_read = 0;
+        while self.pending.len() < WINDOW_SIZE {
+            self.state = match self.state {
+                State::Done => break,
+                State::Member => if self.member_header()? { State::Block } else { State::Done },
+                State::Zlib => {
+                    let (method, flags) = (self.bits.byte()?, self.bits.byte()?);
+                    if method & 0x0f != 8 || flags & 0x20 != 0 || !(method as u16 * 256 + flags as u16).is_multiple_of(31) {
+                        return Err(invalid("not a zlib stream"));
+                    }
+                    State::Block
+                }
+                State::Block => self.block_header()?,
+                State::Stored(0) => State::Block,
+                State::Stored(remaining) => {
+                    let byte = self.bits.bits(8)? as u8;
+                    self.output(byte);
+                    State::Stored(remaining - 1)
+                }
+                State::Codes => match self.bits.decode(&self.literals)? {
+                    symbol @ 0..=255 => {
+                        self.output(symbol as u8);
+                        State::Codes
+                    }
+                    256 => State::Block,
+                    symbol => {
+                        let index = symbol as usize - 257;
+                        if index >= LENGTH_BASE.len() {
+                            return Err(invalid("invalid length code"));
+                        }
+                        let length = LENGTH_BASE[index] as usize + self.bits.bits(LENGTH_EXTRA[index] as u32)? as usize;
+                        let index = self.bits.decode(&self.distances)? as usize;
+                        if index >= DISTANCE_BASE.len() {
+                            return Err(invalid("invalid distance code"));
+                        }
+                        let distance = DISTANCE_BASE[index] as usize + self.bits.bits(DISTANCE_EXTRA[index] as u32)? as usize;
+                        if distance > self.position {
+                            return Err(invalid("distance past the start of the stream"));
+                        }
+                        for _ in 0..length {
+                            let byte = self.window[(self.position - distance) % WINDOW_SIZE];
+                            self.output(byte);
+                        }
+                        State::Codes
+                    }
+                },
+                State::Trailer if self.zlib => State::Done,
+                // The CRC and the length, the data is only listed, not checked
+                State::Trailer => {
+                    self.bits.align();
+                    for _ in 0..8 {
+                        self.bits.byte()?;
+                    }
+                    State::Member
+                }
+            };
+        }
+        Ok(!self.pending.is_empty())
+    }
+}
+
+impl<R: Read> Read for Inflate<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        if self.pending_read == self.pending.len() && !self.fill()? {
+            return Ok(0);
+        }
+        let count = buffer.len().min(self.pending.len() - self.pending_read);
+        buffer[..count].copy_from_slice(&self.pending[self.pending_read..self.pending_read + count]);
+        self.pending_read += count;
+        Ok(count)
+    }
+}
+
+fn peek_gzip(path: &Path, size: u64) -> io::Result<ArchivePeek> {
+    // Compressed tar files are told apart by the header their content starts with
+    let mut head = Vec::with_capacity(TAR_BLOCK);
+    let mut inflate = Inflate::new(File::open(path)?);
+    inflate.by_ref().take(TAR_BLOCK as u64).read_to_end(&mut head)?;
+    if is_tar_header(&head) {
+        let members = read_tar(&mut Inflate::new(File::open(path)?))?;
+        return Ok(ArchivePeek::new("tar.gz", members));
+    }
+    // A single file, its length as the trailer keeps it, modulo 4 GiB
+    let mut file = File::open(path)?;
+    let trailer = read_at(&mut file, size.saturating_sub(4), 4)?;
+    let name = inflate.name.clone().unwrap_or_else(|| {
+        let stem = path.file_stem().map(|stem| stem.to_string_lossy().into_owned());
+        stem.unwrap_or_default()
+    });
+    Ok(ArchivePeek::new("gzip", vec![ArchiveMember { path: name, size: u32_at(&trailer, 0) as u64 }]))
+}
+
+// The members of the file when it's an archive, none for any other file
+pub fn peek(path: &Path, size: u64) -> io::Result<Option<ArchivePeek>> {
+    let mut head = Vec::with_capacity(TAR_BLOCK);
+    File::open(path)?.take(TAR_BLOCK as u64).read_to_end(&mut head)?;
+    if head.starts_with(b"PK\x03\x04") || head.starts_with(b"PK\x05\x06") {
+        return peek_zip(path, size).map(Some);
+    }
+    if is_tar_header(&head) {
+        let members = read_tar(&mut BufReader::new(File::open(path)?))?;
+        return Ok(Some(ArchivePeek::new("tar", members)));
+    }
+    if head.starts_with(&[0x1f, 0x8b]) {
+        return peek_gzip(path, size).map(Some);
+    }
+    Ok(None)
+}
+
+// Fill in the members of the archives, the ones that can't be read are left without
+pub fn peek_archives(entries: &mut [FileEntry]) {
+    for entry in entries.iter_mut().filter(|entry| entry.kind == EntryKind::File) {
+        let path = Path::new(&entry.path);
+        let size = path.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+        entry.archive = peek(path, size).ok().flatten();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    fn zip_entry(name: &str, size: u32, extra: &[u8]) -> Vec<u8> {
+        let mut header = vec![0; 46];
+        header[..4].copy_from_slice(&DIRECTORY_HEADER.to_le_bytes());
+        header[24..28].copy_from_slice(&size.to_le_bytes());
+        header[28..30].copy_from_slice(&(name.len() as u16).to_le_bytes());
+        header[30..32].copy_from_slice(&(extra.len() as u16).to_le_bytes());
+        [&header, name.as_bytes(), extra].concat()
+    }
+
+    // A local header to be told for a zip, the central directory, then its end record claiming
+    // as many entries as asked
+    fn zip(entries: &[Vec<u8>], count: u16, offset: u32) -> Vec<u8> {
+        let mut data = [&b"PK\x03\x04"[..], &[0; 26]].concat();
+        let start = data.len() as u32;
+        data.extend(entries.concat());
+        let mut end = vec![0; 22];
+        end[..4].copy_from_slice(&END_OF_DIRECTORY.to_le_bytes());
+        end[8..10].copy_from_slice(&count.to_le_bytes());
+        end[10..12].copy_from_slice(&count.to_le_bytes());
+        end[16..20].copy_from_slice(&(if offset == 0 { start } else { offset }).to_le_bytes());
+        data.extend(end);
+        data
+    }
+
+    // The zip64 end record and its locator slipped in before the end record
+    fn zip64(mut data: Vec<u8>, directory: u64, record_at: Option<u64>) -> Vec<u8> {
+        let end = data.split_off(data.len() - 22);
+        let record_offset = data.len() as u64;
+        let mut record = vec![0; 56];
+        record[..4].copy_from_slice(&ZIP64_END_OF_DIRECTORY.to_le_bytes());
+        record[24..32].copy_from_slice(&u64::MAX.to_le_bytes());
+        record[48..56].copy_from_slice(&directory.to_le_bytes());
+        let mut locator = vec![0; 20];
+        locator[..4].copy_from_slice(&ZIP64_LOCATOR.to_le_bytes());
+        locator[8..16].copy_from_slice(&record_at.unwrap_or(record_offset).to_le_bytes());
+        [data, record, locator, end].concat()
+    }
+
+    fn tar_header(name: &str, kind: u8, size: &[u8]) -> Vec<u8> {
+        let mut block = vec![0; TAR_BLOCK];
+        block[..name.len()].copy_from_slice(name.as_bytes());
+        block[124..124 + size.len()].copy_from_slice(size);
+        block[156] = kind;
+        block[257..262].copy_from_slice(b"ustar");
+        block
+    }
+
+    fn tar_member(name: &str, kind: u8, content: &[u8]) -> Vec<u8> {
+        let mut data = tar_header(name, kind, format!("{:011o}", content.len()).as_bytes());
+        data.extend(content);
+        data.resize(data.len().next_multiple_of(TAR_BLOCK), 0);
+        data
+    }
+
+    fn members(peek: &ArchivePeek) -> Vec<(&str, u64)> {
+        peek.largest.iter().map(|member| (member.path.as_str(), member.size)).collect()
+    }
+
+    // Archives are read as they come from the results, so whatever their headers claim has to end
+    // in an error or a listing, never a panic, a loop or an allocation of the claimed size
+    #[test]
+    fn reads_truncated_and_hostile_archives() {
+        let dir = env::temp_dir().join(format!("fatass-archive-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("archive");
+        let peek_data = |data: &[u8]| {
+            fs::write(&path, data).unwrap();
+            peek(&path, data.len() as u64)
+        };
+
+        // The entry count of the end record isn't trusted, the directory is read up to its end
+        let zip64_size = [&1u16.to_le_bytes()[..], &8u16.to_le_bytes(), &(5u64 << 30).to_le_bytes()].concat();
+        let entries = vec![zip_entry("big.iso", u32::MAX, &zip64_size), zip_entry("dir/", 0, &[]), zip_entry("a.txt", 10, &[])];
+        let valid = zip(&entries, u16::MAX, 0);
+        let listed = peek_data(&valid).unwrap().unwrap();
+        assert_eq!((listed.format, listed.members), ("zip", 2));
+        assert_eq!(members(&listed), [("big.iso", 5 << 30), ("a.txt", 10)]);
+        assert_eq!(peek_data(&zip(&entries, 0, 0)).unwrap().unwrap().members, 2);
+        // A zip64 size field cut short leaves the size as it was
+        let cut_extra = zip(&[zip_entry("odd", u32::MAX, &[1, 0, 8, 0, 1, 2])], 1, 0);
+        assert_eq!(members(&peek_data(&cut_extra).unwrap().unwrap()), [("odd", u32::MAX as u64)]);
+
+        // The directory of the big ones comes from the zip64 end record
+        let big = zip64(zip(&entries, u16::MAX, u32::MAX), 30, None);
+        assert_eq!(peek_data(&big).unwrap().unwrap().members, 2);
+        for data in [
+            // No end record, or a directory past the end or cut in the middle of an entry
+            [&b"PK\x03\x04"[..], &[0; 100]].concat(),
+            zip(&entries, 1, 1 << 30),
+            [&valid[..60], &valid[valid.len() - 22..]].concat(),
+            // A zip64 offset without its records, or with them pointing anywhere
+            zip(&entries, 1, u32::MAX),
+            zip64(zip(&entries, 1, u32::MAX), 30, Some(u64::MAX - 10)),
+            zip64(zip(&entries, 1, u32::MAX), 30, Some(3)),
+            zip64(zip(&entries, 1, u32::MAX), u64::MAX, None),
+        ] {
+            assert!(peek_data(&data).is_err());
+        }
+
+        // Plain members, a GNU long name and a pax size standing for the header one
+        let long_name = "n".repeat(300);
+        let tar = [
+            tar_member("a.txt", b'0', b"hello"),
+            tar_member("././@LongLink", b'L', long_name.as_bytes()),
+            tar_member("short", b'0', &[1; 600]),
+            tar_member("PaxHeader", b'x', b"16 size=9000000\n"),
+            tar_header("huge.bin", b'0', b"00000000001"),
+        ].concat();
+        let listed = peek_data(&tar).unwrap().unwrap();
+        assert_eq!((listed.format, listed.members), ("tar", 3));
+        // The content of the last one is missing, what came before is listed
+        assert_eq!(members(&listed), [("huge.bin", 9_000_000), (long_name.as_str(), 600), ("a.txt", 5)]);
+        // A size that isn't a number counts as empty
+        let mut garbage = tar_member("garbage", b'0', &[]);
+        garbage[124..136].fill(b'z');
+        assert_eq!(members(&peek_data(&garbage).unwrap().unwrap()), [("garbage", 0)]);
+
+        let mut base256 = [0xff; 12];
+        let negative = tar_header("negative", b'0', &base256);
+        base256[..4].copy_from_slice(&[0x80, 0, 0, 0]);
+        base256[4] = 0x80;
+        let past_i64 = tar_header("past", b'0', &base256);
+        for data in [
+            // Negative in base 256, past what a seek can take, or long names of gigabytes
+            negative,
+            past_i64,
+            tar_header("././@LongLink", b'L', &[0xff; 12]),
+            tar_header("PaxHeader", b'x', b"77777777777"),
+            [tar_member("PaxHeader", b'x', b"30 size=18446744073709551615\n"), tar_header("max", b'0', b"0")].concat(),
+        ] {
+            assert_eq!(peek_data(&data).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
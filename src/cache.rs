@@ -0,0 +1,388 @@
+// What the last scans found in every directory, so the next ones only read again the directories
+// whose mtime changed. Adding, removing or renaming an entry updates the mtime of its directory,
+// writing into an existing file doesn't: only the names are cached, the entries are always stat'ed
+// again so a file growing in place shows its current size. Trees past STREAMING_THRESHOLD files
+// aren't cached at all, their listings would take as much memory as streaming them saves.
+//
+// The cache is a compact binary file: a magic, then one record per directory with its mtime and
+// the names it held, numbers written as LEB128 varints.
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufWriter, Write};
+use std::path::{self, Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::errors::ScanErrors;
+use crate::filter::Filters;
+use crate::hardlinks::file_id;
+use crate::ignore::Ignores;
+use crate::interrupt::interrupted;
+use crate::progress;
+use crate::scanner::STREAMING_THRESHOLD;
+use crate::trace;
+
+const MAGIC: &[u8] = b"FATASSC3";
+
+#[derive(Debug, Clone, PartialEq)]
+struct DirRecord {
+    mtime: Option<SystemTime>,
+    names: Vec<PathBuf>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct ScanCache {
+    dirs: HashMap<PathBuf, DirRecord>,
+    // Directories listed by this run, the records of the others below the walked roots are stale
+    visited: HashSet<PathBuf>,
+    roots: Vec<PathBuf>,
+    // Names listed by this run, past STREAMING_THRESHOLD the walked roots are left out of the cache
+    listed: usize,
+    too_big: bool,
+}
+
+// ~/.cache/fatass/scan.cache, or under $XDG_CACHE_HOME or %LOCALAPPDATA% when set
+pub fn default_path() -> Option<PathBuf> {
+    let cache_dir = env::var_os("XDG_CACHE_HOME")
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| if cfg!(windows) { env::var_os("LOCALAPPDATA").map(PathBuf::from) } else { None })
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))?;
+    Some(cache_dir.join("fatass").join("scan.cache"))
+}
+
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn path_bytes(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().as_bytes().to_vec()
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).to_string())
+}
+
+struct Encoder<W: Write> {
+    out: W,
+}
+
+impl<W: Write> Encoder<W> {
+    fn varint(&mut self, mut value: u64) -> io::Result<()> {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                return self.out.write_all(&[byte]);
+            }
+            self.out.write_all(&[byte | 0x80])?;
+        }
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.varint(bytes.len() as u64)?;
+        self.out.write_all(bytes)
+    }
+
+    // 0 for none, seconds since the epoch plus one otherwise, then the nanoseconds
+    fn time(&mut self, time: Option<SystemTime>) -> io::Result<()> {
+        match time.and_then(|time| time.duration_since(UNIX_EPOCH).ok()) {
+            Some(duration) => {
+                self.varint(duration.as_secs() + 1)?;
+                self.varint(duration.subsec_nanos() as u64)
+            }
+            None => self.varint(0),
+        }
+    }
+}
+
+struct Decoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn varint(&mut self) -> Option<u64> {
+        let mut value: u64 = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = *self.data.get(self.pos)?;
+            self.pos += 1;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    fn bytes(&mut self) -> Option<&'a [u8]> {
+        let len = self.varint()? as usize;
+        let bytes = self.data.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    fn time(&mut self) -> Option<Option<SystemTime>> {
+        match self.varint()? {
+            0 => Some(None),
+            seconds => {
+                let nanos = self.varint()?;
+                Some(Some(UNIX_EPOCH + Duration::new(seconds - 1, nanos.min(999_999_999) as u32)))
+            }
+        }
+    }
+}
+
+impl ScanCache {
+    // An unreadable or outdated cache is the same as none
+    pub(crate) fn load(path: &Path) -> ScanCache {
+        let mut cache = ScanCache::default();
+        let Ok(data) = fs::read(path) else { return cache };
+        if !data.starts_with(MAGIC) {
+            return cache;
+        }
+        let mut decoder = Decoder { data: &data, pos: MAGIC.len() };
+        while decoder.pos < data.len() {
+            match decode_record(&mut decoder) {
+                Some((dir, record)) => cache.dirs.insert(dir, record),
+                None => return ScanCache::default(),
+            };
+        }
+        cache
+    }
+
+    pub(crate) fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Written aside then renamed, so an interrupted save doesn't leave half a cache
+        let partial = path.with_extension("partial");
+        let mut encoder = Encoder { out: BufWriter::new(File::create(&partial)?) };
+        encoder.out.write_all(MAGIC)?;
+        for (dir, record) in &self.dirs {
+            let below_roots = self.roots.iter().any(|root| dir.starts_with(root));
+            if below_roots && (self.too_big || !self.visited.contains(dir)) {
+                continue;
+            }
+            encoder.bytes(&path_bytes(dir))?;
+            encoder.time(record.mtime)?;
+            encoder.varint(record.names.len() as u64)?;
+            for name in &record.names {
+                encoder.bytes(&path_bytes(name))?;
+            }
+        }
+        encoder.out.flush()?;
+        drop(encoder);
+        fs::rename(&partial, path)
+    }
+
+    // The entries of dir with their metadata, the names from the cache when its mtime didn't change
+    // since they were listed. A directory with unreadable entries isn't cached, the next scan tries again
+    fn list(&mut self, dir: &Path, mtime: Option<SystemTime>, refresh: bool, errors: &ScanErrors) -> Vec<(PathBuf, Metadata)> {
+        let key = path::absolute(dir).unwrap_or_else(|_| dir.to_path_buf());
+        self.visited.insert(key.clone());
+        progress::entered(dir);
+        let errors_before = errors.count();
+        let cached = self.dirs.get(&key).filter(|record| !refresh && mtime.is_some() && record.mtime == mtime);
+        let entries: Vec<(PathBuf, Metadata)> = match cached {
+            Some(record) => {
+                trace!("Reusing the cached listing of {}", dir.display());
+                record.names.iter()
+                    .filter_map(|name| {
+                        let path = dir.join(name);
+                        Some((name.clone(), errors.retry(&path, || fs::symlink_metadata(&path))?))
+                    })
+                    .collect()
+            }
+            None => {
+                trace!("Reading {}", dir.display());
+                errors.retry(dir, || fs::read_dir(dir))
+                    .map(|entries| {
+                        entries.filter_map(|entry| errors.io_ok(dir, entry))
+                            .filter_map(|entry| Some((PathBuf::from(entry.file_name()), errors.retry(&entry.path(), || entry.metadata())?)))
+                            .collect()
+                    })
+                    .unwrap_or_default()
+            }
+        };
+
+        self.listed += entries.len();
+        if self.listed > STREAMING_THRESHOLD && !self.too_big {
+            trace!("Not caching the listings of a tree past {} entries", STREAMING_THRESHOLD);
+            self.too_big = true;
+            let roots = &self.roots;
+            self.dirs.retain(|dir, _| !roots.iter().any(|root| dir.starts_with(root)));
+        }
+        if errors.count() == errors_before && !self.too_big {
+            let names = entries.iter().map(|(name, _)| name.clone()).collect();
+            self.dirs.insert(key, DirRecord { mtime, names });
+        }
+        entries
+    }
+}
+
+fn decode_record(decoder: &mut Decoder) -> Option<(PathBuf, DirRecord)> {
+    let dir = path_from_bytes(decoder.bytes()?);
+    let mtime = decoder.time()?;
+    let count = decoder.varint()?;
+    let mut names = Vec::new();
+    for _ in 0..count {
+        names.push(path_from_bytes(decoder.bytes()?));
+    }
+    Some((dir, DirRecord { mtime, names }))
+}
+
+// Walk the search path like walk() does, with the listings of unchanged directories taken from
+// the cache, and call visit with every file kept
+pub(crate) fn walk_cached(search_path: &str, filters: &Filters, cache: &mut ScanCache, refresh: bool, errors: &ScanErrors, mut visit: impl FnMut(&Path, &Metadata)) {
+    let root = Path::new(search_path);
+    let Some(root_metadata) = errors.retry(root, || fs::metadata(root)) else { return };
+    if !root_metadata.is_dir() {
+        if filters.keeps_file(root, root) {
+            visit(root, &root_metadata);
+        }
+        return;
+    }
+    cache.roots.push(path::absolute(root).unwrap_or_else(|_| root.to_path_buf()));
+    let root_device = file_id(&root_metadata).map(|(dev, _)| dev);
+    let mut visited_ids: HashSet<(u64, u64)> = HashSet::new();
+
+    // Directories left to list, with their depth and the ignore rules applying to their content
+    let mut stack = vec![(root.to_path_buf(), 0, filters.ignores_for(root, &Ignores::default()))];
+    if filters.max_depth == Some(0) {
+        stack.clear();
+    }
+    while let Some((dir, depth, ignores)) = stack.pop() {
+        if interrupted() {
+            break;
+        }
+        let Some(dir_metadata) = errors.retry(&dir, || fs::metadata(&dir)) else { continue };
+        if filters.follow_symlinks && file_id(&dir_metadata).is_some_and(|id| !visited_ids.insert(id)) {
+            continue;
+        }
+
+        for (name, metadata) in cache.list(&dir, dir_metadata.modified().ok(), refresh, errors) {
+            if interrupted() {
+                break;
+            }
+            let path = dir.join(&name);
+            // Following a symlink means looking at its target, dangling ones are skipped
+            let mut metadata = metadata;
+            if metadata.file_type().is_symlink() && filters.follows_link(&path) {
+                let Ok(target_metadata) = fs::metadata(&path) else { continue };
+                metadata = target_metadata;
+            }
+            let is_dir = metadata.is_dir();
+            if !filters.keeps_visible(&path, is_dir, &ignores) {
+                continue;
+            }
+            if is_dir {
+                let other_device = file_id(&metadata).map(|(dev, _)| dev).is_some_and(|dev| Some(dev) != root_device);
+                if filters.one_file_system && other_device {
+                    continue;
+                }
+                let within_depth = filters.max_depth.is_none_or(|max_depth| depth + 1 < max_depth);
+                if within_depth && filters.keeps_dir(root, &path) {
+                    let child_ignores = filters.ignores_for(&path, &ignores);
+                    stack.push((path, depth + 1, child_ignores));
+                }
+                continue;
+            }
+            if filters.keeps_file(root, &path) {
+                visit(&path, &metadata);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encoded(write: impl FnOnce(&mut Encoder<&mut Vec<u8>>) -> io::Result<()>) -> Vec<u8> {
+        let mut data = Vec::new();
+        write(&mut Encoder { out: &mut data }).unwrap();
+        data
+    }
+
+    #[test]
+    fn decodes_what_it_encodes() {
+        let time = UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_789);
+        let data = encoded(|encoder| {
+            for value in [0, 127, 128, 300, u64::MAX] {
+                encoder.varint(value)?;
+            }
+            encoder.bytes(b"dir/name")?;
+            encoder.time(Some(time))?;
+            encoder.time(None)
+        });
+        assert_eq!(&data[..5], &[0, 0x7f, 0x80, 0x01, 0xac]);
+        let mut decoder = Decoder { data: &data, pos: 0 };
+        for value in [0, 127, 128, 300, u64::MAX] {
+            assert_eq!(decoder.varint(), Some(value));
+        }
+        assert_eq!(decoder.bytes(), Some(&b"dir/name"[..]));
+        assert_eq!(decoder.time(), Some(Some(time)));
+        assert_eq!(decoder.time(), Some(None));
+        assert_eq!(decoder.varint(), None);
+
+        // Past the end, too long and over long varints are all errors
+        assert_eq!(Decoder { data: &[0x80], pos: 0 }.varint(), None);
+        assert_eq!(Decoder { data: &[0xff; 11], pos: 0 }.varint(), None);
+        assert_eq!(Decoder { data: &[5, b'a'], pos: 0 }.bytes(), None);
+        assert_eq!(Decoder { data: &encoded(|encoder| encoder.varint(u64::MAX)), pos: 0 }.bytes(), None);
+    }
+
+    #[test]
+    fn rejects_broken_caches_and_changed_dirs() {
+        let dir = env::temp_dir().join(format!("fatass-cache-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let cache_path = dir.join("scan.cache");
+        let record = encoded(|encoder| {
+            encoder.bytes(b"/data")?;
+            encoder.time(Some(UNIX_EPOCH))?;
+            encoder.varint(1)?;
+            encoder.bytes(b"a.log")
+        });
+
+        let valid = [MAGIC, &record].concat();
+        fs::write(&cache_path, &valid).unwrap();
+        assert_eq!(ScanCache::load(&cache_path).dirs[Path::new("/data")].names, [PathBuf::from("a.log")]);
+        // Another magic, a cut record, a name count past the end or a missing file are no cache
+        for data in [[b"FATASSC2", &record[..]].concat(), valid[..valid.len() - 2].to_vec(), [MAGIC, &record[..record.len() - 7], &[0xff, 0xff, 0xff, 0x0f]].concat()] {
+            fs::write(&cache_path, data).unwrap();
+            assert!(ScanCache::load(&cache_path).dirs.is_empty());
+        }
+        assert!(ScanCache::load(&dir.join("none")).dirs.is_empty());
+
+        // An unchanged mtime reuses the names but not the sizes, a changed one lists again
+        let listed = dir.join("listed");
+        fs::create_dir(&listed).unwrap();
+        fs::write(listed.join("a.log"), "abc").unwrap();
+        let errors = ScanErrors::default();
+        let mut cache = ScanCache::default();
+        let mtime = Some(UNIX_EPOCH + Duration::from_secs(1));
+        assert_eq!(cache.list(&listed, mtime, false, &errors).len(), 1);
+        fs::write(listed.join("a.log"), "abcdef").unwrap();
+        fs::write(listed.join("b.log"), "b").unwrap();
+        let entries = cache.list(&listed, mtime, false, &errors);
+        assert_eq!(entries.iter().map(|(name, metadata)| (name.to_str().unwrap(), metadata.len())).collect::<Vec<_>>(), [("a.log", 6)]);
+        assert_eq!(cache.list(&listed, Some(UNIX_EPOCH + Duration::from_secs(2)), false, &errors).len(), 2);
+        assert_eq!(cache.list(&listed, mtime, true, &errors).len(), 2);
+
+        cache.save(&cache_path).unwrap();
+        assert_eq!(ScanCache::load(&cache_path).dirs.len(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,479 @@
+// Checksums of the files kept in the results, to tell whether two giant files on different hosts
+// are the same without copying them over. MD5 and SHA-256 as RFC 1321 and FIPS 180-4 define them,
+// BLAKE3 after its reference implementation
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::throttle;
+use crate::{EntryKind, FileEntry};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HashAlgorithm {
+    Md5,
+    Sha256,
+    Blake3,
+}
+
+impl HashAlgorithm {
+    pub const NAMES: [&'static str; 3] = ["md5", "sha256", "blake3"];
+
+    pub fn parse(value: &str) -> Option<HashAlgorithm> {
+        match value {
+            "md5" => Some(HashAlgorithm::Md5),
+            "sha256" => Some(HashAlgorithm::Sha256),
+            "blake3" => Some(HashAlgorithm::Blake3),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Md5 => "MD5",
+            HashAlgorithm::Sha256 => "SHA-256",
+            HashAlgorithm::Blake3 => "BLAKE3",
+        }
+    }
+}
+
+const MD5_SHIFTS: [u32; 64] = [
+    7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22,
+    5, 9, 14, 20, 5, 9, 14, 20, 5, 9, 14, 20, 5, 9, 14, 20,
+    4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23,
+    6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21,
+];
+
+const MD5_CONSTANTS: [u32; 64] = [
+    0xd76aa478, 0xe8c7b756, 0x242070db, 0xc1bdceee, 0xf57c0faf, 0x4787c62a, 0xa8304613, 0xfd469501,
+    0x698098d8, 0x8b44f7af, 0xffff5bb1, 0x895cd7be, 0x6b901122, 0xfd987193, 0xa679438e, 0x49b40821,
+    0xf61e2562, 0xc040b340, 0x265e5a51, 0xe9b6c7aa, 0xd62f105d, 0x02441453, 0xd8a1e681, 0xe7d3fbc8,
+    0x21e1cde6, 0xc33707d6, 0xf4d50d87, 0x455a14ed, 0xa9e3e905, 0xfcefa3f8, 0x676f02d9, 0x8d2a4c8a,
+    0xfffa3942, 0x8771f681, 0x6d9d6122, 0xfde5380c, 0xa4beea44, 0x4bdecfa9, 0xf6bb4b60, 0xbebfbc70,
+    0x289b7ec6, 0xeaa127fa, 0xd4ef3085, 0x04881d05, 0xd9d4d039, 0xe6db99e5, 0x1fa27cf8, 0xc4ac5665,
+    0xf4292244, 0x432aff97, 0xab9423a7, 0xfc93a039, 0x655b59c3, 0x8f0ccc92, 0xffeff47d, 0x85845dd1,
+    0x6fa87e4f, 0xfe2ce6e0, 0xa3014314, 0x4e0811a1, 0xf7537e82, 0xbd3af235, 0x2ad7d2bb, 0xeb86d391,
+];
+
+const SHA256_CONSTANTS: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+// The first words of SHA-256 are BLAKE3's too
+const SHA256_INITIAL: [u32; 8] = [0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19];
+
+// MD5 and SHA-256 both eat 64 byte blocks and pad the last one with the message length
+struct Blocks {
+    buffer: [u8; 64],
+    buffered: usize,
+    length: u64,
+}
+
+impl Blocks {
+    fn new() -> Blocks {
+        Blocks { buffer: [0; 64], buffered: 0, length: 0 }
+    }
+
+    fn update(&mut self, mut input: &[u8], mut compress: impl FnMut(&[u8; 64])) {
+        self.length += input.len() as u64;
+        while !input.is_empty() {
+            let take = (64 - self.buffered).min(input.len());
+            self.buffer[self.buffered..self.buffered + take].copy_from_slice(&input[..take]);
+            self.buffered += take;
+            input = &input[take..];
+            if self.buffered == 64 {
+                compress(&self.buffer);
+                self.buffered = 0;
+            }
+        }
+    }
+
+    // A one bit, zeros, then the length in bits in the last 8 bytes
+    fn finish(&mut self, big_endian: bool, mut compress: impl FnMut(&[u8; 64])) {
+        let bits = self.length.wrapping_mul(8);
+        let mut padding = vec![0x80];
+        padding.resize(if self.buffered < 56 { 56 - self.buffered } else { 120 - self.buffered }, 0);
+        padding.extend(if big_endian { bits.to_be_bytes() } else { bits.to_le_bytes() });
+        let length = self.length;
+        self.update(&padding, &mut compress);
+        self.length = length;
+    }
+}
+
+struct Md5 {
+    state: [u32; 4],
+    blocks: Blocks,
+}
+
+fn md5_compress(state: &mut [u32; 4], block: &[u8; 64]) {
+    let words: Vec<u32> = block.chunks_exact(4).map(|word| u32::from_le_bytes(word.try_into().unwrap())).collect();
+    let [mut a, mut b, mut c, mut d] = *state;
+    for i in 0..64 {
+        let (f, g) = match i / 16 {
+            0 => ((b & c) | (!b & d), i),
+            1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+            2 => (b ^ c ^ d, (3 * i + 5) % 16),
+            _ => (c ^ (b | !d), (7 * i) % 16),
+        };
+        let rotated = a.wrapping_add(f).wrapping_add(MD5_CONSTANTS[i]).wrapping_add(words[g]).rotate_left(MD5_SHIFTS[i]);
+        (a, d, c) = (d, c, b);
+        b = b.wrapping_add(rotated);
+    }
+    for (word, added) in state.iter_mut().zip([a, b, c, d]) {
+        *word = word.wrapping_add(added);
+    }
+}
+
+impl Md5 {
+    fn new() -> Md5 {
+        Md5 { state: [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476], blocks: Blocks::new() }
+    }
+
+    fn update(&mut self, input: &[u8]) {
+        let state = &mut self.state;
+        self.blocks.update(input, |block| md5_compress(state, block));
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let state = &mut self.state;
+        self.blocks.finish(false, |block| md5_compress(state, block));
+        self.state.iter().flat_map(|word| word.to_le_bytes()).collect()
+    }
+}
+
+struct Sha256 {
+    state: [u32; 8],
+    blocks: Blocks,
+}
+
+fn sha256_compress(state: &mut [u32; 8], block: &[u8; 64]) {
+    let mut schedule = [0u32; 64];
+    for (word, bytes) in schedule.iter_mut().zip(block.chunks_exact(4)) {
+        *word = u32::from_be_bytes(bytes.try_into().unwrap());
+    }
+    for i in 16..64 {
+        let s0 = schedule[i - 15].rotate_right(7) ^ schedule[i - 15].rotate_right(18) ^ (schedule[i - 15] >> 3);
+        let s1 = schedule[i - 2].rotate_right(17) ^ schedule[i - 2].rotate_right(19) ^ (schedule[i - 2] >> 10);
+        schedule[i] = schedule[i - 16].wrapping_add(s0).wrapping_add(schedule[i - 7]).wrapping_add(s1);
+    }
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+    for i in 0..64 {
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let choice = (e & f) ^ (!e & g);
+        let temp1 = h.wrapping_add(s1).wrapping_add(choice).wrapping_add(SHA256_CONSTANTS[i]).wrapping_add(schedule[i]);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let majority = (a & b) ^ (a & c) ^ (b & c);
+        let temp2 = s0.wrapping_add(majority);
+        (h, g, f, e, d, c, b, a) = (g, f, e, d.wrapping_add(temp1), c, b, a, temp1.wrapping_add(temp2));
+    }
+    for (word, added) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+        *word = word.wrapping_add(added);
+    }
+}
+
+impl Sha256 {
+    fn new() -> Sha256 {
+        Sha256 { state: SHA256_INITIAL, blocks: Blocks::new() }
+    }
+
+    fn update(&mut self, input: &[u8]) {
+        let state = &mut self.state;
+        self.blocks.update(input, |block| sha256_compress(state, block));
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let state = &mut self.state;
+        self.blocks.finish(true, |block| sha256_compress(state, block));
+        self.state.iter().flat_map(|word| word.to_be_bytes()).collect()
+    }
+}
+
+const BLAKE3_BLOCK_LEN: usize = 64;
+const BLAKE3_CHUNK_LEN: usize = 1024;
+const CHUNK_START: u32 = 1 << 0;
+const CHUNK_END: u32 = 1 << 1;
+const PARENT: u32 = 1 << 2;
+const ROOT: u32 = 1 << 3;
+const MESSAGE_PERMUTATION: [usize; 16] = [2, 6, 3, 10, 7, 0, 4, 13, 1, 11, 12, 5, 9, 14, 15, 8];
+
+fn blake3_mix(state: &mut [u32; 16], [a, b, c, d]: [usize; 4], x: u32, y: u32) {
+    state[a] = state[a].wrapping_add(state[b]).wrapping_add(x);
+    state[d] = (state[d] ^ state[a]).rotate_right(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_right(12);
+    state[a] = state[a].wrapping_add(state[b]).wrapping_add(y);
+    state[d] = (state[d] ^ state[a]).rotate_right(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_right(7);
+}
+
+fn blake3_compress(chaining_value: &[u32; 8], block: &[u32; 16], counter: u64, block_len: u32, flags: u32) -> [u32; 16] {
+    let mut state = [0u32; 16];
+    state[..8].copy_from_slice(chaining_value);
+    state[8..12].copy_from_slice(&SHA256_INITIAL[..4]);
+    state[12..].copy_from_slice(&[counter as u32, (counter >> 32) as u32, block_len, flags]);
+    let mut message = *block;
+    for round in 0..7 {
+        // Columns then diagonals
+        blake3_mix(&mut state, [0, 4, 8, 12], message[0], message[1]);
+        blake3_mix(&mut state, [1, 5, 9, 13], message[2], message[3]);
+        blake3_mix(&mut state, [2, 6, 10, 14], message[4], message[5]);
+        blake3_mix(&mut state, [3, 7, 11, 15], message[6], message[7]);
+        blake3_mix(&mut state, [0, 5, 10, 15], message[8], message[9]);
+        blake3_mix(&mut state, [1, 6, 11, 12], message[10], message[11]);
+        blake3_mix(&mut state, [2, 7, 8, 13], message[12], message[13]);
+        blake3_mix(&mut state, [3, 4, 9, 14], message[14], message[15]);
+        if round < 6 {
+            message = MESSAGE_PERMUTATION.map(|index| message[index]);
+        }
+    }
+    for i in 0..8 {
+        state[i] ^= state[i + 8];
+        state[i + 8] ^= chaining_value[i];
+    }
+    state
+}
+
+fn first_words(words: [u32; 16]) -> [u32; 8] {
+    words[..8].try_into().unwrap()
+}
+
+fn block_words(block: &[u8; BLAKE3_BLOCK_LEN]) -> [u32; 16] {
+    let mut words = [0u32; 16];
+    for (word, bytes) in words.iter_mut().zip(block.chunks_exact(4)) {
+        *word = u32::from_le_bytes(bytes.try_into().unwrap());
+    }
+    words
+}
+
+// The last compression of a chunk or parent node, left undone until it's known to be the root
+struct Node {
+    chaining_value: [u32; 8],
+    block: [u32; 16],
+    counter: u64,
+    block_len: u32,
+    flags: u32,
+}
+
+impl Node {
+    fn chaining_value(&self) -> [u32; 8] {
+        first_words(blake3_compress(&self.chaining_value, &self.block, self.counter, self.block_len, self.flags))
+    }
+
+    fn root_hash(&self) -> Vec<u8> {
+        let words = blake3_compress(&self.chaining_value, &self.block, 0, self.block_len, self.flags | ROOT);
+        words[..8].iter().flat_map(|word| word.to_le_bytes()).collect()
+    }
+
+    fn parent(left: &[u32; 8], right: &[u32; 8]) -> Node {
+        let mut block = [0u32; 16];
+        block[..8].copy_from_slice(left);
+        block[8..].copy_from_slice(right);
+        Node { chaining_value: SHA256_INITIAL, block, counter: 0, block_len: BLAKE3_BLOCK_LEN as u32, flags: PARENT }
+    }
+}
+
+// The 1 KiB chunk being hashed
+struct Chunk {
+    chaining_value: [u32; 8],
+    counter: u64,
+    block: [u8; BLAKE3_BLOCK_LEN],
+    block_len: usize,
+    blocks_compressed: usize,
+}
+
+impl Chunk {
+    fn new(counter: u64) -> Chunk {
+        Chunk { chaining_value: SHA256_INITIAL, counter, block: [0; BLAKE3_BLOCK_LEN], block_len: 0, blocks_compressed: 0 }
+    }
+
+    fn len(&self) -> usize {
+        BLAKE3_BLOCK_LEN * self.blocks_compressed + self.block_len
+    }
+
+    fn start_flag(&self) -> u32 {
+        if self.blocks_compressed == 0 { CHUNK_START } else { 0 }
+    }
+
+    fn update(&mut self, mut input: &[u8]) {
+        while !input.is_empty() {
+            // The last block of the chunk is kept for its output
+            if self.block_len == BLAKE3_BLOCK_LEN {
+                let block = block_words(&self.block);
+                self.chaining_value = first_words(blake3_compress(&self.chaining_value, &block, self.counter, BLAKE3_BLOCK_LEN as u32, self.start_flag()));
+                self.blocks_compressed += 1;
+                self.block = [0; BLAKE3_BLOCK_LEN];
+                self.block_len = 0;
+            }
+            let take = (BLAKE3_BLOCK_LEN - self.block_len).min(input.len());
+            self.block[self.block_len..self.block_len + take].copy_from_slice(&input[..take]);
+            self.block_len += take;
+            input = &input[take..];
+        }
+    }
+
+    fn output(&self) -> Node {
+        Node {
+            chaining_value: self.chaining_value,
+            block: block_words(&self.block),
+            counter: self.counter,
+            block_len: self.block_len as u32,
+            flags: self.start_flag() | CHUNK_END,
+        }
+    }
+}
+
+struct Blake3 {
+    chunk: Chunk,
+    // Chaining values of the complete subtrees on the left, merged as chunks add up to powers of two
+    stack: Vec<[u32; 8]>,
+}
+
+impl Blake3 {
+    fn new() -> Blake3 {
+        Blake3 { chunk: Chunk::new(0), stack: Vec::new() }
+    }
+
+    fn update(&mut self, mut input: &[u8]) {
+        while !input.is_empty() {
+            if self.chunk.len() == BLAKE3_CHUNK_LEN {
+                let mut chaining_value = self.chunk.output().chaining_value();
+                let mut chunks = self.chunk.counter + 1;
+                while chunks & 1 == 0 {
+                    chaining_value = Node::parent(&self.stack.pop().unwrap(), &chaining_value).chaining_value();
+                    chunks >>= 1;
+                }
+                self.stack.push(chaining_value);
+                self.chunk = Chunk::new(self.chunk.counter + 1);
+            }
+            let take = (BLAKE3_CHUNK_LEN - self.chunk.len()).min(input.len());
+            self.chunk.update(&input[..take]);
+            input = &input[take..];
+        }
+    }
+
+    fn finish(self) -> Vec<u8> {
+        let mut node = self.chunk.output();
+        for left in self.stack.iter().rev() {
+            node = Node::parent(left, &node.chaining_value());
+        }
+        node.root_hash()
+    }
+}
+
+enum Digest {
+    Md5(Md5),
+    Sha256(Sha256),
+    Blake3(Box<Blake3>),
+}
+
+impl Digest {
+    fn new(algorithm: HashAlgorithm) -> Digest {
+        match algorithm {
+            HashAlgorithm::Md5 => Digest::Md5(Md5::new()),
+            HashAlgorithm::Sha256 => Digest::Sha256(Sha256::new()),
+            HashAlgorithm::Blake3 => Digest::Blake3(Box::new(Blake3::new())),
+        }
+    }
+
+    fn update(&mut self, input: &[u8]) {
+        match self {
+            Digest::Md5(md5) => md5.update(input),
+            Digest::Sha256(sha256) => sha256.update(input),
+            Digest::Blake3(blake3) => blake3.update(input),
+        }
+    }
+
+    // Lowercase hexadecimal, as md5sum, sha256sum and b3sum print it
+    fn finish(self) -> String {
+        let bytes = match self {
+            Digest::Md5(md5) => md5.finish(),
+            Digest::Sha256(sha256) => sha256.finish(),
+            Digest::Blake3(blake3) => blake3.finish(),
+        };
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
+
+pub fn hash_bytes(input: &[u8], algorithm: HashAlgorithm) -> String {
+    let mut digest = Digest::new(algorithm);
+    digest.update(input);
+    digest.finish()
+}
+
+pub fn hash_file(path: &Path, algorithm: HashAlgorithm) -> io::Result<String> {
+    hash_reader(&mut File::open(path)?, algorithm)
+}
+
+// The checksum of everything the reader gives, a decompressing pipe as well as a file
+pub fn hash_reader(reader: &mut dyn Read, algorithm: HashAlgorithm) -> io::Result<String> {
+    let mut digest = Digest::new(algorithm);
+    let mut buffer = vec![0u8; 1 << 16];
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => return Ok(digest.finish()),
+            Ok(read) => {
+                throttle::charge(read as u64);
+                digest.update(&buffer[..read]);
+            }
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+// Fill in the checksum of the files, the ones that can't be read are left without
+pub fn hash_entries(entries: &mut [FileEntry], algorithm: HashAlgorithm) {
+    for entry in entries.iter_mut().filter(|entry| entry.kind == EntryKind::File) {
+        entry.checksum = hash_file(Path::new(&entry.path), algorithm).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(algorithm: HashAlgorithm, input: &[u8]) -> String {
+        let mut digest = Digest::new(algorithm);
+        // Split updates must hash the same as a single one
+        let (head, tail) = input.split_at(input.len() / 3);
+        digest.update(head);
+        digest.update(tail);
+        digest.finish()
+    }
+
+    #[test]
+    fn md5_matches_the_rfc_vectors() {
+        assert_eq!(hash(HashAlgorithm::Md5, b""), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(hash(HashAlgorithm::Md5, b"abc"), "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(
+            hash(HashAlgorithm::Md5, b"12345678901234567890123456789012345678901234567890123456789012345678901234567890"),
+            "57edf4a22be3c955ac49da2e2107b67a"
+        );
+    }
+
+    #[test]
+    fn sha256_matches_the_fips_vectors() {
+        assert_eq!(hash(HashAlgorithm::Sha256, b""), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(hash(HashAlgorithm::Sha256, b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(
+            hash(HashAlgorithm::Sha256, b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+    }
+
+    #[test]
+    fn blake3_matches_the_reference() {
+        assert_eq!(hash(HashAlgorithm::Blake3, b""), "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262");
+        assert_eq!(hash(HashAlgorithm::Blake3, b"abc"), "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85");
+        // Three chunks and a byte, as in the official test vectors
+        let input: Vec<u8> = (0..3073).map(|i| (i % 251) as u8).collect();
+        assert_eq!(hash(HashAlgorithm::Blake3, &input), "7124b49501012f81cc7f11ca069ec9226cecb8a2c850cfe644e327d22d3e1cd3");
+    }
+}
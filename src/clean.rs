@@ -0,0 +1,163 @@
+// What fatass clean offers to do with each of the top results, one at a time. Asking is up to the
+// caller, this carries out the answers and keeps track of them for the summary at the end
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::compress::{compress_file, Compressor, Outcome};
+use crate::delete::{delete_entry, DeleteMethod};
+use crate::offload::verify_copy;
+use crate::{EntryKind, FileEntry};
+
+pub const CHOICES: &str = "[d]elete, [t]rash, [c]ompress, [m]ove, [o]pen folder, [s]kip, [q]uit";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Delete,
+    Trash,
+    // Gzip in place, the file becomes FILE.gz
+    Compress,
+    // Into another directory, copied then removed when it's on another filesystem
+    Move,
+    Skip,
+    // Show the containing folder in the file manager, then ask again
+    Open,
+    // Leave this file and the rest as they are
+    Quit,
+}
+
+impl Action {
+    // The action of an answer, its first letter or the whole word, nothing skips
+    pub fn parse(answer: &str) -> Option<Action> {
+        match answer.trim().to_lowercase().as_str() {
+            "d" | "delete" => Some(Action::Delete),
+            "t" | "trash" => Some(Action::Trash),
+            "c" | "compress" => Some(Action::Compress),
+            "m" | "move" => Some(Action::Move),
+            "" | "s" | "skip" => Some(Action::Skip),
+            "o" | "open" => Some(Action::Open),
+            "q" | "quit" => Some(Action::Quit),
+            _ => None,
+        }
+    }
+
+    pub fn past_tense(self) -> &'static str {
+        match self {
+            Action::Delete => "deleted",
+            Action::Trash => "trashed",
+            Action::Compress => "compressed",
+            Action::Move => "moved",
+            Action::Skip | Action::Quit => "skipped",
+            Action::Open => "opened",
+        }
+    }
+}
+
+// How long ago in the largest unit that fits, "40 minutes", "3 days" or "2 years"
+pub fn describe_age(age: Duration) -> String {
+    const UNITS: [(u64, &str); 6] = [(365 * 86400, "year"), (30 * 86400, "month"), (7 * 86400, "week"), (86400, "day"), (3600, "hour"), (60, "minute")];
+    let seconds = age.as_secs();
+    let (count, unit) = UNITS.iter()
+        .find(|(length, _)| seconds >= *length)
+        .map_or((seconds, "second"), |&(length, unit)| (seconds / length, unit));
+    format!("{} {}{}", count, unit, if count == 1 { "" } else { "s" })
+}
+
+// What was done to the files and which were left as they were
+#[derive(Debug, Default)]
+pub struct CleanReport {
+    // The action carried out on each file with the bytes it took off where it was
+    pub executed: Vec<(Action, String, u64)>,
+    pub declined: Vec<(String, u64)>,
+    pub failures: Vec<(String, io::Error)>,
+}
+
+impl CleanReport {
+    pub fn record(&mut self, entry: &FileEntry, action: Action, result: io::Result<u64>) {
+        match result {
+            Ok(freed) => self.executed.push((action, entry.path.clone(), freed)),
+            Err(err) => self.failures.push((entry.path.clone(), err)),
+        }
+    }
+
+    pub fn decline(&mut self, entry: &FileEntry) {
+        self.declined.push((entry.path.clone(), entry.size));
+    }
+
+    // How many files the action was carried out on and the bytes they freed
+    pub fn totals(&self, action: Action) -> (usize, u64) {
+        self.executed.iter()
+            .filter(|(executed, _, _)| *executed == action)
+            .fold((0, 0), |(count, bytes), (_, _, freed)| (count + 1, bytes + freed))
+    }
+}
+
+// Carry out the action, returns the bytes it freed: the whole file but for compression which
+// frees what gzip saved. Moving needs the destination directory
+pub fn execute(entry: &FileEntry, action: Action, destination: Option<&Path>) -> io::Result<u64> {
+    let path = Path::new(&entry.path);
+    match action {
+        Action::Delete => delete_entry(entry, DeleteMethod::Remove).map(|_| entry.size),
+        Action::Trash => delete_entry(entry, DeleteMethod::Trash).map(|_| entry.size),
+        Action::Compress if entry.kind == EntryKind::Directory => Err(io::Error::other("only files can be compressed")),
+        Action::Compress => match compress_file(path, Compressor::Gzip)? {
+            Outcome::Compressed { saved } => Ok(saved),
+            Outcome::NotSmaller => Err(io::Error::other("it doesn't get any smaller compressed")),
+        },
+        Action::Move => {
+            let destination = destination.ok_or_else(|| io::Error::other("no destination given"))?;
+            move_into(path, destination).map(|_| entry.size)
+        }
+        Action::Skip | Action::Open | Action::Quit => Ok(0),
+    }
+}
+
+// Move the file into the directory, returns where it ended up
+fn move_into(path: &Path, directory: &Path) -> io::Result<PathBuf> {
+    let name = path.file_name().ok_or_else(|| io::Error::other("not a file"))?;
+    let target = directory.join(name);
+    if target.exists() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", target.display())));
+    }
+    match fs::rename(path, &target) {
+        // Across volumes the original only goes once the copy is known to match it
+        Err(err) if err.kind() == io::ErrorKind::CrossesDevices => {
+            fs::copy(path, &target)?;
+            if let Err(err) = verify_copy(path, &target) {
+                let _ = fs::remove_file(&target);
+                return Err(err);
+            }
+            fs::remove_file(path)?;
+        }
+        moved => moved?,
+    }
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_answers_and_ages() {
+        assert_eq!(Action::parse("d"), Some(Action::Delete));
+        assert_eq!(Action::parse(" Trash\n"), Some(Action::Trash));
+        assert_eq!(Action::parse("\n"), Some(Action::Skip));
+        assert_eq!(Action::parse("o"), Some(Action::Open));
+        assert_eq!(Action::parse("x"), None);
+
+        assert_eq!(describe_age(Duration::from_secs(45)), "45 seconds");
+        assert_eq!(describe_age(Duration::from_secs(3600)), "1 hour");
+        assert_eq!(describe_age(Duration::from_secs(100 * 86400)), "3 months");
+        assert_eq!(describe_age(Duration::from_secs(800 * 86400)), "2 years");
+
+        let mut report = CleanReport::default();
+        report.record(&FileEntry::new(String::from("a.log"), 100), Action::Compress, Ok(80));
+        report.record(&FileEntry::new(String::from("b.log"), 50), Action::Compress, Ok(30));
+        report.record(&FileEntry::new(String::from("c.iso"), 500), Action::Delete, Err(io::Error::other("busy")));
+        assert_eq!(report.totals(Action::Compress), (2, 110));
+        assert_eq!(report.totals(Action::Delete), (0, 0));
+        assert_eq!(report.failures.len(), 1);
+    }
+}
@@ -0,0 +1,1374 @@
+// Command line parsing: the subcommands, the options each of them accepts, and the help built
+// from the same table so the two can't drift apart
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use fatass::checksum::HashAlgorithm;
+use fatass::compress::Compressor;
+use fatass::errors::{DEFAULT_RETRIES, MAX_RETRIES};
+use fatass::exec::ExecCommand;
+use fatass::expr::RankExpr;
+use fatass::groups::GroupKey;
+use fatass::logging::Verbosity;
+use fatass::output::{parse_color_thresholds, Column};
+use fatass::paths::PathStyle;
+use fatass::presets::Preset;
+use fatass::priority::Priority;
+use fatass::remote;
+use fatass::s3::S3Prefix;
+use fatass::script::ScriptAction;
+use fatass::source::FileSource;
+use fatass::template::Template;
+use fatass::throttle;
+use fatass::time::{parse_interval, parse_time_bound};
+use fatass::{parse_size, ColorChoice, Config, FileTypes, Glob, HardlinkMode, OutputFormat, OutputOptions, Rank, Regex, SortKey, Theme, UnitStyle};
+
+use crate::completions::Shell;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    Scan,
+    Dirs,
+    Dupes,
+    Inodes,
+    Compressible,
+    Docker,
+    S3,
+    Clean,
+    Offload,
+    Compress,
+    Diff,
+    Watch,
+    Daemon,
+    Serve,
+    ExportMetrics,
+    Profiles,
+    Completions,
+}
+
+impl Command {
+    pub const ALL: [Command; 17] = [
+        Command::Scan, Command::Dirs, Command::Dupes, Command::Inodes, Command::Compressible, Command::Docker, Command::S3, Command::Clean, Command::Offload, Command::Compress, Command::Diff, Command::Watch,
+        Command::Daemon, Command::Serve, Command::ExportMetrics, Command::Profiles, Command::Completions,
+    ];
+
+    pub fn parse(value: &str) -> Option<Command> {
+        Command::ALL.into_iter().find(|command| command.name() == value)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Scan => "scan",
+            Command::Dirs => "dirs",
+            Command::Dupes => "dupes",
+            Command::Inodes => "inodes",
+            Command::Compressible => "compressible",
+            Command::Docker => "docker",
+            Command::S3 => "s3",
+            Command::Clean => "clean",
+            Command::Offload => "offload",
+            Command::Compress => "compress",
+            Command::Diff => "diff",
+            Command::Watch => "watch",
+            Command::Daemon => "daemon",
+            Command::Serve => "serve",
+            Command::ExportMetrics => "export-metrics",
+            Command::Profiles => "profiles",
+            Command::Completions => "completions",
+        }
+    }
+
+    pub fn about(&self) -> &'static str {
+        match self {
+            Command::Scan => "Report the fattest files, the default when no command is given",
+            Command::Dirs => "Report the fattest directories, by recursive size",
+            Command::Dupes => "Report groups of duplicate files and the space they waste",
+            Command::Inodes => "Report the directories holding the most files, below them and right inside them",
+            Command::Compressible => "Sample the fattest files and report how much compressing each of them would save",
+            Command::Docker => "Report the space Docker or Podman storage takes per image, container and volume",
+            Command::S3 => "Report the fattest objects of an S3 bucket, listed by the aws CLI, with key prefixes as directories",
+            Command::Clean => "Go through the fattest files one by one, deleting, trashing, compressing or moving each",
+            Command::Offload => "Move the fattest files to another volume, under the same relative paths, removing each original once its copy checks out",
+            Command::Compress => "Compress the fattest files in place, keeping each original until its compressed copy checks out",
+            Command::Diff => "Show what grew, shrank, appeared or disappeared between two snapshots",
+            Command::Watch => "Scan again every interval and show the fattest entries whenever they change",
+            Command::Daemon => "Scan every interval, keep the snapshots and report what grew since the previous scan",
+            Command::Serve => "Scan once, or load a snapshot, then serve an HTML report to browse by directory",
+            Command::ExportMetrics => "Write the scan as Prometheus gauges, for node_exporter's textfile collector",
+            Command::Profiles => "List the profiles of the config file",
+            Command::Completions => "Print the completion script of a shell: bash, zsh, fish, powershell",
+        }
+    }
+
+    pub fn accepts(&self, scope: Scope) -> bool {
+        match self {
+            Command::Watch => !matches!(scope, Scope::Metrics | Scope::Notify | Scope::Daemon | Scope::Serve | Scope::Offload | Scope::Compress),
+            Command::Scan | Command::Dirs | Command::S3 | Command::Clean => !matches!(scope, Scope::Watch | Scope::Metrics | Scope::Daemon | Scope::Serve | Scope::Offload | Scope::Compress),
+            Command::Offload => !matches!(scope, Scope::Watch | Scope::Metrics | Scope::Daemon | Scope::Serve | Scope::Compress),
+            Command::Compress => !matches!(scope, Scope::Watch | Scope::Metrics | Scope::Daemon | Scope::Serve | Scope::Offload),
+            Command::Dupes | Command::Inodes | Command::Compressible | Command::Docker => matches!(scope, Scope::Global | Scope::Report | Scope::Walk),
+            Command::Daemon => !matches!(scope, Scope::List | Scope::Serve | Scope::Offload | Scope::Compress),
+            Command::Serve => matches!(scope, Scope::Global | Scope::Report | Scope::Walk | Scope::Serve),
+            Command::ExportMetrics => matches!(scope, Scope::Global | Scope::Walk | Scope::Metrics),
+            Command::Diff => matches!(scope, Scope::Global | Scope::Report),
+            Command::Profiles | Command::Completions => scope == Scope::Global,
+        }
+    }
+
+    // What the arguments not belonging to an option are, if the command takes any
+    pub fn positional(&self) -> Option<&'static str> {
+        match self {
+            Command::Profiles => None,
+            Command::Diff => Some("snapshot"),
+            Command::Completions => Some("shell"),
+            Command::S3 => Some("bucket"),
+            _ => Some("path"),
+        }
+    }
+}
+
+// Which commands an option makes sense for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scope {
+    // Every command
+    Global,
+    // Commands printing results
+    Report,
+    // Every command walking the search paths
+    Walk,
+    // Commands listing the fattest entries
+    List,
+    // Commands scanning on a schedule
+    Watch,
+    // Commands exporting metrics
+    Metrics,
+    // Commands that can notify a webhook
+    Notify,
+    // The daemon command alone
+    Daemon,
+    // The serve command alone
+    Serve,
+    // The offload command alone
+    Offload,
+    // The compress command alone
+    Compress,
+}
+
+// What the value of an option can be, for shell completion
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Values {
+    Any,
+    Choices(&'static [&'static str]),
+    Path,
+    // The names of the file types
+    Types,
+}
+
+#[derive(Debug)]
+pub struct OptionSpec {
+    pub long: &'static str,
+    pub short: Option<char>,
+    // The placeholder shown in the help for options taking a value, None for flags
+    pub value: Option<&'static str>,
+    pub repeatable: bool,
+    pub scope: Scope,
+    pub help: &'static str,
+    pub values: Values,
+}
+
+impl OptionSpec {
+    const fn completing(mut self, values: Values) -> OptionSpec {
+        self.values = values;
+        self
+    }
+}
+
+const fn flag(long: &'static str, short: Option<char>, scope: Scope, help: &'static str) -> OptionSpec {
+    OptionSpec { long, short, value: None, repeatable: false, scope, help, values: Values::Any }
+}
+
+// A flag given again to mean more, like -vv
+const fn counted(long: &'static str, short: Option<char>, scope: Scope, help: &'static str) -> OptionSpec {
+    OptionSpec { long, short, value: None, repeatable: true, scope, help, values: Values::Any }
+}
+
+const fn single(long: &'static str, short: Option<char>, value: &'static str, scope: Scope, help: &'static str) -> OptionSpec {
+    OptionSpec { long, short, value: Some(value), repeatable: false, scope, help, values: Values::Any }
+}
+
+const fn repeated(long: &'static str, short: Option<char>, value: &'static str, scope: Scope, help: &'static str) -> OptionSpec {
+    OptionSpec { long, short, value: Some(value), repeatable: true, scope, help, values: Values::Any }
+}
+
+// The optional columns --columns can add
+pub const COLUMNS: [&str; 15] = ["path", "size", "apparent", "links", "mode", "owner", "group", "mtime", "atime", "type", "checksum", "xattrs", "percent", "cumulative", "bar"];
+
+// In the order they are shown in the help, "{types}" stands for the known file types
+pub static OPTIONS: [OptionSpec; 118] = [
+    flag("help", Some('h'), Scope::Global, "Show this help message and exit"),
+    flag("quiet", Some('q'), Scope::Global, "Print only the results and the errors, without the progress bar and status lines"),
+    counted("verbose", Some('v'), Scope::Global, "Log the steps and timings of the scan, -vv also every directory read and entry skipped"),
+    single("color", None, "WHEN", Scope::Global, "Color the output: auto, always, never (default: auto, off with NO_COLOR or when piped)").completing(Values::Choices(&["auto", "always", "never"])),
+    single("config", None, "FILE", Scope::Global, "Read defaults from FILE instead of ~/.config/fatass/config.toml").completing(Values::Path),
+    single("profile", None, "NAME", Scope::Walk, "Apply the [profile.NAME] settings of the config file on top of its defaults"),
+    repeated("path", Some('p'), "PATH", Scope::Walk, "Add a search path, repeatable or given as positional paths (default: ./)").completing(Values::Path),
+    single("files-from", None, "LIST", Scope::Walk, "Add the paths of LIST, one per line or NUL separated, - for stdin").completing(Values::Path),
+    single("count", Some('c'), "COUNT", Scope::Report, "Set the fatass count (default: 100)"),
+    single("limit", None, "COUNT", Scope::List, "Same as --count, for paging through the results with --offset"),
+    single("offset", None, "N", Scope::List, "Leave out the N fattest results and show the ones after them, e.g. --offset 100 --limit 100 for the second page"),
+    single("top-percent", None, "PERCENT", Scope::List, "Show the fattest PERCENT of the entries scanned instead of a fixed count, e.g. 1 for the top percentile"),
+    single("threads", Some('t'), "N", Scope::Walk, "Set the number of walker threads (default: 1)"),
+    flag("nice", None, Scope::Walk, "Lower the CPU and disk priority of fatass like nice and ionice, so the services of the machine come first"),
+    flag("idle", None, Scope::Walk, "Only use the CPU and the disks when nothing else does, background mode on macOS and Windows"),
+    single("io-limit", None, "RATE", Scope::Walk, "Read at most RATE from the disks, e.g. 50MB/s, each metadata operation counting as 4 KB"),
+    single("output", Some('o'), "FMT", Scope::Report, "Set the output format: table, json, ndjson, csv, plain, markdown, html, ncdu, treemap (default: table)").completing(Values::Choices(&["table", "json", "ndjson", "csv", "plain", "markdown", "html", "ncdu", "treemap"])),
+    flag("print0", None, Scope::List, "Print only the paths, without their sizes, each ending with a NUL for xargs -0, implies --output plain"),
+    single("format", None, "TEMPLATE", Scope::List, "Print a line per entry after a template like '{size:>10} {mtime} {path}', implies --output plain"),
+    single("output-file", None, "PATH", Scope::Report, "Write the results to PATH instead of stdout").completing(Values::Path),
+    flag("dirs", Some('d'), Scope::List, "Report the fattest directories (recursive size) instead of files"),
+    flag("smallest", None, Scope::List, "Report the smallest non-empty entries instead of the fattest, smallest first"),
+    single("rank", None, "KEY", Scope::List, "Pick the results by size, or by stale: size times the time since last read or written (default: size)").completing(Values::Choices(&["size", "stale"])),
+    single("rank-expr", None, "EXPR", Scope::List, "Pick the results scoring the highest by an arithmetic expression of size, apparent, age, age_days, mtime_days, atime_days, depth and links, e.g. 'size * age_days'"),
+    flag("empty", None, Scope::List, "List the zero byte files and the empty directories instead, only the directories with fatass dirs"),
+    flag("tui", None, Scope::List, "Browse the fattest entries interactively"),
+    single("sort", None, "KEY", Scope::List, "Order the results by size, mtime, atime, name or ext (default: size)").completing(Values::Choices(&["size", "mtime", "atime", "name", "ext"])),
+    flag("reverse", None, Scope::List, "Reverse the order of the results"),
+    flag("disk-usage", None, Scope::List, "Measure the space allocated on disk instead of the file length"),
+    flag("show-apparent", None, Scope::List, "Add the file length column, with --disk-usage"),
+    single("max-depth", None, "N", Scope::Walk, "Don't look deeper than N levels below the search paths"),
+    single("max-files", None, "N", Scope::List, "Stop the scan after going through N files, showing the fattest found until then, with exit status 1"),
+    single("timeout", None, "DURATION", Scope::List, "Stop the scan after DURATION, e.g. 30s, 5m, 1h, showing the fattest found until then, with exit status 1"),
+    flag("one-file-system", Some('x'), Scope::Walk, "Don't descend into other mounted filesystems"),
+    flag("show-errors", None, Scope::Walk, "List the paths that couldn't be read and why at the end, only their count otherwise"),
+    single("retries", None, "N", Scope::Walk, "Try reading a path again N times, waiting longer each time, when it fails with a transient error like a network timeout, at most 10 (default: 2)"),
+    flag("follow-symlinks", None, Scope::Walk, "Walk into symlinked directories, loops are detected and skipped"),
+    flag("skip-junctions", None, Scope::Walk, "With --follow-symlinks, still don't walk into Windows junctions, which mostly lead to folders walked already"),
+    single("count-hardlinks", None, "MODE", Scope::List, "Count hard linked files once or for every link: once, all (default: all)").completing(Values::Choices(&["once", "all"])),
+    flag("show-links", None, Scope::List, "Add a hard link count column"),
+    flag("detect-type", None, Scope::List, "Add a content type column, sniffed from the first bytes of the files shown"),
+    single("hash", None, "ALGO", Scope::List, "Add a checksum column for the files shown, hashed once the scan is over: md5, sha256 or blake3").completing(Values::Choices(&HashAlgorithm::NAMES)),
+    flag("git-aware", None, Scope::List, "Report every .git directory as a single entry, with the largest blobs of its object store"),
+    flag("peek-archives", None, Scope::List, "List the largest members of the zip, tar and gzip files shown, below the table or in the JSON"),
+    flag("ads", None, Scope::List, "On Windows, add the alternate data streams of NTFS files to their size, hidden streams can hold anything"),
+    flag("ads-entries", None, Scope::List, "With --ads, list each stream as an entry of its own after its file, as FILE:STREAM"),
+    flag("apfs", None, Scope::List, "On macOS, add resource forks to file sizes, and with --disk-usage count the blocks APFS clones share once"),
+    flag("xattrs", None, Scope::List, "Add what the extended attributes of files take, names and values, to their size"),
+    flag("show-xattrs", None, Scope::List, "Add a column with the extended attributes of the entries shown, user.* ones and the like, system ones left out"),
+    flag("physical", None, Scope::List, "On Linux, size files by the extents the filesystem maps for them, extents reflinked or deduplicated files share counted once"),
+    flag("fast", None, Scope::List, "On Windows, read the sizes of an NTFS drive from its master file table instead of walking it, much faster on whole drives, as an administrator"),
+    single("remote", None, "HOST:PATH", Scope::List, "Scan PATH on HOST over SSH, GNU find lists the files there and the rest happens here, e.g. admin@db1:/var/lib, or an sftp:// or ftp:// URL, listed by lftp"),
+    single("units", None, "STYLE", Scope::Report, "Show sizes in binary (1024, KiB), si (1000, kB), bytes (exact) or legacy (1024, KB) units (default: legacy)").completing(Values::Choices(&["binary", "si", "bytes", "legacy"])),
+    single("theme", None, "THEME", Scope::Report, "Color the tables with the default, light or mono theme").completing(Values::Choices(&["default", "light", "mono"])),
+    single("color-thresholds", None, "LIST", Scope::List, "Color table rows by size, comma separated SIZE=COLOR like 10G=red,1G=yellow"),
+    flag("show-mtime", None, Scope::List, "Add a last modified time column"),
+    flag("show-atime", None, Scope::List, "Add a last accessed time column"),
+    repeated("columns", None, "LIST", Scope::List, "Columns to show in order, comma separated: path, size, apparent, links, mode, owner, group (Unix), mtime, atime, type, checksum (sha256 without --hash), xattrs, percent, cumulative, bar (tables only). Without path or size they follow those two").completing(Values::Choices(&COLUMNS)),
+    single("paths", None, "STYLE", Scope::List, "Show the paths relative to the current directory or absolute (default: as found under the search paths)").completing(Values::Choices(&["relative", "absolute"])),
+    flag("tilde", None, Scope::List, "Show the home directory as ~ in tables, Markdown and HTML"),
+    flag("full-paths", None, Scope::List, "Keep long paths whole instead of cutting their middle to fit the terminal"),
+    flag("no-pager", None, Scope::Report, "Print tables taller than the terminal as they are instead of through $PAGER or less"),
+    flag("show-percent", None, Scope::List, "Add the share of the scanned total and, for files, the running total of the shares"),
+    single("group-by", None, "KEY", Scope::List, "Sum up the files by ext, dir, owner or year instead of listing them").completing(Values::Choices(&["ext", "dir", "owner", "year"])),
+    single("per-group", None, "N", Scope::List, "Show the N fattest files of each group, with --group-by (default: 1)"),
+    single("take-bytes", None, "SIZE", Scope::List, "Show the fewest of the fattest files adding up to SIZE instead of a fixed count, e.g. 500G"),
+    single("free-target", None, "SIZE", Scope::List, "Keep the fewest of the fattest files, or the stalest with --rank stale, whose removal leaves SIZE free on the filesystem scanned: a cleanup plan --delete can carry out"),
+    flag("fs-usage", None, Scope::List, "Start with the total, used and free space of the filesystems scanned, with how much of it the scan and the biggest result take"),
+    flag("stats", None, Scope::List, "Sum up every scanned file after the results: totals, mean, median, p95 and a size histogram"),
+    flag("estimate", None, Scope::List, "Estimate the total size and size histogram from random probes of the tree for 5s or --timeout, with 95% confidence intervals, instead of scanning"),
+    single("fail-if-over", None, "SIZE", Scope::List, "Exit with status 3 when any file, or directory with fatass dirs, is bigger than SIZE"),
+    single("fail-if-total-over", None, "SIZE", Scope::List, "Exit with status 3 when everything scanned adds up to more than SIZE"),
+    single("notify-webhook", None, "URL", Scope::Notify, "POST a JSON summary to URL, a Slack webhook or any other, when a --notify-if limit is exceeded"),
+    single("notify-if-over", None, "SIZE", Scope::Notify, "Notify the webhook when any file, or directory with fatass dirs, is bigger than SIZE"),
+    single("notify-if-total-over", None, "SIZE", Scope::Notify, "Notify the webhook when everything scanned adds up to more than SIZE"),
+    flag("delete", None, Scope::List, "Ask to delete each result once they are shown"),
+    flag("yes", None, Scope::List, "Delete without asking, with --delete or fatass clean"),
+    flag("trash", None, Scope::List, "Move deleted entries to the trash instead of removing them"),
+    single("open", None, "N", Scope::List, "Open the folder holding the Nth result in the file manager once they are shown, o in --tui"),
+    single("copy", None, "N", Scope::List, "Copy the path of the Nth result to the clipboard once they are shown, y in --tui"),
+    single("emit-script", None, "FILE", Scope::List, "Write a script doing --script-action to each result to FILE, to review and run later: PowerShell when FILE ends with .ps1, sh otherwise").completing(Values::Path),
+    single("script-action", None, "ACTION", Scope::List, "What the --emit-script script does to each result: rm, trash, compress or mv (default: rm)").completing(Values::Choices(&ScriptAction::NAMES)),
+    single("script-dest", None, "DIR", Scope::List, "The directory the results are moved into with --script-action mv").completing(Values::Path),
+    single("exec", None, "COMMAND", Scope::List, "Run COMMAND for each result once they are shown, its words filled in like --format templates, e.g. 'assets tag {path} {bytes}'"),
+    single("exec-parallel", None, "N", Scope::List, "Run up to N --exec commands at once (default: 1)"),
+    single("save-snapshot", None, "FILE", Scope::List, "Save every scanned entry to FILE, to compare with later").completing(Values::Path),
+    single("export-sqlite", None, "FILE", Scope::List, "Write every scanned file to the SQLite database FILE, to query with SQL").completing(Values::Path),
+    single("export-parquet", None, "FILE", Scope::List, "Write every scanned file to the Parquet file FILE, for DuckDB, Spark and the like").completing(Values::Path),
+    single("compare", None, "FILE", Scope::List, "Show what changed since the snapshot FILE instead of the fattest entries").completing(Values::Path),
+    flag("no-cache", None, Scope::List, "Walk every directory, without reading or updating the scan cache"),
+    flag("refresh", None, Scope::List, "List every directory again, for filesystems whose mtimes are too coarse to see a directory change"),
+    single("interval", None, "DURATION", Scope::Watch, "Scan again every DURATION, e.g. 30s, 5m, 1h (default: 1m, 6h for fatass daemon)"),
+    single("state-dir", None, "DIR", Scope::Daemon, "Keep the snapshots of the scans in DIR (default: ~/.local/state/fatass)").completing(Values::Path),
+    single("dest", None, "DIR", Scope::Offload, "Move the results under DIR, at their paths below the search path they were found under").completing(Values::Path),
+    flag("leave-symlinks", None, Scope::Offload, "Leave a symlink to the moved copy where each file was"),
+    single("with", None, "TOOL", Scope::Compress, "Compress with gzip, zstd or xz, the command of that name (default: gzip)").completing(Values::Choices(&Compressor::NAMES)),
+    single("port", None, "PORT", Scope::Serve, "Serve the report on PORT (default: 8080)"),
+    single("bind", None, "ADDR", Scope::Serve, "Listen on ADDR, 0.0.0.0 to share the report with the network (default: 127.0.0.1)"),
+    single("from-snapshot", None, "FILE", Scope::Serve, "Serve the files of the snapshot FILE instead of scanning").completing(Values::Path),
+    single("textfile", None, "FILE", Scope::Metrics, "Write the metrics to FILE, replacing it at once, instead of stdout").completing(Values::Path),
+    single("top", None, "N", Scope::Metrics, "Export the size of the N fattest files, labeled by path (default: 10)"),
+    single("progress", None, "MODE", Scope::Walk, "Show the scan progress as a bar or as JSON lines on stderr for wrapping programs: bar, json (default: bar)").completing(Values::Choices(&["bar", "json"])),
+    flag("progress-bytes", None, Scope::Walk, "Count the progress bar in bytes, with the throughput and the time left, sizing the files upfront without the cache"),
+    flag("streaming", None, Scope::List, "Scan in a single pass with a spinner, the default on huge trees, -o ndjson then writes every file as it's found"),
+    single("min-size", None, "SIZE", Scope::Walk, "Skip files smaller than SIZE, e.g. 500M, 2GiB, 1GB, 4096"),
+    single("older-than", None, "AGE", Scope::Walk, "Only report files last modified before AGE, e.g. 90d, 6mo, 1y, 2023-01-31"),
+    single("newer-than", None, "AGE", Scope::Walk, "Only report files last modified after AGE, e.g. 7d, 12h, 2w"),
+    repeated("ext", None, "EXTS", Scope::Walk, "Only report files with these extensions, e.g. log,tmp,iso (repeatable)"),
+    repeated("type", None, "TYPES", Scope::Walk, "Only report files of these types: {types} (repeatable)").completing(Values::Types),
+    repeated("exclude", None, "GLOB", Scope::Walk, "Skip files and directories matching GLOB (repeatable)"),
+    repeated("include", None, "GLOB", Scope::Walk, "Only report files matching GLOB (repeatable)"),
+    repeated("path-regex", None, "RE", Scope::Walk, "Only report files whose full path matches RE (repeatable)"),
+    repeated("path-regex-exclude", None, "RE", Scope::Walk, "Skip files and directories whose full path matches RE (repeatable)"),
+    repeated("preset", None, "NAME", Scope::Walk, "Skip the system paths of a whole drive scan: system-scan, linux, macos, windows (repeatable)").completing(Values::Choices(&Preset::NAMES)),
+    flag("respect-gitignore", None, Scope::Walk, "Skip files ignored by .gitignore files, and hidden files unless --hidden"),
+    flag("no-fatassignore", None, Scope::Walk, "Don't skip what the .fatassignore files list"),
+    flag("hidden", None, Scope::Walk, "Include hidden files and directories (default unless --respect-gitignore)"),
+    flag("no-hidden", None, Scope::Walk, "Skip hidden files and directories"),
+];
+
+fn find_long(long: &str) -> Option<&'static OptionSpec> {
+    OPTIONS.iter().find(|option| option.long == long)
+}
+
+fn find_short(short: char) -> Option<&'static OptionSpec> {
+    OPTIONS.iter().find(|option| option.short == Some(short))
+}
+
+// What the command line asked for, options are known by their long name
+#[derive(Debug)]
+pub struct Matches {
+    pub command: Command,
+    // Whether the command was named, bare `fatass --help` shows the full help
+    pub explicit_command: bool,
+    // Every option given, in order, positional arguments under the name the command gives them
+    occurrences: Vec<(&'static str, Option<String>)>,
+}
+
+impl Matches {
+    pub fn flag(&self, long: &str) -> bool {
+        self.occurrences.iter().any(|(name, _)| *name == long)
+    }
+
+    // How many times a flag was given
+    pub fn count(&self, long: &str) -> usize {
+        self.occurrences.iter().filter(|(name, _)| *name == long).count()
+    }
+
+    pub fn value(&self, long: &str) -> Option<&str> {
+        self.values(long).pop()
+    }
+
+    pub fn values(&self, long: &str) -> Vec<&str> {
+        self.occurrences.iter()
+            .filter(|(name, _)| *name == long)
+            .filter_map(|(_, value)| value.as_deref())
+            .collect()
+    }
+
+    // Which of the options was given last, for pairs like --hidden and --no-hidden
+    pub fn last_of(&self, longs: &[&str]) -> Option<&'static str> {
+        self.occurrences.iter().rev().map(|(name, _)| *name).find(|name| longs.contains(name))
+    }
+
+    fn push(&mut self, option: &'static OptionSpec, value: Option<String>) -> Result<(), String> {
+        if !self.command.accepts(option.scope) {
+            return Err(format!("--{} can't be used with fatass {}.", option.long, self.command.name()));
+        }
+        if !option.repeatable && self.flag(option.long) {
+            return Err(format!("--{} was given more than once.", option.long));
+        }
+        self.occurrences.push((option.long, value));
+        Ok(())
+    }
+
+    fn push_positional(&mut self, arg: &str) -> Result<(), String> {
+        let Some(name) = self.command.positional() else {
+            return Err(format!("fatass {} doesn't take arguments, got '{}'.", self.command.name(), arg));
+        };
+        self.occurrences.push((name, Some(arg.to_string())));
+        Ok(())
+    }
+
+    // Combinations parsing alone lets through but that can't do anything sensible
+    fn check(&self) -> Result<(), String> {
+        if self.value("retries").and_then(|value| value.parse::<u32>().ok()).is_some_and(|retries| retries > MAX_RETRIES) {
+            return Err(format!("--retries can't be more than {}, a path still failing by then isn't coming back.", MAX_RETRIES));
+        }
+        if self.flag("quiet") && self.flag("verbose") {
+            return Err("--quiet and --verbose can't be used together.".to_string());
+        }
+        if self.value("progress") == Some("json") && self.flag("progress-bytes") {
+            return Err("--progress-bytes sizes the progress bar, it can't be used with --progress json.".to_string());
+        }
+        if self.flag("print0") && (self.flag("group-by") || self.flag("compare") || self.flag("tui")) {
+            return Err("--print0 only prints paths, it can't be used with --group-by, --compare or --tui.".to_string());
+        }
+        if self.value("format").is_some() && (self.flag("print0") || self.flag("group-by") || self.flag("compare") || self.flag("tui")) {
+            return Err("--format writes its own lines, it can't be used with --print0, --group-by, --compare or --tui.".to_string());
+        }
+        if self.flag("smallest") && self.flag("empty") {
+            return Err("--smallest and --empty can't be used together.".to_string());
+        }
+        if self.flag("empty") && self.flag("min-size") {
+            return Err("--empty lists zero byte entries, it can't be used with --min-size.".to_string());
+        }
+        if self.command == Command::S3 && self.flag("remote") {
+            return Err("fatass s3 lists a bucket, it can't be used with --remote.".to_string());
+        }
+        if self.flag("remote") || self.command == Command::S3 {
+            let listed_by = if self.command == Command::S3 { "fatass s3 lists the objects with the aws CLI" } else { "--remote lists the files on the other machine" };
+            if self.command == Command::Clean {
+                return Err("fatass clean deletes files here, it can't be used with --remote.".to_string());
+            }
+            if matches!(self.command, Command::Offload | Command::Compress) {
+                return Err(format!("fatass {} changes files here, it can't be used with --remote.", self.command.name()));
+            }
+            let local = ["path", "files-from", "fast", "ads", "apfs", "physical", "xattrs", "show-xattrs", "fs-usage", "estimate", "io-limit", "free-target", "delete", "trash", "emit-script", "exec", "open", "tui", "empty", "hash", "detect-type", "peek-archives", "git-aware", "respect-gitignore"];
+            if let Some(long) = local.into_iter().find(|long| self.flag(long)) {
+                return Err(format!("{}, it can't be used with --{}.", listed_by, long));
+            }
+            if self.value("output") == Some("ncdu") {
+                return Err(format!("--output ncdu walks the tree here, it can't be used with {}.", if self.command == Command::S3 { "fatass s3" } else { "--remote" }));
+            }
+        }
+        if self.flag("fast") {
+            let walked = ["files-from", "ads", "physical", "xattrs", "empty", "hash", "detect-type", "peek-archives", "git-aware", "respect-gitignore"];
+            if let Some(long) = walked.into_iter().find(|long| self.flag(long)) {
+                return Err(format!("--fast reads the master file table instead of walking, it can't be used with --{}.", long));
+            }
+            if self.value("output") == Some("ncdu") {
+                return Err("--output ncdu walks the tree, it can't be used with --fast.".to_string());
+            }
+        }
+        if self.flag("ads-entries") && !self.flag("ads") {
+            return Err("--ads-entries only changes how --ads counts the streams, it needs it.".to_string());
+        }
+        if self.flag("skip-junctions") && !self.flag("follow-symlinks") {
+            return Err("--skip-junctions only changes what --follow-symlinks walks into, it needs it.".to_string());
+        }
+        if self.flag("git-aware") && self.flag("empty") {
+            return Err("--git-aware can't be used with --empty.".to_string());
+        }
+        if self.flag("rank-expr") && (self.flag("rank") || self.flag("smallest") || self.flag("empty")) {
+            return Err("--rank-expr can't be used with --rank, --smallest or --empty.".to_string());
+        }
+        let stale = self.value("rank") == Some("stale");
+        if stale && (self.flag("smallest") || self.flag("empty")) {
+            return Err("--rank stale can't be used with --smallest or --empty.".to_string());
+        }
+        if stale && (self.command == Command::Dirs || self.flag("dirs")) {
+            return Err("--rank stale weighs files by their age, it can't be used with fatass dirs.".to_string());
+        }
+        let ranking = ["smallest", "empty", "rank-expr", "rank"].into_iter().find(|long| if *long == "rank" { stale } else { self.flag(long) });
+        if let Some(long) = ranking {
+            if let Some(other) = ["group-by", "tui", "fail-if-over", "notify-if-over"].into_iter().find(|other| self.flag(other)) {
+                return Err(format!("--{} can't be used with --{}.", long, other));
+            }
+        }
+        if self.command == Command::Inodes {
+            if let Some(long) = ["min-size", "progress-bytes"].into_iter().find(|long| self.flag(long)) {
+                return Err(format!("fatass inodes counts entries whatever their size, it can't be used with --{}.", long));
+            }
+        }
+        if self.flag("per-group") && !self.flag("group-by") {
+            return Err("--per-group only applies with --group-by.".to_string());
+        }
+        if let Some(long) = ["max-files", "timeout"].into_iter().find(|long| self.flag(long) && self.flag("group-by")) {
+            return Err(format!("--group-by sums every file it walks, it can't be used with --{}.", long));
+        }
+        let deleting = self.flag("delete") || self.flag("trash") || self.command == Command::Clean;
+        if self.value("files-from") == Some("-") && deleting && !self.flag("yes") {
+            return Err("--files-from - reads stdin, which leaves nothing to confirm deletions with, add --yes.".to_string());
+        }
+        if self.flag("yes") && !deleting {
+            return Err("--yes only applies with --delete, --trash or fatass clean.".to_string());
+        }
+        if self.flag("tui") && deleting {
+            return Err("--tui can't be used with --delete, --trash or fatass clean.".to_string());
+        }
+        if self.flag("tui") && self.flag("output-file") {
+            return Err("--tui can't be used with --output-file.".to_string());
+        }
+        if self.flag("compare") && (deleting || self.flag("tui")) {
+            return Err("--compare can't be used with --delete, --trash, --tui or fatass clean.".to_string());
+        }
+        if self.command == Command::Serve {
+            if let Some(long) = ["output", "output-file"].into_iter().find(|long| self.flag(long)) {
+                return Err(format!("fatass serve answers in HTML over HTTP, it can't be used with --{}.", long));
+            }
+            if self.flag("from-snapshot") && self.flag("path") {
+                return Err("--from-snapshot serves the files of the snapshot, it can't be used with search paths.".to_string());
+            }
+        }
+        if self.flag("nice") && self.flag("idle") {
+            return Err("--idle lowers the priority further than --nice, give one of them.".to_string());
+        }
+        if self.flag("no-cache") && self.flag("refresh") {
+            return Err("--refresh updates the cache, it can't be used with --no-cache.".to_string());
+        }
+        if self.flag("tui") && (self.flag("save-snapshot") || self.flag("export-sqlite") || self.flag("export-parquet")) {
+            return Err("--tui can't be used with --save-snapshot, --export-sqlite or --export-parquet.".to_string());
+        }
+        if self.command == Command::Dirs || self.flag("dirs") {
+            if let Some(long) = ["export-sqlite", "export-parquet"].into_iter().find(|long| self.flag(long)) {
+                return Err(format!("--{} writes files, it can't be used with fatass dirs.", long));
+            }
+        }
+        if self.command == Command::Offload && !self.flag("dest") {
+            return Err("fatass offload needs --dest, the directory to move the results under.".to_string());
+        }
+        if matches!(self.command, Command::Offload | Command::Compress) {
+            if let Some(long) = ["dirs", "delete", "trash", "exec", "emit-script", "tui", "compare", "group-by"].into_iter().find(|long| self.flag(long)) {
+                return Err(format!("fatass {} changes files, it can't be used with --{}.", self.command.name(), long));
+            }
+        }
+        if let Some(long) = ["open", "copy"].into_iter().find(|long| self.flag(long)) {
+            if self.command == Command::Watch {
+                return Err(format!("--{} can't be used with fatass watch.", long));
+            }
+            if let Some(other) = ["tui", "group-by", "compare"].into_iter().find(|other| self.flag(other)) {
+                return Err(format!("--{} can't be used with --{}.", long, other));
+            }
+        }
+        if self.flag("estimate") {
+            if self.command != Command::Scan {
+                return Err(format!("--estimate samples the tree instead of scanning it, it can't be used with fatass {}.", self.command.name()));
+            }
+            let scanning = ["dirs", "fast", "empty", "stats", "max-files", "save-snapshot", "compare", "export-sqlite", "export-parquet", "tui", "group-by", "delete", "trash", "exec", "emit-script", "open", "copy", "free-target", "take-bytes"];
+            if let Some(long) = scanning.into_iter().find(|long| self.flag(long)) {
+                return Err(format!("--estimate samples the tree instead of scanning it, it can't be used with --{}.", long));
+            }
+            if self.value("output").is_some_and(|output| !["table", "plain"].contains(&output)) {
+                return Err("--estimate writes a report, it can only be used with --output table or plain.".to_string());
+            }
+        }
+        if self.flag("limit") && self.flag("count") {
+            return Err("--limit is the same as --count, give one of them.".to_string());
+        }
+        if self.flag("top-percent") {
+            if let Some(long) = ["count", "limit", "free-target"].into_iter().find(|long| self.flag(long)) {
+                return Err(format!("--top-percent sets how many results to show, it can't be used with --{}.", long));
+            }
+        }
+        if let Some(long) = ["offset", "top-percent"].into_iter().find(|long| self.flag(long)) {
+            if self.command == Command::Watch {
+                return Err(format!("--{} can't be used with fatass watch.", long));
+            }
+            if let Some(other) = ["group-by", "tui", "compare", "free-target"].into_iter().find(|other| self.flag(other)) {
+                return Err(format!("--{} can't be used with --{}.", long, other));
+            }
+        }
+        if self.flag("exec-parallel") && !self.flag("exec") {
+            return Err("--exec-parallel only applies with --exec.".to_string());
+        }
+        if self.flag("exec") {
+            if deleting || matches!(self.command, Command::Watch) {
+                return Err("--exec runs on the results as they are, it can't be used with --delete, --trash, fatass clean or fatass watch.".to_string());
+            }
+            if let Some(long) = ["group-by", "tui", "compare"].into_iter().find(|long| self.flag(long)) {
+                return Err(format!("--exec can't be used with --{}.", long));
+            }
+        }
+        if !self.flag("emit-script") {
+            if let Some(long) = ["script-action", "script-dest"].into_iter().find(|long| self.flag(long)) {
+                return Err(format!("--{} only applies with --emit-script.", long));
+            }
+        }
+        if (self.value("script-action") == Some("mv")) != self.flag("script-dest") {
+            return Err("--script-action mv and --script-dest go together.".to_string());
+        }
+        if self.flag("emit-script") {
+            if deleting || matches!(self.command, Command::Watch) {
+                return Err("--emit-script leaves the results to the script, it can't be used with --delete, --trash, fatass clean or fatass watch.".to_string());
+            }
+            if let Some(long) = ["group-by", "tui", "compare"].into_iter().find(|long| self.flag(long)) {
+                return Err(format!("--emit-script can't be used with --{}.", long));
+            }
+        }
+        if self.flag("stats") && (self.command == Command::Dirs || self.flag("dirs")) {
+            return Err("--stats sums up files, it can't be used with fatass dirs.".to_string());
+        }
+        if let Some(long) = ["stats", "fs-usage"].into_iter().find(|long| self.flag(long)) {
+            if self.flag("tui") || self.flag("compare") {
+                return Err(format!("--{} can't be used with --tui or --compare.", long));
+            }
+        }
+        if self.flag("free-target") {
+            if matches!(self.command, Command::Dirs | Command::Clean | Command::Watch) || self.flag("dirs") {
+                return Err(format!("--free-target plans which files to remove, it can't be used with fatass {}.", self.command.name()));
+            }
+            if let Some(long) = ["smallest", "empty", "rank-expr", "group-by", "tui", "compare"].into_iter().find(|long| self.flag(long)) {
+                return Err(format!("--free-target plans which files to remove, it can't be used with --{}.", long));
+            }
+        }
+        if self.flag("take-bytes") {
+            if matches!(self.command, Command::Dirs | Command::Watch) || self.flag("dirs") {
+                return Err(format!("--take-bytes adds up files, it can't be used with fatass {}.", self.command.name()));
+            }
+            let rejected = ["count", "limit", "offset", "top-percent", "free-target", "smallest", "empty", "group-by", "tui", "compare"];
+            if let Some(long) = rejected.into_iter().find(|long| self.flag(long)) {
+                return Err(format!("--take-bytes sets how many results to show, it can't be used with --{}.", long));
+            }
+        }
+        let notify_limits = self.flag("notify-if-over") || self.flag("notify-if-total-over");
+        if self.flag("notify-webhook") != notify_limits {
+            return Err("--notify-webhook and --notify-if-over or --notify-if-total-over go together.".to_string());
+        }
+        if let Some(long) = ["fail-if-over", "fail-if-total-over", "notify-webhook"].into_iter().find(|long| self.flag(long)) {
+            if self.flag("tui") || self.flag("compare") {
+                return Err(format!("--{} can't be used with --tui or --compare.", long));
+            }
+        }
+        if self.flag("group-by") {
+            if matches!(self.command, Command::Dirs | Command::Clean | Command::Watch) || self.flag("dirs") {
+                return Err(format!("--group-by can't be used with fatass {}.", self.command.name()));
+            }
+            let rejected = ["tui", "stats", "fs-usage", "save-snapshot", "export-sqlite", "export-parquet", "compare", "fail-if-over", "fail-if-total-over", "notify-webhook", "delete", "trash"];
+            if let Some(long) = rejected.into_iter().find(|long| self.flag(long)) {
+                return Err(format!("--group-by can't be used with --{}.", long));
+            }
+        }
+        if self.command == Command::Watch {
+            let rejected = ["delete", "trash", "tui", "save-snapshot", "export-sqlite", "export-parquet", "compare", "stats", "fs-usage", "fail-if-over", "fail-if-total-over"];
+            if let Some(long) = rejected.into_iter().find(|long| self.flag(long)) {
+                return Err(format!("--{} can't be used with fatass watch.", long));
+            }
+        }
+        Ok(())
+    }
+
+    // The value of an option as parsed, none when it wasn't given, the error when it doesn't parse
+    fn parsed<T>(&self, long: &str, parse: impl FnOnce(&str) -> Option<T>, error: &str) -> Result<Option<T>, String> {
+        self.value(long).map(|value| parse(value).ok_or_else(|| error.to_string())).transpose()
+    }
+
+    // Whether colors were asked for or against, on auto the colored crate already looks at
+    // NO_COLOR, CLICOLOR and whether stdout is a terminal
+    pub fn color(&self) -> Result<ColorChoice, String> {
+        let color = self.parsed("color", ColorChoice::parse, "Invalid color value. Please provide one of: auto, always, never.")?;
+        Ok(color.unwrap_or(ColorChoice::Auto))
+    }
+
+    // Quiet, or each -v telling more
+    pub fn verbosity(&self) -> Verbosity {
+        match (self.flag("quiet"), self.count("verbose")) {
+            (true, _) => Verbosity::Quiet,
+            (false, 0) => Verbosity::Normal,
+            (false, 1) => Verbosity::Verbose,
+            (false, _) => Verbosity::Trace,
+        }
+    }
+
+    // The one shell fatass completions writes the script of
+    pub fn shell(&self) -> Result<Shell, String> {
+        let shells = self.values("shell");
+        shells.first().and_then(|value| Shell::parse(value)).filter(|_| shells.len() == 1)
+            .ok_or_else(|| format!("Please provide one shell among: {}.", Shell::NAMES.join(", ")))
+    }
+
+    // The typed options, the settings of the config file, or of the profile picked, filling in the
+    // ones that weren't given
+    pub fn options(&self, config: &Config) -> Result<Options, String> {
+        Options::new(self, config)
+    }
+}
+
+// What the options ask for once parsed and checked. Whatever the arguments and the config file are
+// enough to tell wrong is rejected here, what needs the filesystem is left to the commands
+pub struct Options {
+    // Results listed, with the count of --top for fatass export-metrics
+    pub count: usize,
+    // Results left out before the ones listed
+    pub offset: usize,
+    pub top_percent: Option<f64>,
+    // Fattest files exported as metrics
+    pub metrics_top: usize,
+    pub threads: usize,
+    pub priority: Option<Priority>,
+    // Bytes per second
+    pub io_limit: Option<u64>,
+    pub json_progress: bool,
+    pub output_format: OutputFormat,
+    pub output_file: Option<PathBuf>,
+    // The table width and the completeness of the scan are left for the commands to fill in
+    pub output_options: OutputOptions,
+    pub unit_style: UnitStyle,
+    // None keeps the results in the order they were ranked
+    pub sort_key: Option<SortKey>,
+    pub reverse: bool,
+    pub rank: Rank,
+    pub group_key: Option<GroupKey>,
+    pub per_group: usize,
+    // What the results shown get read for once the scan is over
+    pub detect_type: bool,
+    pub list_xattrs: bool,
+    pub hash_algorithm: Option<HashAlgorithm>,
+    pub show_percent: bool,
+    // --open and --copy with the number of the result they name, from 1
+    pub hand_over: Vec<(&'static str, usize)>,
+    pub compressor: Compressor,
+    pub exec_command: Option<ExecCommand>,
+    pub exec_parallel: usize,
+    pub script_action: ScriptAction,
+    pub dirs_mode: bool,
+    // Deleting, trashing or cleaning the results once they are shown
+    pub deleting: bool,
+    // A remote machine or a bucket listing the files instead of the local filesystem
+    pub source: Option<Arc<dyn FileSource>>,
+    pub min_size: u64,
+    pub hardlinks: HardlinkMode,
+    pub max_depth: Option<usize>,
+    pub retries: u32,
+    pub max_files: Option<u64>,
+    pub timeout: Option<Duration>,
+    pub interval: Duration,
+    pub modified_before: Option<SystemTime>,
+    pub modified_after: Option<SystemTime>,
+    pub fail_if_over: Option<u64>,
+    pub fail_if_total_over: Option<u64>,
+    pub notify_if_over: Option<u64>,
+    pub notify_if_total_over: Option<u64>,
+    pub free_target: Option<u64>,
+    pub byte_budget: Option<u64>,
+    pub extensions: Vec<String>,
+    pub excludes: Vec<Glob>,
+    pub includes: Vec<Glob>,
+    pub path_regexes: Vec<Regex>,
+    pub path_regex_excludes: Vec<Regex>,
+    pub respect_gitignore: bool,
+    pub fatassignore: bool,
+    pub hidden: bool,
+    pub git_aware: bool,
+    pub port: u16,
+}
+
+impl Options {
+    fn new(matches: &Matches, config: &Config) -> Result<Options, String> {
+        let command = matches.command;
+        // A profile's settings go on top of the defaults
+        let settings = match matches.value("profile") {
+            Some(name) => match config.profile(name) {
+                Some(settings) => settings,
+                None if config.profiles.is_empty() => return Err(format!("Unknown profile '{}', the config file defines none.", name)),
+                None => return Err(format!("Unknown profile '{}'. Please provide one of: {}.", name, config.profile_names().join(", "))),
+            },
+            None => config.settings.clone(),
+        };
+        let number = |long: &str| matches.parsed(long, |value| value.parse::<usize>().ok(), &format!("Invalid {} value. Please provide a valid number.", long.replace('-', " ")));
+        let positive = |long: &str| {
+            let error = format!("Invalid {} value. Please provide a number greater than 0.", long);
+            matches.parsed(long, |value| value.parse::<usize>().ok().filter(|number| *number > 0), &error)
+        };
+        let size = |long: &str| matches.parsed(long, parse_size, &format!("Invalid --{} value. Please provide a size like 500M, 2GiB or 4096.", long));
+        let dirs_mode = command == Command::Dirs || (matches!(command, Command::Watch | Command::S3) && matches.flag("dirs"));
+
+        let metrics_top = number("top")?.unwrap_or(10);
+        let count_value = matches.value("count").or(matches.value("limit"));
+        let count = count_value.map(|value| value.parse::<usize>().map_err(|_| "Invalid count value. Please provide a valid number.".to_string())).transpose()?;
+        let mut count = count.or(settings.count).unwrap_or(100);
+        if command == Command::ExportMetrics {
+            count = metrics_top;
+        }
+        let top_percent = matches.parsed("top-percent", |value| value.trim_end_matches('%').parse::<f64>().ok().filter(|percent| *percent > 0.0 && *percent <= 100.0),
+            "Invalid top-percent value. Please provide a number above 0 and up to 100.")?;
+
+        let priority = if matches.flag("idle") { Some(Priority::Idle) } else if matches.flag("nice") { Some(Priority::Nice) } else { None };
+        let json_progress = match matches.value("progress") {
+            None | Some("bar") => false,
+            Some("json") => true,
+            Some(_) => return Err("Invalid progress mode. Please provide one of: bar, json.".to_string()),
+        };
+
+        let given_output = matches.parsed("output", OutputFormat::parse, "Invalid output format. Please provide one of: table, json, ndjson, csv, plain, markdown, html, ncdu, treemap.")?;
+        let mut output_format = given_output.or(settings.output).unwrap_or(OutputFormat::Table);
+        // NUL separated paths and line templates are kinds of plain output
+        let not_plain = given_output.is_some_and(|output| output != OutputFormat::Plain);
+        if matches.flag("print0") {
+            if not_plain {
+                return Err("--print0 only applies with --output plain.".to_string());
+            }
+            output_format = OutputFormat::Plain;
+        }
+        let mut template = None;
+        if let Some(template_value) = matches.value("format") {
+            if not_plain {
+                return Err("--format only applies with --output plain.".to_string());
+            }
+            template = Some(Template::parse(template_value).map_err(|err| format!("Invalid format: {}.", err))?);
+            output_format = OutputFormat::Plain;
+        }
+        // ncdu exports and treemaps hold the whole tree of a scan, not the fattest entries of a report
+        if matches!(output_format, OutputFormat::Ncdu | OutputFormat::Treemap) {
+            let rejected = ["dirs", "group-by", "compare", "save-snapshot", "export-sqlite", "export-parquet", "stats", "fail-if-over", "fail-if-total-over", "notify-webhook", "tui", "delete", "trash"];
+            if command != Command::Scan || rejected.iter().any(|name| matches.flag(name)) {
+                let name = if output_format == OutputFormat::Ncdu { "ncdu" } else { "treemap" };
+                return Err(format!("--output {} only applies to fatass scan, without --dirs, --group-by, --compare, --save-snapshot, the exports, --stats, the --fail-if and --notify options, --tui, --delete or --trash.", name));
+            }
+        }
+
+        let sort_key = matches.parsed("sort", SortKey::parse, "Invalid sort key. Please provide one of: size, mtime, atime, name, ext.")?.or(settings.sort).unwrap_or(SortKey::Size);
+        let mut rank = matches.parsed("rank", Rank::parse, "Invalid rank. Please provide one of: size, stale.")?
+            .unwrap_or(if matches.flag("smallest") { Rank::Smallest } else { Rank::Size });
+        if let Some(expr_value) = matches.value("rank-expr") {
+            let expr = RankExpr::parse(expr_value).map_err(|err| format!("Invalid ranking expression, {}.", err))?;
+            rank = Rank::Expr(Arc::new(expr));
+        }
+        // The smallest entries are listed smallest first, --reverse turns them around as it does the
+        // fattest. Other rankings stay in the order of their score unless another order is asked for
+        let reverse = matches.flag("reverse") != (rank == Rank::Smallest && sort_key == SortKey::Size);
+        let sort_key = (matches!(rank, Rank::Size | Rank::Smallest) || matches.value("sort").is_some()).then_some(sort_key);
+        let group_key = matches.parsed("group-by", GroupKey::parse, "Invalid group key. Please provide one of: ext, dir, owner, year.")?;
+        let per_group = number("per-group")?.unwrap_or(1);
+
+        // A list naming the path or the size is the whole table in its order, otherwise its columns
+        // follow them
+        let mut listed: Vec<Column> = Vec::new();
+        for list_value in matches.values("columns") {
+            for name in list_value.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+                match Column::parse(name) {
+                    Some(column) if !listed.contains(&column) => listed.push(column),
+                    Some(_) => {}
+                    None => return Err(format!("Unknown column '{}'. Please provide one of: {}.", name, COLUMNS.join(", "))),
+                }
+            }
+        }
+        let hash_error = format!("Invalid hash. Please provide one of: {}.", HashAlgorithm::NAMES.join(", "));
+        let hash_algorithm = matches.parsed("hash", HashAlgorithm::parse, &hash_error)?;
+        let flagged: Vec<Column> = [
+            ("show-percent", Column::Percent),
+            ("show-apparent", Column::ApparentSize),
+            ("show-links", Column::Links),
+            ("show-mtime", Column::Modified),
+            ("show-atime", Column::Accessed),
+            ("detect-type", Column::ContentType),
+            ("show-xattrs", Column::Xattrs),
+        ].into_iter().filter(|(flag, _)| matches.flag(flag)).map(|(_, column)| column).chain(hash_algorithm.map(|_| Column::Checksum)).collect();
+        let columns = if listed.contains(&Column::Path) || listed.contains(&Column::Size) {
+            let mut columns = listed;
+            for column in flagged {
+                if !columns.contains(&column) {
+                    columns.push(column);
+                }
+            }
+            columns
+        } else {
+            let mut wanted = listed;
+            wanted.extend(flagged);
+            // Directories contain each other, their shares don't add up
+            if wanted.contains(&Column::Percent) && !dirs_mode {
+                wanted.push(Column::CumulativePercent);
+            }
+            let order = [
+                Column::Path, Column::Size, Column::Percent, Column::CumulativePercent, Column::ApparentSize, Column::Links,
+                Column::Mode, Column::Owner, Column::Group, Column::Modified, Column::Accessed, Column::ContentType, Column::Checksum, Column::Xattrs, Column::Bar,
+            ];
+            order.into_iter().filter(|column| matches!(column, Column::Path | Column::Size) || wanted.contains(column)).collect()
+        };
+        if dirs_mode && columns.contains(&Column::CumulativePercent) {
+            return Err("Directories contain each other, the cumulative column can't be shown for them.".to_string());
+        }
+        let detect_type = columns.contains(&Column::ContentType);
+        let list_xattrs = columns.contains(&Column::Xattrs);
+        // The checksum column alone hashes with SHA-256
+        let hash_algorithm = columns.contains(&Column::Checksum).then(|| hash_algorithm.unwrap_or(HashAlgorithm::Sha256));
+        let show_percent = columns.iter().any(|column| matches!(column, Column::Percent | Column::CumulativePercent))
+            || template.as_ref().is_some_and(Template::uses_percent);
+
+        let mut hand_over = Vec::new();
+        for long in ["open", "copy"] {
+            let error = format!("Invalid {} value. Please provide the number of a result, from 1.", long);
+            if let Some(rank) = matches.parsed(long, |value| value.parse::<usize>().ok().filter(|rank| *rank > 0), &error)? {
+                hand_over.push((long, rank));
+            }
+        }
+        let compressor_error = format!("Invalid compressor. Please provide one of: {}.", Compressor::NAMES.join(", "));
+        let compressor = matches.parsed("with", Compressor::parse, &compressor_error)?.unwrap_or_default();
+        let exec_command = matches.value("exec").map(ExecCommand::parse).transpose().map_err(|err| format!("Invalid exec command: {}.", err))?;
+        let action_error = format!("Invalid script action. Please provide one of: {}.", ScriptAction::NAMES.join(", "));
+        let script_action = matches.parsed("script-action", ScriptAction::parse, &action_error)?.unwrap_or(ScriptAction::Remove);
+
+        let color_thresholds = match matches.value("color-thresholds") {
+            Some(thresholds_value) => parse_color_thresholds(thresholds_value)
+                .map_err(|err| format!("Invalid color thresholds: {}. Please provide sizes and colors like 10G=red,1G=yellow.", err))?,
+            None => settings.color_thresholds.clone().unwrap_or_default(),
+        };
+        let output_options = OutputOptions {
+            theme: matches.parsed("theme", Theme::parse, "Invalid theme. Please provide one of: default, light, mono.")?.or(settings.theme).unwrap_or_default(),
+            columns,
+            print0: matches.flag("print0"),
+            template,
+            percent_of: None,
+            color_thresholds,
+            path_style: matches.parsed("paths", PathStyle::parse, "Invalid paths value. Please provide one of: relative, absolute.")?.unwrap_or_default(),
+            tilde: matches.flag("tilde"),
+            table_width: None,
+            completeness: None,
+        };
+
+        // The bucket of fatass s3 is searched like a remote
+        let mut source = None;
+        if let Some(remote_value) = matches.value("remote") {
+            let remote_source = remote::parse(remote_value).ok_or_else(|| {
+                format!("Invalid remote '{}'. Please provide it as HOST:PATH, e.g. admin@db1:/var/lib, or as an sftp:// or ftp:// URL.", remote_value)
+            })?;
+            source = Some(remote_source);
+        }
+        if command == Command::S3 {
+            let buckets = matches.values("bucket");
+            let Some(bucket) = buckets.first().and_then(|value| S3Prefix::parse(value)).filter(|_| buckets.len() == 1) else {
+                return Err("Please provide one bucket URL, e.g. s3://bucket/prefix.".to_string());
+            };
+            source = Some(Arc::new(bucket));
+        }
+
+        let mut modified_bounds = [None, None];
+        for (flag, bound) in ["older-than", "newer-than"].into_iter().zip(&mut modified_bounds) {
+            let error = format!("Invalid --{} value. Please provide a duration like 90d, 2w, 6mo or a date like 2023-01-31.", flag);
+            *bound = matches.parsed(flag, |value| parse_time_bound(value, SystemTime::now()), &error)?;
+        }
+        let [modified_before, modified_after] = modified_bounds;
+        if matches.value("notify-webhook").is_some_and(|webhook| !webhook.starts_with("http://") && !webhook.starts_with("https://")) {
+            return Err("Invalid webhook. Please provide an http:// or https:// URL.".to_string());
+        }
+
+        // Every extension, given directly or through a file type, both comma separated and repeatable
+        let mut file_types = FileTypes::default();
+        for (name, type_extensions) in &config.types {
+            file_types.define(name, type_extensions.clone());
+        }
+        let mut extensions = settings.extensions;
+        for name in &settings.types {
+            let type_extensions = file_types.extensions(name)
+                .ok_or_else(|| format!("Invalid config, unknown file type '{}'. Please provide one of: {}.", name, file_types.names().join(", ")))?;
+            extensions.extend(type_extensions.iter().cloned());
+        }
+        for list_value in matches.values("ext") {
+            extensions.extend(list_value.split(',').map(str::trim).filter(|item| !item.is_empty()).map(String::from));
+        }
+        for list_value in matches.values("type") {
+            for item in list_value.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+                let type_extensions = file_types.extensions(item)
+                    .ok_or_else(|| format!("Unknown file type '{}'. Please provide one of: {}.", item, file_types.names().join(", ")))?;
+                extensions.extend(type_extensions.iter().cloned());
+            }
+        }
+
+        let (mut excludes, mut includes) = (settings.excludes, settings.includes);
+        for (flag, globs) in [("exclude", &mut excludes), ("include", &mut includes)] {
+            for glob_value in matches.values(flag) {
+                globs.push(Glob::new(glob_value)?);
+            }
+        }
+        // Presets skip the system paths of a whole drive
+        let (mut path_regexes, mut path_regex_excludes) = (Vec::new(), Vec::new());
+        for preset_value in matches.values("preset") {
+            let preset = Preset::parse(preset_value)
+                .ok_or_else(|| format!("Unknown preset '{}'. Please provide one of: {}.", preset_value, Preset::NAMES.join(", ")))?;
+            path_regex_excludes.extend(preset.path_excludes());
+            excludes.extend(preset.name_excludes());
+        }
+        for (flag, regexes) in [("path-regex", &mut path_regexes), ("path-regex-exclude", &mut path_regex_excludes)] {
+            for regex_value in matches.values(flag) {
+                regexes.push(Regex::new(regex_value)?);
+            }
+        }
+
+        // Respecting gitignore hides dotfiles too, unless explicitly asked otherwise
+        let respect_gitignore = matches.flag("respect-gitignore") || settings.respect_gitignore.unwrap_or(false);
+        let hidden = match matches.last_of(&["hidden", "no-hidden"]) {
+            Some(flag) => flag == "hidden",
+            None => settings.hidden.unwrap_or(!respect_gitignore),
+        };
+        // Git awareness needs the .git directories walked
+        let git_aware = matches.flag("git-aware");
+        if git_aware && (respect_gitignore || !hidden) {
+            return Err("--git-aware needs the .git directories, it can't be used with --respect-gitignore or without hidden files.".to_string());
+        }
+
+        Ok(Options {
+            count,
+            offset: number("offset")?.unwrap_or(0),
+            top_percent,
+            metrics_top,
+            threads: positive("threads")?.or(settings.threads).unwrap_or(1),
+            priority,
+            io_limit: matches.parsed("io-limit", throttle::parse_rate, "Invalid I/O limit. Please provide a rate like 50MB/s.")?,
+            json_progress,
+            output_format,
+            output_file: matches.value("output-file").map(PathBuf::from),
+            output_options,
+            unit_style: matches.parsed("units", UnitStyle::parse, "Invalid units. Please provide one of: binary, si, bytes, legacy.")?.or(settings.units).unwrap_or_default(),
+            sort_key,
+            reverse,
+            rank,
+            group_key,
+            per_group,
+            detect_type,
+            list_xattrs,
+            hash_algorithm,
+            show_percent,
+            hand_over,
+            compressor,
+            exec_command,
+            exec_parallel: positive("exec-parallel")?.unwrap_or(1),
+            script_action,
+            dirs_mode,
+            // Trashing is a recoverable kind of deletion, so --trash alone is enough to start it, clean always does
+            deleting: matches.flag("delete") || matches.flag("trash") || command == Command::Clean,
+            source,
+            min_size: matches.parsed("min-size", parse_size, "Invalid min size value. Please provide a size like 500M, 2GiB or 4096.")?.or(settings.min_size).unwrap_or(0),
+            hardlinks: matches.parsed("count-hardlinks", HardlinkMode::parse, "Invalid hardlinks mode. Please provide one of: once, all.")?.unwrap_or(HardlinkMode::All),
+            max_depth: number("max-depth")?,
+            retries: matches.parsed("retries", |value| value.parse::<u32>().ok(), "Invalid retries value. Please provide a valid number.")?.unwrap_or(DEFAULT_RETRIES),
+            max_files: matches.parsed("max-files", |value| value.parse::<u64>().ok().filter(|files| *files > 0), "Invalid max files value. Please provide a number above 0.")?,
+            timeout: matches.parsed("timeout", parse_interval, "Invalid timeout. Please provide a duration like 30s, 5m or 1h.")?,
+            interval: matches.parsed("interval", parse_interval, "Invalid interval. Please provide a duration like 30s, 5m or 1h.")?
+                .unwrap_or(Duration::from_secs(if command == Command::Daemon { 6 * 60 * 60 } else { 60 })),
+            modified_before,
+            modified_after,
+            fail_if_over: size("fail-if-over")?,
+            fail_if_total_over: size("fail-if-total-over")?,
+            notify_if_over: size("notify-if-over")?,
+            notify_if_total_over: size("notify-if-total-over")?,
+            free_target: size("free-target")?,
+            byte_budget: size("take-bytes")?,
+            extensions,
+            excludes,
+            includes,
+            path_regexes,
+            path_regex_excludes,
+            respect_gitignore,
+            fatassignore: !matches.flag("no-fatassignore") && settings.fatassignore.unwrap_or(true),
+            hidden,
+            git_aware,
+            port: matches.parsed("port", |value| value.parse::<u16>().ok(), "Invalid port. Please provide a number from 0 to 65535.")?.unwrap_or(8080),
+        })
+    }
+}
+
+// Parse the arguments following the program name, the error reads well after "Error: "
+pub fn parse(args: &[String]) -> Result<Matches, String> {
+    let mut rest = args;
+    let mut matches = Matches { command: Command::Scan, explicit_command: false, occurrences: Vec::new() };
+    if let Some(command) = rest.first().and_then(|arg| Command::parse(arg)) {
+        matches.command = command;
+        matches.explicit_command = true;
+        rest = &rest[1..];
+    }
+
+    let mut index = 0;
+    while index < rest.len() {
+        let arg = &rest[index];
+        index += 1;
+
+        if arg == "--" {
+            for path in &rest[index..] {
+                matches.push_positional(path)?;
+            }
+            break;
+        }
+
+        if let Some(long) = arg.strip_prefix("--") {
+            let (name, attached) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (long, None),
+            };
+            let option = find_long(name).ok_or_else(|| format!("Unknown option '--{}'. Run fatass --help to see the options.", name))?;
+            let value = match (option.value, attached) {
+                (None, Some(_)) => return Err(format!("--{} doesn't take a value.", option.long)),
+                (None, None) => None,
+                (Some(_), Some(value)) => Some(value),
+                (Some(_), None) => {
+                    let value = rest.get(index).ok_or_else(|| format!("No value provided after --{} option.", option.long))?;
+                    index += 1;
+                    Some(value.clone())
+                }
+            };
+            matches.push(option, value)?;
+            continue;
+        }
+
+        // Short flags can be grouped, like -dx, the first one taking a value ends the group: -c50
+        if let Some(shorts) = arg.strip_prefix('-').filter(|shorts| !shorts.is_empty()) {
+            for (position, short) in shorts.char_indices() {
+                let option = find_short(short).ok_or_else(|| format!("Unknown option '-{}'. Run fatass --help to see the options.", short))?;
+                if option.value.is_none() {
+                    matches.push(option, None)?;
+                    continue;
+                }
+                let attached = &shorts[position + short.len_utf8()..];
+                let value = if !attached.is_empty() {
+                    attached.strip_prefix('=').unwrap_or(attached).to_string()
+                } else {
+                    let value = rest.get(index).ok_or_else(|| format!("No value provided after -{} option.", short))?;
+                    index += 1;
+                    value.clone()
+                };
+                matches.push(option, Some(value))?;
+                break;
+            }
+            continue;
+        }
+
+        matches.push_positional(arg)?;
+    }
+
+    // --dirs predates the dirs command
+    if matches.command == Command::Scan && matches.flag("dirs") {
+        matches.command = Command::Dirs;
+    }
+    matches.check()?;
+    Ok(matches)
+}
+
+fn print_options(command: Command) {
+    let types = FileTypes::default().names().join(", ");
+    for option in OPTIONS.iter().filter(|option| command.accepts(option.scope)) {
+        let mut usage = format!("--{}", option.long);
+        if let Some(short) = option.short {
+            usage.push_str(&format!(", -{}", short));
+        }
+        if let Some(value) = option.value {
+            usage.push_str(&format!(" <{}>", value));
+        }
+        println!("  {:<20} {}", usage, option.help.replace("{types}", &types));
+    }
+}
+
+pub fn print_help(command: Command, explicit_command: bool) {
+    if explicit_command {
+        let positional = match command.positional() {
+            Some("shell") => " <SHELL>",
+            Some("snapshot") => " <OLD> <NEW>",
+            Some("bucket") => " <s3://BUCKET/PREFIX>",
+            Some(_) => " [PATH]...",
+            None => "",
+        };
+        println!("Usage: fatass {} [OPTIONS]{}", command.name(), positional);
+        println!("\n{}", command.about());
+        println!("\nOptions:");
+        print_options(command);
+        return;
+    }
+
+    println!("Usage: fatass [COMMAND] [OPTIONS] [PATH]...");
+
+    println!("\nCommands:");
+    for command in Command::ALL {
+        println!("  {:<20} {}", command.name(), command.about());
+    }
+
+    println!("\nOptions:");
+    print_options(command);
+    println!("  Values can be attached too: --count=50, -c50");
+
+    println!("\nExamples:");
+    println!("  fatass --path /some/path --count 50");
+    println!("  fatass -p /another/path -c 75");
+    println!("  fatass /home /var /srv");
+    println!("  fatass dirs ~ --max-depth 2");
+    println!("  fatass --sort mtime -c 20");
+    println!("  fatass --show-mtime --show-atime");
+    println!("  fatass /srv --columns owner,group,mode");
+    println!("  fatass /srv --columns size,mtime,path -o csv");
+    println!("  fatass -c 12 --show-percent");
+    println!("  fatass -c 12 --columns percent,bar");
+    println!("  fatass /scratch --detect-type --min-size 1G");
+    println!("  fatass /srv/images -c 5 --hash sha256 -o json");
+    println!("  fatass /backups -c 5 --peek-archives");
+    println!("  fatass ~/src --git-aware");
+    println!("  fatass --color-thresholds 10G=red,1G=yellow");
+    println!("  fatass ~/projects --paths relative --full-paths");
+    println!("  fatass ~ --tilde");
+    println!("  fatass / -c 500 --no-pager");
+    println!("  fatass --disk-usage --show-apparent");
+    println!("  fatass --count-hardlinks once --show-links");
+    println!("  fatass -p /mnt/nfs -t 16");
+    println!("  fatass dupes /srv/media --progress-bytes");
+    println!("  fatass /data -o json --progress json 2> progress.ndjson");
+    println!("  fatass /srv -vv --exclude '*.log'");
+    println!("  fatass / -x");
+    println!("  sudo fatass / --preset system-scan");
+    println!("  fatass C:\\ --fast -c 50");
+    println!("  fatass C:\\Users --follow-symlinks --skip-junctions");
+    println!("  fatass D:\\Downloads --ads --ads-entries");
+    println!("  fatass ~/Library --apfs --disk-usage");
+    println!("  fatass /srv/btrfs --physical --show-apparent");
+    println!("  fatass ~/Pictures --xattrs --show-xattrs");
+    println!("  fatass /mnt/export --max-depth 2");
+    println!("  fatass -o json | jq '.results[0].path'");
+    println!("  fatass ~/tmp --older-than 1y --print0 | xargs -0 rm");
+    println!("  find /srv -name '*.bak' -print0 | fatass --files-from -");
+    println!("  fatass --remote admin@db1:/var/lib -c 20");
+    println!("  fatass --remote sftp://admin@nas/share --group-by dir");
+    println!("  fatass /srv --format '{{bytes}}\\t{{owner}}\\t{{mtime_epoch}}\\t{{path}}'");
+    println!("  fatass --min-size 1G");
+    println!("  fatass ~/projects --smallest -c 20");
+    println!("  fatass /srv --rank stale --show-atime");
+    println!("  fatass /srv --rank-expr 'size * age_days / (depth + 1)'");
+    println!("  fatass /srv --empty --older-than 30d");
+    println!("  fatass ~ -c 20 --stats");
+    println!("  fatass / -x --fs-usage");
+    println!("  fatass ~ --free-target 100G --delete");
+    println!("  fatass /srv --take-bytes 500G --rank stale");
+    println!("  fatass /mnt/nas --timeout 5m --max-files 1000000");
+    println!("  fatass /mnt/nas --estimate --timeout 30s");
+    println!("  fatass /mnt/nas --idle --io-limit 20MB/s");
+    println!("  fatass target/release -c 5 --fail-if-over 50M --fail-if-total-over 2G");
+    println!("  fatass /srv --notify-webhook https://hooks.slack.com/services/... --notify-if-total-over 500G");
+    println!("  fatass --older-than 90d --min-size 100M");
+    println!("  fatass /home --type video");
+    println!("  fatass /srv --group-by ext");
+    println!("  fatass /home --group-by owner --per-group 3");
+    println!("  fatass --ext log,tmp --ext .bak");
+    println!("  fatass -o csv --output-file fatass.csv");
+    println!("  fatass -o html --output-file report.html");
+    println!("  fatass -o ndjson --streaming | vector --config ingest.toml");
+    println!("  fatass -o ncdu --output-file tree.json && ncdu -f tree.json");
+    println!("  fatass /mnt/nas -o treemap --output-file treemap.svg");
+    println!("  fatass --color always | less -R");
+    println!("  fatass --exclude 'node_modules/**' --exclude '*.iso'");
+    println!("  fatass /srv --path-regex 'backup-\\d{{4}}-\\d{{2}}'");
+    println!("  fatass dupes ~/Pictures --min-size 1M");
+    println!("  fatass inodes /var -c 20");
+    println!("  fatass compressible /var/log --min-size 100M");
+    println!("  sudo fatass docker -c 20");
+    println!("  fatass s3 s3://backups/db/ --dirs --max-depth 2");
+    println!("  AWS_PROFILE=prod fatass s3 s3://logs --group-by year");
+    println!("  fatass clean --min-size 1G");
+    println!("  fatass /var/log --min-size 1G --emit-script rm.sh --script-action compress");
+    println!("  fatass ~/Videos --ext mov --exec 'assets tag {{path}} {{bytes}}' --exec-parallel 4");
+    println!("  fatass offload /srv/media --older-than 180d --min-size 1G --dest /mnt/archive --leave-symlinks");
+    println!("  fatass compress /var/log --min-size 500M --older-than 7d --with zstd");
+    println!("  fatass ~/Downloads -c 5 --copy 1");
+    println!("  fatass /srv -o json --offset 100 --limit 100");
+    println!("  fatass /srv --top-percent 1");
+    println!("  fatass ~ --save-snapshot monday.snap");
+    println!("  fatass ~ --export-sqlite scan.db && sqlite3 scan.db 'SELECT extension, SUM(size) FROM files GROUP BY 1'");
+    println!("  fatass / --export-parquet $(hostname).parquet");
+    println!("  fatass ~ --compare monday.snap");
+    println!("  fatass diff monday.snap friday.snap");
+    println!("  fatass watch /data --interval 5m -c 20");
+    println!("  fatass daemon /data --interval 6h --textfile /var/lib/node_exporter/fatass.prom");
+    println!("  fatass serve /mnt/nas --bind 0.0.0.0 --port 8080");
+    println!("  fatass export-metrics /srv --textfile /var/lib/node_exporter/fatass.prom");
+    println!("  fatass dirs --delete --trash");
+    println!("  fatass completions bash > ~/.local/share/bash-completion/completions/fatass");
+
+    println!("\nConfig:");
+    println!("  Defaults can be set in ~/.config/fatass/config.toml, flags given on the command line win:");
+    println!("    count = 50");
+    println!("    output = \"table\"");
+    println!("    exclude = [\"node_modules/**\", \"*.iso\"]");
+    println!("    units = \"binary\"");
+    println!("    theme = \"mono\"");
+    println!("    color_thresholds = [\"10G=red\", \"1G=yellow\"]");
+    println!("    [types]");
+    println!("    scans = [\"dcm\", \"nii\"]");
+    println!("    [profile.media]");
+    println!("    type = [\"video\", \"audio\"]");
+    println!("    min_size = \"100M\"");
+    println!("  Then: fatass --profile media ~/Downloads");
+
+    println!("\nExit status:");
+    println!("  0 when everything went fine, 1 when something failed, paths couldn't be read or the results are");
+    println!("  partial, the scan interrupted or stopped by --max-files or --timeout, 2 for invalid arguments,");
+    println!("  3 when --fail-if-over or --fail-if-total-over is exceeded.");
+
+    println!("\nNote:");
+    println!("  If the provided path or count value contains spaces, enclose it in quotes.");
+    println!("  Single threaded scans cache the names in each directory in ~/.cache/fatass/scan.cache, only the");
+    println!("  directories whose mtime changed are listed again, every entry is still measured. --streaming");
+    println!("  scans and trees of more than 100000 files aren't cached.");
+    println!("  --output json writes {{\"partial\", \"errors\", \"results\"}}, the results are no longer a top-level array.");
+    println!("  --output plain prints size<TAB>path lines, --print0 the bare paths without their sizes.");
+    println!("  A .fatassignore file, in the syntax of .gitignore, skips what it lists below its directory.");
+    println!("  The fields of --format, with an alignment and width like {{size:>10}}:");
+    println!("  {}.", fatass::template::FIELDS.join(", "));
+    println!("  Run fatass COMMAND --help to see the options of a command.");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_line(line: &str) -> Result<Matches, String> {
+        let args: Vec<String> = line.split_whitespace().map(String::from).collect();
+        parse(&args)
+    }
+
+    #[test]
+    fn values_can_be_attached_or_follow() {
+        for line in ["--count 50", "--count=50", "-c 50", "-c50", "-c=50"] {
+            assert_eq!(parse_line(line).unwrap().value("count"), Some("50"), "{}", line);
+        }
+        assert!(parse_line("--count").is_err());
+        assert!(parse_line("--delete=yes").is_err());
+    }
+
+    #[test]
+    fn paths_keep_their_order() {
+        let matches = parse_line("/a -p /b --sort name /c -- --d").unwrap();
+        assert_eq!(matches.values("path"), ["/a", "/b", "/c", "--d"]);
+        assert_eq!(matches.value("sort"), Some("name"));
+    }
+
+    #[test]
+    fn subcommands_limit_the_options() {
+        assert_eq!(parse_line("dupes /a").unwrap().command, Command::Dupes);
+        assert_eq!(parse_line("inodes /a").unwrap().command, Command::Inodes);
+        assert_eq!(parse_line("compressible /a").unwrap().command, Command::Compressible);
+        assert_eq!(parse_line("docker").unwrap().command, Command::Docker);
+        assert_eq!(parse_line("-dx").unwrap().command, Command::Dirs);
+        assert!(parse_line("dupes --delete").is_err());
+        assert!(parse_line("profiles /a").is_err());
+        assert_eq!(parse_line("completions zsh").unwrap().value("shell"), Some("zsh"));
+        assert!(parse_line("--nope").is_err());
+        assert!(parse_line("--count 1 --count 2").is_err());
+    }
+
+    #[test]
+    fn rejects_bad_combinations() {
+        assert!(parse_line("--yes").is_err());
+        assert!(parse_line("clean --yes").is_ok());
+        assert!(parse_line("--tui --delete").is_err());
+        assert!(parse_line("watch --interval 5m --dirs").is_ok());
+        assert!(parse_line("watch --trash").is_err());
+        assert!(parse_line("--interval 5m").is_err());
+        assert!(parse_line("--group-by owner --delete").is_err());
+        assert!(parse_line("--fail-if-over 1G --tui").is_err());
+        assert!(parse_line("dirs --fail-if-total-over 1T").is_ok());
+        assert!(parse_line("--notify-if-over 1G").is_err());
+        assert!(parse_line("--notify-webhook http://localhost/hook --notify-if-over 1G").is_ok());
+        assert!(parse_line("--offset 100 --limit 100").is_ok());
+        assert!(parse_line("--limit 100 --count 100").is_err());
+        assert!(parse_line("--top-percent 1 -c 5").is_err());
+        assert!(parse_line("--take-bytes 500G").is_ok());
+        assert!(parse_line("--estimate --timeout 10s").is_ok());
+        assert!(parse_line("--estimate --stats").is_err());
+        assert!(parse_line("--take-bytes 500G --offset 10").is_err());
+        assert!(parse_line("--retries 10").is_ok());
+        assert!(parse_line("--retries 20").is_err());
+        assert!(parse_line("--group-by ext --max-files 10").is_err());
+    }
+
+    #[test]
+    fn options_parse_their_values() {
+        let options = |line: &str| parse_line(line).unwrap().options(&Config::default());
+        for line in ["--count x", "--threads 0", "--sort weight", "--min-size lots", "--older-than soon", "--columns path,color", "--exclude [a", "--notify-webhook ftp://host --notify-if-over 1G"] {
+            assert!(options(line).is_err(), "{}", line);
+        }
+        assert!(options("--profile nightly").err().is_some_and(|err| err.contains("defines none")));
+        assert!(options("-o json --print0").is_err());
+        assert!(options("dirs --show-percent --columns cumulative").is_err());
+        assert!(options("--git-aware --respect-gitignore").is_err());
+
+        let parsed = options("--limit 5 --smallest -o csv --ext rs,toml --hidden").unwrap();
+        assert_eq!((parsed.count, parsed.output_format, parsed.hidden), (5, OutputFormat::Csv, true));
+        assert_eq!(parsed.extensions, ["rs", "toml"]);
+        // The smallest come smallest first, unless reversed
+        assert!(parsed.reverse);
+        assert_eq!(options("export-metrics --top 3").unwrap().count, 3);
+        assert_eq!(options("--print0").unwrap().output_format, OutputFormat::Plain);
+    }
+}
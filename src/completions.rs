@@ -0,0 +1,222 @@
+// Completion scripts for `fatass completions <SHELL>`, generated from the option table so every
+// option and the values it takes stay known to the shells
+use fatass::FileTypes;
+
+use crate::cli::{Command, OptionSpec, Values, OPTIONS};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+impl Shell {
+    pub const NAMES: [&'static str; 4] = ["bash", "zsh", "fish", "powershell"];
+
+    pub fn parse(value: &str) -> Option<Shell> {
+        match value.to_lowercase().as_str() {
+            "bash" => Some(Shell::Bash),
+            "zsh" => Some(Shell::Zsh),
+            "fish" => Some(Shell::Fish),
+            "powershell" | "pwsh" => Some(Shell::Powershell),
+            _ => None,
+        }
+    }
+}
+
+pub fn script(shell: Shell) -> String {
+    match shell {
+        Shell::Bash => bash(),
+        Shell::Zsh => zsh(),
+        Shell::Fish => fish(),
+        Shell::Powershell => powershell(),
+    }
+}
+
+fn command_options(command: Command) -> impl Iterator<Item = &'static OptionSpec> {
+    OPTIONS.iter().filter(move |option| command.accepts(option.scope))
+}
+
+// The finite set of values an option takes, if it has one
+fn choices(option: &OptionSpec) -> Option<Vec<String>> {
+    match option.values {
+        Values::Choices(choices) => Some(choices.iter().map(|choice| choice.to_string()).collect()),
+        Values::Types => Some(FileTypes::default().names().iter().map(|name| name.to_string()).collect()),
+        Values::Any | Values::Path => None,
+    }
+}
+
+fn spellings(option: &OptionSpec) -> Vec<String> {
+    let mut spellings = vec![format!("--{}", option.long)];
+    if let Some(short) = option.short {
+        spellings.push(format!("-{}", short));
+    }
+    spellings
+}
+
+fn command_names() -> Vec<&'static str> {
+    Command::ALL.iter().map(|command| command.name()).collect()
+}
+
+fn bash() -> String {
+    let mut script = String::from("_fatass() {\n");
+    script.push_str("    local cur=\"${COMP_WORDS[COMP_CWORD]}\" prev=\"${COMP_WORDS[COMP_CWORD-1]}\" command=scan\n");
+    script.push_str(&format!("    case \"${{COMP_WORDS[1]}}\" in\n        {}) command=\"${{COMP_WORDS[1]}}\" ;;\n    esac\n\n", command_names().join("|")));
+
+    script.push_str("    case \"$prev\" in\n");
+    for option in OPTIONS.iter().filter(|option| option.value.is_some()) {
+        let reply = match (option.values, choices(option)) {
+            (Values::Path, _) => "COMPREPLY=($(compgen -f -- \"$cur\"))".to_string(),
+            (_, Some(choices)) => format!("COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))", choices.join(" ")),
+            // Free values, nothing to suggest
+            _ => "COMPREPLY=()".to_string(),
+        };
+        script.push_str(&format!("        {})\n            {}\n            return ;;\n", spellings(option).join("|"), reply));
+    }
+    script.push_str("    esac\n\n");
+
+    script.push_str("    if [[ \"$cur\" == -* ]]; then\n        local options\n        case \"$command\" in\n");
+    for command in Command::ALL {
+        let options: Vec<String> = command_options(command).flat_map(spellings).collect();
+        script.push_str(&format!("            {}) options=\"{}\" ;;\n", command.name(), options.join(" ")));
+    }
+    script.push_str("        esac\n        COMPREPLY=($(compgen -W \"$options\" -- \"$cur\"))\n");
+    script.push_str(&format!("    elif [[ \"$command\" == completions ]]; then\n        COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n", Shell::NAMES.join(" ")));
+    script.push_str("    elif [[ \"$command\" == diff ]]; then\n        COMPREPLY=($(compgen -f -- \"$cur\"))\n");
+    script.push_str("    elif [[ \"$command\" != profiles ]]; then\n");
+    script.push_str("        COMPREPLY=($(compgen -d -- \"$cur\"))\n");
+    script.push_str(&format!("        (( COMP_CWORD == 1 )) && COMPREPLY+=($(compgen -W \"{}\" -- \"$cur\"))\n", command_names().join(" ")));
+    script.push_str("    fi\n}\n\ncomplete -o filenames -F _fatass fatass\n");
+    script
+}
+
+// Brackets have a meaning in _arguments specs, quotes end them
+fn zsh_escape(text: &str) -> String {
+    text.replace('\'', "'\\''").replace('[', "\\[").replace(']', "\\]")
+}
+
+fn zsh() -> String {
+    let mut script = String::from("#compdef fatass\n\n");
+    for command in Command::ALL {
+        let mut specs = Vec::new();
+        for option in command_options(command) {
+            let action = match (option.values, choices(option)) {
+                (_, _) if option.value.is_none() => String::new(),
+                (Values::Path, _) => format!(":{}:_files", option.value.unwrap_or_default()),
+                (_, Some(choices)) => format!(":{}:({})", option.value.unwrap_or_default(), choices.join(" ")),
+                _ => format!(":{}: ", option.value.unwrap_or_default()),
+            };
+            let repeat = if option.repeatable { "*" } else { "" };
+            let help = zsh_escape(&option.help.replace("{types}", &FileTypes::default().names().join(", ")));
+            let long_suffix = if option.value.is_some() { "=" } else { "" };
+            specs.push(format!("'{}--{}{}[{}]{}'", repeat, option.long, long_suffix, help, action));
+            if let Some(short) = option.short {
+                let short_suffix = if option.value.is_some() { "+" } else { "" };
+                specs.push(format!("'{}-{}{}[{}]{}'", repeat, short, short_suffix, help, action));
+            }
+        }
+        match command.positional() {
+            Some("shell") => specs.push(format!("':shell:({})'", Shell::NAMES.join(" "))),
+            Some("snapshot") => specs.extend(["':old snapshot:_files'".to_string(), "':new snapshot:_files'".to_string()]),
+            Some("bucket") => specs.push("':bucket URL: '".to_string()),
+            Some(_) => specs.push("'*:path:_files'".to_string()),
+            None => {}
+        }
+        script.push_str(&format!("_fatass_{}() {{\n    _arguments -s -S \\\n        {}\n", command.name(), specs.join(" \\\n        ")));
+        script.push_str("}\n\n");
+    }
+
+    script.push_str("_fatass() {\n    local -a commands=(\n");
+    for command in Command::ALL {
+        script.push_str(&format!("        '{}:{}'\n", command.name(), zsh_escape(command.about()).replace(':', "\\:")));
+    }
+    script.push_str("    )\n    case $words[2] in\n");
+    script.push_str(&format!("        {})\n", command_names().join("|")));
+    script.push_str("            local command=$words[2]\n            shift words\n            (( CURRENT-- ))\n            _fatass_$command ;;\n");
+    script.push_str("        *)\n            (( CURRENT == 2 )) && _describe 'command' commands\n            _fatass_scan ;;\n    esac\n}\n\n");
+    script.push_str("_fatass \"$@\"\n");
+    script
+}
+
+fn fish() -> String {
+    let mut script = String::from("complete -c fatass -f\n");
+    let names = command_names().join(" ");
+    for command in Command::ALL {
+        let about = command.about().replace('\'', "\\'");
+        script.push_str(&format!("complete -c fatass -n 'not __fish_seen_subcommand_from {}' -a {} -d '{}'\n", names, command.name(), about));
+    }
+
+    // The commands an option can't be used with
+    let excluded = |option: &OptionSpec| -> Vec<&'static str> {
+        Command::ALL.iter().filter(|command| !command.accepts(option.scope)).map(|command| command.name()).collect()
+    };
+    for option in OPTIONS.iter() {
+        let mut line = String::from("complete -c fatass");
+        let excluded = excluded(option);
+        if !excluded.is_empty() {
+            line.push_str(&format!(" -n 'not __fish_seen_subcommand_from {}'", excluded.join(" ")));
+        }
+        line.push_str(&format!(" -l {}", option.long));
+        if let Some(short) = option.short {
+            line.push_str(&format!(" -s {}", short));
+        }
+        if option.value.is_some() {
+            line.push_str(" -r");
+            match (option.values, choices(option)) {
+                (Values::Path, _) => line.push_str(" -F"),
+                (_, Some(choices)) => line.push_str(&format!(" -a '{}'", choices.join(" "))),
+                _ => {}
+            }
+        }
+        let help = option.help.replace("{types}", &FileTypes::default().names().join(", ")).replace('\'', "\\'");
+        line.push_str(&format!(" -d '{}'\n", help));
+        script.push_str(&line);
+    }
+
+    script.push_str(&format!("complete -c fatass -n '__fish_seen_subcommand_from completions' -a '{}'\n", Shell::NAMES.join(" ")));
+    script.push_str("complete -c fatass -n 'not __fish_seen_subcommand_from profiles completions' -F\n");
+    script
+}
+
+fn powershell_list(items: &[String]) -> String {
+    let quoted: Vec<String> = items.iter().map(|item| format!("'{}'", item)).collect();
+    format!("@({})", quoted.join(", "))
+}
+
+fn powershell() -> String {
+    let mut script = String::from("Register-ArgumentCompleter -Native -CommandName fatass -ScriptBlock {\n");
+    script.push_str("    param($wordToComplete, $commandAst, $cursorPosition)\n");
+    let commands: Vec<String> = command_names().iter().map(|name| name.to_string()).collect();
+    script.push_str(&format!("    $commands = {}\n", powershell_list(&commands)));
+
+    script.push_str("    $values = @{\n");
+    for option in OPTIONS.iter() {
+        let Some(choices) = choices(option) else { continue };
+        for spelling in spellings(option) {
+            script.push_str(&format!("        '{}' = {}\n", spelling, powershell_list(&choices)));
+        }
+    }
+    let shells: Vec<String> = Shell::NAMES.iter().map(|name| name.to_string()).collect();
+    script.push_str("    }\n    $options = @{\n");
+    for command in Command::ALL {
+        let options: Vec<String> = command_options(command).flat_map(spellings).collect();
+        script.push_str(&format!("        '{}' = {}\n", command.name(), powershell_list(&options)));
+    }
+    script.push_str("    }\n\n");
+
+    script.push_str("    $words = @($commandAst.CommandElements | ForEach-Object { $_.ToString() })\n");
+    script.push_str("    $command = 'scan'\n");
+    script.push_str("    if ($words.Count -gt 1 -and $commands -contains $words[1]) { $command = $words[1] }\n");
+    script.push_str("    $previous = if ($wordToComplete) { $words[-2] } else { $words[-1] }\n\n");
+    script.push_str("    if ($values.ContainsKey($previous)) { $candidates = $values[$previous] }\n");
+    script.push_str("    elseif ($wordToComplete -like '-*') { $candidates = $options[$command] }\n");
+    script.push_str(&format!("    elseif ($command -eq 'completions') {{ $candidates = {} }}\n", powershell_list(&shells)));
+    script.push_str("    elseif ($words.Count -le 2) { $candidates = $commands }\n");
+    // Nothing returned lets Powershell complete paths
+    script.push_str("    else { return }\n\n");
+    script.push_str("    $candidates | Where-Object { $_ -like \"$wordToComplete*\" } | ForEach-Object {\n");
+    script.push_str("        [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterValue', $_)\n    }\n}\n");
+    script
+}
@@ -0,0 +1,137 @@
+// Compressing files in place with the gzip, zstd or xz command, for fatass compress and the
+// compress action of fatass clean. The compressed file is written beside the original, which is
+// only removed once decompressing it again gives back the very same bytes
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::checksum::{hash_file, hash_reader, HashAlgorithm};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Compressor {
+    #[default]
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+impl Compressor {
+    pub const NAMES: [&'static str; 3] = ["gzip", "zstd", "xz"];
+
+    pub fn parse(value: &str) -> Option<Compressor> {
+        match value {
+            "gzip" => Some(Compressor::Gzip),
+            "zstd" => Some(Compressor::Zstd),
+            "xz" => Some(Compressor::Xz),
+            _ => None,
+        }
+    }
+
+    // The command, named like the compressor
+    pub fn name(self) -> &'static str {
+        Compressor::NAMES[self as usize]
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Compressor::Gzip => "gz",
+            Compressor::Zstd => "zst",
+            Compressor::Xz => "xz",
+        }
+    }
+
+    // Run the command with the arguments then the file, its output going to stdout
+    fn command(self, arguments: &[&str], path: &Path) -> Command {
+        let mut command = Command::new(self.name());
+        command.args(arguments).arg("--").arg(path).stdin(Stdio::null()).stderr(Stdio::piped());
+        command
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    // The original replaced by the compressed file, with the bytes that saved
+    Compressed { saved: u64 },
+    // Compressing didn't make it any smaller, the original was kept
+    NotSmaller,
+}
+
+// The file the compressor writes, FILE.gz, FILE.zst or FILE.xz
+pub fn compressed_path(path: &Path, compressor: Compressor) -> PathBuf {
+    let mut compressed = OsString::from(path.as_os_str());
+    compressed.push(".");
+    compressed.push(compressor.extension());
+    PathBuf::from(compressed)
+}
+
+fn command_error(compressor: Compressor, err: io::Error) -> io::Error {
+    if err.kind() == io::ErrorKind::NotFound {
+        io::Error::other(format!("{} isn't installed", compressor.name()))
+    } else {
+        err
+    }
+}
+
+// Compress the file beside itself, check the result decompresses to the same bytes, then replace
+// the original with it keeping its permissions and modification time
+pub fn compress_file(path: &Path, compressor: Compressor) -> io::Result<Outcome> {
+    let target = compressed_path(path, compressor);
+    if target.symlink_metadata().is_ok() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", target.display())));
+    }
+    let metadata = fs::metadata(path)?;
+    let written = write_compressed(path, &target, compressor).and_then(|_| verify(path, &target, compressor));
+    let compressed_size = match written.and_then(|_| fs::metadata(&target)) {
+        Ok(compressed) => compressed.len(),
+        Err(err) => {
+            let _ = fs::remove_file(&target);
+            return Err(err);
+        }
+    };
+    if compressed_size >= metadata.len() {
+        fs::remove_file(&target)?;
+        return Ok(Outcome::NotSmaller);
+    }
+    fs::set_permissions(&target, metadata.permissions())?;
+    fs::File::options().write(true).open(&target)?.set_modified(metadata.modified()?)?;
+    fs::remove_file(path)?;
+    Ok(Outcome::Compressed { saved: metadata.len() - compressed_size })
+}
+
+fn write_compressed(path: &Path, target: &Path, compressor: Compressor) -> io::Result<()> {
+    let output = fs::File::options().write(true).create_new(true).open(target)?;
+    let result = compressor.command(&["-c"], path).stdout(output).output().map_err(|err| command_error(compressor, err))?;
+    if !result.status.success() {
+        return Err(io::Error::other(String::from_utf8_lossy(&result.stderr).trim().to_string()));
+    }
+    Ok(())
+}
+
+fn verify(path: &Path, target: &Path, compressor: Compressor) -> io::Result<()> {
+    let mut child = compressor.command(&["-d", "-c"], target).stdout(Stdio::piped()).stderr(Stdio::null()).spawn().map_err(|err| command_error(compressor, err))?;
+    // Dropped before waiting, a decompressor left writing to it would never exit
+    let mut stdout = child.stdout.take().unwrap();
+    let decompressed = hash_reader(&mut stdout, HashAlgorithm::Blake3);
+    drop(stdout);
+    let status = child.wait()?;
+    if !status.success() || decompressed? != hash_file(path, HashAlgorithm::Blake3)? {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "the compressed file doesn't decompress to the original"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_compressed_files() {
+        assert_eq!(Compressor::parse("zstd"), Some(Compressor::Zstd));
+        assert_eq!(Compressor::parse("bzip2"), None);
+        assert_eq!(Compressor::Xz.name(), "xz");
+        assert_eq!(compressed_path(Path::new("/var/log/app.log"), Compressor::Zstd), Path::new("/var/log/app.log.zst"));
+        assert_eq!(compressed_path(Path::new("dump"), Compressor::Gzip), Path::new("dump.gz"));
+    }
+}
@@ -0,0 +1,147 @@
+// How much the fattest files would shrink compressed, told from a few blocks spread over each of
+// them rather than their whole content. Each block goes through a quick LZ77 trial: repeats of 4
+// bytes or more cost a few bytes of match, the literals left what their byte entropy says. Logs and
+// dumps come out way smaller, archives and media about as big as they are
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::interrupt::interrupted;
+use crate::scanner::spinner;
+use crate::throttle;
+use crate::{FileEntry, Scanner};
+
+// Blocks read from every file, whole files when they are smaller than all of them together
+const SAMPLE_BLOCKS: u64 = 16;
+const BLOCK_SIZE: usize = 64 * 1024;
+// What a match costs in the compressed stream, its length and its distance
+const MATCH_COST: f64 = 3.0;
+const MIN_MATCH: usize = 4;
+const HASH_BITS: u32 = 14;
+
+#[derive(Debug, Clone)]
+pub struct Compressible {
+    pub path: String,
+    pub size: u64,
+    // The size it would take compressed
+    pub estimate: u64,
+}
+
+impl Compressible {
+    pub fn savings(&self) -> u64 {
+        self.size.saturating_sub(self.estimate)
+    }
+
+    // How many times smaller it would get, 1 for content that doesn't compress
+    pub fn ratio(&self) -> f64 {
+        if self.estimate == 0 { 1.0 } else { self.size as f64 / self.estimate as f64 }
+    }
+}
+
+// Bits per byte of the literals, by their order 0 entropy
+fn entropy(counts: &[u64; 256], total: u64) -> f64 {
+    counts.iter().filter(|count| **count > 0).map(|count| {
+        let probability = *count as f64 / total as f64;
+        -probability * probability.log2()
+    }).sum()
+}
+
+// The bytes the block would take compressed, never more than it already does
+fn compressed_size(block: &[u8]) -> f64 {
+    let mut last_seen = vec![usize::MAX; 1 << HASH_BITS];
+    let mut literals = [0u64; 256];
+    let mut literal_count = 0;
+    let mut matches = 0;
+    let mut pos = 0;
+    while pos < block.len() {
+        if pos + MIN_MATCH <= block.len() {
+            let word = u32::from_le_bytes(block[pos..pos + MIN_MATCH].try_into().unwrap());
+            let hash = (word.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize;
+            let candidate = last_seen[hash];
+            last_seen[hash] = pos;
+            if candidate != usize::MAX && block[candidate..candidate + MIN_MATCH] == block[pos..pos + MIN_MATCH] {
+                let length = block[pos..].iter().zip(&block[candidate..]).take_while(|(a, b)| a == b).count();
+                matches += 1;
+                pos += length;
+                continue;
+            }
+        }
+        literals[block[pos] as usize] += 1;
+        literal_count += 1;
+        pos += 1;
+    }
+    let literal_bytes = if literal_count == 0 { 0.0 } else { literal_count as f64 * entropy(&literals, literal_count) / 8.0 };
+    (literal_bytes + matches as f64 * MATCH_COST).min(block.len() as f64)
+}
+
+// The share of the file left once compressed, from blocks at even intervals
+fn sample_ratio(path: &Path, size: u64) -> io::Result<f64> {
+    let mut file = File::open(path)?;
+    let mut block = vec![0u8; BLOCK_SIZE];
+    let whole = size <= SAMPLE_BLOCKS * BLOCK_SIZE as u64;
+    let blocks = if whole { size.div_ceil(BLOCK_SIZE as u64) } else { SAMPLE_BLOCKS };
+    let (mut read_bytes, mut compressed_bytes) = (0.0, 0.0);
+    for index in 0..blocks {
+        let offset = if whole { index * BLOCK_SIZE as u64 } else { index * (size - BLOCK_SIZE as u64) / (SAMPLE_BLOCKS - 1) };
+        file.seek(SeekFrom::Start(offset))?;
+        let mut filled = 0;
+        while filled < BLOCK_SIZE {
+            match file.read(&mut block[filled..])? {
+                0 => break,
+                read => filled += read,
+            }
+        }
+        throttle::charge(filled as u64);
+        read_bytes += filled as f64;
+        compressed_bytes += compressed_size(&block[..filled]);
+    }
+    Ok(if read_bytes == 0.0 { 1.0 } else { compressed_bytes / read_bytes })
+}
+
+// The fattest files by the space compressing them would save, up to the scanner count
+pub fn estimate_savings(scanner: &Scanner) -> Vec<Compressible> {
+    let files: Vec<FileEntry> = scanner.scan().collect();
+    let progress_bar = spinner(scanner.progress).with_message("(sampling)");
+    let mut candidates: Vec<Compressible> = Vec::new();
+    for file_entry in files {
+        if interrupted() {
+            break;
+        }
+        let path = Path::new(&file_entry.path);
+        // Files gone or unreadable since the walk are left out
+        let Some(ratio) = scanner.errors.io_ok(path, sample_ratio(path, file_entry.size)) else {
+            continue;
+        };
+        progress_bar.inc(1);
+        candidates.push(Compressible {
+            estimate: (file_entry.size as f64 * ratio).round() as u64,
+            size: file_entry.size,
+            path: file_entry.path,
+        });
+    }
+    progress_bar.finish();
+
+    candidates.sort_by(|a, b| b.savings().cmp(&a.savings()).then_with(|| a.path.cmp(&b.path)));
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeats_compress_and_noise_does_not() {
+        let log: Vec<u8> = (0..BLOCK_SIZE).map(|i| b"GET /index.html 200\n"[i % 20]).collect();
+        assert!(compressed_size(&log) < BLOCK_SIZE as f64 / 100.0);
+
+        // Bytes from a xorshift generator, no repeats to find
+        let mut state: u32 = 2463534242;
+        let noise: Vec<u8> = (0..BLOCK_SIZE).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        }).collect();
+        assert!(compressed_size(&noise) > BLOCK_SIZE as f64 * 0.95);
+    }
+}
@@ -0,0 +1,395 @@
+// Defaults loaded from a config file, written in a subset of TOML:
+//
+//   count = 50
+//   output = "table"
+//   exclude = ["node_modules/**", "*.iso"]
+//   units = "binary"
+//   theme = "mono"
+//   color_thresholds = ["10G=red", "1G=yellow"]
+//
+//   [types]
+//   scans = ["dcm", "nii"]
+//
+//   [profile.media]
+//   type = ["video", "audio"]
+//   min_size = "100M"
+//
+// Tables, strings, integers, booleans and arrays of those are understood, command line flags
+// override whatever the file sets.
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::glob::Glob;
+use crate::output::{parse_color_thresholds, ColorThreshold, OutputFormat, Theme};
+use crate::size::{parse_size, UnitStyle};
+use crate::sort::SortKey;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "a string",
+            Value::Integer(_) => "an integer",
+            Value::Boolean(_) => "a boolean",
+            Value::Array(_) => "an array",
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::String(string) => write!(f, "{:?}", string),
+            Value::Integer(number) => write!(f, "{}", number),
+            Value::Boolean(boolean) => write!(f, "{}", boolean),
+            Value::Array(items) => {
+                let items: Vec<String> = items.iter().map(|item| item.to_string()).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
+        }
+    }
+}
+
+// A key set in the file, with the table it belongs to, e.g. "types" or "" at the top
+#[derive(Debug, Clone)]
+struct Entry {
+    table: String,
+    key: String,
+    value: Value,
+    line: usize,
+}
+
+fn parse_string(chars: &[char], pos: &mut usize) -> Result<String, String> {
+    let quote = chars[*pos];
+    *pos += 1;
+    let mut value = String::new();
+    while let Some(&c) = chars.get(*pos) {
+        *pos += 1;
+        if c == quote {
+            return Ok(value);
+        }
+        // Single quoted strings are literal, like in TOML
+        if c == '\\' && quote == '"' {
+            let escaped = chars.get(*pos).copied().ok_or("unterminated string")?;
+            *pos += 1;
+            value.push(match escaped {
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                '"' => '"',
+                '\\' => '\\',
+                other => return Err(format!("unknown escape \\{}", other)),
+            });
+        } else {
+            value.push(c);
+        }
+    }
+    Err("unterminated string".to_string())
+}
+
+fn skip_blank(chars: &[char], pos: &mut usize) {
+    while let Some(&c) = chars.get(*pos) {
+        if c == '#' {
+            while chars.get(*pos).is_some_and(|c| *c != '\n') {
+                *pos += 1;
+            }
+        } else if c.is_whitespace() {
+            *pos += 1;
+        } else {
+            break;
+        }
+    }
+}
+
+fn parse_value(chars: &[char], pos: &mut usize) -> Result<Value, String> {
+    match chars.get(*pos) {
+        Some('"') | Some('\'') => parse_string(chars, pos).map(Value::String),
+        Some('[') => {
+            *pos += 1;
+            let mut items = Vec::new();
+            loop {
+                // Arrays may span several lines and end with a trailing comma
+                skip_blank(chars, pos);
+                if chars.get(*pos) == Some(&']') {
+                    *pos += 1;
+                    return Ok(Value::Array(items));
+                }
+                items.push(parse_value(chars, pos)?);
+                skip_blank(chars, pos);
+                match chars.get(*pos) {
+                    Some(',') => *pos += 1,
+                    Some(']') => {}
+                    _ => return Err("expected ',' or ']' in array".to_string()),
+                }
+            }
+        }
+        Some(_) => {
+            let start = *pos;
+            while chars.get(*pos).is_some_and(|c| !c.is_whitespace() && !matches!(c, ',' | ']' | '#')) {
+                *pos += 1;
+            }
+            let word: String = chars[start..*pos].iter().collect();
+            match word.as_str() {
+                "true" => Ok(Value::Boolean(true)),
+                "false" => Ok(Value::Boolean(false)),
+                _ => word.replace('_', "").parse().map(Value::Integer).map_err(|_| format!("invalid value '{}'", word)),
+            }
+        }
+        None => Err("missing value".to_string()),
+    }
+}
+
+fn parse_key(text: &str) -> Result<String, String> {
+    let text = text.trim();
+    let chars: Vec<char> = text.chars().collect();
+    if chars.first().is_some_and(|c| *c == '"' || *c == '\'') {
+        let mut pos = 0;
+        return parse_string(&chars, &mut pos);
+    }
+    if text.is_empty() || !text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.') {
+        return Err(format!("invalid key '{}'", text));
+    }
+    Ok(text.to_string())
+}
+
+fn parse_entries(content: &str) -> Result<Vec<Entry>, String> {
+    let chars: Vec<char> = content.chars().collect();
+    let mut entries = Vec::new();
+    let mut table = String::new();
+    let mut pos = 0;
+
+    loop {
+        skip_blank(&chars, &mut pos);
+        if pos >= chars.len() {
+            return Ok(entries);
+        }
+        let line = chars[..pos].iter().filter(|c| **c == '\n').count() + 1;
+        let at_line = |err: String| format!("line {}: {}", line, err);
+
+        let line_end = chars[pos..].iter().position(|c| *c == '\n').map_or(chars.len(), |end| pos + end);
+        if chars[pos] == '[' {
+            let header: String = chars[pos + 1..line_end].iter().collect();
+            let header = header.split('#').next().unwrap_or_default().trim_end();
+            let name = header.strip_suffix(']').ok_or_else(|| at_line("unclosed table header".to_string()))?;
+            table = parse_key(name).map_err(at_line)?;
+            pos = line_end;
+            continue;
+        }
+
+        let equals = chars[pos..line_end].iter().position(|c| *c == '=').ok_or_else(|| at_line("expected key = value".to_string()))?;
+        let key: String = chars[pos..pos + equals].iter().collect();
+        let key = parse_key(&key).map_err(at_line)?;
+        pos += equals + 1;
+        while chars.get(pos).is_some_and(|c| *c == ' ' || *c == '\t') {
+            pos += 1;
+        }
+        let value = parse_value(&chars, &mut pos).map_err(at_line)?;
+
+        // Only a comment may follow a value on its line
+        while chars.get(pos).is_some_and(|c| *c == ' ' || *c == '\t' || *c == '\r') {
+            pos += 1;
+        }
+        if !matches!(chars.get(pos), None | Some('\n') | Some('#')) {
+            return Err(at_line("unexpected text after the value".to_string()));
+        }
+        entries.push(Entry { table: table.clone(), key, value, line });
+    }
+}
+
+// Settings a config file may give, none of them set means the built-in default
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    pub count: Option<usize>,
+    pub threads: Option<usize>,
+    pub output: Option<OutputFormat>,
+    pub sort: Option<SortKey>,
+    pub min_size: Option<u64>,
+    pub excludes: Vec<Glob>,
+    pub includes: Vec<Glob>,
+    pub extensions: Vec<String>,
+    // File type names, looked up once the [types] table is known
+    pub types: Vec<String>,
+    pub respect_gitignore: Option<bool>,
+    pub fatassignore: Option<bool>,
+    pub hidden: Option<bool>,
+    pub units: Option<UnitStyle>,
+    pub theme: Option<Theme>,
+    pub color_thresholds: Option<Vec<ColorThreshold>>,
+}
+
+fn expect_string(key: &str, value: &Value) -> Result<String, String> {
+    match value {
+        Value::String(string) => Ok(string.clone()),
+        other => Err(format!("{} should be a string, not {}", key, other.type_name())),
+    }
+}
+
+fn expect_strings(key: &str, value: &Value) -> Result<Vec<String>, String> {
+    match value {
+        Value::String(string) => Ok(vec![string.clone()]),
+        Value::Array(items) => items.iter().map(|item| expect_string(key, item)).collect(),
+        other => Err(format!("{} should be an array of strings, not {}", key, other.type_name())),
+    }
+}
+
+fn expect_count(key: &str, value: &Value) -> Result<usize, String> {
+    match value {
+        Value::Integer(number) if *number >= 0 => Ok(*number as usize),
+        other => Err(format!("{} should be a positive integer, not {}", key, other.type_name())),
+    }
+}
+
+fn expect_bool(key: &str, value: &Value) -> Result<bool, String> {
+    match value {
+        Value::Boolean(boolean) => Ok(*boolean),
+        other => Err(format!("{} should be a boolean, not {}", key, other.type_name())),
+    }
+}
+
+fn parsed<T>(key: &str, value: &Value, parse: impl Fn(&str) -> Option<T>, expected: &str) -> Result<T, String> {
+    let string = expect_string(key, value)?;
+    parse(&string).ok_or_else(|| format!("invalid {} '{}', expected {}", key, string, expected))
+}
+
+impl Settings {
+    fn set(&mut self, key: &str, value: &Value) -> Result<(), String> {
+        match key {
+            "count" => self.count = Some(expect_count(key, value)?),
+            "threads" => self.threads = Some(expect_count(key, value)?.max(1)),
+            "output" => self.output = Some(parsed(key, value, OutputFormat::parse, "table, json, ndjson, csv, plain, markdown, html, ncdu or treemap")?),
+            "sort" => self.sort = Some(parsed(key, value, SortKey::parse, "size, mtime, atime, name or ext")?),
+            "min_size" => {
+                self.min_size = Some(match value {
+                    Value::Integer(bytes) if *bytes >= 0 => *bytes as u64,
+                    _ => parsed(key, value, parse_size, "a size like 500M")?,
+                })
+            }
+            "exclude" => {
+                for pattern in expect_strings(key, value)? {
+                    self.excludes.push(Glob::new(&pattern)?);
+                }
+            }
+            "include" => {
+                for pattern in expect_strings(key, value)? {
+                    self.includes.push(Glob::new(&pattern)?);
+                }
+            }
+            "ext" => self.extensions.extend(expect_strings(key, value)?),
+            "type" => self.types.extend(expect_strings(key, value)?),
+            "respect_gitignore" => self.respect_gitignore = Some(expect_bool(key, value)?),
+            "fatassignore" => self.fatassignore = Some(expect_bool(key, value)?),
+            "hidden" => self.hidden = Some(expect_bool(key, value)?),
+            "units" => self.units = Some(parsed(key, value, UnitStyle::parse, "binary, si, bytes or legacy")?),
+            "theme" => self.theme = Some(parsed(key, value, Theme::parse, "default, light or mono")?),
+            "color_thresholds" => {
+                let items = expect_strings(key, value)?;
+                self.color_thresholds = Some(parse_color_thresholds(&items.join(",")).map_err(|err| format!("invalid {}: {}", key, err))?);
+            }
+            _ => return Err(format!("unknown setting '{}'", key)),
+        }
+        Ok(())
+    }
+
+    // Layer other on top, what it sets wins and its lists add to the ones already there
+    pub fn merge(&mut self, other: &Settings) {
+        self.count = other.count.or(self.count);
+        self.threads = other.threads.or(self.threads);
+        self.output = other.output.or(self.output);
+        self.sort = other.sort.or(self.sort);
+        self.min_size = other.min_size.or(self.min_size);
+        self.excludes.extend(other.excludes.iter().cloned());
+        self.includes.extend(other.includes.iter().cloned());
+        self.extensions.extend(other.extensions.iter().cloned());
+        self.types.extend(other.types.iter().cloned());
+        self.respect_gitignore = other.respect_gitignore.or(self.respect_gitignore);
+        self.fatassignore = other.fatassignore.or(self.fatassignore);
+        self.hidden = other.hidden.or(self.hidden);
+        self.units = other.units.or(self.units);
+        self.theme = other.theme.or(self.theme);
+        self.color_thresholds = other.color_thresholds.clone().or(self.color_thresholds.take());
+    }
+}
+
+// A named set of settings from a [profile.NAME] table, picked with --profile
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    pub name: String,
+    pub settings: Settings,
+    // The "key = value" lines as written, for listing
+    pub lines: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    pub settings: Settings,
+    // File type categories added to, or overriding, the built-in ones of --type
+    pub types: Vec<(String, Vec<String>)>,
+    pub profiles: Vec<Profile>,
+}
+
+impl Config {
+    pub fn parse(content: &str) -> Result<Config, String> {
+        let mut config = Config::default();
+        for entry in parse_entries(content)? {
+            let at_line = |err: String| format!("line {}: {}", entry.line, err);
+            match entry.table.as_str() {
+                "" => config.settings.set(&entry.key, &entry.value).map_err(at_line)?,
+                "types" => {
+                    let extensions = expect_strings(&entry.key, &entry.value).map_err(at_line)?;
+                    config.types.push((entry.key.clone(), extensions));
+                }
+                table if table.starts_with("profile.") && table.len() > "profile.".len() => {
+                    let name = &table["profile.".len()..];
+                    let index = match config.profiles.iter().position(|profile| profile.name == name) {
+                        Some(index) => index,
+                        None => {
+                            config.profiles.push(Profile { name: name.to_string(), ..Profile::default() });
+                            config.profiles.len() - 1
+                        }
+                    };
+                    let profile = &mut config.profiles[index];
+                    profile.settings.set(&entry.key, &entry.value).map_err(at_line)?;
+                    profile.lines.push(format!("{} = {}", entry.key, entry.value));
+                }
+                other => return Err(at_line(format!("unknown table [{}]", other))),
+            }
+        }
+        Ok(config)
+    }
+
+    // The top level settings with those of the profile on top
+    pub fn profile(&self, name: &str) -> Option<Settings> {
+        let profile = self.profiles.iter().find(|profile| profile.name == name)?;
+        let mut settings = self.settings.clone();
+        settings.merge(&profile.settings);
+        Some(settings)
+    }
+
+    pub fn profile_names(&self) -> Vec<&str> {
+        self.profiles.iter().map(|profile| profile.name.as_str()).collect()
+    }
+
+    pub fn load(path: &Path) -> Result<Config, String> {
+        let content = fs::read_to_string(path).map_err(|err| format!("could not read {}: {}", path.display(), err))?;
+        Config::parse(&content).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    // ~/.config/fatass/config.toml, or under $XDG_CONFIG_HOME or %APPDATA% when set
+    pub fn default_path() -> Option<PathBuf> {
+        let config_dir = env::var_os("XDG_CONFIG_HOME")
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| if cfg!(windows) { env::var_os("APPDATA").map(PathBuf::from) } else { None })
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+        Some(config_dir.join("fatass").join("config.toml"))
+    }
+}
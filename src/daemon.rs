@@ -0,0 +1,62 @@
+// The state fatass daemon keeps between its scans: the snapshot of the last scan and of the one
+// before, so the growth in between can be told again after a restart
+//
+//   ~/.local/state/fatass/latest.snap
+//   ~/.local/state/fatass/previous.snap
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::snapshot::Snapshot;
+
+#[derive(Debug)]
+pub struct StateDir {
+    path: PathBuf,
+}
+
+impl StateDir {
+    pub fn default_path() -> Option<PathBuf> {
+        let state_dir = env::var_os("XDG_STATE_HOME")
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| if cfg!(windows) { env::var_os("LOCALAPPDATA").map(PathBuf::from) } else { None })
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local").join("state")))?;
+        Some(state_dir.join("fatass"))
+    }
+
+    pub fn open(path: &Path) -> io::Result<StateDir> {
+        fs::create_dir_all(path)?;
+        Ok(StateDir { path: path.to_path_buf() })
+    }
+
+    pub fn latest_path(&self) -> PathBuf {
+        self.path.join("latest.snap")
+    }
+
+    fn previous_path(&self) -> PathBuf {
+        self.path.join("previous.snap")
+    }
+
+    // The snapshot of the last scan, none before the first one or when it can't be read, the next
+    // scan then starts over
+    pub fn latest(&self) -> Option<Snapshot> {
+        let latest_path = self.latest_path();
+        if !latest_path.is_file() {
+            return None;
+        }
+        Snapshot::load(&latest_path).ok()
+    }
+
+    // Keep the snapshot of a scan as the latest, the one it replaces becomes the previous. It's
+    // written aside first so a crash midway leaves the latest one whole
+    pub fn record(&self, snapshot: &Snapshot) -> io::Result<()> {
+        let written_path = self.path.join("latest.snap.tmp");
+        snapshot.save(&written_path)?;
+        let latest_path = self.latest_path();
+        if latest_path.is_file() {
+            fs::rename(&latest_path, self.previous_path())?;
+        }
+        fs::rename(&written_path, &latest_path)
+    }
+}
@@ -0,0 +1,49 @@
+// Removing reported entries, the confirmation itself is up to the caller
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::trash::move_to_trash;
+use crate::{EntryKind, FileEntry};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum DeleteMethod {
+    // Unlink for good
+    #[default]
+    Remove,
+    // Move to the platform trash, recoverable
+    Trash,
+}
+
+// What a deletion pass did, failures are kept to be summarized once it's over
+#[derive(Debug, Default)]
+pub struct DeleteReport {
+    pub deleted: usize,
+    pub reclaimed: u64,
+    pub failures: Vec<(String, io::Error)>,
+}
+
+impl DeleteReport {
+    pub fn record(&mut self, entry: &FileEntry, result: io::Result<()>) {
+        match result {
+            Ok(()) => {
+                self.deleted += 1;
+                self.reclaimed += entry.size;
+            }
+            Err(err) => self.failures.push((entry.path.clone(), err)),
+        }
+    }
+}
+
+// Entries inside an already deleted directory are gone and must not be counted twice
+pub fn still_exists(entry: &FileEntry) -> bool {
+    fs::symlink_metadata(Path::new(&entry.path)).is_ok()
+}
+
+pub fn delete_entry(entry: &FileEntry, method: DeleteMethod) -> io::Result<()> {
+    match (method, entry.kind) {
+        (DeleteMethod::Trash, kind) => move_to_trash(Path::new(&entry.path), kind == EntryKind::Directory),
+        (DeleteMethod::Remove, EntryKind::File) => fs::remove_file(&entry.path),
+        (DeleteMethod::Remove, EntryKind::Directory) => fs::remove_dir_all(&entry.path),
+    }
+}
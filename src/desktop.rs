@@ -0,0 +1,97 @@
+// Handing a result over to the desktop: showing it in the file manager, copying its path to the
+// clipboard. Both go through the usual commands of each platform, the clipboard falls back on the
+// OSC 52 escape that asks the terminal itself, which works over SSH too
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// Show the directory holding the path in the file manager, the entry selected where it can be
+pub fn open_folder(path: &Path) -> io::Result<()> {
+    let folder = path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = Command::new("open");
+        command.arg("-R").arg(path);
+        command
+    } else if cfg!(windows) {
+        let mut command = Command::new("explorer");
+        command.arg(folder);
+        command
+    } else {
+        let mut command = Command::new("xdg-open");
+        command.arg(folder);
+        command
+    };
+    // Explorer exits with 1 even when it opened the folder, so only starting it counts
+    command.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).spawn().map(|_| ())
+}
+
+fn base64(input: &[u8]) -> String {
+    let mut encoded = String::with_capacity(input.len().div_ceil(3) * 4);
+    for chunk in input.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (index, byte)| bits | (*byte as u32) << (16 - 8 * index));
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(BASE64[(bits >> (18 - 6 * index) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+// The clipboard commands to try in order, with what they read
+fn clipboard_commands(text: &str) -> Vec<(&'static str, Vec<&'static str>, Vec<u8>)> {
+    if cfg!(target_os = "macos") {
+        vec![("pbcopy", Vec::new(), text.as_bytes().to_vec())]
+    } else if cfg!(windows) {
+        // clip takes anything but ASCII in the code page unless given UTF-16 with its byte order mark
+        let utf16 = std::iter::once(0xfeff).chain(text.encode_utf16()).flat_map(u16::to_le_bytes).collect();
+        vec![("clip", Vec::new(), utf16)]
+    } else {
+        let mut commands = vec![("xclip", vec!["-selection", "clipboard"], text.as_bytes().to_vec()), ("xsel", vec!["--clipboard", "--input"], text.as_bytes().to_vec())];
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            commands.insert(0, ("wl-copy", Vec::new(), text.as_bytes().to_vec()));
+        }
+        commands
+    }
+}
+
+// Put the text on the clipboard, returns what did it. Without any clipboard command the terminal
+// is asked, which can't tell whether it obliged
+pub fn copy_to_clipboard(text: &str) -> io::Result<&'static str> {
+    for (program, arguments, input) in clipboard_commands(text) {
+        let mut child = match Command::new(program).args(arguments).stdin(Stdio::piped()).stdout(Stdio::null()).stderr(Stdio::null()).spawn() {
+            Ok(child) => child,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        };
+        child.stdin.take().unwrap().write_all(&input)?;
+        if child.wait()?.success() {
+            return Ok(program);
+        }
+    }
+    if !console::Term::stderr().is_term() {
+        return Err(io::Error::other("no clipboard command found, install xclip, xsel or wl-clipboard"));
+    }
+    let mut stderr = io::stderr();
+    write!(stderr, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    stderr.flush()?;
+    Ok("the terminal")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64("/srv/média/a b.mov".as_bytes()), "L3Nydi9tw6lkaWEvYSBiLm1vdg==");
+    }
+}
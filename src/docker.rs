@@ -0,0 +1,355 @@
+// Where the space of Docker and Podman goes: their storage root read as images, containers and
+// volumes rather than thousands of layer directories named after hashes. The engines' own metadata
+// ties every layer directory to the images and containers using it. A layer shared by several
+// images counts in the size of each of them, and in the unique size of none. Layers nothing refers
+// to anymore, left by builds and interrupted pulls, add up to a row of their own
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::checksum::{hash_bytes, HashAlgorithm};
+use crate::hardlinks::SeenLinks;
+use crate::interrupt::interrupted;
+use crate::json::Json;
+use crate::progress;
+use crate::scanner::{measure_file, spinner, walk};
+use crate::Scanner;
+
+// Where the engines keep their storage when they run as root
+const SYSTEM_ROOTS: [&str; 2] = ["/var/lib/docker", "/var/lib/containers/storage"];
+// And rootless, below the data home of the user
+const USER_ROOTS: [&str; 2] = ["docker", "containers/storage"];
+const SHORT_ID: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StorageKind {
+    Image,
+    Container,
+    Volume,
+    BuildCache,
+    // Layer directories no image or container refers to
+    Unreferenced,
+    // Metadata, databases and whatever else the engine keeps
+    Other,
+}
+
+impl StorageKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StorageKind::Image => "image",
+            StorageKind::Container => "container",
+            StorageKind::Volume => "volume",
+            StorageKind::BuildCache => "build-cache",
+            StorageKind::Unreferenced => "unreferenced",
+            StorageKind::Other => "other",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StorageUsage {
+    pub kind: StorageKind,
+    pub name: String,
+    // Short image or container id, empty for the rest
+    pub id: String,
+    // Everything it takes, the layers shared with other images included
+    pub size: u64,
+    // What removing it would free
+    pub unique: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Engine {
+    Docker,
+    Podman,
+}
+
+// An image or a container, with the directory names of its layers
+#[derive(Debug)]
+struct Owner {
+    name: String,
+    id: String,
+    layers: Vec<String>,
+    // Kept apart from the layers, e.g. the logs of a container
+    data: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+struct Storage {
+    images: Vec<Owner>,
+    containers: Vec<Owner>,
+    // The directories holding a directory per layer and per volume
+    layers: PathBuf,
+    volumes: PathBuf,
+    build_cache: Option<PathBuf>,
+}
+
+fn short(id: &str) -> String {
+    id.chars().take(SHORT_ID).collect()
+}
+
+fn subdirs(dir: &Path) -> impl Iterator<Item = PathBuf> {
+    fs::read_dir(dir).into_iter().flatten().flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_dir()))
+        .map(|entry| entry.path())
+}
+
+fn dir_name(path: &Path) -> String {
+    path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+fn read_json(path: &Path) -> Option<Json> {
+    Json::parse(&fs::read_to_string(path).ok()?)
+}
+
+// The engine the storage root belongs to and its storage driver, e.g. overlay2
+fn storage_driver(root: &Path) -> Option<(Engine, String)> {
+    let docker = subdirs(&root.join("image")).find(|driver| driver.join("layerdb").is_dir());
+    if let Some(driver) = docker {
+        return Some((Engine::Docker, dir_name(&driver)));
+    }
+    subdirs(root).find_map(|dir| {
+        let driver = dir_name(&dir).strip_suffix("-layers")?.to_string();
+        root.join(format!("{}-images", driver)).is_dir().then_some((Engine::Podman, driver))
+    })
+}
+
+pub fn is_storage_root(path: &Path) -> bool {
+    storage_driver(path).is_some()
+}
+
+// The storage roots of this machine, the system ones then those of the user
+pub fn default_roots() -> Vec<String> {
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local").join("share")));
+    let user_roots = data_home.into_iter().flat_map(|data_home| USER_ROOTS.map(|root| data_home.join(root)));
+    SYSTEM_ROOTS.into_iter().map(PathBuf::from).chain(user_roots)
+        .filter(|root| is_storage_root(root))
+        .map(|root| root.display().to_string())
+        .collect()
+}
+
+// Where the driver puts the directory of every layer
+fn layers_dir(root: &Path, driver: &str) -> PathBuf {
+    match driver {
+        "btrfs" => root.join("btrfs").join("subvolumes"),
+        "vfs" => root.join("vfs").join("dir"),
+        "zfs" => root.join("zfs").join("graph"),
+        "aufs" => root.join("aufs").join("diff"),
+        _ => root.join(driver),
+    }
+}
+
+// Images from the image database, their layers named after the chain of the layers below them,
+// containers from their config and the layer they were given to write to
+fn read_docker(root: &Path, driver: &str) -> Storage {
+    let image_root = root.join("image").join(driver);
+    let layerdb = image_root.join("layerdb");
+
+    let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+    if let Some(repositories) = read_json(&image_root.join("repositories.json")) {
+        for (_, references) in repositories.get("Repositories").map(Json::fields).unwrap_or_default() {
+            for (reference, id) in references.fields() {
+                if let Some(id) = id.as_str() {
+                    tags.entry(id.to_string()).or_default().push(reference.clone());
+                }
+            }
+        }
+    }
+
+    let mut images = Vec::new();
+    for path in fs::read_dir(image_root.join("imagedb").join("content").join("sha256")).into_iter().flatten().flatten().map(|entry| entry.path()) {
+        let Some(config) = read_json(&path) else {
+            continue;
+        };
+        let id = dir_name(&path);
+        let diff_ids = config.get("rootfs").and_then(|rootfs| rootfs.get("diff_ids")).map(Json::items).unwrap_or_default();
+        let mut chain_id = String::new();
+        let mut layers = Vec::new();
+        for diff_id in diff_ids.iter().filter_map(Json::as_str) {
+            // The chain id of a layer is the SHA-256 of "<chain id below> <diff id>"
+            chain_id = if chain_id.is_empty() {
+                diff_id.to_string()
+            } else {
+                format!("sha256:{}", hash_bytes(format!("{} {}", chain_id, diff_id).as_bytes(), HashAlgorithm::Sha256))
+            };
+            let cache_id = chain_id.strip_prefix("sha256:")
+                .and_then(|chain| fs::read_to_string(layerdb.join("sha256").join(chain).join("cache-id")).ok());
+            layers.extend(cache_id.map(|cache_id| cache_id.trim().to_string()));
+        }
+        let mut names = tags.remove(&format!("sha256:{}", id)).unwrap_or_default();
+        // Tags read better than digests
+        names.sort_by_key(|name| (name.contains('@'), name.clone()));
+        let name = names.into_iter().next().unwrap_or_else(|| String::from("<none>"));
+        images.push(Owner { name, id: short(&id), layers, data: None });
+    }
+
+    let mut containers = Vec::new();
+    for path in subdirs(&root.join("containers")) {
+        let id = dir_name(&path);
+        let name = read_json(&path.join("config.v2.json"))
+            .and_then(|config| Some(config.get("Name")?.as_str()?.trim_start_matches('/').to_string()))
+            .unwrap_or_else(|| short(&id));
+        let mut layers = Vec::new();
+        if let Ok(mount_id) = fs::read_to_string(layerdb.join("mounts").join(&id).join("mount-id")) {
+            // The writable layer, and the one below it with the hosts and resolv.conf files
+            let mount_id = mount_id.trim();
+            layers.push(mount_id.to_string());
+            layers.push(format!("{}-init", mount_id));
+        }
+        containers.push(Owner { name, id: short(&id), layers, data: Some(path) });
+    }
+
+    Storage { images, containers, layers: layers_dir(root, driver), volumes: root.join("volumes"), build_cache: Some(root.join("buildkit")) }
+}
+
+// Images and containers from the JSON lists of containers/storage, layers from their parent links
+fn read_podman(root: &Path, driver: &str) -> Storage {
+    let mut parents: HashMap<String, String> = HashMap::new();
+    for file in ["layers.json", "volatile-layers.json"] {
+        let layers = read_json(&root.join(format!("{}-layers", driver)).join(file));
+        for layer in layers.as_ref().map(Json::items).unwrap_or_default() {
+            if let (Some(id), Some(parent)) = (layer.get("id").and_then(Json::as_str), layer.get("parent").and_then(Json::as_str)) {
+                parents.insert(id.to_string(), parent.to_string());
+            }
+        }
+    }
+
+    let owners = |kind: &str, with_parents: bool| -> Vec<Owner> {
+        let dir = root.join(format!("{}-{}", driver, kind));
+        let list = read_json(&dir.join(format!("{}.json", kind)));
+        list.as_ref().map(Json::items).unwrap_or_default().iter().filter_map(|item| {
+            let id = item.get("id")?.as_str()?.to_string();
+            let name = item.get("names").and_then(|names| names.items().first()?.as_str()).map(str::to_string);
+            let mut layers: Vec<String> = Vec::new();
+            let mut layer = item.get("layer").and_then(Json::as_str).map(str::to_string);
+            while let Some(id) = layer.take().filter(|id| !layers.contains(id)) {
+                if with_parents {
+                    layer = parents.get(&id).cloned();
+                }
+                layers.push(id);
+            }
+            Some(Owner {
+                name: name.unwrap_or_else(|| if with_parents { String::from("<none>") } else { short(&id) }),
+                data: Some(dir.join(&id)),
+                id: short(&id),
+                layers,
+            })
+        }).collect()
+    };
+
+    // A container's own layer sits on the top layer of its image, which the image accounts for
+    Storage { images: owners("images", true), containers: owners("containers", false), layers: layers_dir(root, driver), volumes: root.join("volumes"), build_cache: None }
+}
+
+// The directories sized on their own, every file below one of them counts for it
+fn tracked_dirs(storage: &Storage) -> HashMap<PathBuf, u64> {
+    // The l directory of the overlay drivers only holds short symlinks to the layers
+    let mut dirs: Vec<PathBuf> = subdirs(&storage.layers).filter(|dir| dir.file_name() != Some(OsStr::new("l"))).collect();
+    dirs.extend(subdirs(&storage.volumes));
+    dirs.extend(storage.images.iter().chain(&storage.containers).filter_map(|owner| owner.data.clone()));
+    dirs.extend(storage.build_cache.clone());
+    dirs.into_iter().map(|dir| (dir, 0)).collect()
+}
+
+fn attribute(storage: &Storage, sizes: &HashMap<PathBuf, u64>, other: u64) -> Vec<StorageUsage> {
+    let dir_size = |dir: &Path| sizes.get(dir).copied().unwrap_or(0);
+    let layer_size = |layer: &String| dir_size(&storage.layers.join(layer));
+    let data_size = |owner: &Owner| owner.data.as_deref().map_or(0, dir_size);
+
+    // How many images each layer is part of
+    let mut users: HashMap<&String, usize> = HashMap::new();
+    for image in &storage.images {
+        for layer in image.layers.iter().collect::<HashSet<_>>() {
+            *users.entry(layer).or_default() += 1;
+        }
+    }
+
+    let mut usage = Vec::new();
+    for image in &storage.images {
+        let layers: HashSet<&String> = image.layers.iter().collect();
+        let size = layers.iter().map(|layer| layer_size(layer)).sum::<u64>() + data_size(image);
+        let shared: u64 = layers.iter().filter(|layer| users[*layer] > 1).map(|layer| layer_size(layer)).sum();
+        usage.push(StorageUsage { kind: StorageKind::Image, name: image.name.clone(), id: image.id.clone(), size, unique: size - shared });
+    }
+    for container in &storage.containers {
+        let size = container.layers.iter().map(layer_size).sum::<u64>() + data_size(container);
+        usage.push(StorageUsage { kind: StorageKind::Container, name: container.name.clone(), id: container.id.clone(), size, unique: size });
+    }
+    for volume in subdirs(&storage.volumes) {
+        let size = dir_size(&volume);
+        usage.push(StorageUsage { kind: StorageKind::Volume, name: dir_name(&volume), id: String::new(), size, unique: size });
+    }
+    if let Some(build_cache) = storage.build_cache.as_deref().filter(|dir| dir.is_dir()) {
+        let size = dir_size(build_cache);
+        usage.push(StorageUsage { kind: StorageKind::BuildCache, name: dir_name(build_cache), id: String::new(), size, unique: size });
+    }
+
+    let referenced: HashSet<PathBuf> = storage.images.iter().chain(&storage.containers)
+        .flat_map(|owner| owner.layers.iter().map(|layer| storage.layers.join(layer)))
+        .collect();
+    let unreferenced: Vec<u64> = sizes.iter()
+        .filter(|(dir, _)| dir.parent() == Some(&storage.layers) && !referenced.contains(*dir))
+        .map(|(_, size)| *size)
+        .collect();
+    if !unreferenced.is_empty() {
+        let size = unreferenced.iter().sum();
+        let name = format!("{} layer{}", unreferenced.len(), if unreferenced.len() == 1 { "" } else { "s" });
+        usage.push(StorageUsage { kind: StorageKind::Unreferenced, name, id: String::new(), size, unique: size });
+    }
+    if other > 0 {
+        usage.push(StorageUsage { kind: StorageKind::Other, name: String::from("metadata"), id: String::new(), size: other, unique: other });
+    }
+    usage
+}
+
+// The images, containers and volumes of every storage root searched, by what removing them would
+// free, up to the scanner count. Search paths that aren't a storage root are left out
+pub fn storage_usage(scanner: &Scanner) -> Vec<StorageUsage> {
+    let filters = &scanner.filters;
+    let progress_bar = spinner(scanner.progress);
+    progress::start(scanner.json_progress);
+    let seen_links = SeenLinks::new(scanner.hardlinks);
+
+    let mut usage = Vec::new();
+    for search_path in scanner.roots() {
+        let root = Path::new(&search_path);
+        let storage = match storage_driver(root) {
+            Some((Engine::Docker, driver)) => read_docker(root, &driver),
+            Some((Engine::Podman, driver)) => read_podman(root, &driver),
+            None => continue,
+        };
+        let mut sizes = tracked_dirs(&storage);
+        let mut other = 0;
+        for entry in walk(&search_path, filters, &scanner.errors).filter(|e| !e.file_type().is_dir()) {
+            if interrupted() {
+                break;
+            }
+            if !filters.keeps_file(root, entry.path()) {
+                continue;
+            }
+            let Some(metadata) = scanner.errors.walk_metadata(&entry).filter(|metadata| seen_links.first_visit(metadata)) else {
+                continue;
+            };
+            let size = measure_file(entry.path(), &metadata, scanner.disk_usage).size;
+            progress_bar.inc(1);
+            progress::scanned(size);
+            let owner = entry.path().ancestors().skip(1).take_while(|dir| dir.starts_with(root)).find(|dir| sizes.contains_key(*dir));
+            match owner.and_then(|dir| sizes.get_mut(dir)) {
+                Some(dir_size) => *dir_size += size,
+                None => other += size,
+            }
+        }
+        usage.extend(attribute(&storage, &sizes, other));
+    }
+    progress_bar.finish();
+    progress::finish();
+
+    usage.sort_by(|a, b| b.unique.cmp(&a.unique).then_with(|| b.size.cmp(&a.size)).then_with(|| a.name.cmp(&b.name)));
+    usage.truncate(scanner.fatass_count);
+    usage
+}
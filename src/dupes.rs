@@ -0,0 +1,160 @@
+// Duplicate files among the scanned set, grouped by size first, then by a hash of their first
+// bytes, then of their whole content, and finally compared byte for byte so hash collisions can't
+// report different files as copies
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fs::File;
+use std::hash::Hasher;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use indicatif::ProgressBar;
+
+use crate::hardlinks::{HardlinkMode, SeenLinks};
+use crate::progress;
+use crate::scanner::{byte_bar, spinner, walk};
+use crate::throttle;
+use crate::Scanner;
+
+// Files sharing the same content, every copy but one is wasted space
+#[derive(Debug, Clone)]
+pub struct DuplicateGroup {
+    pub size: u64,
+    pub paths: Vec<String>,
+}
+
+impl DuplicateGroup {
+    pub fn wasted(&self) -> u64 {
+        self.size * (self.paths.len() as u64).saturating_sub(1)
+    }
+}
+
+// Enough to tell most same-sized files apart without reading them whole
+const HEAD_SIZE: u64 = 4096;
+const BUFFER_SIZE: usize = 64 * 1024;
+
+// The bar moves by the bytes read, for the byte sized one of the whole file hashes
+fn hash_file(path: &Path, limit: u64, progress_bar: &ProgressBar) -> io::Result<u64> {
+    let mut reader = File::open(path)?.take(limit);
+    let mut hasher = DefaultHasher::new();
+    let mut buffer = vec![0; BUFFER_SIZE];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            return Ok(hasher.finish());
+        }
+        throttle::charge(read as u64);
+        hasher.write(&buffer[..read]);
+        progress_bar.inc(read as u64);
+    }
+}
+
+// Read as much as possible into buffer, short only at the end of the file
+fn read_full(reader: &mut impl Read, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..])? {
+            0 => break,
+            read => filled += read,
+        }
+    }
+    Ok(filled)
+}
+
+fn same_content(left: &Path, right: &Path) -> io::Result<bool> {
+    let (mut left, mut right) = (File::open(left)?, File::open(right)?);
+    let (mut left_buffer, mut right_buffer) = (vec![0; BUFFER_SIZE], vec![0; BUFFER_SIZE]);
+    loop {
+        let left_read = read_full(&mut left, &mut left_buffer)?;
+        let right_read = read_full(&mut right, &mut right_buffer)?;
+        if left_buffer[..left_read] != right_buffer[..right_read] {
+            return Ok(false);
+        }
+        if left_read == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+// Split every group by key, unreadable files are dropped and so are the groups left with one file
+fn refine(groups: Vec<Vec<PathBuf>>, key: impl Fn(&Path) -> Option<u64>) -> Vec<Vec<PathBuf>> {
+    groups.into_iter().flat_map(|group| {
+        let mut by_key: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+        for path in group {
+            if let Some(value) = key(&path) {
+                by_key.entry(value).or_default().push(path);
+            }
+        }
+        by_key.into_values().filter(|group| group.len() > 1)
+    }).collect()
+}
+
+// Split every group into sets of files with the exact same bytes
+fn confirm(groups: Vec<Vec<PathBuf>>) -> Vec<Vec<PathBuf>> {
+    groups.into_iter().flat_map(|group| {
+        let mut copies: Vec<Vec<PathBuf>> = Vec::new();
+        for path in group {
+            match copies.iter_mut().find(|copy| same_content(&copy[0], &path).unwrap_or(false)) {
+                Some(copy) => copy.push(path),
+                None => copies.push(vec![path]),
+            }
+        }
+        copies.into_iter().filter(|copy| copy.len() > 1)
+    }).collect()
+}
+
+// The duplicate groups wasting the most space, up to the scanner count
+pub fn find_duplicates(scanner: &Scanner) -> Vec<DuplicateGroup> {
+    let filters = &scanner.filters;
+    let progress_bar = spinner(scanner.progress);
+    // Hard links share their blocks, deleting one of them wouldn't save anything
+    let seen_links = SeenLinks::new(HardlinkMode::Once);
+
+    let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    progress::start(scanner.json_progress);
+    for search_path in scanner.roots() {
+        let root = Path::new(&search_path);
+        for entry in walk(&search_path, filters, &scanner.errors)
+            .filter(|e| e.file_type().is_file())
+            .filter(|e| filters.keeps_file(root, e.path()))
+        {
+            let size = scanner.errors.walk_metadata(&entry)
+                .filter(|metadata| seen_links.first_visit(metadata))
+                .filter(|metadata| filters.keeps_age(metadata.modified().ok()))
+                .map(|m| m.len())
+                .unwrap_or(0);
+            if filters.keeps_size(size) {
+                by_size.entry(size).or_default().push(entry.into_path());
+            }
+            progress_bar.inc(1);
+            progress::scanned(size);
+        }
+    }
+    progress::finish();
+    let groups: Vec<Vec<PathBuf>> = by_size.into_values().filter(|group| group.len() > 1).collect();
+
+    progress_bar.set_message("(hashing)");
+    let groups = refine(groups, |path| scanner.errors.io_ok(path, hash_file(path, HEAD_SIZE, &ProgressBar::hidden())));
+    // Whole files take as long to hash as they are big, a byte sized bar tells how long is left
+    let hashing_bar = if scanner.progress && scanner.byte_progress {
+        progress_bar.finish();
+        let total_bytes = groups.iter().flatten().filter_map(|path| path.metadata().ok()).map(|metadata| metadata.len()).sum();
+        byte_bar(total_bytes).with_message("(hashing)")
+    } else {
+        ProgressBar::hidden()
+    };
+    let groups = refine(groups, |path| scanner.errors.io_ok(path, hash_file(path, u64::MAX, &hashing_bar)));
+    hashing_bar.finish();
+    let groups = confirm(groups);
+    progress_bar.finish();
+
+    let mut duplicates: Vec<DuplicateGroup> = groups.into_iter().map(|mut paths| {
+        paths.sort();
+        DuplicateGroup {
+            size: paths.first().and_then(|path| path.metadata().ok()).map(|m| m.len()).unwrap_or(0),
+            paths: paths.into_iter().map(|path| path.display().to_string()).collect(),
+        }
+    }).collect();
+    duplicates.sort_by(|a, b| b.wasted().cmp(&a.wasted()).then_with(|| a.paths.cmp(&b.paths)));
+    duplicates.truncate(scanner.fatass_count);
+    duplicates
+}
@@ -0,0 +1,195 @@
+// Paths a scan couldn't read, counted so the results can say whether they are complete and kept to
+// list them on demand. Network filesystems fail now and then on paths that are fine, the metadata
+// operations failing that way are tried again a few times before giving up on them
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use walkdir::DirEntry;
+
+// How many times a transient error is retried unless told otherwise
+pub const DEFAULT_RETRIES: u32 = 2;
+// More would keep a scan stuck on a path that isn't coming back
+pub const MAX_RETRIES: u32 = 10;
+// The wait before the first retry, doubled before each of the next ones up to MAX_BACKOFF
+const FIRST_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+pub struct ScanError {
+    pub path: String,
+    // "permission denied", "filesystem loop" and the like
+    pub kind: String,
+    // The error as the system worded it, "Permission denied (os error 13)"
+    pub message: String,
+    // Whether it might have gone away trying again later, a timeout rather than a missing permission
+    pub transient: bool,
+}
+
+// Errors that may well go away when tried again, those of network filesystems mostly
+pub fn is_transient(err: &io::Error) -> bool {
+    use io::ErrorKind::*;
+    matches!(
+        err.kind(),
+        TimedOut | Interrupted | WouldBlock | ResourceBusy | StaleNetworkFileHandle | ConnectionReset | ConnectionAborted | NotConnected | NetworkDown | NetworkUnreachable | HostUnreachable
+    ) || err.raw_os_error().is_some_and(is_transient_code)
+}
+
+// EIO, which NFS soft mounts and FUSE filesystems give for what timed out underneath
+#[cfg(unix)]
+fn is_transient_code(code: i32) -> bool {
+    code == libc::EIO
+}
+
+#[cfg(windows)]
+fn is_transient_code(code: i32) -> bool {
+    use windows_sys::Win32::Foundation::{ERROR_LOCK_VIOLATION, ERROR_NETNAME_DELETED, ERROR_SEM_TIMEOUT, ERROR_SHARING_VIOLATION, ERROR_UNEXP_NET_ERR};
+
+    [ERROR_SHARING_VIOLATION, ERROR_LOCK_VIOLATION, ERROR_UNEXP_NET_ERR, ERROR_NETNAME_DELETED, ERROR_SEM_TIMEOUT].contains(&(code as u32))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn is_transient_code(_code: i32) -> bool {
+    false
+}
+
+#[derive(Debug)]
+pub struct ScanErrors {
+    errors: Mutex<Vec<ScanError>>,
+    retries: AtomicU32,
+}
+
+impl Default for ScanErrors {
+    fn default() -> ScanErrors {
+        ScanErrors { errors: Mutex::default(), retries: AtomicU32::new(DEFAULT_RETRIES) }
+    }
+}
+
+impl ScanErrors {
+    pub(crate) fn record(&self, path: &Path, err: &io::Error) {
+        self.push(path, err.kind().to_string(), err.to_string(), is_transient(err));
+    }
+
+    // An error told by another program, e.g. find on a remote machine
+    pub(crate) fn record_kind(&self, path: &Path, kind: String) {
+        self.push(path, kind.clone(), kind, false);
+    }
+
+    pub(crate) fn record_walk(&self, err: &walkdir::Error) {
+        let (kind, transient) = match err.io_error() {
+            Some(io_err) => (io_err.kind().to_string(), is_transient(io_err)),
+            None if err.loop_ancestor().is_some() => (String::from("filesystem loop"), false),
+            None => (err.to_string(), false),
+        };
+        self.push(err.path().unwrap_or(Path::new("")), kind, err.to_string(), transient);
+    }
+
+    // The value of a result, none after recording its error
+    pub(crate) fn io_ok<T>(&self, path: &Path, result: io::Result<T>) -> Option<T> {
+        result.map_err(|err| self.record(path, &err)).ok()
+    }
+
+    pub(crate) fn walk_ok<T>(&self, result: Result<T, walkdir::Error>) -> Option<T> {
+        result.map_err(|err| self.record_walk(&err)).ok()
+    }
+
+    pub(crate) fn set_retries(&self, retries: u32) {
+        self.retries.store(retries, Ordering::Relaxed);
+    }
+
+    pub fn retries(&self) -> u32 {
+        self.retries.load(Ordering::Relaxed)
+    }
+
+    // The value of the operation, run again after a growing wait while it fails with a transient
+    // error, none after recording the error it ends with
+    pub(crate) fn retry<T>(&self, path: &Path, mut operation: impl FnMut() -> io::Result<T>) -> Option<T> {
+        let first = operation();
+        self.retry_failed(path, first, operation)
+    }
+
+    fn retry_failed<T>(&self, path: &Path, mut result: io::Result<T>, mut operation: impl FnMut() -> io::Result<T>) -> Option<T> {
+        for attempt in 0..self.retries() {
+            if !result.as_ref().is_err_and(is_transient) {
+                break;
+            }
+            thread::sleep(backoff(attempt));
+            result = operation();
+        }
+        self.io_ok(path, result)
+    }
+
+    // The metadata of a walked entry, read again from its path while it fails with a transient error
+    pub(crate) fn walk_metadata(&self, entry: &DirEntry) -> Option<Metadata> {
+        let path = entry.path();
+        // A symlink the walk followed is known by the type of its target
+        let followed = entry.path_is_symlink() && !entry.file_type().is_symlink();
+        let first = entry.metadata().map_err(io::Error::from);
+        self.retry_failed(path, first, || if followed { fs::metadata(path) } else { fs::symlink_metadata(path) })
+    }
+
+    fn push(&self, path: &Path, kind: String, message: String, transient: bool) {
+        self.errors.lock().unwrap().push(ScanError { path: path.display().to_string(), kind, message, transient });
+    }
+
+    pub(crate) fn clear(&self) {
+        self.errors.lock().unwrap().clear();
+    }
+
+    pub fn count(&self) -> usize {
+        self.errors.lock().unwrap().len()
+    }
+
+    // Errors that might go away scanning again
+    pub fn transient(&self) -> usize {
+        self.errors.lock().unwrap().iter().filter(|error| error.transient).count()
+    }
+
+    // Every error, sorted by path
+    pub fn list(&self) -> Vec<ScanError> {
+        let mut errors = self.errors.lock().unwrap().clone();
+        errors.sort_by(|a, b| a.path.cmp(&b.path));
+        errors
+    }
+}
+
+// The wait before the retry following attempt, counted from 0
+fn backoff(attempt: u32) -> Duration {
+    FIRST_BACKOFF.saturating_mul(1 << attempt.min(16)).min(MAX_BACKOFF)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries_transient_errors_only() {
+        assert!(is_transient(&io::Error::from(io::ErrorKind::TimedOut)));
+        assert!(!is_transient(&io::Error::from(io::ErrorKind::PermissionDenied)));
+
+        let errors = ScanErrors::default();
+        errors.set_retries(1);
+        let mut attempts = 0;
+        let read = errors.retry(Path::new("/mnt/nas/a"), || {
+            attempts += 1;
+            if attempts == 1 { Err(io::Error::from(io::ErrorKind::TimedOut)) } else { Ok(attempts) }
+        });
+        assert_eq!(read, Some(2));
+
+        let mut attempts = 0;
+        let read: Option<()> = errors.retry(Path::new("/mnt/nas/b"), || {
+            attempts += 1;
+            Err(io::Error::from(io::ErrorKind::PermissionDenied))
+        });
+        assert_eq!((read, attempts), (None, 1));
+        assert_eq!((errors.count(), errors.transient()), (1, 0));
+
+        assert_eq!(backoff(0), FIRST_BACKOFF);
+        assert_eq!(backoff(3), Duration::from_millis(800));
+        assert_eq!(backoff(u32::MAX), MAX_BACKOFF);
+    }
+}
@@ -0,0 +1,269 @@
+// Estimating the size of a tree too big to walk in the time there is, with Knuth's random probes:
+// each probe goes down from the search path into one random subdirectory at a time, counting what
+// it finds there as many times over as there were directories to pick from on the way. Averaged
+// over the probes that's an unbiased estimate of the whole tree, their spread gives the confidence
+// interval. Listings are kept between probes, so the top of the tree is only read once
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::hardlinks::file_id;
+use crate::ignore::Ignores;
+use crate::interrupt::interrupted;
+use crate::scanner::measure_file;
+use crate::stats::bucket_bounds;
+use crate::Scanner;
+
+// Probing stops there even with time left, the margins hardly shrink any more
+const MAX_PROBES: usize = 100_000;
+// Quantile of the normal distribution for a 95% confidence interval
+const Z_95: f64 = 1.96;
+
+// Bytes, files and directories, then the files in each bucket of the size histogram
+const BYTES: usize = 0;
+const FILES: usize = 1;
+const DIRS: usize = 2;
+const QUANTITIES: usize = 3 + BUCKETS;
+const BUCKETS: usize = 6;
+
+// An estimated quantity and the half width of its 95% confidence interval
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Interval {
+    pub value: f64,
+    pub margin: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Estimate {
+    pub probes: usize,
+    // Directories actually read by the probes
+    pub dirs_read: usize,
+    pub bytes: Interval,
+    pub files: Interval,
+    // Directories below the search paths
+    pub dirs: Interval,
+    // Files estimated in each bucket of the --stats histogram, with its bounds
+    pub buckets: Vec<(u64, Option<u64>, Interval)>,
+    pub elapsed: Duration,
+}
+
+// Mean and variance of the probes of a search path, updated one probe at a time
+#[derive(Debug, Clone, Copy, Default)]
+struct Moments {
+    count: f64,
+    mean: f64,
+    // Sum of the squared differences from the mean
+    squares: f64,
+}
+
+impl Moments {
+    fn add(&mut self, value: f64) {
+        self.count += 1.0;
+        let delta = value - self.mean;
+        self.mean += delta / self.count;
+        self.squares += delta * (value - self.mean);
+    }
+
+    // The variance of the mean, unknown and taken as none after a single probe
+    fn mean_variance(&self) -> f64 {
+        if self.count < 2.0 {
+            return 0.0;
+        }
+        self.squares / (self.count - 1.0) / self.count
+    }
+}
+
+// The estimates of several search paths add up, and so do their variances
+fn combine<'a>(moments: impl Iterator<Item = &'a Moments>) -> Interval {
+    let (value, variance) = moments.fold((0.0, 0.0), |(value, variance), moments| (value + moments.mean, variance + moments.mean_variance()));
+    Interval { value, margin: Z_95 * variance.sqrt() }
+}
+
+// What a directory holds through the filters: the sizes of its files and the subdirectories to
+// probe with their ignore rules
+#[derive(Debug, Default)]
+struct Listing {
+    sizes: Vec<u64>,
+    subdirs: Vec<(PathBuf, Ignores)>,
+}
+
+struct Sampler<'a> {
+    scanner: &'a Scanner,
+    listings: HashMap<PathBuf, Listing>,
+    // Xorshift state, never 0
+    state: u64,
+}
+
+impl Sampler<'_> {
+    // A number below the bound, the slight bias of the modulo doesn't matter for picking directories
+    fn below(&mut self, bound: usize) -> usize {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        (self.state % bound as u64) as usize
+    }
+
+    fn list(&self, root: &Path, root_device: Option<u64>, dir: &Path, depth: usize, ignores: &Ignores) -> Listing {
+        let filters = &self.scanner.filters;
+        let errors = &self.scanner.errors;
+        let mut listing = Listing::default();
+        let Some(entries) = errors.retry(dir, || fs::read_dir(dir)) else { return listing };
+        for entry in entries.filter_map(|e| errors.io_ok(dir, e)) {
+            let path = entry.path();
+            let Some(mut file_type) = errors.io_ok(&path, entry.file_type()) else { continue };
+            // Following a symlink means looking at its target, dangling ones are skipped
+            let mut target_metadata = None;
+            if file_type.is_symlink() && filters.follows_link(&path) {
+                let Ok(metadata) = fs::metadata(&path) else { continue };
+                file_type = metadata.file_type();
+                target_metadata = Some(metadata);
+                // A probe would go round a link to one of its own parents forever
+                if file_type.is_dir() && fs::canonicalize(&path).is_ok_and(|target| fs::canonicalize(dir).is_ok_and(|dir| dir.starts_with(target))) {
+                    continue;
+                }
+            }
+            if !filters.keeps_visible(&path, file_type.is_dir(), ignores) {
+                continue;
+            }
+            if file_type.is_dir() {
+                if filters.one_file_system {
+                    let metadata = target_metadata.or_else(|| entry.metadata().ok());
+                    let device = metadata.as_ref().and_then(file_id).map(|(dev, _)| dev);
+                    if device.is_some() && device != root_device {
+                        continue;
+                    }
+                }
+                let within_depth = filters.max_depth.is_none_or(|max_depth| depth + 1 < max_depth);
+                if within_depth && filters.keeps_dir(root, &path) {
+                    let child_ignores = filters.ignores_for(&path, ignores);
+                    listing.subdirs.push((path, child_ignores));
+                }
+                continue;
+            }
+            if !filters.keeps_file(root, &path) {
+                continue;
+            }
+            let Some(metadata) = target_metadata.or_else(|| errors.retry(&path, || entry.metadata())) else { continue };
+            let file_entry = measure_file(&path, &metadata, self.scanner.disk_usage);
+            if filters.keeps_entry(&file_entry) {
+                listing.sizes.push(file_entry.size);
+            }
+        }
+        listing
+    }
+
+    // Go down from the search path into random subdirectories until there are none, what each
+    // directory holds weighted by how many others it stands for
+    fn probe(&mut self, root: &Path, bounds: &[u64]) -> [f64; QUANTITIES] {
+        let mut sample = [0.0; QUANTITIES];
+        let filters = &self.scanner.filters;
+        let Some(root_metadata) = self.scanner.errors.retry(root, || fs::metadata(root)) else { return sample };
+        if !root_metadata.is_dir() {
+            let file_entry = measure_file(root, &root_metadata, self.scanner.disk_usage);
+            if filters.keeps_file(root, root) && filters.keeps_entry(&file_entry) {
+                add_file(&mut sample, file_entry.size, 1.0, bounds);
+            }
+            return sample;
+        }
+        if filters.max_depth == Some(0) {
+            return sample;
+        }
+        let root_device = file_id(&root_metadata).map(|(dev, _)| dev);
+        let (mut dir, mut ignores) = (root.to_path_buf(), filters.ignores_for(root, &Ignores::default()));
+        let (mut depth, mut weight) = (0, 1.0);
+        loop {
+            if !self.listings.contains_key(&dir) {
+                let listing = self.list(root, root_device, &dir, depth, &ignores);
+                self.listings.insert(dir.clone(), listing);
+            }
+            let listing = &self.listings[&dir];
+            if depth > 0 {
+                sample[DIRS] += weight;
+            }
+            for size in &listing.sizes {
+                add_file(&mut sample, *size, weight, bounds);
+            }
+            if listing.subdirs.is_empty() {
+                return sample;
+            }
+            let subdirs = listing.subdirs.len();
+            weight *= subdirs as f64;
+            let picked = self.below(subdirs);
+            (dir, ignores) = self.listings[&dir].subdirs[picked].clone();
+            depth += 1;
+        }
+    }
+}
+
+fn add_file(sample: &mut [f64; QUANTITIES], size: u64, weight: f64, bounds: &[u64]) {
+    sample[BYTES] += weight * size as f64;
+    sample[FILES] += weight;
+    sample[3 + bounds.partition_point(|&bound| bound <= size)] += weight;
+}
+
+// Probe the search paths in turn for about as long as the budget, or until interrupted
+pub fn estimate(scanner: &Scanner, budget: Duration) -> Estimate {
+    let started = Instant::now();
+    let roots = scanner.roots();
+    let bounds = bucket_bounds();
+    let seed = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_nanos() as u64);
+    let mut sampler = Sampler { scanner, listings: HashMap::new(), state: seed | 1 };
+    let mut moments = vec![[Moments::default(); QUANTITIES]; roots.len()];
+
+    let mut probes = 0;
+    while probes < roots.len() || (probes < MAX_PROBES && started.elapsed() < budget && !interrupted()) {
+        let index = probes % roots.len();
+        let sample = sampler.probe(Path::new(&roots[index]), &bounds);
+        for (moments, value) in moments[index].iter_mut().zip(sample) {
+            moments.add(value);
+        }
+        probes += 1;
+    }
+
+    let quantity = |index: usize| combine(moments.iter().map(|root_moments| &root_moments[index]));
+    let mut min = 0;
+    let buckets = bounds.iter().copied().map(Some).chain([None]).enumerate()
+        .map(|(index, max)| {
+            let bucket = (min, max, quantity(3 + index));
+            min = max.unwrap_or_default();
+            bucket
+        })
+        .collect();
+    Estimate {
+        probes,
+        dirs_read: sampler.listings.len(),
+        bytes: quantity(BYTES),
+        files: quantity(FILES),
+        dirs: quantity(DIRS),
+        buckets,
+        elapsed: started.elapsed(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sums_the_estimates_of_each_path() {
+        let mut first = Moments::default();
+        for value in [10.0, 20.0, 30.0, 40.0] {
+            first.add(value);
+        }
+        assert_eq!(first.mean, 25.0);
+        // Sample variance 166.67 over 4 probes
+        assert!((first.mean_variance() - 500.0 / 3.0 / 4.0).abs() < 1e-9);
+
+        let mut second = Moments::default();
+        second.add(100.0);
+        second.add(100.0);
+        let interval = combine([first, second].iter());
+        assert_eq!(interval.value, 125.0);
+        assert!((interval.margin - Z_95 * (500.0f64 / 12.0).sqrt()).abs() < 1e-9);
+
+        let mut sample = [0.0; QUANTITIES];
+        add_file(&mut sample, 2048, 3.0, &[1000, 100_000]);
+        assert_eq!((sample[BYTES], sample[FILES], sample[4]), (6144.0, 3.0, 3.0));
+    }
+}
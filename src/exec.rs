@@ -0,0 +1,126 @@
+// A command run for each result once the scan is over, for integrations fatass knows nothing
+// about: tagging files in an asset manager, queueing them for archival. The command line is split
+// into words like a shell would but run without one, each word a template whose fields are
+// filled in per result, so paths with spaces or quotes need no quoting
+use std::io;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::interrupt;
+use crate::owners::Names;
+use crate::template::Template;
+use crate::FileEntry;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecCommand {
+    words: Vec<Template>,
+}
+
+// Split on blanks outside quotes, single quotes keep everything literal and double quotes all but
+// an escaped double quote
+fn split_words(command: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let quoted = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => quoted.push(c),
+                        None => return Err("unclosed single quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let quoted = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') if chars.peek() == Some(&'"') => quoted.push(chars.next().unwrap()),
+                        Some(c) => quoted.push(c),
+                        None => return Err("unclosed double quote".to_string()),
+                    }
+                }
+            }
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+impl ExecCommand {
+    pub fn parse(command: &str) -> Result<ExecCommand, String> {
+        let words = split_words(command)?.iter().map(|word| Template::parse_word(word)).collect::<Result<Vec<Template>, String>>()?;
+        if words.is_empty() {
+            return Err("the command is empty".to_string());
+        }
+        Ok(ExecCommand { words })
+    }
+
+    // The program and its arguments for the entry
+    fn arguments(&self, file_entry: &FileEntry, names: &mut Names) -> Vec<String> {
+        self.words.iter().map(|word| word.render(file_entry, None, names)).collect()
+    }
+
+    // Run the command for each entry, up to parallel at once, until interrupted. Returns how many
+    // entries it was run for and those it failed on with why. Its output goes to stderr, keeping
+    // stdout to the results
+    pub fn run_for_each(&self, entries: &[FileEntry], parallel: usize) -> (usize, Vec<(String, String)>) {
+        let next = AtomicUsize::new(0);
+        let failures = Mutex::new(Vec::new());
+        thread::scope(|scope| {
+            for _ in 0..parallel.clamp(1, entries.len().max(1)) {
+                scope.spawn(|| {
+                    let mut names = Names::default();
+                    while !interrupt::interrupted() {
+                        let Some(file_entry) = entries.get(next.fetch_add(1, Ordering::Relaxed)) else { break };
+                        if let Err(reason) = self.run(file_entry, &mut names) {
+                            failures.lock().unwrap().push((file_entry.path.clone(), reason));
+                        }
+                    }
+                });
+            }
+        });
+        (next.into_inner().min(entries.len()), failures.into_inner().unwrap())
+    }
+
+    fn run(&self, file_entry: &FileEntry, names: &mut Names) -> Result<(), String> {
+        let arguments = self.arguments(file_entry, names);
+        let status = Command::new(&arguments[0])
+            .args(&arguments[1..])
+            .stdin(Stdio::null())
+            .stdout(Stdio::from(io::stderr()))
+            .status()
+            .map_err(|err| format!("could not run {}: {}", arguments[0], err))?;
+        match status.code() {
+            _ if status.success() => Ok(()),
+            Some(code) => Err(format!("exited with {}", code)),
+            None => Err("killed by a signal".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_and_fills_in_commands() {
+        assert_eq!(split_words(r#"tag --label 'big file' "say \"hi\"" {path}"#).unwrap(), ["tag", "--label", "big file", "say \"hi\"", "{path}"]);
+        assert_eq!(split_words("  a  ''  b ").unwrap(), ["a", "", "b"]);
+        assert!(split_words("a 'b").is_err());
+
+        let command = ExecCommand::parse("assets tag --bytes={bytes} {path}").unwrap();
+        let file_entry = FileEntry::new(String::from("/media/it's a clip.mov"), 2048);
+        assert_eq!(command.arguments(&file_entry, &mut Names::default()), ["assets", "tag", "--bytes=2048", "/media/it's a clip.mov"]);
+        assert!(ExecCommand::parse("  ").is_err());
+        assert!(ExecCommand::parse("tag {nope}").is_err());
+    }
+}
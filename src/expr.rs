@@ -0,0 +1,264 @@
+// Arithmetic over the fields of an entry for --rank-expr, the results being the entries scoring the
+// highest, e.g. size * age_days or size / (depth + 1)
+//
+// Supported syntax:
+//   + - * / %           the usual precedence, - also in front of a value
+//   ( )                 grouping
+//   1 0.5 1e6           numbers
+//   size apparent       the size used for the ranking and the length, in bytes
+//   age age_days        time since the entry was last read or written, in seconds or days
+//   mtime_days          days since it was last written
+//   atime_days          days since it was last read
+//   depth links         directories above it in its path, hard links to it
+//
+// Fields an entry doesn't have count as 0, a single link for links. Scores that aren't a number,
+// like 0 / 0, rank last
+use std::path::{Component, Path};
+use std::time::SystemTime;
+
+use crate::FileEntry;
+
+pub const FIELDS: [&str; 8] = ["size", "apparent", "age", "age_days", "mtime_days", "atime_days", "depth", "links"];
+
+const DAY: f64 = 24.0 * 60.0 * 60.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Size,
+    Apparent,
+    Age,
+    AgeDays,
+    ModifiedDays,
+    AccessedDays,
+    Depth,
+    Links,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Field> {
+        match name {
+            "size" => Some(Field::Size),
+            "apparent" => Some(Field::Apparent),
+            "age" => Some(Field::Age),
+            "age_days" => Some(Field::AgeDays),
+            "mtime_days" => Some(Field::ModifiedDays),
+            "atime_days" => Some(Field::AccessedDays),
+            "depth" => Some(Field::Depth),
+            "links" => Some(Field::Links),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Number(f64),
+    Field(Field),
+    Negate(Box<Node>),
+    Binary(Operator, Box<Node>, Box<Node>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RankExpr {
+    root: Node,
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn skip_spaces(&mut self) {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_spaces();
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    // Sums and differences of terms
+    fn expression(&mut self) -> Result<Node, String> {
+        let mut node = self.term()?;
+        loop {
+            let operator = match self.peek() {
+                Some('+') => Operator::Add,
+                Some('-') => Operator::Subtract,
+                _ => return Ok(node),
+            };
+            self.pos += 1;
+            node = Node::Binary(operator, Box::new(node), Box::new(self.term()?));
+        }
+    }
+
+    // Products, quotients and remainders of factors
+    fn term(&mut self) -> Result<Node, String> {
+        let mut node = self.factor()?;
+        loop {
+            let operator = match self.peek() {
+                Some('*') => Operator::Multiply,
+                Some('/') => Operator::Divide,
+                Some('%') => Operator::Remainder,
+                _ => return Ok(node),
+            };
+            self.pos += 1;
+            node = Node::Binary(operator, Box::new(node), Box::new(self.factor()?));
+        }
+    }
+
+    fn factor(&mut self) -> Result<Node, String> {
+        let c = self.peek().ok_or("unexpected end of expression")?;
+        if c == '-' {
+            self.pos += 1;
+            return Ok(Node::Negate(Box::new(self.factor()?)));
+        }
+        if c == '(' {
+            self.pos += 1;
+            let inner = self.expression()?;
+            if !self.eat(')') {
+                return Err("unclosed parenthesis".to_string());
+            }
+            return Ok(inner);
+        }
+        let start = self.pos;
+        if c.is_ascii_digit() || c == '.' {
+            while self.chars.get(self.pos).is_some_and(|c| c.is_ascii_alphanumeric() || *c == '.') {
+                // The sign of an exponent belongs to the number
+                let exponent = matches!(self.chars[self.pos], 'e' | 'E');
+                self.pos += 1;
+                if exponent && matches!(self.chars.get(self.pos), Some('+' | '-')) {
+                    self.pos += 1;
+                }
+            }
+            let number: String = self.chars[start..self.pos].iter().collect();
+            return number.parse().map(Node::Number).map_err(|_| format!("invalid number '{}'", number));
+        }
+        if c.is_ascii_alphabetic() || c == '_' {
+            while self.chars.get(self.pos).is_some_and(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                self.pos += 1;
+            }
+            let name: String = self.chars[start..self.pos].iter().collect();
+            return Field::parse(&name)
+                .map(Node::Field)
+                .ok_or_else(|| format!("unknown field '{}', expected one of: {}", name, FIELDS.join(", ")));
+        }
+        Err(format!("unexpected '{}'", c))
+    }
+}
+
+// Days from a time to now, none when the time is unknown or ahead of now
+fn days_since(now: SystemTime, time: Option<SystemTime>) -> f64 {
+    time.and_then(|time| now.duration_since(time).ok()).map_or(0.0, |age| age.as_secs_f64() / DAY)
+}
+
+fn field_value(field: Field, entry: &FileEntry, now: SystemTime) -> f64 {
+    match field {
+        Field::Size => entry.size as f64,
+        Field::Apparent => entry.apparent_size.unwrap_or(entry.size) as f64,
+        Field::Age => days_since(now, entry.modified.max(entry.accessed)) * DAY,
+        Field::AgeDays => days_since(now, entry.modified.max(entry.accessed)),
+        Field::ModifiedDays => days_since(now, entry.modified),
+        Field::AccessedDays => days_since(now, entry.accessed),
+        Field::Depth => Path::new(&entry.path).components().filter(|component| matches!(component, Component::Normal(_))).count().saturating_sub(1) as f64,
+        Field::Links => entry.links.unwrap_or(1) as f64,
+    }
+}
+
+fn evaluate(node: &Node, entry: &FileEntry, now: SystemTime) -> f64 {
+    match node {
+        Node::Number(number) => *number,
+        Node::Field(field) => field_value(*field, entry, now),
+        Node::Negate(inner) => -evaluate(inner, entry, now),
+        Node::Binary(operator, left, right) => {
+            let (left, right) = (evaluate(left, entry, now), evaluate(right, entry, now));
+            match operator {
+                Operator::Add => left + right,
+                Operator::Subtract => left - right,
+                Operator::Multiply => left * right,
+                Operator::Divide => left / right,
+                Operator::Remainder => left % right,
+            }
+        }
+    }
+}
+
+impl RankExpr {
+    pub fn parse(expression: &str) -> Result<RankExpr, String> {
+        let mut parser = Parser { chars: expression.chars().collect(), pos: 0 };
+        let root = parser.expression()?;
+        match parser.peek() {
+            None => Ok(RankExpr { root }),
+            Some(')') => Err("unmatched ')'".to_string()),
+            Some(c) => Err(format!("unexpected '{}'", c)),
+        }
+    }
+
+    // The score of an entry, its age counted up to now
+    pub fn score(&self, entry: &FileEntry, now: SystemTime) -> f64 {
+        let score = evaluate(&self.root, entry, now);
+        if score.is_nan() { f64::NEG_INFINITY } else { score }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn score(expression: &str, entry: &FileEntry) -> f64 {
+        RankExpr::parse(expression).unwrap().score(entry, SystemTime::UNIX_EPOCH + Duration::from_secs(100 * 86400))
+    }
+
+    #[test]
+    fn follows_the_usual_precedence() {
+        let entry = FileEntry::new("/a".to_string(), 10);
+        assert_eq!(score("1 + 2 * 3", &entry), 7.0);
+        assert_eq!(score("(1 + 2) * 3", &entry), 9.0);
+        assert_eq!(score("-size + 12 % 5", &entry), -8.0);
+        assert_eq!(score("1e3 / 4", &entry), 250.0);
+    }
+
+    #[test]
+    fn reads_the_fields_of_the_entry() {
+        let entry = FileEntry {
+            modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(90 * 86400)),
+            links: Some(2),
+            ..FileEntry::new("/srv/data/a.log".to_string(), 1000)
+        };
+        assert_eq!(score("size * age_days", &entry), 10000.0);
+        assert_eq!(score("atime_days", &entry), 0.0);
+        assert_eq!(score("depth + links", &entry), 4.0);
+        assert_eq!(score("0 / 0", &entry), f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn rejects_what_it_cant_read() {
+        assert!(RankExpr::parse("size *").is_err());
+        assert!(RankExpr::parse("(size").is_err());
+        assert!(RankExpr::parse("size)").is_err());
+        assert!(RankExpr::parse("bytes").is_err());
+        assert!(RankExpr::parse("size size").is_err());
+        assert!(RankExpr::parse("1.2.3").is_err());
+    }
+}
@@ -0,0 +1,186 @@
+// Where the blocks of files really are on Linux, from the FIEMAP ioctl. Btrfs and XFS share
+// extents between reflinked and deduplicated files, which stat counts again for every one of them,
+// and btrfs compresses extents behind the same lengths. Counting each shared extent once, with
+// the first file met holding it, adds up to what the filesystem consumes. ZFS doesn't map its
+// extents, its block counts already reflect compression so the allocated size stands in
+use std::collections::HashSet;
+use std::fs::Metadata;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+#[cfg(any(target_os = "linux", test))]
+const FIEMAP_EXTENT_LAST: u32 = 0x1;
+// Compressed or otherwise encoded, the length is the data once decoded
+#[cfg(any(target_os = "linux", test))]
+const FIEMAP_EXTENT_ENCODED: u32 = 0x8;
+#[cfg(any(target_os = "linux", test))]
+const FIEMAP_EXTENT_SHARED: u32 = 0x2000;
+// struct fiemap_extent is 56 bytes and struct fiemap 32
+#[cfg(any(target_os = "linux", test))]
+const EXTENT_SIZE: usize = 56;
+#[cfg(any(target_os = "linux", test))]
+const HEADER_SIZE: usize = 32;
+
+#[cfg(any(target_os = "linux", test))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Extent {
+    logical: u64,
+    physical: u64,
+    length: u64,
+    flags: u32,
+}
+
+// The shared extents already counted, on which device, and what the scan found on the way
+#[derive(Debug, Default)]
+pub(crate) struct SeenExtents {
+    seen: Mutex<HashSet<(u64, u64)>>,
+    shared_bytes: AtomicU64,
+    compressed_files: AtomicU64,
+}
+
+impl SeenExtents {
+    pub(crate) fn clear(&self) {
+        self.seen.lock().unwrap().clear();
+        self.shared_bytes.store(0, Ordering::Relaxed);
+        self.compressed_files.store(0, Ordering::Relaxed);
+    }
+
+    // Bytes of shared extents left out because another file counted them, and the compressed files met
+    pub(crate) fn found(&self) -> (u64, u64) {
+        (self.shared_bytes.load(Ordering::Relaxed), self.compressed_files.load(Ordering::Relaxed))
+    }
+
+    // The bytes the extents take that no file counted before
+    #[cfg(any(target_os = "linux", test))]
+    fn count(&self, device: u64, extents: &[Extent]) -> u64 {
+        if extents.iter().any(|extent| extent.flags & FIEMAP_EXTENT_ENCODED != 0) {
+            self.compressed_files.fetch_add(1, Ordering::Relaxed);
+        }
+        let mut size = 0;
+        for extent in extents {
+            // Only the shared ones can be met again, no need to remember the others
+            if extent.flags & FIEMAP_EXTENT_SHARED != 0 && !self.seen.lock().unwrap().insert((device, extent.physical)) {
+                self.shared_bytes.fetch_add(extent.length, Ordering::Relaxed);
+                continue;
+            }
+            size += extent.length;
+        }
+        size
+    }
+}
+
+// The extents of a struct fiemap the kernel filled, and whether the last one is among them
+#[cfg(any(target_os = "linux", test))]
+fn parse_extents(buffer: &[u8]) -> (Vec<Extent>, bool) {
+    let word = |offset: usize| u32::from_ne_bytes(buffer[offset..offset + 4].try_into().unwrap());
+    let long = |offset: usize| u64::from_ne_bytes(buffer[offset..offset + 8].try_into().unwrap());
+    let mapped = word(20) as usize;
+    let mut extents = Vec::with_capacity(mapped);
+    let mut last = mapped == 0;
+    for index in 0..mapped {
+        let offset = HEADER_SIZE + index * EXTENT_SIZE;
+        if offset + EXTENT_SIZE > buffer.len() {
+            break;
+        }
+        let flags = word(offset + 40);
+        last |= flags & FIEMAP_EXTENT_LAST != 0;
+        extents.push(Extent { logical: long(offset), physical: long(offset + 8), length: long(offset + 16), flags });
+    }
+    (extents, last)
+}
+
+// Every extent of the file, none when the filesystem doesn't map them
+#[cfg(target_os = "linux")]
+fn read_extents(path: &Path) -> Option<Vec<Extent>> {
+    use std::fs::File;
+    use std::os::fd::AsRawFd;
+
+    // _IOWR('f', 11, struct fiemap)
+    const FS_IOC_FIEMAP: u64 = 0xc020_660b;
+    const FIEMAP_FLAG_SYNC: u32 = 0x1;
+    const BATCH: u32 = 256;
+
+    let file = File::open(path).ok()?;
+    let mut extents = Vec::new();
+    let mut start = 0u64;
+    loop {
+        let mut header = Vec::with_capacity(HEADER_SIZE);
+        header.extend(start.to_ne_bytes());
+        header.extend((u64::MAX - start).to_ne_bytes());
+        for word in [FIEMAP_FLAG_SYNC, 0, BATCH, 0] {
+            header.extend(word.to_ne_bytes());
+        }
+        // Words of 8 bytes keep the buffer aligned for the kernel
+        let mut buffer = vec![0u64; (HEADER_SIZE + BATCH as usize * EXTENT_SIZE) / 8];
+        for (word, bytes) in buffer.iter_mut().zip(header.chunks_exact(8)) {
+            *word = u64::from_ne_bytes(bytes.try_into().unwrap());
+        }
+        // SAFETY: the buffer holds the header and as many extents as fm_extent_count tells the kernel
+        if unsafe { libc::ioctl(file.as_raw_fd(), FS_IOC_FIEMAP as _, buffer.as_mut_ptr()) } != 0 {
+            return None;
+        }
+        let bytes: Vec<u8> = buffer.iter().flat_map(|word| word.to_ne_bytes()).collect();
+        let (batch, last) = parse_extents(&bytes);
+        let end = batch.last().map_or(start, |extent| extent.logical + extent.length);
+        extents.extend(batch);
+        if last || end <= start {
+            return Some(extents);
+        }
+        start = end;
+    }
+}
+
+// The bytes the file takes on the disk that no file met before shares, none when the filesystem
+// doesn't map its extents
+#[cfg(target_os = "linux")]
+pub(crate) fn physical_size(path: &Path, metadata: &Metadata, seen: &SeenExtents) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    let extents = read_extents(path)?;
+    Some(seen.count(metadata.dev(), &extents))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn physical_size(_path: &Path, _metadata: &Metadata, _seen: &SeenExtents) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fiemap(extents: &[(u64, u64, u64, u32)]) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        for word in [0u64, u64::MAX] {
+            buffer.extend(word.to_ne_bytes());
+        }
+        for word in [0, extents.len() as u32, 256, 0] {
+            buffer.extend(u32::to_ne_bytes(word));
+        }
+        for &(logical, physical, length, flags) in extents {
+            for word in [logical, physical, length, 0, 0] {
+                buffer.extend(word.to_ne_bytes());
+            }
+            for word in [flags, 0, 0, 0] {
+                buffer.extend(u32::to_ne_bytes(word));
+            }
+        }
+        buffer
+    }
+
+    #[test]
+    fn counts_shared_extents_once() {
+        let (extents, last) = parse_extents(&fiemap(&[(0, 1 << 20, 4096, FIEMAP_EXTENT_SHARED), (4096, 8 << 20, 8192, FIEMAP_EXTENT_ENCODED | FIEMAP_EXTENT_LAST)]));
+        assert!(last);
+        assert_eq!(extents[1], Extent { logical: 4096, physical: 8 << 20, length: 8192, flags: FIEMAP_EXTENT_ENCODED | FIEMAP_EXTENT_LAST });
+
+        let seen = SeenExtents::default();
+        assert_eq!(seen.count(1, &extents), 12288);
+        // A reflinked copy only adds the extents it doesn't share
+        let (copy, _) = parse_extents(&fiemap(&[(0, 1 << 20, 4096, FIEMAP_EXTENT_SHARED), (4096, 9 << 20, 8192, FIEMAP_EXTENT_LAST)]));
+        assert_eq!(seen.count(1, &copy), 8192);
+        assert_eq!(seen.count(2, &copy), 12288);
+        assert_eq!(seen.found(), (4096, 1));
+        assert_eq!(parse_extents(&fiemap(&[])), (Vec::new(), true));
+    }
+}
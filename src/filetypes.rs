@@ -0,0 +1,62 @@
+// File type categories for --type, each one is a list of extensions
+// The built-in ones can be extended or overridden with define()
+
+const BUILTIN: [(&str, &[&str]); 8] = [
+    ("video", &["mp4", "mkv", "avi", "mov", "wmv", "flv", "webm", "m4v", "mpg", "mpeg", "ts", "vob"]),
+    ("audio", &["mp3", "flac", "wav", "aac", "ogg", "opus", "m4a", "wma", "aiff", "alac"]),
+    ("image", &["jpg", "jpeg", "png", "gif", "bmp", "tiff", "tif", "webp", "heic", "raw", "cr2", "nef", "psd", "svg"]),
+    ("archive", &["zip", "tar", "gz", "tgz", "bz2", "xz", "zst", "7z", "rar", "tar.gz", "tar.xz", "tar.zst", "tar.bz2"]),
+    ("document", &["pdf", "doc", "docx", "xls", "xlsx", "ppt", "pptx", "odt", "ods", "odp", "epub", "txt", "md"]),
+    ("disk", &["iso", "img", "dmg", "vmdk", "vdi", "vhd", "vhdx", "qcow2"]),
+    ("log", &["log", "out", "err"]),
+    ("temp", &["tmp", "temp", "bak", "swp", "old", "part", "crdownload"]),
+];
+
+#[derive(Debug, Clone)]
+pub struct FileTypes {
+    categories: Vec<(String, Vec<String>)>,
+}
+
+impl Default for FileTypes {
+    fn default() -> FileTypes {
+        FileTypes {
+            categories: BUILTIN.iter()
+                .map(|(name, extensions)| (name.to_string(), extensions.iter().map(|ext| ext.to_string()).collect()))
+                .collect(),
+        }
+    }
+}
+
+impl FileTypes {
+    // Add a category, or replace the extensions of an existing one
+    pub fn define(&mut self, name: &str, extensions: Vec<String>) {
+        let extensions: Vec<String> = extensions.iter().map(|ext| normalize_extension(ext)).collect();
+        match self.categories.iter_mut().find(|(existing, _)| existing == name) {
+            Some((_, existing)) => *existing = extensions,
+            None => self.categories.push((name.to_string(), extensions)),
+        }
+    }
+
+    pub fn extensions(&self, name: &str) -> Option<&[String]> {
+        self.categories.iter()
+            .find(|(existing, _)| existing.eq_ignore_ascii_case(name))
+            .map(|(_, extensions)| extensions.as_slice())
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.categories.iter().map(|(name, _)| name.as_str()).collect()
+    }
+}
+
+// "ISO" and ".iso" both become "iso"
+pub fn normalize_extension(extension: &str) -> String {
+    extension.trim().trim_start_matches('.').to_lowercase()
+}
+
+// Whether the file name ends with one of the extensions, multi part ones like "tar.gz" included
+pub fn has_extension(file_name: &str, extensions: &[String]) -> bool {
+    let file_name = file_name.to_lowercase();
+    extensions.iter().any(|ext| {
+        file_name.len() > ext.len() + 1 && file_name.ends_with(ext.as_str()) && file_name[..file_name.len() - ext.len()].ends_with('.')
+    })
+}
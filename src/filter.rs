@@ -0,0 +1,229 @@
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::filetypes::has_extension;
+use crate::glob::Glob;
+use crate::ignore::{Ignores, FATASSIGNORE, GITIGNORE};
+use crate::regex::Regex;
+use crate::windows;
+use crate::{trace, FileEntry};
+
+// Rules deciding which files are worth looking at during the walk
+#[derive(Debug, Clone)]
+pub(crate) struct Filters {
+    pub(crate) min_size: u64,
+    pub(crate) excludes: Vec<Glob>,
+    pub(crate) includes: Vec<Glob>,
+    pub(crate) respect_gitignore: bool,
+    // Read the .fatassignore files met on the way
+    pub(crate) fatassignore: bool,
+    pub(crate) hidden: bool,
+    // Walk into symlinked directories, and measure symlinked files by their target
+    pub(crate) follow_symlinks: bool,
+    // Even then, leave the junctions of Windows alone
+    pub(crate) skip_junctions: bool,
+    // Don't cross into other mounted filesystems than the search path one
+    pub(crate) one_file_system: bool,
+    // Deepest level walked below the search path, unbounded when none
+    pub(crate) max_depth: Option<usize>,
+    // Bounds on the last modification time of files
+    pub(crate) modified_before: Option<SystemTime>,
+    pub(crate) modified_after: Option<SystemTime>,
+    // Only files with one of these extensions, lowercased and without the dot, any when empty
+    pub(crate) extensions: Vec<String>,
+    // Matched against the full path, unlike globs which see the path relative to the search path
+    pub(crate) path_regexes: Vec<Regex>,
+    pub(crate) path_regex_excludes: Vec<Regex>,
+}
+
+impl Default for Filters {
+    fn default() -> Filters {
+        Filters {
+            min_size: 0,
+            excludes: Vec::new(),
+            includes: Vec::new(),
+            respect_gitignore: false,
+            fatassignore: true,
+            hidden: true,
+            follow_symlinks: false,
+            skip_junctions: false,
+            one_file_system: false,
+            max_depth: None,
+            modified_before: None,
+            modified_after: None,
+            extensions: Vec::new(),
+            path_regexes: Vec::new(),
+            path_regex_excludes: Vec::new(),
+        }
+    }
+}
+
+impl Filters {
+    // Hidden and ignored entries are skipped whatever their type, dirs included
+    pub(crate) fn keeps_visible(&self, path: &Path, is_dir: bool, ignores: &Ignores) -> bool {
+        let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+        if !self.hidden && name.starts_with('.') {
+            trace!("Skipping {}: hidden", path.display());
+            return false;
+        }
+        if (self.respect_gitignore && name == ".git") || ignores.is_ignored(path, is_dir) {
+            trace!("Skipping {}: ignored", path.display());
+            return false;
+        }
+        true
+    }
+
+    // Whether a symlink is walked into or measured by its target
+    pub(crate) fn follows_link(&self, path: &Path) -> bool {
+        if !self.follow_symlinks {
+            return false;
+        }
+        if self.skip_junctions && windows::is_junction(path) {
+            trace!("Skipping {}: a junction", path.display());
+            return false;
+        }
+        true
+    }
+
+    pub(crate) fn reads_ignore_files(&self) -> bool {
+        self.respect_gitignore || self.fatassignore
+    }
+
+    // The ignore rules applying to the content of dir, a .fatassignore has the last word over a
+    // .gitignore next to it
+    pub(crate) fn ignores_for(&self, dir: &Path, parent: &Ignores) -> Ignores {
+        match (self.respect_gitignore, self.fatassignore) {
+            (true, true) => parent.child(dir, &[GITIGNORE, FATASSIGNORE]),
+            (true, false) => parent.child(dir, &[GITIGNORE]),
+            (false, true) => parent.child(dir, &[FATASSIGNORE]),
+            (false, false) => Ignores::default(),
+        }
+    }
+
+    pub(crate) fn keeps_size(&self, size: u64) -> bool {
+        size != 0 && size >= self.min_size
+    }
+
+    // Files with no known modification time can't be placed, so any age bound skips them
+    pub(crate) fn keeps_age(&self, modified: Option<SystemTime>) -> bool {
+        if self.modified_before.is_none() && self.modified_after.is_none() {
+            return true;
+        }
+        let Some(modified) = modified else { return false };
+        self.modified_before.is_none_or(|before| modified < before)
+            && self.modified_after.is_none_or(|after| modified > after)
+    }
+
+    // A path listed rather than walked, its directories go through the checks the walk would have
+    // pruned them with, and it can't be deeper than the walk would go
+    pub(crate) fn keeps_listed(&self, root: &Path, path: &Path) -> bool {
+        if self.max_depth.is_some_and(|max_depth| path.strip_prefix(root).is_ok_and(|relative| relative.components().count() > max_depth)) {
+            return false;
+        }
+        let no_ignores = Ignores::default();
+        let mut dirs = path.ancestors().skip(1).take_while(|dir| *dir != root && dir.starts_with(root));
+        dirs.all(|dir| self.keeps_visible(dir, true, &no_ignores) && self.keeps_dir(root, dir))
+            && self.keeps_visible(path, false, &no_ignores)
+            && self.keeps_file(root, path)
+    }
+
+    pub(crate) fn keeps_entry(&self, file_entry: &FileEntry) -> bool {
+        self.keeps_size(file_entry.size) && self.keeps_age(file_entry.modified)
+    }
+
+    // Excluded directories are pruned, includes only apply to files since any dir may contain a match
+    pub(crate) fn keeps_dir(&self, root: &Path, path: &Path) -> bool {
+        if !self.path_regex_excludes.is_empty() {
+            let full_path = path.to_string_lossy();
+            if self.path_regex_excludes.iter().any(|regex| regex.is_match(&full_path)) {
+                trace!("Skipping {}: excluded by a path regex", path.display());
+                return false;
+            }
+        }
+        if self.excludes.is_empty() {
+            return true;
+        }
+        let relative = relative_path(root, path);
+        let kept = !self.excludes.iter().any(|glob| glob.matches_dir(&relative));
+        if !kept {
+            trace!("Skipping {}: excluded", path.display());
+        }
+        kept
+    }
+
+    pub(crate) fn keeps_file(&self, root: &Path, path: &Path) -> bool {
+        if !self.extensions.is_empty() {
+            let file_name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+            if !has_extension(&file_name, &self.extensions) {
+                trace!("Skipping {}: other extension", path.display());
+                return false;
+            }
+        }
+        if !self.path_regexes.is_empty() || !self.path_regex_excludes.is_empty() {
+            let full_path = path.to_string_lossy();
+            if self.path_regex_excludes.iter().any(|regex| regex.is_match(&full_path)) {
+                trace!("Skipping {}: excluded by a path regex", path.display());
+                return false;
+            }
+            if !self.path_regexes.is_empty() && !self.path_regexes.iter().any(|regex| regex.is_match(&full_path)) {
+                trace!("Skipping {}: no path regex matches", path.display());
+                return false;
+            }
+        }
+        if self.excludes.is_empty() && self.includes.is_empty() {
+            return true;
+        }
+        let relative = relative_path(root, path);
+        if self.excludes.iter().any(|glob| glob.matches(&relative)) {
+            trace!("Skipping {}: excluded", path.display());
+            return false;
+        }
+        if !self.includes.is_empty() && !self.includes.iter().any(|glob| glob.matches(&relative)) {
+            trace!("Skipping {}: not included", path.display());
+            return false;
+        }
+        true
+    }
+}
+
+// Path relative to the search path with '/' separators, the form globs are matched against
+pub(crate) fn relative_path(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let relative = relative.to_string_lossy();
+    if std::path::MAIN_SEPARATOR == '/' {
+        relative.to_string()
+    } else {
+        relative.replace(std::path::MAIN_SEPARATOR, "/")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prunes_excluded_dirs_and_includes_files() {
+        let root = Path::new("/srv");
+        let filters = Filters {
+            excludes: vec![Glob::new("node_modules/**").unwrap(), Glob::new("*.iso").unwrap()],
+            includes: vec![Glob::new("*.rs").unwrap(), Glob::new("*.iso").unwrap()],
+            hidden: false,
+            respect_gitignore: true,
+            ..Filters::default()
+        };
+        assert!(!filters.keeps_dir(root, &root.join("web/node_modules")));
+        // Includes never prune, a directory of any name may hold a match
+        assert!(filters.keeps_dir(root, &root.join("docs")));
+        assert!(filters.keeps_file(root, &root.join("src/main.rs")));
+        assert!(!filters.keeps_file(root, &root.join("src/notes.txt")));
+        // Excludes win over includes
+        assert!(!filters.keeps_file(root, &root.join("images/disk.iso")));
+
+        let no_ignores = Ignores::default();
+        assert!(!filters.keeps_visible(&root.join(".cache"), true, &no_ignores));
+        assert!(!filters.keeps_visible(&root.join("repo/.git"), true, &no_ignores));
+        assert!(!filters.keeps_listed(root, &root.join("web/node_modules/a/b.rs")));
+        assert!(!filters.keeps_listed(root, &root.join("src/.hidden/b.rs")));
+        assert!(filters.keeps_listed(root, &root.join("src/lib/b.rs")));
+    }
+}
@@ -0,0 +1,163 @@
+// Git repositories seen as what they cost: the whole .git directory as a single entry rather than
+// thousands of objects and pack fragments, and the largest blobs it stores, read from the pack
+// indexes and the loose objects. Deltified blobs are left out, their size is only known by
+// rebuilding them, which the big binary files bloating repositories rarely are anyway
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use crate::archive::{ArchiveMember, ArchivePeek, Inflate};
+use crate::{EntryKind, FileEntry};
+
+const GIT_DIR: &str = ".git";
+const IDX_MAGIC: &[u8] = b"\xfftOc";
+const OBJECT_BLOB: u8 = 3;
+
+// The .git directory a path is below, the innermost one for submodules
+pub(crate) fn store_of(path: &Path) -> Option<&Path> {
+    path.ancestors().skip(1).find(|dir| dir.file_name() == Some(OsStr::new(GIT_DIR)))
+}
+
+// Sizes and apparent sizes of the files below every .git directory found
+#[derive(Debug, Default)]
+pub(crate) struct GitStores {
+    sizes: HashMap<PathBuf, (u64, u64)>,
+}
+
+impl GitStores {
+    pub(crate) fn add(&mut self, store: &Path, file_entry: &FileEntry) {
+        let sizes = self.sizes.entry(store.to_path_buf()).or_insert((0, 0));
+        sizes.0 += file_entry.size;
+        sizes.1 += file_entry.apparent_size.unwrap_or(file_entry.size);
+    }
+
+    pub(crate) fn merge(&mut self, other: GitStores) {
+        for (store, (size, apparent_size)) in other.sizes {
+            let sizes = self.sizes.entry(store).or_insert((0, 0));
+            sizes.0 += size;
+            sizes.1 += apparent_size;
+        }
+    }
+
+    // One directory entry for every store, timestamped by the directory itself
+    pub(crate) fn into_entries(self, disk_usage: bool) -> impl Iterator<Item = FileEntry> {
+        self.sizes.into_iter().map(move |(store, (size, apparent_size))| {
+            let mut store_entry = FileEntry::new_dir(store.display().to_string(), size);
+            if disk_usage {
+                store_entry = store_entry.with_apparent_size(apparent_size);
+            }
+            match fs::metadata(&store) {
+                Ok(metadata) => store_entry.with_metadata(&metadata),
+                Err(_) => store_entry,
+            }
+        })
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn be_u32(bytes: &[u8], offset: usize) -> io::Result<u32> {
+    let word = bytes.get(offset..offset + 4).ok_or_else(|| invalid("truncated pack index"))?;
+    Ok(u32::from_be_bytes(word.try_into().unwrap()))
+}
+
+// The blobs of a pack, by the object headers at the offsets its version 2 index gives
+fn packed_blobs(idx_path: &Path, blobs: &mut Vec<ArchiveMember>) -> io::Result<()> {
+    let idx = fs::read(idx_path)?;
+    if !idx.starts_with(IDX_MAGIC) || be_u32(&idx, 4)? != 2 {
+        return Err(invalid("not a version 2 pack index"));
+    }
+    let count = be_u32(&idx, 8 + 255 * 4)? as usize;
+    let names = 8 + 256 * 4;
+    let offsets = names + count * 24;
+    let large_offsets = offsets + count * 4;
+    let mut objects: Vec<(u64, usize)> = Vec::with_capacity(count);
+    for index in 0..count {
+        let offset = be_u32(&idx, offsets + index * 4)?;
+        // Offsets past 2 GiB are kept in a table of 64 bit ones
+        let offset = if offset & 0x8000_0000 != 0 {
+            let at = large_offsets + (offset & 0x7fff_ffff) as usize * 8;
+            (be_u32(&idx, at)? as u64) << 32 | be_u32(&idx, at + 4)? as u64
+        } else {
+            offset as u64
+        };
+        objects.push((offset, index));
+    }
+    // In pack order, so the headers are read going forward
+    objects.sort_unstable();
+
+    let mut pack = BufReader::with_capacity(64 * 1024, File::open(idx_path.with_extension("pack"))?);
+    let mut position = 0u64;
+    for (offset, index) in objects {
+        pack.seek_relative(offset as i64 - position as i64)?;
+        let mut byte = [0u8];
+        pack.read_exact(&mut byte)?;
+        let mut read = 1;
+        let kind = (byte[0] >> 4) & 7;
+        let mut size = (byte[0] & 0x0f) as u64;
+        let mut shift = 4;
+        while byte[0] & 0x80 != 0 && shift < 64 {
+            pack.read_exact(&mut byte)?;
+            read += 1;
+            size |= ((byte[0] & 0x7f) as u64) << shift;
+            shift += 7;
+        }
+        position = offset + read;
+        if kind == OBJECT_BLOB {
+            blobs.push(ArchiveMember { path: hex(&idx[names + index * 20..names + index * 20 + 20]), size });
+        }
+    }
+    Ok(())
+}
+
+// A loose object's header, "blob <size>" then a NUL, its name is its directory and file names
+fn loose_blob(path: &Path) -> io::Result<Option<u64>> {
+    let mut header = Vec::new();
+    Inflate::zlib(File::open(path)?).take(32).read_to_end(&mut header)?;
+    let end = header.iter().position(|byte| *byte == 0).ok_or_else(|| invalid("no object header"))?;
+    let header = String::from_utf8_lossy(&header[..end]).into_owned();
+    Ok(header.strip_prefix("blob ").and_then(|size| size.parse().ok()))
+}
+
+// The largest blobs of the object store
+pub fn largest_blobs(store: &Path) -> io::Result<ArchivePeek> {
+    let objects = store.join("objects");
+    let mut blobs = Vec::new();
+    for entry in fs::read_dir(objects.join("pack"))?.flatten() {
+        let path = entry.path();
+        if path.extension() == Some(OsStr::new("idx")) {
+            // A pack being written or damaged only hides its own blobs
+            let _ = packed_blobs(&path, &mut blobs);
+        }
+    }
+    for dir in fs::read_dir(&objects)?.flatten() {
+        let prefix = dir.file_name().to_string_lossy().into_owned();
+        if prefix.len() != 2 || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+            continue;
+        }
+        for object in fs::read_dir(dir.path())?.flatten() {
+            if let Ok(Some(size)) = loose_blob(&object.path()) {
+                blobs.push(ArchiveMember { path: format!("{}{}", prefix, object.file_name().to_string_lossy()), size });
+            }
+        }
+    }
+    Ok(ArchivePeek::new("git", blobs))
+}
+
+// Fill in the largest blobs of the .git directories among the entries
+pub fn peek_repositories(entries: &mut [FileEntry]) {
+    for entry in entries.iter_mut().filter(|entry| entry.kind == EntryKind::Directory) {
+        let path = Path::new(&entry.path);
+        if path.file_name() == Some(OsStr::new(GIT_DIR)) {
+            entry.archive = largest_blobs(path).ok();
+        }
+    }
+}
@@ -0,0 +1,193 @@
+// Small glob matcher for --include/--exclude, paths are always matched with '/' separators
+//
+// Supported syntax:
+//   *      anything except '/'
+//   **     anything, including '/'
+//   ?      a single character except '/'
+//   [abc]  a character class, [!abc] negated, [a-z] ranges
+//
+// Patterns without a '/' are matched against the file name only, the others against the path
+// relative to the search path, at any depth unless they start with '/'.
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Literal(char),
+    AnyChar,
+    Star,
+    DoubleStar,
+    // "**/" matches zero or more whole directories
+    DoubleStarSlash,
+    Class { negated: bool, ranges: Vec<(char, char)> },
+}
+
+#[derive(Debug, Clone)]
+pub struct Glob {
+    tokens: Vec<Token>,
+    basename_only: bool,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Result<Glob, String> {
+        let normalized = pattern.replace('\\', "/");
+        let basename_only = !normalized.trim_end_matches('/').contains('/');
+
+        // Directory-only patterns like "target/" behave like "target" since dirs are matched too
+        let mut body = normalized.trim_end_matches('/').to_string();
+        if body.is_empty() {
+            return Err(format!("invalid glob '{}': empty pattern", pattern));
+        }
+        if !basename_only {
+            body = match body.strip_prefix('/') {
+                Some(anchored) => anchored.to_string(),
+                None if body.starts_with("**/") => body,
+                None => format!("**/{}", body),
+            };
+        }
+
+        Ok(Glob {
+            tokens: tokenize(&body).map_err(|err| format!("invalid glob '{}': {}", pattern, err))?,
+            basename_only,
+        })
+    }
+
+    // Match a '/' separated path relative to the search path
+    pub fn matches(&self, relative_path: &str) -> bool {
+        let text: Vec<char> = if self.basename_only {
+            relative_path.rsplit('/').next().unwrap_or(relative_path).chars().collect()
+        } else {
+            relative_path.chars().collect()
+        };
+        match_from(&self.tokens, &text)
+    }
+
+    // A directory also matches when the pattern covers everything below it, e.g. "node_modules/**"
+    pub fn matches_dir(&self, relative_path: &str) -> bool {
+        self.matches(relative_path) || (!self.basename_only && self.matches(&format!("{}/", relative_path)))
+    }
+}
+
+fn tokenize(pattern: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                if chars.get(i + 2) == Some(&'/') {
+                    tokens.push(Token::DoubleStarSlash);
+                    i += 3;
+                } else {
+                    tokens.push(Token::DoubleStar);
+                    i += 2;
+                }
+                continue;
+            }
+            '*' => tokens.push(Token::Star),
+            '?' => tokens.push(Token::AnyChar),
+            '[' => {
+                let mut j = i + 1;
+                let negated = matches!(chars.get(j), Some('!') | Some('^'));
+                if negated {
+                    j += 1;
+                }
+
+                let mut ranges = Vec::new();
+                // A ']' right after the opening bracket is a literal
+                let mut first = true;
+                while j < chars.len() && (chars[j] != ']' || first) {
+                    first = false;
+                    if chars.get(j + 1) == Some(&'-') && chars.get(j + 2).is_some_and(|c| *c != ']') {
+                        ranges.push((chars[j], chars[j + 2]));
+                        j += 3;
+                    } else {
+                        ranges.push((chars[j], chars[j]));
+                        j += 1;
+                    }
+                }
+                if j >= chars.len() {
+                    return Err("unclosed character class".to_string());
+                }
+
+                tokens.push(Token::Class { negated, ranges });
+                i = j + 1;
+                continue;
+            }
+            c => tokens.push(Token::Literal(c)),
+        }
+        i += 1;
+    }
+
+    Ok(tokens)
+}
+
+fn match_from(tokens: &[Token], text: &[char]) -> bool {
+    let Some((token, rest)) = tokens.split_first() else {
+        return text.is_empty();
+    };
+
+    match token {
+        Token::Literal(c) => text.first() == Some(c) && match_from(rest, &text[1..]),
+        Token::AnyChar => text.first().is_some_and(|c| *c != '/') && match_from(rest, &text[1..]),
+        Token::Class { negated, ranges } => {
+            text.first().is_some_and(|c| {
+                *c != '/' && ranges.iter().any(|(low, high)| low <= c && c <= high) != *negated
+            }) && match_from(rest, &text[1..])
+        }
+        Token::Star => {
+            for skip in 0..=text.len() {
+                if match_from(rest, &text[skip..]) {
+                    return true;
+                }
+                if skip < text.len() && text[skip] == '/' {
+                    break;
+                }
+            }
+            false
+        }
+        Token::DoubleStar => (0..=text.len()).any(|skip| match_from(rest, &text[skip..])),
+        Token::DoubleStarSlash => {
+            match_from(rest, text)
+                || text.iter().enumerate().any(|(i, c)| *c == '/' && match_from(rest, &text[i + 1..]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_like_exclude_globs() {
+        let cases = [
+            ("*.iso", "a/b/x.iso", true),
+            ("*.iso", "x.iso.bak", false),
+            ("node_modules/**", "web/node_modules/a/b.js", true),
+            ("node_modules/**", "node_modules/a.js", true),
+            ("/build", "build", true),
+            ("/build", "src/build", false),
+            ("src/*.rs", "src/main.rs", true),
+            ("src/*.rs", "lib/src/main.rs", true),
+            ("src/*.rs", "src/a/main.rs", false),
+            ("a/**/z", "a/z", true),
+            ("a/**/z", "a/b/c/z", true),
+            ("a/**/z", "b/z", false),
+            ("?.txt", "a.txt", true),
+            ("?.txt", "ab.txt", false),
+            ("[!a-c]x", "dx", true),
+            ("[!a-c]x", "bx", false),
+            ("[]]", "]", true),
+            ("dir\\*.tmp", "dir/a.tmp", true),
+            ("**", "any/thing", true),
+        ];
+        for (pattern, path, expected) in cases {
+            assert_eq!(Glob::new(pattern).unwrap().matches(path), expected, "{} on {}", pattern, path);
+        }
+
+        // A directory is pruned when the pattern covers everything below it
+        assert!(Glob::new("node_modules/**").unwrap().matches_dir("web/node_modules"));
+        assert!(!Glob::new("*.iso/**").unwrap().matches_dir("isos"));
+        assert!(Glob::new("").is_err());
+        assert!(Glob::new("[abc").is_err());
+    }
+}
@@ -0,0 +1,116 @@
+// Totals of the scanned files gathered by extension, directory, owner or year, to see at a glance
+// which kind of files takes the space
+use std::collections::HashMap;
+use std::iter;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::hardlinks::SeenLinks;
+use crate::owners::Names;
+use crate::scanner::{spinner, walk};
+use crate::time::year;
+use crate::topn::TopN;
+use crate::{FileEntry, Scanner};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GroupKey {
+    Ext,
+    // The directory the files are directly in
+    Dir,
+    Owner,
+    // The year the files were last modified
+    Year,
+}
+
+impl GroupKey {
+    pub fn parse(value: &str) -> Option<GroupKey> {
+        match value {
+            "ext" => Some(GroupKey::Ext),
+            "dir" => Some(GroupKey::Dir),
+            "owner" => Some(GroupKey::Owner),
+            "year" => Some(GroupKey::Year),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            GroupKey::Ext => "Extension",
+            GroupKey::Dir => "Directory",
+            GroupKey::Owner => "Owner",
+            GroupKey::Year => "Year",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Group {
+    pub name: String,
+    pub size: u64,
+    pub files: u64,
+    // The fattest files of the group, biggest first
+    pub top: Vec<FileEntry>,
+}
+
+// The groups taking the most space, up to the scanner count, each with its per_group fattest files
+pub fn group_files(scanner: &Scanner, key: GroupKey, per_group: usize) -> Vec<Group> {
+    // Size, file count and fattest files of each group
+    let mut groups: HashMap<String, (u64, u64, TopN)> = HashMap::new();
+    let mut names = Names::default();
+
+    // Listed files come through the scan, which applies the filters already
+    if scanner.source.is_some() {
+        let listed = Mutex::new((&mut groups, &mut names));
+        scanner.scan_with(&|file_entry| {
+            let (groups, names) = &mut *listed.lock().unwrap();
+            add_to_group(groups, names, key, per_group, file_entry.clone());
+        });
+        return biggest_groups(groups, scanner.fatass_count);
+    }
+
+    let filters = &scanner.filters;
+    let progress_bar = spinner(scanner.progress);
+    let seen_links = SeenLinks::new(scanner.hardlinks);
+    for search_path in scanner.roots() {
+        let root = Path::new(&search_path);
+        for entry in walk(&search_path, filters, &scanner.errors)
+            .filter(|e| !e.file_type().is_dir())
+            .filter(|e| filters.keeps_file(root, e.path()))
+        {
+            progress_bar.inc(1);
+            let Some(metadata) = scanner.errors.walk_metadata(&entry).filter(|metadata| seen_links.first_visit(metadata)) else { continue };
+            let file_entry = scanner.measure(entry.path(), &metadata);
+            let stream_entries = scanner.stream_entries(&file_entry);
+            for file_entry in iter::once(file_entry).chain(stream_entries).filter(|file_entry| filters.keeps_entry(file_entry)) {
+                add_to_group(&mut groups, &mut names, key, per_group, file_entry);
+            }
+        }
+    }
+    progress_bar.finish();
+    biggest_groups(groups, scanner.fatass_count)
+}
+
+fn add_to_group(groups: &mut HashMap<String, (u64, u64, TopN)>, names: &mut Names, key: GroupKey, per_group: usize, file_entry: FileEntry) {
+    let name = match key {
+        GroupKey::Ext => match file_entry.extension() {
+            extension if extension.is_empty() => String::from("(none)"),
+            extension => format!(".{}", extension),
+        },
+        GroupKey::Dir => Path::new(&file_entry.path).parent().map(|dir| dir.display().to_string()).unwrap_or_default(),
+        GroupKey::Owner => file_entry.uid.map(|uid| names.user(uid)).unwrap_or_else(|| String::from("unknown")),
+        GroupKey::Year => file_entry.modified.map(|modified| year(modified).to_string()).unwrap_or_else(|| String::from("unknown")),
+    };
+    let (size, files, top) = groups.entry(name).or_insert_with(|| (0, 0, TopN::new(per_group)));
+    *size += file_entry.size;
+    *files += 1;
+    top.push(file_entry);
+}
+
+fn biggest_groups(groups: HashMap<String, (u64, u64, TopN)>, count: usize) -> Vec<Group> {
+    let mut groups: Vec<Group> = groups.into_iter()
+        .map(|(name, (size, files, top))| Group { name, size, files, top: top.into_sorted_vec() })
+        .collect();
+    groups.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+    groups.truncate(count);
+    groups
+}
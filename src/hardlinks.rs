@@ -0,0 +1,78 @@
+// Hard links make one file reachable from several paths, counting it once means remembering the
+// (device, inode) pairs already met. Other platforms don't expose them, every path counts there.
+use std::collections::HashSet;
+use std::fs::Metadata;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum HardlinkMode {
+    // Every path to a file counts at full size
+    #[default]
+    All,
+    // Only the first path reached counts
+    Once,
+}
+
+impl HardlinkMode {
+    pub fn parse(value: &str) -> Option<HardlinkMode> {
+        match value {
+            "all" => Some(HardlinkMode::All),
+            "once" => Some(HardlinkMode::Once),
+            _ => None,
+        }
+    }
+}
+
+// Shared by the walker threads of a scan
+#[derive(Debug, Default)]
+pub(crate) struct SeenLinks {
+    mode: HardlinkMode,
+    seen: Mutex<HashSet<(u64, u64)>>,
+}
+
+impl SeenLinks {
+    pub(crate) fn new(mode: HardlinkMode) -> SeenLinks {
+        SeenLinks { mode, seen: Mutex::new(HashSet::new()) }
+    }
+
+    // Whether the file should be counted, false when another of its links already was
+    pub(crate) fn first_visit(&self, metadata: &Metadata) -> bool {
+        self.first_visit_stat(link_count(metadata), file_id(metadata))
+    }
+
+    // Same as first_visit, from a link count and file id known already
+    pub(crate) fn first_visit_stat(&self, links: Option<u64>, id: Option<(u64, u64)>) -> bool {
+        // Files with a single link can't be met twice, no need to remember them
+        if self.mode == HardlinkMode::All || links.unwrap_or(1) <= 1 {
+            return true;
+        }
+        match id {
+            Some(id) => self.seen.lock().unwrap().insert(id),
+            None => true,
+        }
+    }
+}
+
+// The (device, inode) pair identifying a file whatever the path it's reached from
+#[cfg(unix)]
+pub(crate) fn file_id(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn file_id(_metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+// How many paths lead to the file
+#[cfg(unix)]
+pub(crate) fn link_count(metadata: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.nlink())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn link_count(_metadata: &Metadata) -> Option<u64> {
+    None
+}
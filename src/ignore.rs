@@ -0,0 +1,156 @@
+// Gitignore style rule files, loaded per directory while walking
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::glob::Glob;
+use crate::filter::relative_path;
+
+pub(crate) const GITIGNORE: &str = ".gitignore";
+// Same syntax, for what only scans should skip, e.g. scratch directories kept next to the data
+pub(crate) const FATASSIGNORE: &str = ".fatassignore";
+
+#[derive(Debug)]
+struct Rule {
+    glob: Glob,
+    negated: bool,
+    dir_only: bool,
+}
+
+// The rules of one ignore file, relative to the directory holding it
+#[derive(Debug)]
+struct IgnoreFile {
+    dir: PathBuf,
+    rules: Vec<Rule>,
+}
+
+impl IgnoreFile {
+    fn load(dir: &Path, file: &Path) -> Option<IgnoreFile> {
+        let content = fs::read_to_string(file).ok()?;
+        let rules: Vec<Rule> = content.lines().filter_map(parse_rule).collect();
+        if rules.is_empty() {
+            return None;
+        }
+        Some(IgnoreFile { dir: dir.to_path_buf(), rules })
+    }
+
+    // Last matching rule wins, none means this file has no opinion
+    fn matched(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let relative = relative_path(&self.dir, path);
+        self.rules.iter().rev()
+            .find(|rule| (is_dir || !rule.dir_only) && rule.glob.matches(&relative))
+            .map(|rule| !rule.negated)
+    }
+}
+
+fn parse_rule(line: &str) -> Option<Rule> {
+    let line = line.trim_end();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let (negated, line) = match line.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, line.strip_prefix('\\').unwrap_or(line)),
+    };
+    let dir_only = line.ends_with('/');
+    let body = line.trim_end_matches('/');
+
+    // Unlike --exclude globs, a slash anywhere but the end anchors the rule to the ignore file directory
+    let pattern = if body.contains('/') && !body.starts_with('/') && !body.starts_with("**/") {
+        format!("/{}", body)
+    } else {
+        body.to_string()
+    };
+
+    Some(Rule { glob: Glob::new(&pattern).ok()?, negated, dir_only })
+}
+
+// Every ignore file from the search path down to the current directory, deepest last
+#[derive(Debug, Clone, Default)]
+pub struct Ignores {
+    files: Vec<Arc<IgnoreFile>>,
+}
+
+impl Ignores {
+    // The rules applying inside dir, with the ignore files it holds added on top of its parents ones
+    pub fn child(&self, dir: &Path, names: &[&str]) -> Ignores {
+        let mut files = self.files.clone();
+
+        // The excludes of a repository go along with its .gitignore files
+        let git_exclude = names.contains(&GITIGNORE).then(|| dir.join(".git").join("info").join("exclude"));
+        let candidates = git_exclude.into_iter().chain(names.iter().map(|name| dir.join(name)));
+        for candidate in candidates {
+            if let Some(file) = IgnoreFile::load(dir, &candidate) {
+                files.push(Arc::new(file));
+            }
+        }
+
+        Ignores { files }
+    }
+
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        self.files.iter().rev()
+            .find_map(|file| file.matched(path, is_dir))
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Checked against git check-ignore, a path counts as ignored when one of its parent directories
+    // is, the walk never goes into those
+    #[test]
+    fn ignores_like_git() {
+        let content = "# comment\n*.log\n!keep.log\nbuild/\n/root_only.txt\ndocs/*.md\n**/cache\na/**/z\n[Tt]emp*\n\\#hash\n\\!bang\nout/\n!out/keep.txt\n";
+        let root = Path::new("/repo");
+        let file = IgnoreFile { dir: root.to_path_buf(), rules: content.lines().filter_map(parse_rule).collect() };
+        let ignores = Ignores { files: vec![Arc::new(file)] };
+        let ignored = |relative: &str, is_dir: bool| {
+            let path = root.join(relative);
+            path.ancestors().skip(1).take_while(|dir| *dir != root).any(|dir| ignores.is_ignored(dir, true))
+                || ignores.is_ignored(&path, is_dir)
+        };
+
+        let cases = [
+            ("app.log", false, true),
+            ("sub/app.log", false, true),
+            ("keep.log", false, false),
+            ("sub/keep.log", false, false),
+            ("build", true, true),
+            ("sub/build", true, true),
+            ("build/x", false, true),
+            ("f2/build", false, false),
+            ("root_only.txt", false, true),
+            ("sub/root_only.txt", false, false),
+            ("docs/a.md", false, true),
+            ("docs/sub/a.md", false, false),
+            ("sub/docs/a.md", false, false),
+            ("x/y/cache", true, true),
+            ("cache", false, true),
+            ("a/z", false, true),
+            ("a/b/c/z", false, true),
+            ("b/a/z", false, false),
+            ("Temp1", false, true),
+            ("temp2", false, true),
+            ("xtemp", false, false),
+            ("#hash", false, true),
+            ("!bang", false, true),
+            ("out", true, true),
+            ("out/keep.txt", false, true),
+        ];
+        for (relative, is_dir, expected) in cases {
+            assert_eq!(ignored(relative, is_dir), expected, "{}", relative);
+        }
+
+        // A deeper ignore file overrides its parents, anchored to its own directory
+        let nested = IgnoreFile { dir: root.join("sub"), rules: "!app.log\n/local\n".lines().filter_map(parse_rule).collect() };
+        let ignores = Ignores { files: vec![ignores.files[0].clone(), Arc::new(nested)] };
+        assert!(!ignores.is_ignored(&root.join("sub/app.log"), false));
+        assert!(ignores.is_ignored(&root.join("sub/other.log"), false));
+        assert!(ignores.is_ignored(&root.join("sub/local"), false));
+        assert!(!ignores.is_ignored(&root.join("sub/deeper/local"), false));
+    }
+}
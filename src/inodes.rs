@@ -0,0 +1,65 @@
+// Directories holding the most entries, for filesystems running out of inodes long before they run
+// out of space. Every file, directory and link below a directory counts once for it, the direct
+// count only for those right inside it
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::interrupt::interrupted;
+use crate::progress;
+use crate::scanner::{spinner, walk};
+use crate::Scanner;
+
+#[derive(Debug, Clone)]
+pub struct DirCount {
+    pub path: String,
+    // Entries anywhere below the directory
+    pub entries: u64,
+    // Entries right inside it
+    pub direct: u64,
+}
+
+// The most crowded directories, up to the scanner count, by their recursive count
+pub fn count_entries(scanner: &Scanner) -> Vec<DirCount> {
+    let filters = &scanner.filters;
+    let progress_bar = spinner(scanner.progress);
+    progress::start(scanner.json_progress);
+
+    let mut counts: HashMap<PathBuf, (u64, u64)> = HashMap::new();
+    for search_path in scanner.roots() {
+        let root = Path::new(&search_path);
+        for entry in walk(&search_path, filters, &scanner.errors).filter(|e| e.depth() > 0) {
+            if interrupted() {
+                break;
+            }
+            // Directories were kept by the walk already, files still go through the globs and types
+            let is_dir = entry.file_type().is_dir();
+            if !is_dir && !filters.keeps_file(root, entry.path()) {
+                continue;
+            }
+            if filters.modified_before.is_some() || filters.modified_after.is_some() {
+                let modified = scanner.errors.walk_metadata(&entry).and_then(|metadata| metadata.modified().ok());
+                if !filters.keeps_age(modified) {
+                    continue;
+                }
+            }
+            progress_bar.inc(1);
+            progress::scanned(0);
+            for (depth, dir) in entry.path().ancestors().skip(1).take_while(|dir| dir.starts_with(root)).enumerate() {
+                let count = counts.entry(dir.to_path_buf()).or_insert((0, 0));
+                count.0 += 1;
+                if depth == 0 {
+                    count.1 += 1;
+                }
+            }
+        }
+    }
+    progress_bar.finish();
+    progress::finish();
+
+    let mut counts: Vec<DirCount> = counts.into_iter()
+        .map(|(dir, (entries, direct))| DirCount { path: dir.display().to_string(), entries, direct })
+        .collect();
+    counts.sort_by(|a, b| b.entries.cmp(&a.entries).then_with(|| a.path.cmp(&b.path)));
+    counts.truncate(scanner.fatass_count);
+    counts
+}
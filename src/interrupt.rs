@@ -0,0 +1,121 @@
+// Ctrl-C during a scan stops it where it is so the fattest entries found so far can still be shown,
+// a second Ctrl-C exits right away like it would have without the handler. The --max-files and
+// --timeout limits stop a scan the same way, for that scan only
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// What stopped the current scan short of Ctrl-C
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Limit {
+    Files,
+    Time,
+}
+
+const NO_LIMIT: u8 = 0;
+static REACHED: AtomicU8 = AtomicU8::new(NO_LIMIT);
+static MAX_FILES: AtomicU64 = AtomicU64::new(u64::MAX);
+static FILES: AtomicU64 = AtomicU64::new(0);
+// Milliseconds past EPOCH the scan has to be over by
+static DEADLINE: AtomicU64 = AtomicU64::new(u64::MAX);
+static EPOCH: OnceLock<Instant> = OnceLock::new();
+
+// Only an atomic swap and _exit, both safe to call from a signal handler
+fn on_interrupt() {
+    if INTERRUPTED.swap(true, Ordering::SeqCst) {
+        exit_now();
+    }
+}
+
+// Whether to stop, on Ctrl-C or a limit of the current scan
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed) || limit_reached().is_some()
+}
+
+fn elapsed_millis() -> u64 {
+    EPOCH.get_or_init(Instant::now).elapsed().as_millis() as u64
+}
+
+fn reach(limit: Limit) {
+    let _ = REACHED.compare_exchange(NO_LIMIT, limit as u8 + 1, Ordering::Relaxed, Ordering::Relaxed);
+}
+
+pub(crate) fn limit_reached() -> Option<Limit> {
+    if REACHED.load(Ordering::Relaxed) == NO_LIMIT && elapsed_millis() >= DEADLINE.load(Ordering::Relaxed) {
+        reach(Limit::Time);
+    }
+    match REACHED.load(Ordering::Relaxed) {
+        NO_LIMIT => None,
+        1 => Some(Limit::Files),
+        _ => Some(Limit::Time),
+    }
+}
+
+// Set the limits of the scan about to start, none for an exhaustive one
+pub(crate) fn limit_scan(max_files: Option<u64>, timeout: Option<Duration>) {
+    REACHED.store(NO_LIMIT, Ordering::Relaxed);
+    FILES.store(0, Ordering::Relaxed);
+    MAX_FILES.store(max_files.unwrap_or(u64::MAX), Ordering::Relaxed);
+    let deadline = timeout.map_or(u64::MAX, |timeout| elapsed_millis().saturating_add(timeout.as_millis() as u64));
+    DEADLINE.store(deadline, Ordering::Relaxed);
+}
+
+// Count a file the scan went through, reaching --max-files stops it
+pub(crate) fn scanned_file() {
+    if FILES.fetch_add(1, Ordering::Relaxed) + 1 >= MAX_FILES.load(Ordering::Relaxed) {
+        reach(Limit::Files);
+    }
+}
+
+// Lift the limits once the scan is over, returns the one that stopped it if any
+pub(crate) fn end_scan() -> Option<Limit> {
+    let limit = limit_reached();
+    limit_scan(None, None);
+    limit
+}
+
+#[cfg(unix)]
+fn exit_now() {
+    // SAFETY: _exit is async-signal-safe, 130 is what shells report for SIGINT
+    unsafe { libc::_exit(130) }
+}
+
+#[cfg(not(unix))]
+fn exit_now() {
+    std::process::exit(130);
+}
+
+#[cfg(unix)]
+pub fn install() {
+    extern "C" fn handler(_signal: libc::c_int) {
+        on_interrupt();
+    }
+    // SAFETY: the handler only touches an atomic before possibly exiting
+    unsafe {
+        libc::signal(libc::SIGINT, handler as extern "C" fn(libc::c_int) as libc::sighandler_t);
+    }
+}
+
+#[cfg(windows)]
+pub fn install() {
+    use windows_sys::Win32::Foundation::BOOL;
+    use windows_sys::Win32::System::Console::{SetConsoleCtrlHandler, CTRL_C_EVENT};
+
+    // Runs on a thread of its own, returning 1 tells Windows the event was handled
+    unsafe extern "system" fn handler(ctrl_type: u32) -> BOOL {
+        if ctrl_type != CTRL_C_EVENT {
+            return 0;
+        }
+        on_interrupt();
+        1
+    }
+    // SAFETY: the handler is a plain function living as long as the program
+    unsafe {
+        SetConsoleCtrlHandler(Some(handler), 1);
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn install() {}
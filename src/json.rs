@@ -0,0 +1,193 @@
+// Just enough JSON to read the metadata other tools leave on disk, e.g. the image and container
+// descriptions of Docker and Podman. Numbers are kept as written, nothing here does math on them
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Json {
+    Null,
+    Boolean(bool),
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    // The document, none when it isn't valid JSON
+    pub(crate) fn parse(text: &str) -> Option<Json> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut pos = 0;
+        let value = parse_value(&chars, &mut pos)?;
+        skip_space(&chars, &mut pos);
+        (pos == chars.len()).then_some(value)
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<&Json> {
+        self.fields().iter().find(|(name, _)| name == key).map(|(_, value)| value)
+    }
+
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    // The items of an array, none for anything else
+    pub(crate) fn items(&self) -> &[Json] {
+        match self {
+            Json::Array(items) => items,
+            _ => &[],
+        }
+    }
+
+    pub(crate) fn fields(&self) -> &[(String, Json)] {
+        match self {
+            Json::Object(fields) => fields,
+            _ => &[],
+        }
+    }
+}
+
+fn skip_space(chars: &[char], pos: &mut usize) {
+    while chars.get(*pos).is_some_and(|c| c.is_whitespace()) {
+        *pos += 1;
+    }
+}
+
+fn parse_hex4(chars: &[char], pos: &mut usize) -> Option<u32> {
+    let digits: String = chars.get(*pos..*pos + 4)?.iter().collect();
+    *pos += 4;
+    u32::from_str_radix(&digits, 16).ok()
+}
+
+fn parse_string(chars: &[char], pos: &mut usize) -> Option<String> {
+    *pos += 1;
+    let mut value = String::new();
+    loop {
+        let c = *chars.get(*pos)?;
+        *pos += 1;
+        match c {
+            '"' => return Some(value),
+            '\\' => {
+                let escaped = *chars.get(*pos)?;
+                *pos += 1;
+                value.push(match escaped {
+                    'n' => '\n',
+                    't' => '\t',
+                    'r' => '\r',
+                    'b' => '\u{8}',
+                    'f' => '\u{c}',
+                    'u' => {
+                        let mut code = parse_hex4(chars, pos)?;
+                        // Characters past the basic plane come as a pair of surrogates
+                        if (0xd800..0xdc00).contains(&code) && chars.get(*pos..*pos + 2) == Some(&['\\', 'u']) {
+                            *pos += 2;
+                            let low = parse_hex4(chars, pos)?;
+                            code = 0x10000 + ((code - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff);
+                        }
+                        char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+                    }
+                    other => other,
+                });
+            }
+            c => value.push(c),
+        }
+    }
+}
+
+fn parse_value(chars: &[char], pos: &mut usize) -> Option<Json> {
+    skip_space(chars, pos);
+    match chars.get(*pos)? {
+        '"' => parse_string(chars, pos).map(Json::String),
+        '[' => {
+            *pos += 1;
+            let mut items = Vec::new();
+            skip_space(chars, pos);
+            if chars.get(*pos) == Some(&']') {
+                *pos += 1;
+                return Some(Json::Array(items));
+            }
+            loop {
+                items.push(parse_value(chars, pos)?);
+                skip_space(chars, pos);
+                match chars.get(*pos)? {
+                    ',' => *pos += 1,
+                    ']' => {
+                        *pos += 1;
+                        return Some(Json::Array(items));
+                    }
+                    _ => return None,
+                }
+            }
+        }
+        '{' => {
+            *pos += 1;
+            let mut fields = Vec::new();
+            skip_space(chars, pos);
+            if chars.get(*pos) == Some(&'}') {
+                *pos += 1;
+                return Some(Json::Object(fields));
+            }
+            loop {
+                skip_space(chars, pos);
+                if chars.get(*pos) != Some(&'"') {
+                    return None;
+                }
+                let key = parse_string(chars, pos)?;
+                skip_space(chars, pos);
+                if chars.get(*pos) != Some(&':') {
+                    return None;
+                }
+                *pos += 1;
+                fields.push((key, parse_value(chars, pos)?));
+                skip_space(chars, pos);
+                match chars.get(*pos)? {
+                    ',' => *pos += 1,
+                    '}' => {
+                        *pos += 1;
+                        return Some(Json::Object(fields));
+                    }
+                    _ => return None,
+                }
+            }
+        }
+        _ => {
+            let start = *pos;
+            while chars.get(*pos).is_some_and(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '+' | '.')) {
+                *pos += 1;
+            }
+            let word: String = chars[start..*pos].iter().collect();
+            match word.as_str() {
+                "null" => Some(Json::Null),
+                "true" => Some(Json::Boolean(true)),
+                "false" => Some(Json::Boolean(false)),
+                _ if word.parse::<f64>().is_ok() => Some(Json::Number(word)),
+                _ => None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_nested_documents() {
+        let json = Json::parse(r#" {"Name": "/web", "rootfs": {"diff_ids": ["sha256:ab", "sha256:cd"]}, "Size": -1.5e3, "Tty": false, "Args": null, "Note": "caf\u00e9 \ud83d\udc33"} "#).unwrap();
+        assert_eq!(json.get("Name").and_then(Json::as_str), Some("/web"));
+        let diff_ids: Vec<&str> = json.get("rootfs").and_then(|rootfs| rootfs.get("diff_ids")).unwrap().items().iter().filter_map(Json::as_str).collect();
+        assert_eq!(diff_ids, ["sha256:ab", "sha256:cd"]);
+        assert_eq!(json.get("Size"), Some(&Json::Number("-1.5e3".to_string())));
+        assert_eq!(json.get("Tty"), Some(&Json::Boolean(false)));
+        assert_eq!(json.get("Args"), Some(&Json::Null));
+        assert_eq!(json.get("Note").and_then(Json::as_str), Some("café 🐳"));
+        assert_eq!(Json::parse("[]"), Some(Json::Array(Vec::new())));
+    }
+
+    #[test]
+    fn rejects_broken_documents() {
+        for text in ["", "{", "[1,]", "{\"a\" 1}", "\"open", "[1] 2", "nope"] {
+            assert_eq!(Json::parse(text), None, "{}", text);
+        }
+    }
+}
@@ -0,0 +1,175 @@
+pub mod archive;
+pub mod cache;
+pub mod checksum;
+pub mod clean;
+pub mod compress;
+pub mod compressible;
+pub mod config;
+pub mod daemon;
+pub mod delete;
+pub mod desktop;
+pub mod docker;
+pub mod dupes;
+pub mod errors;
+pub mod estimate;
+pub mod exec;
+pub mod expr;
+pub mod filetypes;
+pub mod ftp;
+pub mod git;
+pub mod glob;
+pub mod groups;
+pub mod inodes;
+pub mod interrupt;
+pub mod logging;
+pub mod macos;
+pub mod magic;
+pub mod metrics;
+pub mod mft;
+pub mod ncdu;
+pub mod notify;
+pub mod offload;
+pub mod output;
+pub mod pager;
+pub mod parquet;
+pub mod paths;
+pub mod presets;
+pub mod priority;
+pub mod regex;
+pub mod remote;
+pub mod s3;
+pub mod script;
+pub mod serve;
+pub mod size;
+pub mod snapshot;
+pub mod sort;
+pub mod source;
+pub mod sqlite;
+pub mod stats;
+pub mod template;
+pub mod throttle;
+pub mod time;
+pub mod tree;
+pub mod treemap;
+pub mod tui;
+pub mod volumes;
+pub mod xattrs;
+mod extents;
+mod filter;
+mod hardlinks;
+mod ignore;
+mod json;
+mod markup;
+mod owners;
+mod progress;
+mod scanner;
+mod topn;
+mod trash;
+mod windows;
+
+pub use filetypes::FileTypes;
+pub use glob::Glob;
+pub use hardlinks::HardlinkMode;
+pub use config::Config;
+pub use output::{ColorChoice, OutputFormat, OutputOptions, Theme};
+pub use owners::format_mode;
+pub use regex::Regex;
+pub use scanner::{Scan, Scanner};
+pub use size::{allocated_size, format_size, parse_size, set_unit_style, UnitStyle};
+pub use sort::SortKey;
+pub use topn::{Rank, TopN};
+
+use std::fs::Metadata;
+use std::path::Path;
+use std::time::SystemTime;
+
+use archive::ArchivePeek;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EntryKind {
+    File,
+    Directory,
+}
+
+impl EntryKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EntryKind::File => "file",
+            EntryKind::Directory => "dir",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FileEntry {
+    pub path: String,
+    pub size: u64,
+    // The length of the entry, when its size is the allocated space instead
+    pub apparent_size: Option<u64>,
+    pub kind: EntryKind,
+    pub modified: Option<SystemTime>,
+    pub accessed: Option<SystemTime>,
+    // Number of hard links to the file, where the platform tells
+    pub links: Option<u64>,
+    // Owning user and group ids and the mode bits, on Unix
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub mode: Option<u32>,
+    // What the content looks like, e.g. application/gzip, when it was sniffed
+    pub content_type: Option<&'static str>,
+    // Hexadecimal checksum of the content, when it was hashed
+    pub checksum: Option<String>,
+    // The notable extended attributes, comma separated, when they were listed
+    pub xattrs: Option<String>,
+    // The largest members when it's an archive that was peeked into
+    pub archive: Option<ArchivePeek>,
+}
+
+impl FileEntry {
+    pub fn new(path: String, size: u64) -> FileEntry {
+        FileEntry { path, size, apparent_size: None, kind: EntryKind::File, modified: None, accessed: None, links: None, uid: None, gid: None, mode: None, content_type: None, checksum: None, xattrs: None, archive: None }
+    }
+
+    // A directory rollup, its size is the sum of every file below it
+    pub fn new_dir(path: String, size: u64) -> FileEntry {
+        FileEntry { path, size, apparent_size: None, kind: EntryKind::Directory, modified: None, accessed: None, links: None, uid: None, gid: None, mode: None, content_type: None, checksum: None, xattrs: None, archive: None }
+    }
+
+    // Carry the timestamps of the metadata already fetched during the walk
+    pub fn with_metadata(mut self, metadata: &Metadata) -> FileEntry {
+        self.modified = metadata.modified().ok();
+        self.accessed = metadata.accessed().ok();
+        (self.uid, self.gid, self.mode) = owners::ownership(metadata);
+        if self.kind == EntryKind::File {
+            self.links = hardlinks::link_count(metadata);
+        }
+        self
+    }
+
+    pub fn with_apparent_size(mut self, apparent_size: u64) -> FileEntry {
+        self.apparent_size = Some(apparent_size);
+        self
+    }
+
+    pub fn name(&self) -> String {
+        Path::new(&self.path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.path.clone())
+    }
+
+    // Lowercased extension, empty for directories and files without one
+    pub fn extension(&self) -> String {
+        if self.kind == EntryKind::Directory {
+            return String::new();
+        }
+        Path::new(&self.path)
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default()
+    }
+
+    pub fn get_str_size(&self) -> String {
+        format_size(self.size)
+    }
+}
@@ -0,0 +1,142 @@
+// What APFS keeps beside the length and blocks stat tells: the resource forks of old Mac files,
+// the blocks clones share with each other, and the Time Machine local snapshots mounted like any
+// volume. Finder counts forks and a clone only takes the blocks it changed, so without them the
+// sizes don't add up to what the disk holds. Decoding the getattrlist buffer is plain byte work,
+// only the call itself is macOS only
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Mutex;
+
+// Where macOS mounts the local snapshots, before and since Big Sur
+const LOCAL_SNAPSHOTS: [&str; 3] = ["/Volumes/com.apple.TimeMachine.localsnapshots/", "/Volumes/.timemachine/", "/.MobileBackups/"];
+
+// The attribute groups asked for, the values come back in this order, the absent ones left out
+#[cfg(any(target_os = "macos", test))]
+const ATTR_FILE_RSRCLENGTH: u32 = 0x1000;
+#[cfg(any(target_os = "macos", test))]
+const ATTR_FILE_RSRCALLOCSIZE: u32 = 0x2000;
+#[cfg(any(target_os = "macos", test))]
+const ATTR_CMNEXT_PRIVATESIZE: u32 = 0x8;
+#[cfg(any(target_os = "macos", test))]
+const ATTR_CMNEXT_CLONEID: u32 = 0x100;
+#[cfg(any(target_os = "macos", test))]
+const ATTR_CMNEXT_EXT_FLAGS: u32 = 0x200;
+// Set in the extended flags of files that may share blocks with a clone
+#[cfg(any(target_os = "macos", test))]
+const EF_MAY_SHARE_BLOCKS: u64 = 0x1;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct ApfsAttributes {
+    // Length and allocated size of the resource fork, 0 for most files
+    pub(crate) fork_length: u64,
+    pub(crate) fork_allocated: u64,
+    // The clone family of a file that may share blocks, with the bytes only it holds
+    pub(crate) clone: Option<(u64, u64)>,
+}
+
+// The clone families already counted, the next files of one only count their private blocks
+#[derive(Debug, Default)]
+pub(crate) struct SeenClones {
+    seen: Mutex<HashSet<u64>>,
+}
+
+impl SeenClones {
+    pub(crate) fn first_visit(&self, clone_id: u64) -> bool {
+        self.seen.lock().unwrap().insert(clone_id)
+    }
+
+    pub(crate) fn clear(&self) {
+        self.seen.lock().unwrap().clear();
+    }
+}
+
+// Whether the path is in a Time Machine local snapshot, which macOS purges itself when it runs
+// short of space
+pub fn in_local_snapshot(path: &str) -> bool {
+    LOCAL_SNAPSHOTS.iter().any(|snapshot| path.starts_with(snapshot) || path == snapshot.trim_end_matches('/'))
+}
+
+// The attributes of a getattrlist buffer asked with ATTR_CMN_RETURNED_ATTRS: its length, the five
+// groups of attributes returned, then their 8 byte values
+#[cfg(any(target_os = "macos", test))]
+fn parse_attributes(buffer: &[u8]) -> Option<ApfsAttributes> {
+    let word = |offset: usize| buffer.get(offset..offset + 4).map(|bytes| u32::from_ne_bytes(bytes.try_into().unwrap()));
+    let length = word(0)? as usize;
+    let (file, fork) = (word(16)?, word(20)?);
+    let mut values = buffer.get(24..length)?.chunks_exact(8).map(|bytes| u64::from_ne_bytes(bytes.try_into().unwrap()));
+    let mut value = |returned: u32, bit: u32| if returned & bit != 0 { values.next() } else { None };
+
+    let fork_length = value(file, ATTR_FILE_RSRCLENGTH).unwrap_or(0);
+    let fork_allocated = value(file, ATTR_FILE_RSRCALLOCSIZE).unwrap_or(0);
+    let private_size = value(fork, ATTR_CMNEXT_PRIVATESIZE);
+    let clone_id = value(fork, ATTR_CMNEXT_CLONEID);
+    let shares_blocks = value(fork, ATTR_CMNEXT_EXT_FLAGS).is_some_and(|flags| flags & EF_MAY_SHARE_BLOCKS != 0);
+    let clone = clone_id.zip(private_size).filter(|_| shares_blocks);
+    Some(ApfsAttributes { fork_length, fork_allocated, clone })
+}
+
+#[cfg(target_os = "macos")]
+pub(crate) fn apfs_attributes(path: &Path) -> ApfsAttributes {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let Ok(c_path) = CString::new(path.as_os_str().as_bytes()) else { return ApfsAttributes::default() };
+    let mut request = libc::attrlist {
+        bitmapcount: libc::ATTR_BIT_MAP_COUNT,
+        reserved: 0,
+        commonattr: libc::ATTR_CMN_RETURNED_ATTRS,
+        volattr: 0,
+        dirattr: 0,
+        fileattr: ATTR_FILE_RSRCLENGTH | ATTR_FILE_RSRCALLOCSIZE,
+        // The extended common attributes go in the fork group
+        forkattr: ATTR_CMNEXT_PRIVATESIZE | ATTR_CMNEXT_CLONEID | ATTR_CMNEXT_EXT_FLAGS,
+    };
+    let mut buffer = [0u8; 128];
+    let options = libc::FSOPT_NOFOLLOW | libc::FSOPT_ATTR_CMN_EXTENDED;
+    // SAFETY: c_path is nul terminated, request and buffer outlive the call which writes at most buffer.len() bytes
+    let read = unsafe { libc::getattrlist(c_path.as_ptr(), &mut request as *mut _ as *mut libc::c_void, buffer.as_mut_ptr() as *mut libc::c_void, buffer.len(), options) };
+    if read != 0 {
+        return ApfsAttributes::default();
+    }
+    parse_attributes(&buffer).unwrap_or_default()
+}
+
+#[cfg(not(target_os = "macos"))]
+pub(crate) fn apfs_attributes(_path: &Path) -> ApfsAttributes {
+    ApfsAttributes::default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(file: u32, fork: u32, values: &[u64]) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&(24 + 8 * values.len() as u32).to_ne_bytes());
+        for group in [0x8000_0000, 0, 0, file, fork] {
+            buffer.extend_from_slice(&u32::to_ne_bytes(group));
+        }
+        for value in values {
+            buffer.extend_from_slice(&value.to_ne_bytes());
+        }
+        buffer
+    }
+
+    #[test]
+    fn reads_apfs_attributes() {
+        let all_file = ATTR_FILE_RSRCLENGTH | ATTR_FILE_RSRCALLOCSIZE;
+        let all_fork = ATTR_CMNEXT_PRIVATESIZE | ATTR_CMNEXT_CLONEID | ATTR_CMNEXT_EXT_FLAGS;
+        let clone = parse_attributes(&buffer(all_file, all_fork, &[300, 4096, 8192, 77, EF_MAY_SHARE_BLOCKS])).unwrap();
+        assert_eq!(clone, ApfsAttributes { fork_length: 300, fork_allocated: 4096, clone: Some((77, 8192)) });
+        // Files that don't share blocks have a clone id too, and HFS+ volumes tell none of it
+        let plain = parse_attributes(&buffer(all_file, all_fork, &[0, 0, 8192, 78, 0])).unwrap();
+        assert_eq!(plain, ApfsAttributes::default());
+        let hfs = parse_attributes(&buffer(all_file, 0, &[300, 4096])).unwrap();
+        assert_eq!(hfs, ApfsAttributes { fork_length: 300, fork_allocated: 4096, clone: None });
+        assert_eq!(parse_attributes(&[0; 8]), None);
+
+        assert!(in_local_snapshot("/Volumes/com.apple.TimeMachine.localsnapshots/Backups.backupdb/Mac/2024-05-01-102233/Data/Users/me/a.mov"));
+        assert!(in_local_snapshot("/Volumes/.timemachine/1A2B/2024-05-01-102233.backup/Users/me/a.mov"));
+        assert!(!in_local_snapshot("/Volumes/Backup/a.mov"));
+    }
+}
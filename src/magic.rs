@@ -0,0 +1,113 @@
+// Content types told from the first bytes of files rather than their names, so the 40 GB data.tmp
+// shows up as the core dump or the tarball it really is. Only the entries being shown are read
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::{EntryKind, FileEntry};
+
+// Enough for every signature below, tar's sits at 257
+const SNIFF_SIZE: usize = 512;
+
+// Signatures at the start of a file, the first matching one wins
+const SIGNATURES: &[(&[u8], &str)] = &[
+    (b"\x1f\x8b", "application/gzip"),
+    (b"BZh", "application/x-bzip2"),
+    (b"\xfd7zXZ\x00", "application/x-xz"),
+    (b"\x28\xb5\x2f\xfd", "application/zstd"),
+    (b"\x04\x22\x4d\x18", "application/x-lz4"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+    (b"Rar!\x1a\x07", "application/vnd.rar"),
+    (b"%PDF-", "application/pdf"),
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF8", "image/gif"),
+    (b"\x1a\x45\xdf\xa3", "video/x-matroska"),
+    (b"ID3", "audio/mpeg"),
+    (b"fLaC", "audio/flac"),
+    (b"OggS", "audio/ogg"),
+    (b"SQLite format 3\x00", "application/vnd.sqlite3"),
+    (b"PAR1", "application/vnd.apache.parquet"),
+    (b"QFI\xfb", "application/x-qemu-disk"),
+    (b"\xd4\xc3\xb2\xa1", "application/vnd.tcpdump.pcap"),
+    (b"\xa1\xb2\xc3\xd4", "application/vnd.tcpdump.pcap"),
+    (b"\x0a\x0d\x0d\x0a", "application/x-pcapng"),
+    (b"\xcf\xfa\xed\xfe", "application/x-mach-binary"),
+    (b"\xfe\xed\xfa\xcf", "application/x-mach-binary"),
+    (b"MZ", "application/vnd.microsoft.portable-executable"),
+];
+
+// Content types whose data is compressed already, squeezing it again saves next to nothing
+const COMPRESSED: [&str; 20] = [
+    "application/gzip", "application/x-bzip2", "application/x-xz", "application/zstd", "application/x-lz4", "application/zip",
+    "application/x-7z-compressed", "application/vnd.rar", "application/vnd.apache.parquet", "image/png", "image/jpeg", "image/gif",
+    "image/webp", "video/x-matroska", "video/mp4", "video/quicktime", "video/x-msvideo", "audio/mpeg", "audio/flac", "audio/ogg",
+];
+
+// The content type of the bytes a file starts with
+fn sniff(head: &[u8]) -> &'static str {
+    if head.is_empty() {
+        return "inode/x-empty";
+    }
+    // ELF files tell executables, libraries and core dumps apart by their type at 16
+    if head.starts_with(b"\x7fELF") {
+        let little_endian = head.get(5) == Some(&1);
+        let elf_type = match head.get(16..18) {
+            Some([first, second]) if little_endian => u16::from_le_bytes([*first, *second]),
+            Some([first, second]) => u16::from_be_bytes([*first, *second]),
+            _ => 0,
+        };
+        return match elf_type {
+            1 => "application/x-object",
+            3 => "application/x-sharedlib",
+            4 => "application/x-coredump",
+            _ => "application/x-executable",
+        };
+    }
+    if head.starts_with(b"RIFF") && head.len() >= 12 {
+        match &head[8..12] {
+            b"WEBP" => return "image/webp",
+            b"WAVE" => return "audio/wav",
+            b"AVI " => return "video/x-msvideo",
+            _ => {}
+        }
+    }
+    if head.get(4..8) == Some(b"ftyp") {
+        return if head.get(8..11) == Some(b"qt ") { "video/quicktime" } else { "video/mp4" };
+    }
+    if head.get(257..262) == Some(b"ustar") {
+        return "application/x-tar";
+    }
+    if let Some((_, content_type)) = SIGNATURES.iter().find(|(signature, _)| head.starts_with(signature)) {
+        return content_type;
+    }
+    // Text has no NUL byte and decodes, a character cut at the end of the sample aside
+    let text = match std::str::from_utf8(head) {
+        Ok(_) => true,
+        Err(err) => err.error_len().is_none(),
+    };
+    if text && !head.contains(&0) { "text/plain" } else { "application/octet-stream" }
+}
+
+fn read_head(path: &Path) -> io::Result<Vec<u8>> {
+    let mut head = Vec::with_capacity(SNIFF_SIZE);
+    File::open(path)?.take(SNIFF_SIZE as u64).read_to_end(&mut head)?;
+    Ok(head)
+}
+
+// The content type of the file, none when it can't be read
+pub fn content_type(path: &Path) -> Option<&'static str> {
+    read_head(path).ok().map(|head| sniff(&head))
+}
+
+pub fn is_compressed(content_type: &str) -> bool {
+    COMPRESSED.contains(&content_type)
+}
+
+// Fill in the content type of the files, the ones that can't be read anymore are left without
+pub fn detect_types(entries: &mut [FileEntry]) {
+    for entry in entries.iter_mut().filter(|entry| entry.kind == EntryKind::File) {
+        entry.content_type = content_type(Path::new(&entry.path));
+    }
+}
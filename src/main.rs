@@ -1,216 +1,1471 @@
+use std::collections::HashSet;
 use std::env;
-use std::path::Path;
-use walkdir::WalkDir;
-use colored::Colorize;
-use std::time::Instant;
-use indicatif::{ProgressBar, ProgressStyle};
-use tabled::{
-    settings::{
-        object::{Columns, Rows}, Alignment, Style,
-        style::BorderColor,
-        themes::Colorization, Color
-    },
-    Tabled,
-    Table
-};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{self, ExitCode};
+use std::sync::{Arc, Mutex};
+use colored::{ColoredString, Colorize};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use fatass::archive;
+use fatass::cache;
+use fatass::checksum;
+use fatass::clean::{self, Action, CleanReport};
+use fatass::compress::{compress_file, Compressor, Outcome};
+use fatass::compressible::estimate_savings;
+use fatass::daemon::StateDir;
+use fatass::desktop;
+use fatass::delete::{delete_entry, still_exists, DeleteMethod, DeleteReport};
+use fatass::docker;
+use fatass::dupes::find_duplicates;
+use fatass::git;
+use fatass::groups::{group_files, GroupKey};
+use fatass::inodes::count_entries;
+use fatass::estimate::estimate;
+use fatass::interrupt::{self, Limit};
+use fatass::logging::{self, Verbosity};
+use fatass::macos;
+use fatass::magic;
+use fatass::verbose;
+use fatass::metrics::{Metrics, ScanSummary};
+use fatass::ncdu::{export_tree, write_export};
+use fatass::notify::{self, Notification};
+use fatass::offload::{destination_of, offload_file, OffloadReport};
+use fatass::pager;
+use fatass::priority;
+use fatass::mft::MftVolume;
+use fatass::script::create_script;
+use fatass::source::FileSource;
+use fatass::output::{write_candidate, write_compressible, write_diff, write_docker, write_dupes, write_estimate, write_groups, write_inodes, write_results, write_stats, write_volumes, write_watch, Completeness, OutputOptions};
+use fatass::parquet::ParquetExport;
+use fatass::serve::{serve, Report};
+use fatass::snapshot::{diff, Change, Snapshot};
+use fatass::sort::sort_entries;
+use fatass::sqlite::SqliteExport;
+use fatass::throttle;
+use fatass::time::format_time;
+use fatass::tree::DirTree;
+use fatass::treemap::write_treemap;
+use fatass::volumes::volumes_of;
+use fatass::xattrs;
+mod cli;
+mod completions;
 
-#[derive(Debug, Clone)]
-struct FileData {
-    path: String,
-    size: u64,
+use cli::{Command, Matches, Options};
+use fatass::{format_size, set_unit_style, tui, ColorChoice, Config, EntryKind, FileEntry, OutputFormat, Rank, Scanner, SortKey, Theme};
+
+// Exit statuses: 1 when something failed or the results are partial, 2 for invalid arguments, 3
+// when --fail-if-over or --fail-if-total-over found more than they allow
+const EXIT_OK: u8 = 0;
+const EXIT_FAILED: u8 = 1;
+const EXIT_USAGE: u8 = 2;
+const EXIT_OVER: u8 = 3;
+
+// Unreadable paths leave the results incomplete, the scan still ran to its end
+// What the JSON document tells of the scan behind a report
+fn completeness(scanner: &Scanner) -> Option<Completeness> {
+    Some(Completeness { partial: scanner.cut_short(), errors: scanner.errors().list() })
+}
+
+// Unreadable paths and partial results alike fail
+fn scan_status(scanner: &Scanner) -> u8 {
+    if scanner.errors().count() > 0 || scanner.cut_short() { EXIT_FAILED } else { EXIT_OK }
+}
+
+// What went over the limits, one sentence each, the entries coming biggest first
+fn threshold_breaches(entries: &[FileEntry], total_bytes: u64, entry_limit: Option<u64>, total_limit: Option<u64>, dirs_mode: bool) -> Vec<String> {
+    let mut breaches = Vec::new();
+    if let (Some(limit), Some(fattest)) = (entry_limit, entries.first()) {
+        if fattest.size > limit {
+            let kind = if dirs_mode { "directory" } else { "file" };
+            breaches.push(format!("The fattest {}, {}, takes {}, over the {} limit", kind, fattest.path, fattest.get_str_size(), format_size(limit)));
+        }
+    }
+    if let Some(limit) = total_limit.filter(|&limit| total_bytes > limit) {
+        let kind = if dirs_mode { "directories" } else { "files" };
+        breaches.push(format!("The scanned {} take {}, over the {} limit", kind, format_size(total_bytes), format_size(limit)));
+    }
+    breaches
+}
+
+// Keep the fewest of the ranked entries adding up to the budget, returns what they add up to,
+// short of the budget if even all of them are
+fn take_bytes(entries: &mut Vec<FileEntry>, budget: u64) -> u64 {
+    let mut taken = 0;
+    let picked = entries.iter()
+        .take_while(|file_entry| {
+            let needed = taken < budget;
+            if needed {
+                taken += file_entry.size;
+            }
+            needed
+        })
+        .count();
+    entries.truncate(picked);
+    taken
+}
+
+// Keep the fewest of the ranked files whose removal frees the deficit, those with other hard links
+// would free nothing and are left out. What they free, short of the deficit if even all of them are
+fn plan_cleanup(entries: &mut Vec<FileEntry>, deficit: u64) -> u64 {
+    entries.retain(|file_entry| file_entry.links.unwrap_or(1) <= 1);
+    take_bytes(entries, deficit)
+}
+
+// The paths of a list file, or of stdin for "-", one per line or NUL separated like find -print0 writes them
+fn read_path_list(list: &str) -> io::Result<Vec<String>> {
+    let mut content = Vec::new();
+    if list == "-" {
+        io::stdin().read_to_end(&mut content)?;
+    } else {
+        content = fs::read(list)?;
+    }
+    let separator = if content.contains(&0) { 0 } else { b'\n' };
+    Ok(content.split(|byte| *byte == separator)
+        .map(|path| String::from_utf8_lossy(path).trim_end_matches('\r').to_string())
+        .filter(|path| !path.is_empty())
+        .collect())
+}
+
+// Sort the results when a key is given, they stay in the order they were ranked otherwise
+fn order_entries(entries: &mut [FileEntry], sort_key: Option<SortKey>, reverse: bool) {
+    match sort_key {
+        Some(sort_key) => sort_entries(entries, sort_key, reverse),
+        None if reverse => entries.reverse(),
+        None => {}
+    }
+}
+
+// Say how many paths couldn't be read, the results miss whatever is below them, and list them when asked
+fn report_errors(scanner: &Scanner, show_errors: bool, output_format: OutputFormat) {
+    let count = scanner.errors().count();
+    if count == 0 {
+        return;
+    }
+    let paths = if count == 1 { "path" } else { "paths" };
+    // Transient errors still there after the retries, scanning again later might get through
+    let transient = match scanner.errors().transient() {
+        0 => String::new(),
+        transient => format!(" ({} transient)", transient),
+    };
+    if !show_errors {
+        let message = format!("{} {} could not be read{}, the results may be incomplete, --show-errors lists them", count, paths, transient);
+        print_status(&message.yellow(), output_format);
+        return;
+    }
+    print_status(&format!("{} {} could not be read{}:", count, paths, transient).yellow(), output_format);
+    for error in scanner.errors().list() {
+        let transient = if error.transient { " (transient)" } else { "" };
+        print_status(&format!("  {}: {}{}", error.path, error.kind, transient), output_format);
+    }
+}
+
+// Print decorative messages where they won't get mixed with the results
+fn print_status(message: &str, output_format: OutputFormat) {
+    if !logging::enabled(Verbosity::Normal) {
+        return;
+    }
+    if output_format.status_to_stderr() {
+        eprintln!("{}", message);
+    } else if let Err(err) = writeln!(io::stdout(), "{}", message) {
+        exit_on_closed_pipe(err);
+    }
+}
+
+// Ask a question on stderr, none when stdin is closed
+fn ask(question: &str) -> Option<String> {
+    eprint!("{} ", question);
+    let _ = io::stderr().flush();
+    let mut answer = String::new();
+    match io::stdin().read_line(&mut answer) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(answer.trim().to_string()),
+    }
+}
+
+// Ask a yes/no question on stderr, anything but yes is a no
+fn confirm(question: &str) -> bool {
+    ask(&format!("{} [y/N]", question)).is_some_and(|answer| matches!(answer.to_lowercase().as_str(), "y" | "yes"))
 }
 
-impl FileData {
-    fn new(path: String, size: u64) -> FileData {
-        FileData { path, size }
+// Walk through the results, deleting the confirmed ones, then summarize what happened, returns
+// whether every deletion went fine
+fn delete_results(files: &[FileEntry], method: DeleteMethod, assume_yes: bool, output_format: OutputFormat) -> bool {
+    let mut report = DeleteReport::default();
+    for file_entry in files {
+        if !still_exists(file_entry) {
+            continue;
+        }
+        let verb = if method == DeleteMethod::Trash { "Trash" } else { "Delete" };
+        let question = format!("{} {} ({})?", verb, file_entry.path, file_entry.get_str_size());
+        if !assume_yes && !confirm(&question) {
+            continue;
+        }
+        report.record(file_entry, delete_entry(file_entry, method));
     }
 
-    fn get_str_size(&self) -> String {
-        let mut size = self.size as f64;
-        let mut suffix = String::from("Bytes");
+    // Trashed entries still take space until the trash is emptied
+    let summary = match method {
+        DeleteMethod::Remove => format!("Deleted {} entries, {} reclaimed", report.deleted, format_size(report.reclaimed)),
+        DeleteMethod::Trash => format!("Moved {} entries to the trash, {} to reclaim by emptying it", report.deleted, format_size(report.reclaimed)),
+    };
+    print_status(&summary.cyan(), output_format);
+    if !report.failures.is_empty() {
+        eprintln!("{}", format!("Error: Could not delete {} entries:", report.failures.len()).red());
+        for (path, err) in &report.failures {
+            eprintln!("{}", format!("  {}: {}", path, err).red());
+        }
+    }
+    report.failures.is_empty()
+}
 
-        let units: [&str; 8] = ["KB", "MB", "GB", "TB", "PB", "EB", "ZB", "YB"];
+// Walk through the results one at a time showing what each is, carrying out what's picked for
+// it, then sum up what was done and what was left, returns whether every action went fine
+fn clean_results(files: &[FileEntry], output_format: OutputFormat) -> bool {
+    let mut report = CleanReport::default();
+    let now = SystemTime::now();
+    let mut remaining = files.iter().filter(|file_entry| still_exists(file_entry)).enumerate();
+    while let Some((index, file_entry)) = remaining.next() {
+        let mut file_entry = file_entry.clone();
+        magic::detect_types(std::slice::from_mut(&mut file_entry));
+        let age = file_entry.modified
+            .and_then(|modified| now.duration_since(modified).ok())
+            .map_or(String::from("modified at an unknown time"), |age| format!("modified {} ago", clean::describe_age(age)));
+        eprintln!("{} {}", format!("[{}/{}]", index + 1, files.len()).cyan(), file_entry.path);
+        eprintln!("  {}, {}, {}", file_entry.get_str_size(), age, file_entry.content_type.unwrap_or("unknown type"));
 
-        for unit in units {
-            if size < 1024.0 {
-                break;
+        // Opening the folder asks again, a closed stdin quits
+        let action = loop {
+            let Some(answer) = ask(&format!("  {}?", clean::CHOICES)) else { break Action::Quit };
+            match Action::parse(&answer) {
+                Some(Action::Open) => {
+                    if let Err(err) = desktop::open_folder(Path::new(&file_entry.path)) {
+                        eprintln!("{}", format!("Error: Could not open the folder: {}", err).red());
+                    }
+                }
+                Some(action) => break action,
+                None => eprintln!("  Answer with one of {}", clean::CHOICES),
+            }
+        };
+        match action {
+            Action::Quit => {
+                report.decline(&file_entry);
+                for (_, file_entry) in remaining.by_ref() {
+                    report.decline(file_entry);
+                }
             }
-            size /= 1024.0;
-            suffix = unit.to_string();
+            Action::Skip => report.decline(&file_entry),
+            Action::Move => match ask("  Move into which directory?").filter(|answer| !answer.is_empty()) {
+                Some(directory) => report.record(&file_entry, action, clean::execute(&file_entry, action, Some(Path::new(&directory)))),
+                None => report.decline(&file_entry),
+            },
+            _ => report.record(&file_entry, action, clean::execute(&file_entry, action, None)),
+        }
+    }
+
+    let executed = report.executed.len();
+    print_status(&format!("Cleaned up {} of {} files:", executed, executed + report.declined.len() + report.failures.len()).cyan(), output_format);
+    for (action, path, freed) in &report.executed {
+        print_status(&format!("  {:<10} {} ({})", action.past_tense(), path, format_size(*freed)), output_format);
+    }
+    // Trashed files still take space until the trash is emptied, moved ones take it elsewhere
+    let totals = [
+        (Action::Delete, "deleted", "reclaimed"),
+        (Action::Trash, "trashed", "to reclaim by emptying the trash"),
+        (Action::Compress, "compressed", "saved"),
+        (Action::Move, "moved", "moved off"),
+    ];
+    let summary: Vec<String> = totals.iter()
+        .filter_map(|&(action, verb, outcome)| {
+            let (count, bytes) = report.totals(action);
+            (count > 0).then(|| format!("{} {}, {} {}", count, verb, format_size(bytes), outcome))
+        })
+        .collect();
+    if !summary.is_empty() {
+        print_status(&summary.join("; ").cyan(), output_format);
+    }
+    if !report.declined.is_empty() {
+        let declined: u64 = report.declined.iter().map(|(_, size)| size).sum();
+        let files = if report.declined.len() == 1 { String::from("1 file") } else { format!("{} files", report.declined.len()) };
+        print_status(&format!("Left {} as they were, {}", files, format_size(declined)).cyan(), output_format);
+    }
+    if !report.failures.is_empty() {
+        eprintln!("{}", format!("Error: Could not clean up {} files:", report.failures.len()).red());
+        for (path, err) in &report.failures {
+            eprintln!("{}", format!("  {}: {}", path, err).red());
         }
+    }
+    report.failures.is_empty()
+}
 
-        let size_str = if size.fract() == 0.0 {
-            format!("{:.0}", size)
-        } else {
-            format!("{:.2}", size)
+// Compress the results one at a time, leaving out those compressed already or hard linked, then
+// sum up the savings, returns whether every file went fine
+fn compress_results(files: &[FileEntry], compressor: Compressor, output_format: OutputFormat) -> bool {
+    let (mut compressed, mut saved, mut not_smaller) = (0, 0, 0);
+    let mut skipped = Vec::new();
+    let mut failures = Vec::new();
+    for file_entry in files {
+        if interrupt::interrupted() {
+            print_status(&"Interrupted, the files left weren't compressed.".yellow(), output_format);
+            break;
+        }
+        let path = Path::new(&file_entry.path);
+        // Compressing one of several hard links frees nothing and breaks the link
+        if file_entry.links.is_some_and(|links| links > 1) {
+            skipped.push((&file_entry.path, "hard linked"));
+            continue;
+        }
+        if let Some(content_type) = magic::content_type(path).filter(|content_type| magic::is_compressed(content_type)) {
+            skipped.push((&file_entry.path, content_type));
+            continue;
+        }
+        verbose!("Compressing {} with {}", file_entry.path, compressor.name());
+        match compress_file(path, compressor) {
+            Ok(Outcome::Compressed { saved: file_saved }) => {
+                compressed += 1;
+                saved += file_saved;
+            }
+            Ok(Outcome::NotSmaller) => not_smaller += 1,
+            Err(err) => failures.push((&file_entry.path, err)),
+        }
+    }
+
+    print_status(&format!("Compressed {} files with {}, {} saved", compressed, compressor.name(), format_size(saved)).cyan(), output_format);
+    if !skipped.is_empty() {
+        print_status(&format!("Left out {} files:", skipped.len()).yellow(), output_format);
+        for (path, reason) in &skipped {
+            print_status(&format!("  {}: {}", path, reason), output_format);
+        }
+    }
+    if not_smaller > 0 {
+        print_status(&format!("Kept {} files as they were, they didn't get any smaller.", not_smaller).yellow(), output_format);
+    }
+    if !failures.is_empty() {
+        eprintln!("{}", format!("Error: Could not compress {} files:", failures.len()).red());
+        for (path, err) in &failures {
+            eprintln!("{}", format!("  {}: {}", path, err).red());
+        }
+    }
+    failures.is_empty()
+}
+
+// Move the results under the destination one at a time, then summarize what happened, returns
+// whether every file made it
+fn offload_results(files: &[FileEntry], roots: &[String], destination: &Path, leave_symlinks: bool, output_format: OutputFormat) -> bool {
+    let mut report = OffloadReport::default();
+    for file_entry in files {
+        if interrupt::interrupted() {
+            print_status(&format!("Interrupted, {} files left where they were.", files.len() - report.moved - report.failures.len()).yellow(), output_format);
+            break;
+        }
+        let path = Path::new(&file_entry.path);
+        let target = destination_of(path, roots, destination);
+        verbose!("Moving {} to {}", file_entry.path, target.display());
+        report.record(file_entry, offload_file(path, &target, leave_symlinks));
+    }
+
+    print_status(&format!("Moved {} files to {}, {} freed", report.moved, destination.display(), format_size(report.bytes)).cyan(), output_format);
+    if !report.failures.is_empty() {
+        eprintln!("{}", format!("Error: Could not move {} files:", report.failures.len()).red());
+        for (path, err) in &report.failures {
+            eprintln!("{}", format!("  {}: {}", path, err).red());
+        }
+    }
+    report.failures.is_empty()
+}
+
+// Stdout was closed by its reader, e.g. head had the lines it wanted: stop there quietly and
+// successfully, as the SIGPIPE Rust ignores would have, instead of reporting an error
+fn exit_on_closed_pipe(err: io::Error) -> io::Error {
+    if err.kind() == io::ErrorKind::BrokenPipe {
+        process::exit(EXIT_OK.into());
+    }
+    err
+}
+
+// Write to the output file when one was given, stdout otherwise, returns whether it went fine
+fn write_output(output_file: &Option<PathBuf>, output_format: OutputFormat, write: impl Fn(&mut dyn Write) -> io::Result<()>) -> bool {
+    let written = match output_file {
+        Some(file_path) => fs::File::create(file_path)
+            .and_then(|file| {
+                let mut writer = io::BufWriter::new(file);
+                write(&mut writer)?;
+                writer.flush()
+            })
+            .map(|_| print_status(&format!("Results written to {}", file_path.display()).cyan(), output_format)),
+        None => write(&mut io::stdout().lock()).map_err(exit_on_closed_pipe),
+    };
+    if let Err(err) = &written {
+        eprintln!("{}", format!("Error: Could not write the results: {}", err).red());
+    }
+    written.is_ok()
+}
+
+// Same as write_output, tables taller than the terminal going through the pager when paging
+fn write_paged(output_file: &Option<PathBuf>, output_format: OutputFormat, paging: bool, write: impl Fn(&mut dyn Write) -> io::Result<()>) -> bool {
+    if !paging {
+        return write_output(output_file, output_format, write);
+    }
+    let mut buffer = Vec::new();
+    if let Err(err) = write(&mut buffer) {
+        eprintln!("{}", format!("Error: Could not write the results: {}", err).red());
+        return false;
+    }
+    // A pager that can't be started leaves the table to be printed as usual
+    if pager::taller_than_terminal(&buffer) && pager::page(&buffer).is_ok() {
+        return true;
+    }
+    write_output(output_file, output_format, |out| out.write_all(&buffer))
+}
+
+// List the profiles of the config file with the settings each one gives
+fn print_profiles(config: &Config, config_path: Option<&Path>) {
+    let Some(config_path) = config_path else {
+        let default_path = Config::default_path().map(|path| path.display().to_string()).unwrap_or_default();
+        println!("No config file found, profiles are [profile.NAME] tables in {}", default_path);
+        return;
+    };
+    if config.profiles.is_empty() {
+        println!("No profiles in {}, add them as [profile.NAME] tables", config_path.display());
+        return;
+    }
+    println!("Profiles in {}:", config_path.display());
+    for profile in &config.profiles {
+        println!("  {}", profile.name.cyan());
+        for line in &profile.lines {
+            println!("    {}", line);
+        }
+    }
+}
+
+// Write the biggest changes between two snapshots then sum them all up, returns whether they were written
+fn report_changes(mut changes: Vec<Change>, fatass_count: usize, output_format: OutputFormat, output_file: &Option<PathBuf>, output_options: &OutputOptions, runtime_start: Instant) -> bool {
+    let changed = changes.len();
+    let net: i128 = changes.iter().map(|change| change.delta()).sum();
+    changes.truncate(fatass_count);
+    if !write_output(output_file, output_format, |out| write_diff(out, &changes, output_format, output_options)) {
+        return false;
+    }
+    let sign = if net < 0 { "-" } else { "+" };
+    let net = format!("{}{}", sign, format_size(net.unsigned_abs().min(u64::MAX as u128) as u64));
+    let end_message = format!("Found {} changed entries, {} overall, in {:?}", changed, net, runtime_start.elapsed()).green();
+    print_status(&end_message, output_format);
+    true
+}
+
+fn main() -> ExitCode {
+    ExitCode::from(run())
+}
+
+// Get args from command line, returns the exit status
+// Tell an invalid argument, the exit status goes with it
+fn usage_error(message: &str) -> u8 {
+    eprintln!("{}", format!("Error: {}", message).red());
+    EXIT_USAGE
+}
+
+// What the commands share once the options are checked and the scanner built
+struct Context {
+    matches: Matches,
+    options: Options,
+    search_paths: Vec<String>,
+    scanner: Scanner,
+    paging: bool,
+    runtime_start: Instant,
+}
+
+fn run() -> u8 {
+    let runtime_start = Instant::now();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let matches = match cli::parse(&args) {
+        Ok(matches) => matches,
+        Err(err) => return usage_error(&err),
+    };
+    let command = matches.command;
+
+    let color_choice = match matches.color() {
+        Ok(color_choice) => color_choice,
+        Err(err) => return usage_error(&err),
+    };
+    if color_choice != ColorChoice::Auto {
+        let enabled = color_choice == ColorChoice::Always;
+        colored::control::set_override(enabled);
+        // The progress bars and the tui color through console
+        console::set_colors_enabled(enabled);
+        console::set_colors_enabled_stderr(enabled);
+    }
+
+    if matches.flag("help") {
+        cli::print_help(command, matches.explicit_command);
+        return EXIT_OK;
+    }
+
+    let verbosity = matches.verbosity();
+    logging::set_verbosity(verbosity);
+
+    // Completion scripts don't depend on the config, a broken one shouldn't stop them
+    if command == Command::Completions {
+        return match matches.shell() {
+            Ok(shell) => {
+                print!("{}", completions::script(shell));
+                EXIT_OK
+            }
+            Err(err) => usage_error(&err),
         };
+    }
+
+    // Load the config file given with --config, or the default one when there is one
+    let config_path = match matches.value("config") {
+        Some(config_value) => Some(PathBuf::from(config_value)),
+        None => Config::default_path().filter(|path| path.is_file()),
+    };
+    let config = match config_path.as_ref().map(|path| Config::load(path)).transpose() {
+        Ok(config) => config.unwrap_or_default(),
+        Err(err) => return usage_error(&format!("Invalid config, {}", err)),
+    };
+    if let Some(config_path) = &config_path {
+        verbose!("Read the config from {}", config_path.display());
+    }
+    if command == Command::Profiles {
+        print_profiles(&config, config_path.as_deref());
+        return EXIT_OK;
+    }
+
+    let mut options = match matches.options(&config) {
+        Ok(options) => options,
+        Err(err) => return usage_error(&err),
+    };
+    set_unit_style(options.unit_style);
+    if options.output_options.theme == Theme::Mono {
+        colored::control::set_override(false);
+    }
+    // A table going to a file would only keep the escapes, unless they were asked for
+    let table_colors = match color_choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => colored::control::SHOULD_COLORIZE.should_colorize() && options.output_file.is_none(),
+    };
+    if !table_colors {
+        options.output_options.theme = Theme::Mono;
+    }
+    // Tables printed to a terminal are kept to its width, unless the paths are wanted whole
+    let table_on_stdout = options.output_format == OutputFormat::Table && options.output_file.is_none();
+    if table_on_stdout && !matches.flag("full-paths") {
+        options.output_options.table_width = console::Term::stdout().size_checked().map(|(_, columns)| columns as usize);
+    }
+    // Tables too long for the terminal are paged, unless a confirmation has to be answered below them
+    let paging = table_on_stdout && !matches.flag("no-pager") && !options.deleting && console::Term::stdout().is_term();
 
-        format!("{} {}", size_str, suffix)
+    if command == Command::Diff {
+        return run_diff(&matches, &options, runtime_start);
+    }
+
+    let search_paths = match search_paths(&matches, &mut options.source) {
+        Ok(search_paths) => search_paths,
+        Err(err) => return usage_error(&err),
+    };
+    // A lower priority is set before any thread is started
+    if let Some(priority) = options.priority {
+        if let Err(err) = priority::lower(priority) {
+            eprintln!("{}", format!("Error: Could not lower the priority: {}", err).red());
+            return EXIT_FAILED;
+        }
+    }
+    if let Some(rate) = options.io_limit {
+        throttle::set_limit(Some(rate));
+    }
+    let scanner = build_scanner(&matches, &options, &search_paths, verbosity);
+    let context = Context { matches, options, search_paths, scanner, paging, runtime_start };
+
+    if context.matches.flag("tui") {
+        if let Err(err) = tui::run(&context.scanner) {
+            eprintln!("{}", format!("Error: {}", err).red());
+            return EXIT_FAILED;
+        }
+        return EXIT_OK;
+    }
+    if command == Command::Serve {
+        return run_serve(context);
+    }
+    // An estimate probes the tree instead of scanning it
+    if context.matches.flag("estimate") {
+        return run_estimate(context);
+    }
+    match context.options.output_format {
+        OutputFormat::Ncdu => return run_ncdu(context),
+        OutputFormat::Treemap => return run_treemap(context),
+        _ => {}
+    }
+    match (command, context.options.group_key) {
+        (Command::Inodes, _) => run_inodes(context),
+        (Command::Compressible, _) => run_compressible(context),
+        (Command::Docker, _) => run_docker(context),
+        (Command::Dupes, _) => run_dupes(context),
+        (_, Some(group_key)) => run_groups(context, group_key),
+        (Command::ExportMetrics, _) => run_export_metrics(context),
+        (Command::Watch, _) => run_watch(context),
+        (Command::Daemon, _) => run_daemon(context),
+        _ => run_scan(context),
     }
 }
 
-#[allow(non_snake_case)]
-#[derive(Tabled)]
-struct FileDataTable {
-    Path: String,
-    Size: String,
+// Every path searched: the ones given, the ones of a path list, the root of a remote or a bucket,
+// the container storage found for fatass docker, or the current directory. The drive of a single
+// path is read from its master file table with --fast
+fn search_paths(matches: &Matches, source: &mut Option<Arc<dyn FileSource>>) -> Result<Vec<String>, String> {
+    let mut search_paths: Vec<String> = Vec::new();
+    for path_value in matches.values("path") {
+        if !Path::new(path_value).exists() {
+            return Err(format!("Invalid path '{}'. Please provide a valid path.", path_value));
+        }
+        search_paths.push(path_value.to_string());
+    }
+    if let Some(list_value) = matches.value("files-from") {
+        let listed = read_path_list(list_value).map_err(|err| format!("Can't read the path list '{}': {}", list_value, err))?;
+        // Lists go stale, what's gone since it was written is left out
+        for path_value in listed {
+            if Path::new(&path_value).exists() {
+                search_paths.push(path_value);
+            } else {
+                verbose!("Skipping {} from the path list: it doesn't exist", path_value);
+            }
+        }
+        if search_paths.is_empty() {
+            return Err(format!("None of the paths of '{}' exist.", list_value));
+        }
+    }
+    if let Some(source) = source {
+        search_paths.push(source.path());
+    }
+    if matches.command == Command::Docker {
+        if let Some(path_value) = search_paths.iter().find(|path_value| !docker::is_storage_root(Path::new(path_value))) {
+            return Err(format!("'{}' isn't a Docker or Podman storage root, e.g. /var/lib/docker.", path_value));
+        }
+        if search_paths.is_empty() {
+            search_paths = docker::default_roots();
+        }
+        if search_paths.is_empty() {
+            return Err("No Docker or Podman storage found, please give the path of its root.".to_string());
+        }
+    }
+    if search_paths.is_empty() {
+        search_paths.push(String::from("./"));
+    }
+    if matches.flag("fast") {
+        let [path_value] = &search_paths[..] else {
+            return Err("--fast reads one drive, please give a single path.".to_string());
+        };
+        let volume = MftVolume::for_path(Path::new(path_value)).map_err(|err| format!("--fast can't read '{}', {}.", path_value, err))?;
+        *source = Some(Arc::new(volume));
+    }
+    Ok(search_paths)
 }
 
-#[allow(non_snake_case)]
-impl FileDataTable {
-    fn new(Path: String, Size: String) -> FileDataTable {
-        FileDataTable { Path, Size }
+fn build_scanner(matches: &Matches, options: &Options, search_paths: &[String], verbosity: Verbosity) -> Scanner {
+    let command = matches.command;
+    // Snapshots, the daemon, the report server, treemaps, cleanup plans, byte budgets and percentiles
+    // need every entry, not only the fattest
+    let every_entry = matches.flag("save-snapshot") || matches.flag("compare") || matches!(command, Command::Daemon | Command::Serve)
+        || options.output_format == OutputFormat::Treemap || options.free_target.is_some() || options.byte_budget.is_some() || options.top_percent.is_some();
+    let mut scanner = Scanner::new(search_paths[0].clone())
+        .count(if every_entry { usize::MAX } else { options.count.saturating_add(options.offset) })
+        .threads(options.threads)
+        .dirs(options.dirs_mode)
+        .rank(options.rank.clone())
+        .empty(matches.flag("empty"))
+        // Scripts reading plain output have no use for a spinner, and log lines would run through it
+        .progress(options.output_format != OutputFormat::Plain && verbosity == Verbosity::Normal && !options.json_progress)
+        .json_progress(options.json_progress)
+        .streaming(matches.flag("streaming"))
+        .byte_progress(matches.flag("progress-bytes"))
+        .disk_usage(matches.flag("disk-usage"))
+        .ads(matches.flag("ads"))
+        .ads_entries(matches.flag("ads-entries"))
+        .apfs(matches.flag("apfs"))
+        .physical(matches.flag("physical"))
+        .xattrs(matches.flag("xattrs"))
+        // Watching and the daemon scan in a loop, they would write the whole cache file every time. A byte
+        // sized bar needs every file sized upfront, which cached scans skip. Path lists are one-offs, and
+        // the cached walk doesn't list the alternate data streams
+        .cache(if matches.flag("no-cache") || matches.flag("files-from") || matches.flag("progress-bytes") || matches.flag("ads") || matches!(command, Command::Watch | Command::Daemon) { None } else { cache::default_path() })
+        .refresh_cache(matches.flag("refresh"))
+        .stats(matches.flag("stats"))
+        .count_hardlinks(options.hardlinks)
+        .follow_symlinks(matches.flag("follow-symlinks"))
+        .skip_junctions(matches.flag("skip-junctions"))
+        .max_depth(options.max_depth)
+        .retries(options.retries)
+        .max_files(options.max_files)
+        .timeout(options.timeout)
+        .modified_before(options.modified_before)
+        .modified_after(options.modified_after)
+        .one_file_system(matches.flag("one-file-system"))
+        .min_size(options.min_size)
+        .respect_gitignore(options.respect_gitignore)
+        .fatassignore(options.fatassignore)
+        .hidden(options.hidden)
+        .git_aware(options.git_aware && !options.dirs_mode)
+        .source(options.source.clone());
+    for search_path in &search_paths[1..] {
+        scanner = scanner.path(search_path.clone());
     }
+    for extension in &options.extensions {
+        scanner = scanner.extension(extension);
+    }
+    for glob in &options.excludes {
+        scanner = scanner.exclude(glob.clone());
+    }
+    for glob in &options.includes {
+        scanner = scanner.include(glob.clone());
+    }
+    for regex in &options.path_regexes {
+        scanner = scanner.path_regex(regex.clone());
+    }
+    for regex in &options.path_regex_excludes {
+        scanner = scanner.path_regex_exclude(regex.clone());
+    }
+    scanner
+}
+
+fn run_diff(matches: &Matches, options: &Options, runtime_start: Instant) -> u8 {
+    let snapshot_paths = matches.values("snapshot");
+    let [old_path, new_path] = snapshot_paths[..] else {
+        return usage_error("Please provide the old and the new snapshot to compare.");
+    };
+    let (old, new) = match (Snapshot::load(Path::new(old_path)), Snapshot::load(Path::new(new_path))) {
+        (Ok(old), Ok(new)) => (old, new),
+        (Err(err), _) | (_, Err(err)) => {
+            eprintln!("{}", format!("Error: Invalid snapshot, {}", err).red());
+            return EXIT_FAILED;
+        }
+    };
+    let reported = report_changes(diff(&old.entries, &new.entries), options.count, options.output_format, &options.output_file, &options.output_options, runtime_start);
+    if reported { EXIT_OK } else { EXIT_FAILED }
+}
+
+fn run_serve(context: Context) -> u8 {
+    let Context { matches, options, scanner, .. } = context;
+    let output_format = options.output_format;
+    let files = match matches.value("from-snapshot") {
+        Some(snapshot_value) => match Snapshot::load(Path::new(snapshot_value)) {
+            Ok(snapshot) if snapshot.entries.iter().any(|entry| entry.kind != EntryKind::File) => {
+                eprintln!("{}", "Error: The snapshot holds directories, serve a snapshot of files.".red());
+                return EXIT_FAILED;
+            }
+            Ok(snapshot) => snapshot.entries,
+            Err(err) => {
+                eprintln!("{}", format!("Error: Invalid snapshot, {}", err).red());
+                return EXIT_FAILED;
+            }
+        },
+        None => {
+            print_status(&"Scanning files ...".cyan(), output_format);
+            let files = scanner.scan().collect();
+            report_errors(&scanner, matches.flag("show-errors"), output_format);
+            files
+        }
+    };
+    let report = Report::new(files, options.count);
+    let address = format!("{}:{}", matches.value("bind").unwrap_or("127.0.0.1"), options.port);
+    let listener = match TcpListener::bind(&address) {
+        Ok(listener) => listener,
+        Err(err) => {
+            eprintln!("{}", format!("Error: Could not listen on {}: {}", address, err).red());
+            return EXIT_FAILED;
+        }
+    };
+    let local_address = listener.local_addr().map_or(address, |local_address| local_address.to_string());
+    let message = format!("Serving the report of {} files on http://{}/, press Ctrl-C to stop ...", report.files(), local_address);
+    print_status(&message.green(), output_format);
+    serve(&listener, &report);
+    EXIT_OK
+}
+
+fn run_estimate(context: Context) -> u8 {
+    let Context { matches, options, search_paths, scanner, .. } = context;
+    let output_format = options.output_format;
+    let budget = options.timeout.unwrap_or(Duration::from_secs(5));
+    print_status(&format!("Sampling {} for {:?} ...", search_paths.join(", "), budget).cyan(), output_format);
+    interrupt::install();
+    let estimate = estimate(&scanner, budget);
+    if !write_output(&options.output_file, output_format, |out| write_estimate(out, &estimate)) {
+        return EXIT_FAILED;
+    }
+    report_errors(&scanner, matches.flag("show-errors"), output_format);
+    if interrupt::interrupted() { EXIT_FAILED } else { EXIT_OK }
+}
+
+fn run_ncdu(context: Context) -> u8 {
+    let Context { matches, options, scanner, runtime_start, .. } = context;
+    let output_format = options.output_format;
+    print_status(&"Exporting the tree ...".cyan(), output_format);
+    let export = match export_tree(&scanner) {
+        Ok(export) => export,
+        Err(err) => {
+            eprintln!("{}", format!("Error: Could not export the tree, {}", err).red());
+            return EXIT_FAILED;
+        }
+    };
+    if !write_output(&options.output_file, output_format, |out| write_export(out, &export)) {
+        return EXIT_FAILED;
+    }
+    report_errors(&scanner, matches.flag("show-errors"), output_format);
+    let end_message = format!("Exported {} files in {:?}", export.files, runtime_start.elapsed()).green();
+    print_status(&end_message, output_format);
+    scan_status(&scanner)
 }
 
-fn print_help() {
-    println!("Usage: fatass [OPTIONS]");
+fn run_treemap(context: Context) -> u8 {
+    let Context { matches, options, scanner, runtime_start, .. } = context;
+    let output_format = options.output_format;
+    print_status(&"Scanning files ...".cyan(), output_format);
+    let files: Vec<FileEntry> = scanner.scan().collect();
+    let tree = DirTree::new(&files);
+    if !write_output(&options.output_file, output_format, |out| write_treemap(out, &tree)) {
+        return EXIT_FAILED;
+    }
+    report_errors(&scanner, matches.flag("show-errors"), output_format);
+    let end_message = format!("Drew the treemap of {} files in {:?}", files.len(), runtime_start.elapsed()).green();
+    print_status(&end_message, output_format);
+    scan_status(&scanner)
+}
 
-    println!("\nOptions:");
-    println!("  --help, -h           Show this help message and exit");
-    println!("  --path, -p <PATH>    Set the search path (default: ./)");
-    println!("  --count, -c <COUNT>  Set the fatass count (default: 100)");
+fn run_inodes(context: Context) -> u8 {
+    let Context { matches, mut options, scanner, paging, runtime_start, .. } = context;
+    let output_format = options.output_format;
+    print_status(&"Counting files ...".cyan(), output_format);
+    let counts = count_entries(&scanner);
+    options.output_options.completeness = completeness(&scanner);
+    if !write_paged(&options.output_file, output_format, paging, |out| write_inodes(out, &counts, output_format, &options.output_options)) {
+        return EXIT_FAILED;
+    }
+    report_errors(&scanner, matches.flag("show-errors"), output_format);
+    let end_message = format!("Found the {} most crowded directories in {:?}", counts.len(), runtime_start.elapsed()).green();
+    print_status(&end_message, output_format);
+    scan_status(&scanner)
+}
 
-    println!("\nExamples:");
-    println!("  fatass --path /some/path --count 50");
-    println!("  fatass -p /another/path -c 75");
+fn run_compressible(context: Context) -> u8 {
+    let Context { matches, mut options, scanner, paging, runtime_start, .. } = context;
+    let output_format = options.output_format;
+    print_status(&"Sampling the fattest files ...".cyan(), output_format);
+    let candidates = estimate_savings(&scanner);
+    options.output_options.completeness = completeness(&scanner);
+    if !write_paged(&options.output_file, output_format, paging, |out| write_compressible(out, &candidates, output_format, &options.output_options)) {
+        return EXIT_FAILED;
+    }
+    let savings: u64 = candidates.iter().map(|candidate| candidate.savings()).sum();
+    report_errors(&scanner, matches.flag("show-errors"), output_format);
+    let end_message = format!("Compressing {} files would save about {} in {:?}", candidates.len(), format_size(savings), runtime_start.elapsed()).green();
+    print_status(&end_message, output_format);
+    scan_status(&scanner)
+}
 
-    println!("\nNote:");
-    println!("  If the provided path or count value contains spaces, enclose it in quotes.");
+fn run_docker(context: Context) -> u8 {
+    let Context { matches, mut options, scanner, paging, runtime_start, .. } = context;
+    let output_format = options.output_format;
+    print_status(&"Reading the container storage ...".cyan(), output_format);
+    let usage = docker::storage_usage(&scanner);
+    options.output_options.completeness = completeness(&scanner);
+    if !write_paged(&options.output_file, output_format, paging, |out| write_docker(out, &usage, output_format, &options.output_options)) {
+        return EXIT_FAILED;
+    }
+    let unique: u64 = usage.iter().map(|row| row.unique).sum();
+    report_errors(&scanner, matches.flag("show-errors"), output_format);
+    let end_message = format!("Found {} images, containers and volumes taking {} of their own in {:?}", usage.len(), format_size(unique), runtime_start.elapsed()).green();
+    print_status(&end_message, output_format);
+    scan_status(&scanner)
 }
 
-fn reverse_binary_search_insert_index(arr: &[FileData], target_size: &u64) -> Option<usize> {
-    let mut low = 0;
-    let mut high = arr.len();
+fn run_dupes(context: Context) -> u8 {
+    let Context { matches, mut options, scanner, paging, runtime_start, .. } = context;
+    let output_format = options.output_format;
+    print_status(&"Looking for duplicate files ...".cyan(), output_format);
+    let groups = find_duplicates(&scanner);
+    options.output_options.completeness = completeness(&scanner);
+    if !write_paged(&options.output_file, output_format, paging, |out| write_dupes(out, &groups, output_format, &options.output_options)) {
+        return EXIT_FAILED;
+    }
+    let wasted: u64 = groups.iter().map(|group| group.wasted()).sum();
+    report_errors(&scanner, matches.flag("show-errors"), output_format);
+    let end_message = format!("Found {} duplicate groups wasting {} in {:?}", groups.len(), format_size(wasted), runtime_start.elapsed()).green();
+    print_status(&end_message, output_format);
+    scan_status(&scanner)
+}
 
-    // Check if smaller than the smaller file, if so return none to skip
-    if target_size < &arr[arr.len() - 1].size {
-        return None;
+fn run_groups(context: Context, group_key: GroupKey) -> u8 {
+    let Context { matches, mut options, scanner, paging, runtime_start, .. } = context;
+    let output_format = options.output_format;
+    print_status(&"Grouping files ...".cyan(), output_format);
+    let groups = group_files(&scanner, group_key, options.per_group);
+    options.output_options.completeness = completeness(&scanner);
+    if !write_paged(&options.output_file, output_format, paging, |out| write_groups(out, &groups, group_key, output_format, &options.output_options)) {
+        return EXIT_FAILED;
     }
+    report_errors(&scanner, matches.flag("show-errors"), output_format);
+    let end_message = format!("Found the fattest {} groups in {:?}", groups.len(), runtime_start.elapsed()).green();
+    print_status(&end_message, output_format);
+    scan_status(&scanner)
+}
 
-    while low != high {
-        let mid = (low + high) / 2;
+fn run_export_metrics(context: Context) -> u8 {
+    let Context { matches, scanner, runtime_start, .. } = context;
+    // The metrics are machine readable, the messages stay off stdout as with the other such formats
+    let status_format = OutputFormat::Plain;
+    print_status(&"Scanning files ...".cyan(), status_format);
+    let metrics = Mutex::new(Metrics::default());
+    let scan_start = Instant::now();
+    let mut scan = scanner.scan_with(&|file_entry| metrics.lock().unwrap().add(file_entry));
+    let fattest: Vec<FileEntry> = scan.by_ref().collect();
+    let summary = ScanSummary {
+        fattest: &fattest,
+        files: scan.stats().files(),
+        bytes: scan.total_bytes(),
+        duration: scan_start.elapsed(),
+        unreadable: scanner.errors().count(),
+        growth: None,
+    };
+    let metrics = metrics.into_inner().unwrap();
+    let written = match matches.value("textfile") {
+        Some(textfile_value) => metrics.write_textfile(Path::new(textfile_value), &summary)
+            .map(|_| print_status(&format!("Metrics written to {}", textfile_value).cyan(), status_format)),
+        None => metrics.write(&mut io::stdout().lock(), &summary).map_err(exit_on_closed_pipe),
+    };
+    if let Err(err) = written {
+        eprintln!("{}", format!("Error: Could not write the metrics: {}", err).red());
+        return EXIT_FAILED;
+    }
+    report_errors(&scanner, matches.flag("show-errors"), status_format);
+    let end_message = format!("Exported the metrics of {} files in {:?}", summary.files, runtime_start.elapsed()).green();
+    print_status(&end_message, status_format);
+    scan_status(&scanner)
+}
 
-        match arr[mid].size.cmp(target_size) {
-            std::cmp::Ordering::Equal => return Some(mid),
-            std::cmp::Ordering::Less => high = mid,
-            std::cmp::Ordering::Greater => low = mid + 1,
+// Read what the columns asked for show of the results, only the entries shown are read whatever the
+// scan went through. Each step is told unless the results are redrawn in a loop
+fn annotate_results(files: &mut [FileEntry], matches: &Matches, options: &Options, announce: bool) {
+    let output_format = options.output_format;
+    if options.detect_type {
+        magic::detect_types(files);
+    }
+    if options.list_xattrs {
+        xattrs::list_entries(files);
+    }
+    if let Some(algorithm) = options.hash_algorithm {
+        if announce {
+            print_status(&format!("Hashing {} results with {} ...", files.len(), algorithm.label()).cyan(), output_format);
         }
+        checksum::hash_entries(files, algorithm);
     }
+    if matches.flag("peek-archives") {
+        if announce {
+            print_status(&"Looking inside the archives ...".cyan(), output_format);
+        }
+        archive::peek_archives(files);
+    }
+    if options.git_aware {
+        git::peek_repositories(files);
+    }
+}
+
+fn run_watch(context: Context) -> u8 {
+    let Context { matches, mut options, search_paths, mut scanner, .. } = context;
+    let output_format = options.output_format;
+    let interval_label = matches.value("interval").unwrap_or("1m");
+    let clear_screen = options.output_file.is_none() && output_format == OutputFormat::Table && console::Term::stdout().is_term();
+    let mut previous: Option<Vec<(String, u64)>> = None;
+    print_status(&format!("Watching {} every {}, press Ctrl-C to stop ...", search_paths.join(", "), interval_label).cyan(), output_format);
+    loop {
+        let mut scan = scanner.scan();
+        let mut biggest_files: Vec<FileEntry> = scan.by_ref().collect();
+        if options.show_percent {
+            options.output_options.percent_of = Some(scan.total_bytes());
+        }
+        biggest_files.truncate(options.count);
+        order_entries(&mut biggest_files, options.sort_key, options.reverse);
+        annotate_results(&mut biggest_files, &matches, &options, false);
+        // The spinner would only flicker over the results from now on
+        scanner = scanner.progress(false);
 
-    Some(low)
+        let current: Vec<(String, u64)> = biggest_files.iter().map(|entry| (entry.path.clone(), entry.size)).collect();
+        if previous.as_ref() != Some(&current) {
+            // Nothing is new on the first round, everything would be highlighted otherwise
+            let new_paths: HashSet<String> = match &previous {
+                Some(previous) => {
+                    let known: HashSet<&String> = previous.iter().map(|(path, _)| path).collect();
+                    current.iter().map(|(path, _)| path).filter(|path| !known.contains(path)).cloned().collect()
+                }
+                None => HashSet::new(),
+            };
+            if clear_screen {
+                let _ = console::Term::stdout().clear_screen();
+            }
+            if !write_output(&options.output_file, output_format, |out| write_watch(out, &biggest_files, &new_paths, output_format, &options.output_options)) {
+                return EXIT_FAILED;
+            }
+            let status = format!("Updated at {}, {} new entries, next scan in {}", format_time(SystemTime::now()), new_paths.len(), interval_label);
+            print_status(&status.green(), output_format);
+            previous = Some(current);
+        }
+        thread::sleep(options.interval);
+    }
 }
 
-// Get args from command line
-fn main() {
-    let runtime_start = Instant::now();
-    let args: Vec<String> = env::args().collect();
-    let mut search_path: String = String::from("./");
-    let mut fatass_count: usize = 100;
+fn run_daemon(context: Context) -> u8 {
+    let Context { matches, options, search_paths, mut scanner, .. } = context;
+    let output_format = options.output_format;
+    let Some(state_path) = matches.value("state-dir").map(PathBuf::from).or_else(StateDir::default_path) else {
+        return usage_error("No default state directory on this system. Please provide one with --state-dir.");
+    };
+    let state_dir = match StateDir::open(&state_path) {
+        Ok(state_dir) => state_dir,
+        Err(err) => {
+            eprintln!("{}", format!("Error: Could not create the state directory {}: {}", state_path.display(), err).red());
+            return EXIT_FAILED;
+        }
+    };
+    let interval_label = matches.value("interval").unwrap_or("6h");
+    let message = format!("Scanning {} every {}, snapshots in {}, press Ctrl-C to stop ...", search_paths.join(", "), interval_label, state_path.display());
+    print_status(&message.cyan(), output_format);
+    // A restart picks up where the last scan left
+    let mut previous = state_dir.latest();
+    loop {
+        let round_start = Instant::now();
+        let metrics = Mutex::new(Metrics::default());
+        let mut scan = scanner.scan_with(&|file_entry| metrics.lock().unwrap().add(file_entry));
+        let entries: Vec<FileEntry> = scan.by_ref().collect();
+        let duration = round_start.elapsed();
+        // The spinner would only flicker over the results from now on
+        scanner = scanner.progress(false);
 
-    // Check if help was asked
-    if let Some(_index) = args.iter().position(|arg| arg == "--help" || arg == "-h") {
-        print_help();
-        return;
+        let changes = previous.as_ref().map(|previous| diff(&previous.entries, &entries));
+        let growth = changes.as_ref().map(|changes| changes.iter().map(Change::delta).sum::<i128>());
+        match changes {
+            Some(changes) if changes.is_empty() => print_status(&"Nothing changed since the previous scan".green(), output_format),
+            Some(changes) => {
+                report_changes(changes, options.count, output_format, &options.output_file, &options.output_options, round_start);
+            }
+            None => {
+                let message = format!("First scan, {} files ({}) recorded, what grows shows from the next scan", entries.len(), format_size(scan.total_bytes()));
+                print_status(&message.green(), output_format);
+            }
+        }
+
+        if let Some(textfile_value) = matches.value("textfile") {
+            let summary = ScanSummary {
+                fattest: &entries[..entries.len().min(options.metrics_top)],
+                files: scan.stats().files(),
+                bytes: scan.total_bytes(),
+                duration,
+                unreadable: scanner.errors().count(),
+                growth,
+            };
+            if let Err(err) = metrics.into_inner().unwrap().write_textfile(Path::new(textfile_value), &summary) {
+                eprintln!("{}", format!("Error: Could not write the metrics: {}", err).red());
+            }
+        }
+        let breaches = threshold_breaches(&entries, scan.total_bytes(), options.notify_if_over, options.notify_if_total_over, false);
+        if let Some(webhook_value) = matches.value("notify-webhook").filter(|_| !breaches.is_empty()) {
+            let notification = Notification {
+                breaches: &breaches,
+                paths: &search_paths,
+                total_bytes: scan.total_bytes(),
+                files: scan.stats().files(),
+                top: &entries[..entries.len().min(10)],
+            };
+            match notify::post(webhook_value, &notification.to_json()) {
+                Ok(()) => print_status(&format!("Over the limit, notified {}", webhook_value).yellow(), output_format),
+                Err(err) => eprintln!("{}", format!("Error: Could not notify the webhook: {}", err).red()),
+            }
+        }
+        report_errors(&scanner, matches.flag("show-errors"), output_format);
+
+        // A failed round is told but doesn't stop the next ones
+        let snapshot = Snapshot::new(entries);
+        if let Err(err) = state_dir.record(&snapshot) {
+            eprintln!("{}", format!("Error: Could not save the snapshot: {}", err).red());
+        }
+        previous = Some(snapshot);
+        print_status(&format!("Scanned at {}, next scan in {}", format_time(SystemTime::now()), interval_label).green(), output_format);
+        thread::sleep(options.interval);
     }
+}
 
-    // Check if path was given, if so, set it
-    if let Some(index) = args.iter().position(|arg| arg == "--path" || arg == "-p") {
-        // Check if there is a value after "--path"
-        if let Some(path_value) = args.get(index + 1) {
-            let path = Path::new(path_value);
+// What was made of the ranked entries to reach a free space target or a byte budget
+enum Picked {
+    // The mount point, its free bytes, how far it is from the target and what removing the results frees
+    CleanupPlan(String, u64, u64, u64),
+    BudgetMessage(ColoredString),
+    Ranked,
+}
 
-            if path.exists() {
-                // Path is valid, assign it to search_path
-                search_path = path.to_string_lossy().to_string();
-            } else {
-                eprintln!("{}", "Error: Invalid path. Please provide a valid path.".red());
-                return;
+// Keep the results asked for from the ranked entries, the files to remove to reach a free space
+// target, the fewest adding up to a byte budget, or a share or a page of them
+fn pick_results(biggest_files: &mut Vec<FileEntry>, scanner: &Scanner, options: &Options) -> Result<Picked, String> {
+    let output_format = options.output_format;
+    if let Some(target) = options.free_target {
+        let volumes = volumes_of(&scanner.roots());
+        let [volume] = volumes.as_slice() else {
+            return Err(format!("--free-target plans for one filesystem, the search paths are on {}.", volumes.len()));
+        };
+        let mount = volume.mount.display().to_string();
+        let deficit = target.saturating_sub(volume.free);
+        if deficit == 0 {
+            print_status(&format!("{} free on {}, the {} target is met already", format_size(volume.free), mount, format_size(target)).green(), output_format);
+            biggest_files.clear();
+            return Ok(Picked::Ranked);
+        }
+        print_status(&format!("Only {} free on {}, {} short of the {} target", format_size(volume.free), mount, format_size(deficit), format_size(target)).yellow(), output_format);
+        let freed = plan_cleanup(biggest_files, deficit);
+        return Ok(Picked::CleanupPlan(mount, volume.free, deficit, freed));
+    }
+    if let Some(budget) = options.byte_budget {
+        let taken = take_bytes(biggest_files, budget);
+        let message = if taken >= budget {
+            let files = if biggest_files.len() == 1 { String::from("This file") } else { format!("These {} files", biggest_files.len()) };
+            format!("{} add up to {}, reaching the {} asked", files, format_size(taken), format_size(budget)).cyan()
+        } else {
+            format!("Every file scanned only adds up to {}, {} short of the {} asked", format_size(taken), format_size(budget - taken), format_size(budget)).yellow()
+        };
+        return Ok(Picked::BudgetMessage(message));
+    }
+    let count = match options.top_percent {
+        Some(percent) => (biggest_files.len() as f64 * percent / 100.0).ceil() as usize,
+        None => options.count,
+    };
+    biggest_files.truncate(count.saturating_add(options.offset));
+    biggest_files.drain(..options.offset.min(biggest_files.len()));
+    Ok(Picked::Ranked)
+}
+
+// Hand the results over, script them, run a command on them, delete, compress or offload them.
+// Nothing is done to the results of a scan cut short, the reason is told instead
+fn act_on_results(context: &Context, biggest_files: &[FileEntry], cut_short: Option<&str>) -> bool {
+    let Context { matches, options, scanner, .. } = context;
+    let output_format = options.output_format;
+    let mut succeeded = true;
+
+    // Hand the results open and copy name over to the desktop
+    for &(long, rank) in &options.hand_over {
+        let Some(file_entry) = biggest_files.get(rank - 1) else {
+            eprintln!("{}", format!("Error: Could not {} result {}, there are only {}.", long, rank, biggest_files.len()).red());
+            succeeded = false;
+            continue;
+        };
+        let handed_over = if long == "open" {
+            desktop::open_folder(Path::new(&file_entry.path)).map(|_| format!("Opened the folder of {}", file_entry.path))
+        } else {
+            desktop::copy_to_clipboard(&file_entry.path).map(|by| format!("Copied {} with {}", file_entry.path, by))
+        };
+        match handed_over {
+            Ok(message) => print_status(&message.cyan(), output_format),
+            Err(err) => {
+                let what = if long == "open" { "open the folder of" } else { "copy the path of" };
+                eprintln!("{}", format!("Error: Could not {} {}: {}", what, file_entry.path, err).red());
+                succeeded = false;
             }
+        }
+    }
+
+    // The script is written for the results without running it
+    if let Some(script_value) = matches.value("emit-script") {
+        match create_script(Path::new(script_value), biggest_files, options.script_action, matches.value("script-dest")) {
+            Ok(()) => print_status(&format!("Script for {} entries written to {}, review it before running it", biggest_files.len(), script_value).cyan(), output_format),
+            Err(err) => {
+                eprintln!("{}", format!("Error: Could not write the script: {}", err).red());
+                succeeded = false;
+            }
+        }
+    }
+
+    if let Some(exec_command) = &options.exec_command {
+        if let Some(cut_short) = cut_short {
+            print_status(&format!("Nothing run, {}.", cut_short).yellow(), output_format);
         } else {
-            eprintln!("{}", "Error: No value provided after --path option.".red());
-            return;
+            let (ran, failures) = exec_command.run_for_each(biggest_files, options.exec_parallel);
+            print_status(&format!("Ran the command for {} entries", ran - failures.len()).cyan(), output_format);
+            if ran < biggest_files.len() {
+                print_status(&format!("Interrupted, {} entries left without running it.", biggest_files.len() - ran).yellow(), output_format);
+            }
+            if !failures.is_empty() {
+                eprintln!("{}", format!("Error: The command failed for {} entries:", failures.len()).red());
+                for (path, reason) in &failures {
+                    eprintln!("{}", format!("  {}: {}", path, reason).red());
+                }
+                succeeded = false;
+            }
         }
     }
 
-    // Check if count was, if so, set it
-    if let Some(index) = args.iter().position(|arg| arg == "--count" || arg == "-c") {
-        // Check if there is a value after "--count"
-        if let Some(count_value) = args.get(index + 1) {
-            if let Ok(parsed_count) = count_value.parse::<usize>() {
-                fatass_count = parsed_count;
-            } else {
-                eprintln!("{}", "Error: Invalid count value. Please provide a valid number.".red());
-                return;
+    if let (true, Some(cut_short)) = (options.deleting, cut_short) {
+        print_status(&format!("Nothing deleted, {}.", cut_short).yellow(), output_format);
+    } else if options.deleting {
+        // fatass clean asks what to do with each file, unless told yes to deleting them all
+        let method = if matches.flag("trash") { DeleteMethod::Trash } else { DeleteMethod::Remove };
+        let cleaned = if matches.command == Command::Clean && !matches.flag("yes") {
+            clean_results(biggest_files, output_format)
+        } else {
+            delete_results(biggest_files, method, matches.flag("yes"), output_format)
+        };
+        succeeded &= cleaned;
+    }
+
+    if matches.command == Command::Compress {
+        if let Some(cut_short) = cut_short {
+            print_status(&format!("Nothing compressed, {}.", cut_short).yellow(), output_format);
+        } else if !compress_results(biggest_files, options.compressor, output_format) {
+            succeeded = false;
+        }
+    }
+    if matches.command == Command::Offload {
+        let destination = Path::new(matches.value("dest").unwrap_or("."));
+        if let Some(cut_short) = cut_short {
+            print_status(&format!("Nothing moved, {}.", cut_short).yellow(), output_format);
+        } else if !offload_results(biggest_files, &scanner.roots(), destination, matches.flag("leave-symlinks"), output_format) {
+            succeeded = false;
+        }
+    }
+    succeeded
+}
+
+fn run_scan(context: Context) -> u8 {
+    let Context { matches, options, search_paths, scanner, paging, runtime_start } = &context;
+    let output_format = options.output_format;
+    if options.dirs_mode {
+        print_status(&"Summing directory sizes ...".cyan(), output_format);
+    } else if scanner.is_parallel() {
+        print_status(&format!("Scanning files with {} threads ...", options.threads).cyan(), output_format);
+    } else if matches.flag("streaming") {
+        print_status(&"Scanning files ...".cyan(), output_format);
+    } else {
+        // Count the number of file to check
+        print_status(&"Gathering files ...".cyan(), output_format);
+    }
+    // Streamed NDJSON gets every candidate on stdout as it's found, ahead of the results
+    let stream_candidates = output_format == OutputFormat::Ndjson && matches.flag("streaming")
+        && options.output_file.is_none() && matches.value("compare").is_none();
+    let export = match matches.value("export-sqlite").map(|export_value| SqliteExport::create(Path::new(export_value))) {
+        Some(Ok(export)) => Some(Mutex::new(export)),
+        Some(Err(err)) => {
+            eprintln!("{}", format!("Error: Could not create the database: {}", err).red());
+            return EXIT_FAILED;
+        }
+        None => None,
+    };
+    let parquet = match matches.value("export-parquet").map(|parquet_value| ParquetExport::create(Path::new(parquet_value), scanner.roots())) {
+        Some(Ok(parquet)) => Some(Mutex::new(parquet)),
+        Some(Err(err)) => {
+            eprintln!("{}", format!("Error: Could not create the Parquet file: {}", err).red());
+            return EXIT_FAILED;
+        }
+        None => None,
+    };
+    interrupt::install();
+    let mut scan = scanner.scan_with(&|file_entry| {
+        if stream_candidates {
+            let _ = write_candidate(&mut io::stdout().lock(), file_entry).map_err(exit_on_closed_pipe);
+        }
+        if let Some(export) = &export {
+            export.lock().unwrap().add(file_entry);
+        }
+        if let Some(parquet) = &parquet {
+            parquet.lock().unwrap().add(file_entry);
+        }
+    });
+    let mut biggest_files: Vec<FileEntry> = scan.by_ref().collect();
+    let output_options = OutputOptions {
+        percent_of: options.show_percent.then_some(scan.total_bytes()),
+        completeness: completeness(scanner),
+        ..options.output_options.clone()
+    };
+    let interrupted = scan.interrupted();
+    // Why the results are partial, for the messages of what was left undone
+    let stopped_after = match scan.limit_reached() {
+        Some(Limit::Files) => Some(format!("{} files", options.max_files.unwrap_or_default())),
+        Some(Limit::Time) => matches.value("timeout").map(String::from),
+        None => None,
+    };
+    let cut_short = stopped_after.as_ref().map_or_else(|| String::from("the scan was interrupted"), |after| format!("the scan stopped after {}", after));
+    if interrupted {
+        let stopped = stopped_after.as_ref().map_or_else(|| String::from("Interrupted"), |after| format!("Stopped after {}", after));
+        let summary = format!("{}, partial results from the {} files ({}) scanned so far", stopped, scan.stats().files(), format_size(scan.total_bytes()));
+        print_status(&summary.yellow(), output_format);
+    }
+    let partial = if interrupted { " (partial)" } else { "" };
+
+    if let (Some(export), Some(export_value)) = (export, matches.value("export-sqlite")) {
+        match export.into_inner().unwrap().finish() {
+            Ok(files) => print_status(&format!("{} files exported to {}{}", files, export_value, partial).cyan(), output_format),
+            Err(err) => {
+                eprintln!("{}", format!("Error: Could not export to the database: {}", err).red());
+                return EXIT_FAILED;
             }
+        }
+    }
+    if let (Some(parquet), Some(parquet_value)) = (parquet, matches.value("export-parquet")) {
+        match parquet.into_inner().unwrap().finish() {
+            Ok(files) => print_status(&format!("{} files exported to {}{}", files, parquet_value, partial).cyan(), output_format),
+            Err(err) => {
+                eprintln!("{}", format!("Error: Could not export to the Parquet file: {}", err).red());
+                return EXIT_FAILED;
+            }
+        }
+    }
+
+    // A partial snapshot or comparison would list everything not scanned yet as gone
+    if interrupted && (matches.flag("save-snapshot") || matches.flag("compare")) {
+        print_status(&"Snapshots are only saved or compared after complete scans.".yellow(), output_format);
+    }
+
+    // Snapshots hold every entry scanned, the results only the fattest of them
+    if let Some(snapshot_value) = matches.value("save-snapshot").filter(|_| !interrupted) {
+        let snapshot = Snapshot::new(biggest_files.clone());
+        match snapshot.save(Path::new(snapshot_value)) {
+            Ok(()) => print_status(&format!("Snapshot of {} entries saved to {}", snapshot.entries.len(), snapshot_value).cyan(), output_format),
+            Err(err) => {
+                eprintln!("{}", format!("Error: Could not save the snapshot: {}", err).red());
+                return EXIT_FAILED;
+            }
+        }
+    }
+    if let Some(compare_value) = matches.value("compare").filter(|_| !interrupted) {
+        let old = match Snapshot::load(Path::new(compare_value)) {
+            Ok(old) => old,
+            Err(err) => {
+                eprintln!("{}", format!("Error: Invalid snapshot, {}", err).red());
+                return EXIT_FAILED;
+            }
+        };
+        // Files and directories never share a path, comparing one with the other would list everything
+        if old.entries.first().is_some_and(|entry| entry.kind != biggest_files.first().map_or(entry.kind, |new| new.kind)) {
+            eprintln!("{}", "Error: The snapshot wasn't taken with the same command, compare files with files and dirs with dirs.".red());
+            return EXIT_FAILED;
+        }
+        report_errors(scanner, matches.flag("show-errors"), output_format);
+        if !report_changes(diff(&old.entries, &biggest_files), options.count, output_format, &options.output_file, &output_options, *runtime_start) {
+            return EXIT_FAILED;
+        }
+        return scan_status(scanner);
+    }
+
+    // The thresholds go by everything scanned, the results are still biggest first here
+    let over = threshold_breaches(&biggest_files, scan.total_bytes(), options.fail_if_over, options.fail_if_total_over, options.dirs_mode);
+    let notify_breaches = threshold_breaches(&biggest_files, scan.total_bytes(), options.notify_if_over, options.notify_if_total_over, options.dirs_mode);
+    let picked = match pick_results(&mut biggest_files, scanner, options) {
+        Ok(picked) => picked,
+        Err(err) => {
+            eprintln!("{}", format!("Error: {}", err).red());
+            return EXIT_FAILED;
+        }
+    };
+    let output_start = Instant::now();
+    order_entries(&mut biggest_files, options.sort_key, options.reverse);
+    annotate_results(&mut biggest_files, matches, options, true);
+
+    // The filesystems header goes on top of a table printed as is, with the status messages otherwise
+    let volumes = matches.flag("fs-usage").then(|| volumes_of(&scanner.roots()));
+    let header_on_top = output_format == OutputFormat::Table && options.output_file.is_none();
+    if let Some(volumes) = volumes.as_ref().filter(|_| !header_on_top) {
+        let written = if output_format.status_to_stderr() {
+            write_volumes(&mut io::stderr().lock(), volumes, scan.total_bytes(), &biggest_files)
         } else {
-            eprintln!("{}", "Error: No value provided after --count option.".red());
-            return;
-        }
-    }
-
-    // Count the number of file to check
-    println!("{}", "Gathering files ...".cyan());
-
-    let walker = WalkDir::new(&search_path)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| !e.file_type().is_dir())
-        .filter(|e| e.metadata().map(|m| m.len()).unwrap_or(0) != 0)
-        .collect::<Vec<_>>();
-    let total_files = walker.len() as u64;
-
-    let progress_bar = ProgressBar::new(total_files);
-    progress_bar.set_style(ProgressStyle::with_template("[{elapsed_precise}] {bar:50.cyan/blue} {pos:>7}/{len:7} {msg}")
-        .unwrap()
-        .progress_chars("##-"));
-
-    // Create an array to store biggest files
-    let mut biggest_files: Vec<FileData> = Vec::with_capacity(fatass_count);
-    let mut reordered = false;
-    for entry in walker
-    {
-        let file_data = FileData::new(
-            entry.path().display().to_string(),
-            entry.metadata().map(|m| m.len()).unwrap_or(0) as u64
-        );
-
-        if biggest_files.len() < fatass_count {
-            // We fill the vec its not to its capacity
-            biggest_files.push(file_data);
-        } else if biggest_files.len() == fatass_count && reordered == false {
-            // We reorder the current files in the vector because its at its capacity and we need it sorted for binary search
-            biggest_files.sort_by(|a, b| b.size.cmp(&a.size));
-            reordered = true;
-        } else  {
-            // We search where the current file should be in the vec, if none is return it means the current file is smaller than the smaller file in the vector
-            if let Some(i) = reverse_binary_search_insert_index(&biggest_files, &file_data.size) {
-                biggest_files.insert(i, file_data);
-                biggest_files.pop();
-            }
-        }
-
-        progress_bar.inc(1);
-    }
-    progress_bar.finish();
-
-    let tabled_files: Vec<FileDataTable> = biggest_files.iter().map(|file_data| {
-        FileDataTable::new(
-            file_data.path.clone(),
-            file_data.get_str_size()
-        )
-    }).collect();
-
-    let mut table = Table::new(&tabled_files);
-    table
-        .with(Style::rounded())
-        .with(BorderColor::filled(Color::FG_GREEN))
-        .with(Colorization::columns([Color::FG_CYAN, Color::FG_BRIGHT_RED]))
-        .with(Colorization::exact([Color::FG_GREEN], Rows::first()))
-        .modify(Columns::last(), Alignment::right());
-
-    println!("{}", table.to_string());
-
-    let end_message = format!("Found the fattest {} files in {:?}", fatass_count, runtime_start.elapsed()).green();
-    println!("{}", end_message);
-}
\ No newline at end of file
+            write_volumes(&mut io::stdout().lock(), volumes, scan.total_bytes(), &biggest_files).map_err(exit_on_closed_pipe)
+        };
+        if let Err(err) = written {
+            eprintln!("{}", format!("Error: Could not write the filesystems: {}", err).red());
+            return EXIT_FAILED;
+        }
+    }
+    let header = volumes.filter(|_| header_on_top);
+    let written = write_paged(&options.output_file, output_format, *paging, |out| {
+        if let Some(volumes) = &header {
+            write_volumes(out, volumes, scan.total_bytes(), &biggest_files)?;
+        }
+        write_results(out, &biggest_files, output_format, &output_options)
+    });
+    if !written {
+        return EXIT_FAILED;
+    }
+    verbose!("Sorted and wrote {} results in {:?}", biggest_files.len(), output_start.elapsed());
+    let mut status = scan_status(scanner);
+    // A footer to read, it goes with the status messages
+    if matches.flag("stats") {
+        let written = if output_format.status_to_stderr() {
+            write_stats(&mut io::stderr().lock(), scan.stats(), &biggest_files)
+        } else {
+            write_stats(&mut io::stdout().lock(), scan.stats(), &biggest_files).map_err(exit_on_closed_pipe)
+        };
+        if let Err(err) = written {
+            eprintln!("{}", format!("Error: Could not write the stats: {}", err).red());
+            status = EXIT_FAILED;
+        }
+    }
+    if !act_on_results(&context, &biggest_files, interrupted.then_some(cut_short.as_str())) {
+        status = EXIT_FAILED;
+    }
+
+    report_errors(scanner, matches.flag("show-errors"), output_format);
+    match picked {
+        Picked::BudgetMessage(message) => print_status(&message, output_format),
+        Picked::CleanupPlan(mount, free, deficit, freed) => {
+            let files = if biggest_files.len() == 1 { String::from("this file") } else { format!("these {} files", biggest_files.len()) };
+            let message = if freed >= deficit {
+                format!("Removing {} frees {}, leaving {} free on {}", files, format_size(freed), format_size(free + freed), mount).cyan()
+            } else {
+                format!("Removing every file scanned only frees {}, still {} short", format_size(freed), format_size(deficit - freed)).yellow()
+            };
+            print_status(&message, output_format);
+        }
+        Picked::Ranked => {}
+    }
+    // Deleting from a snapshot frees nothing, macOS drops the whole snapshot when it needs the space
+    let in_snapshots = biggest_files.iter().filter(|file_entry| macos::in_local_snapshot(&file_entry.path)).count();
+    if in_snapshots > 0 {
+        let message = format!("{} of the results are in Time Machine local snapshots, purgeable space macOS frees when it runs short", in_snapshots);
+        print_status(&message.yellow(), output_format);
+    }
+    for message in &over {
+        print_status(&message.red(), output_format);
+    }
+    if let Some(webhook_value) = matches.value("notify-webhook").filter(|_| !notify_breaches.is_empty()) {
+        let notification = Notification {
+            breaches: &notify_breaches,
+            paths: search_paths,
+            total_bytes: scan.total_bytes(),
+            files: scan.stats().files(),
+            top: &biggest_files[..biggest_files.len().min(10)],
+        };
+        match notify::post(webhook_value, &notification.to_json()) {
+            Ok(()) => print_status(&format!("Over the limit, notified {}", webhook_value).yellow(), output_format),
+            Err(err) => {
+                eprintln!("{}", format!("Error: Could not notify the webhook: {}", err).red());
+                status = EXIT_FAILED;
+            }
+        }
+    }
+    let kind_label = match (options.dirs_mode, matches.flag("empty")) {
+        (true, true) => "empty directories",
+        (false, true) => "empty files and directories",
+        (true, false) => "directories",
+        (false, false) => "files",
+    };
+    let picked = match options.rank {
+        Rank::Smallest => "smallest",
+        Rank::Stale => "stalest",
+        Rank::Expr(_) => "top ranked",
+        Rank::Size if matches.flag("empty") => "first",
+        Rank::Size => "fattest",
+    };
+    if interrupted {
+        let end_message = format!("Showed the {} {} {} found in {:?} before {}", picked, biggest_files.len(), kind_label, runtime_start.elapsed(), cut_short).yellow();
+        print_status(&end_message, output_format);
+        status = EXIT_FAILED;
+    } else {
+        let end_message = format!("Found the {} {} {} in {:?}", picked, biggest_files.len(), kind_label, runtime_start.elapsed()).green();
+        print_status(&end_message, output_format);
+    }
+    // Going over a threshold is the answer a check asks for, even from a partial scan
+    if over.is_empty() { status } else { EXIT_OVER }
+}
@@ -0,0 +1,131 @@
+// Markdown and HTML renderings of the result tables, to paste into tickets, wikis and reports.
+// The HTML page sorts its rows when a header is clicked, sizes sorting by their value.
+use std::io::{self, Write};
+
+// A table as plain text cells, the same one the terminal shows without its colors
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Markup {
+    pub(crate) title: &'static str,
+    pub(crate) headers: Vec<String>,
+    // Cells may hold several lines
+    pub(crate) rows: Vec<Vec<String>>,
+    // Indexes of the columns holding numbers
+    pub(crate) right_aligned: Vec<usize>,
+    // Where the first cell of each row links to in HTML, rows without one stay plain
+    pub(crate) links: Vec<Option<String>>,
+}
+
+impl Markup {
+    pub(crate) fn new(title: &'static str, headers: Vec<String>) -> Markup {
+        Markup { title, headers, ..Markup::default() }
+    }
+
+    pub(crate) fn right_aligned(mut self, columns: impl IntoIterator<Item = usize>) -> Markup {
+        self.right_aligned.extend(columns);
+        self
+    }
+}
+
+fn markdown_escape(cell: &str) -> String {
+    cell.replace('\\', "\\\\").replace('|', "\\|").replace('\n', "<br>")
+}
+
+pub(crate) fn write_markdown(out: &mut dyn Write, markup: &Markup) -> io::Result<()> {
+    let headers: Vec<String> = markup.headers.iter().map(|header| markdown_escape(header)).collect();
+    writeln!(out, "| {} |", headers.join(" | "))?;
+    let rules: Vec<&str> = (0..markup.headers.len())
+        .map(|index| if markup.right_aligned.contains(&index) { "---:" } else { "---" })
+        .collect();
+    writeln!(out, "| {} |", rules.join(" | "))?;
+    for row in &markup.rows {
+        let cells: Vec<String> = row.iter().map(|cell| markdown_escape(cell)).collect();
+        writeln!(out, "| {} |", cells.join(" | "))?;
+    }
+    Ok(())
+}
+
+pub(crate) fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// Sizes like "1.20 GB", "-300 KiB" or "1,024 Bytes" sort by value, the rest as text
+const SORT_SCRIPT: &str = r#"<script>
+function cellValue(text) {
+  const match = text.replace(/,/g, "").match(/^([+-]?[\d.]+)\s*(Bytes|[kKMGTPEZY]i?B|%)?$/);
+  if (!match) return null;
+  const units = "KMGTPEZY";
+  const unit = match[2] || "";
+  const power = unit.length > 1 && unit !== "Bytes" ? units.indexOf(unit[0].toUpperCase()) + 1 : 0;
+  return parseFloat(match[1]) * Math.pow(1024, power);
+}
+document.querySelectorAll("th").forEach((header, column) => {
+  header.addEventListener("click", () => {
+    const body = header.closest("table").tBodies[0];
+    const descending = header.dataset.order !== "desc";
+    header.parentNode.querySelectorAll("th").forEach(other => delete other.dataset.order);
+    header.dataset.order = descending ? "desc" : "asc";
+    const rows = Array.from(body.rows).sort((a, b) => {
+      const left = a.cells[column].innerText, right = b.cells[column].innerText;
+      const [x, y] = [cellValue(left), cellValue(right)];
+      const order = x !== null && y !== null ? x - y : left.localeCompare(right);
+      return descending ? -order : order;
+    });
+    rows.forEach(row => body.appendChild(row));
+  });
+});
+</script>"#;
+
+const STYLE: &str = "<style>
+body { font-family: sans-serif; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 4px 8px; vertical-align: top; }
+th { cursor: pointer; background: #eee; }
+th[data-order=asc]::after { content: \" \\25B2\"; }
+th[data-order=desc]::after { content: \" \\25BC\"; }
+.number { text-align: right; white-space: nowrap; }
+</style>";
+
+fn write_table(out: &mut dyn Write, markup: &Markup) -> io::Result<()> {
+    writeln!(out, "<table>\n<thead>\n<tr>")?;
+    for header in &markup.headers {
+        writeln!(out, "<th>{}</th>", html_escape(header))?;
+    }
+    writeln!(out, "</tr>\n</thead>\n<tbody>")?;
+    for (row_index, row) in markup.rows.iter().enumerate() {
+        write!(out, "<tr>")?;
+        for (index, cell) in row.iter().enumerate() {
+            let class = if markup.right_aligned.contains(&index) { " class=\"number\"" } else { "" };
+            let mut text = html_escape(cell).replace('\n', "<br>");
+            if let Some(Some(link)) = markup.links.get(row_index).filter(|_| index == 0) {
+                text = format!("<a href=\"{}\">{}</a>", html_escape(link), text);
+            }
+            write!(out, "<td{}>{}</td>", class, text)?;
+        }
+        writeln!(out, "</tr>")?;
+    }
+    writeln!(out, "</tbody>\n</table>")
+}
+
+// A whole page, to open in a browser or attach to a mail
+pub(crate) fn write_html(out: &mut dyn Write, markup: &Markup) -> io::Result<()> {
+    write_html_page(out, markup.title, &[], std::slice::from_ref(markup))
+}
+
+// A page of several tables, each under its own heading, below a trail of links to the pages above it
+pub(crate) fn write_html_page(out: &mut dyn Write, title: &str, trail: &[(String, String)], tables: &[Markup]) -> io::Result<()> {
+    writeln!(out, "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n{}\n</head>\n<body>", html_escape(title), STYLE)?;
+    if !trail.is_empty() {
+        let links: Vec<String> = trail.iter()
+            .map(|(label, link)| format!("<a href=\"{}\">{}</a>", html_escape(link), html_escape(label)))
+            .collect();
+        writeln!(out, "<nav>{}</nav>", links.join(" / "))?;
+    }
+    writeln!(out, "<h1>{}</h1>", html_escape(title))?;
+    for markup in tables {
+        if tables.len() > 1 {
+            writeln!(out, "<h2>{}</h2>", html_escape(markup.title))?;
+        }
+        write_table(out, markup)?;
+    }
+    writeln!(out, "{}\n</body>\n</html>", SORT_SCRIPT)
+}
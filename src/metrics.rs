@@ -0,0 +1,104 @@
+// Gauges in the Prometheus text format, for node_exporter's textfile collector to pick up:
+//
+//   # HELP fatass_scanned_bytes Total size of the files scanned.
+//   # TYPE fatass_scanned_bytes gauge
+//   fatass_scanned_bytes 1073741824
+//   fatass_file_bytes{path="/srv/backup.tar"} 536870912
+//   fatass_extension_bytes{extension="log"} 268435456
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::FileEntry;
+
+// Sizes and counts summed up while a scan goes
+#[derive(Debug, Default)]
+pub struct Metrics {
+    // Files and bytes of each extension, "(none)" for files without one
+    extensions: BTreeMap<String, (u64, u64)>,
+}
+
+// What a scan found besides the extensions, gathered once it's done
+#[derive(Debug)]
+pub struct ScanSummary<'a> {
+    pub fattest: &'a [FileEntry],
+    pub files: usize,
+    pub bytes: u64,
+    pub duration: Duration,
+    pub unreadable: usize,
+    // The net size change since the previous scan, for the scans of fatass daemon
+    pub growth: Option<i128>,
+}
+
+// Backslashes, quotes and newlines are the only characters label values escape
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn write_header(out: &mut dyn Write, name: &str, help: &str) -> io::Result<()> {
+    writeln!(out, "# HELP {} {}", name, help)?;
+    writeln!(out, "# TYPE {} gauge", name)
+}
+
+impl Metrics {
+    pub fn add(&mut self, file_entry: &FileEntry) {
+        let extension = match file_entry.extension() {
+            extension if extension.is_empty() => String::from("(none)"),
+            extension => extension,
+        };
+        let totals = self.extensions.entry(extension).or_insert((0, 0));
+        totals.0 += 1;
+        totals.1 += file_entry.size;
+    }
+
+    pub fn write(&self, out: &mut dyn Write, summary: &ScanSummary) -> io::Result<()> {
+        write_header(out, "fatass_scanned_bytes", "Total size of the files scanned.")?;
+        writeln!(out, "fatass_scanned_bytes {}", summary.bytes)?;
+        write_header(out, "fatass_scanned_files", "Number of files scanned.")?;
+        writeln!(out, "fatass_scanned_files {}", summary.files)?;
+
+        write_header(out, "fatass_file_bytes", "Size of the fattest files, by path.")?;
+        for file_entry in summary.fattest {
+            writeln!(out, "fatass_file_bytes{{path=\"{}\"}} {}", escape_label(&file_entry.path), file_entry.size)?;
+        }
+
+        write_header(out, "fatass_extension_bytes", "Total size of the files scanned, by extension.")?;
+        for (extension, (_, bytes)) in &self.extensions {
+            writeln!(out, "fatass_extension_bytes{{extension=\"{}\"}} {}", escape_label(extension), bytes)?;
+        }
+        write_header(out, "fatass_extension_files", "Number of files scanned, by extension.")?;
+        for (extension, (files, _)) in &self.extensions {
+            writeln!(out, "fatass_extension_files{{extension=\"{}\"}} {}", escape_label(extension), files)?;
+        }
+
+        write_header(out, "fatass_unreadable_paths", "Number of paths the scan couldn't read.")?;
+        writeln!(out, "fatass_unreadable_paths {}", summary.unreadable)?;
+        if let Some(growth) = summary.growth {
+            write_header(out, "fatass_growth_bytes", "Net size change of the files since the previous scan.")?;
+            writeln!(out, "fatass_growth_bytes {}", growth)?;
+        }
+        write_header(out, "fatass_scan_duration_seconds", "How long the scan took.")?;
+        writeln!(out, "fatass_scan_duration_seconds {:.3}", summary.duration.as_secs_f64())
+    }
+
+    // The collector may read the file at any time, so it's written next to it then renamed over it.
+    // The temporary name doesn't end in .prom, the collector skips it
+    pub fn write_textfile(&self, path: &Path, summary: &ScanSummary) -> io::Result<()> {
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        let written = fs::File::create(&temporary).and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            self.write(&mut writer, summary)?;
+            writer.into_inner().map_err(|err| err.into_error())?.sync_all()
+        });
+        match written.and_then(|_| fs::rename(&temporary, path)) {
+            Ok(()) => Ok(()),
+            Err(err) => {
+                let _ = fs::remove_file(&temporary);
+                Err(err)
+            }
+        }
+    }
+}
@@ -0,0 +1,491 @@
+// Whole drive scans of NTFS volumes read from the master file table, for --fast on Windows. Every
+// file has a record there with its names, parent directory, times and sizes, so reading the table
+// in big sequential chunks replaces the stat call per file whole drive walks spend their time on.
+// Reading a volume directly takes an administrator
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Component, Path, Prefix};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::errors::ScanErrors;
+use crate::interrupt::interrupted;
+use crate::source::{FileSource, ListOptions};
+use crate::FileEntry;
+
+const MFT_RECORD: u64 = 0;
+const ROOT_RECORD: u64 = 5;
+// Attribute types
+const STANDARD_INFORMATION: u32 = 0x10;
+const FILE_NAME: u32 = 0x30;
+const DATA: u32 = 0x80;
+const END: u32 = 0xffff_ffff;
+// Record flags
+const IN_USE: u16 = 0x01;
+const DIRECTORY: u16 = 0x02;
+// Data attribute flags, their allocated size is then the compressed one
+const COMPRESSED: u16 = 0x0001;
+const SPARSE: u16 = 0x8000;
+// 8.3 names of files that have a long one too
+const DOS_NAMESPACE: u8 = 2;
+// The last two bytes of every 512 of a record are swapped with the update sequence to detect torn
+// writes, and have to be put back before reading it
+const FIXUP_STRIDE: usize = 512;
+// Record numbers take the low 48 bits of a reference, a sequence number the rest
+const REFERENCE_MASK: u64 = 0xffff_ffff_ffff;
+// Seconds from 1601, where FILETIMEs start, to 1970
+const FILETIME_EPOCH: u64 = 11_644_473_600;
+const CHUNK: u64 = 4 << 20;
+
+// The drive of a search path, all of it is read but only what's below the path is kept
+#[derive(Debug, Clone, PartialEq)]
+pub struct MftVolume {
+    pub drive: char,
+    // As in C:\Users
+    pub path: String,
+}
+
+impl MftVolume {
+    pub fn for_path(path: &Path) -> io::Result<MftVolume> {
+        let path = path.canonicalize()?;
+        let mut components = path.components();
+        let drive = match components.next() {
+            Some(Component::Prefix(prefix)) => match prefix.kind() {
+                Prefix::Disk(letter) | Prefix::VerbatimDisk(letter) => char::from(letter).to_ascii_uppercase(),
+                _ => return Err(io::Error::other("it isn't on a drive with a letter")),
+            },
+            _ => return Err(io::Error::other("it isn't on a Windows drive")),
+        };
+        let names: Vec<String> = components.filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+            _ => None,
+        }).collect();
+        Ok(MftVolume { drive, path: format!("{}:\\{}", drive, names.join("\\")) })
+    }
+}
+
+impl fmt::Display for MftVolume {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path)
+    }
+}
+
+impl FileSource for MftVolume {
+    fn path(&self) -> String {
+        self.path.clone()
+    }
+
+    fn how(&self) -> String {
+        format!("from the master file table of {}:", self.drive)
+    }
+
+    fn list(&self, options: &ListOptions, _: &ScanErrors, visit: &mut dyn FnMut(FileEntry, Option<(u64, u64)>)) -> io::Result<()> {
+        let mut volume = match File::open(format!("\\\\.\\{}:", self.drive)) {
+            Err(err) if err.kind() == io::ErrorKind::PermissionDenied => return Err(io::Error::other(format!("reading {}: directly takes an administrator", self.drive))),
+            opened => opened?,
+        };
+        let mut records: HashMap<u64, Record> = HashMap::new();
+        read_table(&mut volume, |number, record| match record.base {
+            0 => records.entry(number).or_default().merge(record),
+            base => records.entry(base).or_default().merge(record),
+        })?;
+
+        let mut dir_paths: HashMap<u64, Option<String>> = HashMap::new();
+        dir_paths.insert(ROOT_RECORD, Some(format!("{}:\\", self.drive)));
+        let below = Path::new(&self.path);
+        for (number, record) in &records {
+            let Some((parent, name)) = record.name.as_ref().filter(|_| !record.directory) else { continue };
+            let Some(dir) = dir_path(*parent, &records, &mut dir_paths) else { continue };
+            let path = join(&dir, name);
+            if !Path::new(&path).starts_with(below) {
+                continue;
+            }
+            let (size, allocated) = record.size.unwrap_or_default();
+            let mut file_entry = if options.disk_usage { FileEntry::new(path, allocated).with_apparent_size(size) } else { FileEntry::new(path, size) };
+            file_entry.modified = record.modified;
+            file_entry.accessed = record.accessed;
+            file_entry.links = Some(u64::from(record.links));
+            visit(file_entry, Some((u64::from(self.drive), *number)));
+        }
+        Ok(())
+    }
+}
+
+fn join(dir: &str, name: &str) -> String {
+    if dir.ends_with('\\') { format!("{}{}", dir, name) } else { format!("{}\\{}", dir, name) }
+}
+
+// The path of a directory record, none when its chain of parents is broken, e.g. by a directory
+// deleted while reading
+fn dir_path(number: u64, records: &HashMap<u64, Record>, dir_paths: &mut HashMap<u64, Option<String>>) -> Option<String> {
+    if let Some(path) = dir_paths.get(&number) {
+        return path.clone();
+    }
+    // The names up to the first directory whose path is known, the deepest first
+    let mut chain = Vec::new();
+    let mut current = number;
+    let known = loop {
+        if let Some(path) = dir_paths.get(&current) {
+            break path.clone();
+        }
+        match records.get(&current).and_then(|record| record.name.as_ref()) {
+            Some((parent, name)) if chain.len() < 4096 => {
+                chain.push((current, name.as_str()));
+                current = *parent;
+            }
+            _ => break None,
+        }
+    };
+    let mut path = known;
+    for (dir, name) in chain.into_iter().rev() {
+        path = path.map(|parent| join(&parent, name));
+        dir_paths.insert(dir, path.clone());
+    }
+    path
+}
+
+// What a record tells about a file or directory. Extension records hold more attributes of their
+// base record when they don't fit in it
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Record {
+    base: u64,
+    directory: bool,
+    links: u16,
+    // The parent directory record and the long name
+    name: Option<(u64, String)>,
+    // Length and allocated space of the unnamed data stream
+    size: Option<(u64, u64)>,
+    modified: Option<SystemTime>,
+    accessed: Option<SystemTime>,
+}
+
+impl Record {
+    fn merge(&mut self, other: Record) {
+        if other.base == 0 {
+            (self.directory, self.links) = (other.directory, other.links);
+        }
+        self.name = self.name.take().or(other.name);
+        self.size = self.size.or(other.size);
+        self.modified = self.modified.or(other.modified);
+        self.accessed = self.accessed.or(other.accessed);
+    }
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn u64_at(bytes: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(bytes.get(offset..offset + 8)?.try_into().ok()?))
+}
+
+fn filetime(ticks: u64) -> Option<SystemTime> {
+    UNIX_EPOCH.checked_add(Duration::from_secs((ticks / 10_000_000).checked_sub(FILETIME_EPOCH)?))
+}
+
+// Put back the bytes the update sequence took the place of
+fn apply_fixups(record: &mut [u8]) -> Option<()> {
+    let offset = usize::from(u16_at(record, 0x04)?);
+    let count = usize::from(u16_at(record, 0x06)?);
+    let check = u16_at(record, offset)?;
+    for sector in 1..count {
+        let end = sector * FIXUP_STRIDE;
+        if u16_at(record, end - 2)? != check {
+            return None;
+        }
+        let original = record.get(offset + 2 * sector..offset + 2 * sector + 2)?.to_vec();
+        record.get_mut(end - 2..end)?.copy_from_slice(&original);
+    }
+    Some(())
+}
+
+// The type and bytes of each attribute of a record
+fn attributes(record: &[u8]) -> Vec<(u32, &[u8])> {
+    let mut attributes = Vec::new();
+    let mut offset = u16_at(record, 0x14).map_or(record.len(), usize::from);
+    while let Some(kind) = u32_at(record, offset).filter(|kind| *kind != END) {
+        let Some(attribute) = u32_at(record, offset + 4).and_then(|length| record.get(offset..offset + length as usize)) else { break };
+        if attribute.len() < 0x18 {
+            break;
+        }
+        attributes.push((kind, attribute));
+        offset += attribute.len();
+    }
+    attributes
+}
+
+fn resident_value(attribute: &[u8]) -> Option<&[u8]> {
+    let length = u32_at(attribute, 0x10)? as usize;
+    let offset = usize::from(u16_at(attribute, 0x14)?);
+    attribute.get(offset..offset + length)
+}
+
+// The unnamed data stream, the content of the file
+fn is_unnamed_data(kind: u32, attribute: &[u8]) -> bool {
+    kind == DATA && attribute.get(9) == Some(&0)
+}
+
+// A record in use, with the fixups applied
+fn parse_record(record: &mut [u8]) -> Option<Record> {
+    if record.get(..4)? != b"FILE" {
+        return None;
+    }
+    let flags = u16_at(record, 0x16)?;
+    if flags & IN_USE == 0 {
+        return None;
+    }
+    apply_fixups(record)?;
+
+    let mut parsed = Record {
+        base: u64_at(record, 0x20)? & REFERENCE_MASK,
+        directory: flags & DIRECTORY != 0,
+        links: u16_at(record, 0x12)?,
+        ..Record::default()
+    };
+    let mut dos_name = false;
+    for (kind, attribute) in attributes(record) {
+        let non_resident = attribute[8] != 0;
+        match kind {
+            STANDARD_INFORMATION if !non_resident => {
+                let value = resident_value(attribute)?;
+                parsed.modified = filetime(u64_at(value, 0x08)?);
+                parsed.accessed = filetime(u64_at(value, 0x18)?);
+            }
+            // A long name wins over the 8.3 one, the first of hard links over the others
+            FILE_NAME if !non_resident && (parsed.name.is_none() || dos_name) => {
+                let value = resident_value(attribute)?;
+                let length = usize::from(*value.get(0x40)?);
+                let units: Vec<u16> = value.get(0x42..0x42 + 2 * length)?.chunks_exact(2).map(|unit| u16::from_le_bytes([unit[0], unit[1]])).collect();
+                dos_name = *value.get(0x41)? == DOS_NAMESPACE;
+                parsed.name = Some((u64_at(value, 0)? & REFERENCE_MASK, String::from_utf16_lossy(&units)));
+            }
+            // Only the first extent of a stream spread over extension records tells its sizes
+            _ if is_unnamed_data(kind, attribute) && !non_resident => {
+                parsed.size = Some((u64::from(u32_at(attribute, 0x10)?), 0));
+            }
+            _ if is_unnamed_data(kind, attribute) && u64_at(attribute, 0x10)? == 0 => {
+                let flags = u16_at(attribute, 0x0c)?;
+                let allocated = if flags & (COMPRESSED | SPARSE) != 0 { u64_at(attribute, 0x40)? } else { u64_at(attribute, 0x28)? };
+                parsed.size = Some((u64_at(attribute, 0x30)?, allocated));
+            }
+            _ => {}
+        }
+    }
+    Some(parsed)
+}
+
+// Little endian integers of one to eight bytes, as in data runs
+fn le_unsigned(bytes: &[u8]) -> u64 {
+    bytes.iter().rev().fold(0, |value, byte| value << 8 | u64::from(*byte))
+}
+
+// Sign extended from the highest bit of the last byte, offsets between runs may go backwards
+fn le_signed(bytes: &[u8]) -> i64 {
+    let shift = 64 - 8 * bytes.len().clamp(1, 8);
+    ((le_unsigned(bytes) << shift) as i64) >> shift
+}
+
+// The clusters of a non-resident attribute as (first cluster, count) runs, each header byte
+// telling how many bytes the count and the offset from the previous run take
+fn data_runs(attribute: &[u8]) -> Option<Vec<(u64, u64)>> {
+    let mut pos = usize::from(u16_at(attribute, 0x20)?);
+    let mut cluster: i64 = 0;
+    let mut runs = Vec::new();
+    loop {
+        let header = *attribute.get(pos)?;
+        if header == 0 {
+            return Some(runs);
+        }
+        let (count_size, offset_size) = (usize::from(header & 0x0f), usize::from(header >> 4));
+        let count = le_unsigned(attribute.get(pos + 1..pos + 1 + count_size)?);
+        let offset = attribute.get(pos + 1 + count_size..pos + 1 + count_size + offset_size)?;
+        pos += 1 + count_size + offset_size;
+        // Sparse runs take no clusters
+        if offset_size > 0 {
+            cluster += le_signed(offset);
+            runs.push((u64::try_from(cluster).ok()?, count));
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Geometry {
+    bytes_per_cluster: u64,
+    record_size: u64,
+    mft_cluster: u64,
+}
+
+fn parse_boot_sector(boot: &[u8]) -> Option<Geometry> {
+    if boot.get(3..11)? != b"NTFS    " {
+        return None;
+    }
+    let bytes_per_sector = u64::from(u16_at(boot, 0x0b)?);
+    // Past 128 sectors the count is a negative power of two
+    let sectors_per_cluster = match *boot.get(0x0d)? {
+        count if count > 0x80 => 1 << (256 - u32::from(count)),
+        count => u64::from(count),
+    };
+    let bytes_per_cluster = bytes_per_sector * sectors_per_cluster;
+    let record_size = match *boot.get(0x40)? as i8 {
+        clusters if clusters < 0 => 1 << -i32::from(clusters),
+        clusters => clusters as u64 * bytes_per_cluster,
+    };
+    (bytes_per_cluster > 0 && record_size >= 512).then_some(Geometry { bytes_per_cluster, record_size, mft_cluster: u64_at(boot, 0x30)? })
+}
+
+// Hand over every record in use of the table, with its number. Reads go by whole clusters since
+// volumes only read by sectors
+fn read_table<R: Read + Seek>(volume: &mut R, mut visit: impl FnMut(u64, Record)) -> io::Result<()> {
+    let not_ntfs = || io::Error::other("it isn't an NTFS volume");
+    let mut boot = vec![0; 4096];
+    volume.read_exact(&mut boot)?;
+    let geometry = parse_boot_sector(&boot).ok_or_else(not_ntfs)?;
+    let record_size = geometry.record_size as usize;
+
+    // The table is a file too, its first record tells where its clusters are
+    let mut first = vec![0; geometry.record_size.div_ceil(geometry.bytes_per_cluster) as usize * geometry.bytes_per_cluster as usize];
+    volume.seek(SeekFrom::Start(geometry.mft_cluster * geometry.bytes_per_cluster))?;
+    volume.read_exact(&mut first)?;
+    let first = &mut first[..record_size];
+    parse_record(first).filter(|record| record.base == MFT_RECORD).ok_or_else(not_ntfs)?;
+    let (runs, table_size) = attributes(first).into_iter()
+        .find(|(kind, attribute)| is_unnamed_data(*kind, attribute) && attribute[8] != 0)
+        .and_then(|(_, attribute)| Some((data_runs(attribute)?, u64_at(attribute, 0x30)?)))
+        .ok_or_else(not_ntfs)?;
+
+    let total = table_size / geometry.record_size;
+    let (mut number, mut buffer, mut chunk) = (0, Vec::new(), Vec::new());
+    for (cluster, count) in runs {
+        let (mut offset, mut remaining) = (cluster * geometry.bytes_per_cluster, count * geometry.bytes_per_cluster);
+        while remaining > 0 && number < total {
+            if interrupted() {
+                return Ok(());
+            }
+            let length = remaining.min(CHUNK);
+            chunk.resize(length as usize, 0);
+            volume.seek(SeekFrom::Start(offset))?;
+            volume.read_exact(&mut chunk)?;
+            (offset, remaining) = (offset + length, remaining - length);
+
+            // A record may straddle two runs when clusters are smaller than records
+            buffer.extend_from_slice(&chunk);
+            let mut start = 0;
+            while buffer.len() - start >= record_size && number < total {
+                if let Some(record) = parse_record(&mut buffer[start..start + record_size]) {
+                    visit(number, record);
+                }
+                (number, start) = (number + 1, start + record_size);
+            }
+            buffer.drain(..start);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn attribute(kind: u32, non_resident: bool, header: usize, value: &[u8]) -> Vec<u8> {
+        let length = (header + value.len()).div_ceil(8) * 8;
+        let mut attribute = vec![0; length];
+        attribute[..4].copy_from_slice(&kind.to_le_bytes());
+        attribute[4..8].copy_from_slice(&(length as u32).to_le_bytes());
+        attribute[8] = u8::from(non_resident);
+        attribute[header..header + value.len()].copy_from_slice(value);
+        attribute
+    }
+
+    fn resident(kind: u32, value: &[u8]) -> Vec<u8> {
+        let mut attribute = attribute(kind, false, 0x18, value);
+        attribute[0x10..0x14].copy_from_slice(&(value.len() as u32).to_le_bytes());
+        attribute[0x14..0x16].copy_from_slice(&0x18u16.to_le_bytes());
+        attribute
+    }
+
+    fn file_name(parent: u64, name: &str, namespace: u8) -> Vec<u8> {
+        let mut value = vec![0; 0x42];
+        value[..8].copy_from_slice(&(parent | 3 << 48).to_le_bytes());
+        value[0x40] = name.encode_utf16().count() as u8;
+        value[0x41] = namespace;
+        value.extend(name.encode_utf16().flat_map(u16::to_le_bytes));
+        resident(FILE_NAME, &value)
+    }
+
+    fn data(size: u64, allocated: u64, runs: &[u8]) -> Vec<u8> {
+        let mut attribute = attribute(DATA, true, 0x40, runs);
+        attribute[0x20..0x22].copy_from_slice(&0x40u16.to_le_bytes());
+        attribute[0x28..0x30].copy_from_slice(&allocated.to_le_bytes());
+        attribute[0x30..0x38].copy_from_slice(&size.to_le_bytes());
+        attribute
+    }
+
+    // A record as on disk, the end of each 512 bytes swapped with the update sequence
+    fn record(flags: u16, attributes: &[Vec<u8>]) -> Vec<u8> {
+        let mut record = vec![0; 1024];
+        record[..4].copy_from_slice(b"FILE");
+        record[0x04..0x06].copy_from_slice(&0x30u16.to_le_bytes());
+        record[0x06..0x08].copy_from_slice(&3u16.to_le_bytes());
+        record[0x12..0x14].copy_from_slice(&1u16.to_le_bytes());
+        record[0x14..0x16].copy_from_slice(&0x38u16.to_le_bytes());
+        record[0x16..0x18].copy_from_slice(&flags.to_le_bytes());
+        let mut offset = 0x38;
+        for attribute in attributes {
+            record[offset..offset + attribute.len()].copy_from_slice(attribute);
+            offset += attribute.len();
+        }
+        record[offset..offset + 4].copy_from_slice(&END.to_le_bytes());
+        record[1020..1022].copy_from_slice(b"ok");
+        record[0x30..0x32].copy_from_slice(&[0xcd, 0xab]);
+        for sector in 1..3 {
+            let end = sector * 512;
+            let original = [record[end - 2], record[end - 1]];
+            record[0x30 + 2 * sector..0x32 + 2 * sector].copy_from_slice(&original);
+            record[end - 2..end].copy_from_slice(&[0xcd, 0xab]);
+        }
+        record
+    }
+
+    #[test]
+    fn reads_the_master_file_table() {
+        // 4K clusters, 1K records, the table in clusters 4 and 5
+        let mut volume = vec![0; 6 * 4096];
+        volume[3..11].copy_from_slice(b"NTFS    ");
+        volume[0x0b..0x0d].copy_from_slice(&512u16.to_le_bytes());
+        volume[0x0d] = 8;
+        volume[0x30..0x38].copy_from_slice(&4u64.to_le_bytes());
+        volume[0x40] = 0xf6;
+        let records = [
+            (0, record(IN_USE, &[file_name(5, "$MFT", 3), data(8192, 8192, &[0x11, 2, 4, 0])])),
+            (5, record(IN_USE | DIRECTORY, &[file_name(5, ".", 3)])),
+            (6, record(IN_USE | DIRECTORY, &[file_name(5, "Users", 3)])),
+            (7, record(IN_USE, &[file_name(6, "BIGFIL~1.ISO", DOS_NAMESPACE), file_name(6, "big file.iso", 1), data(5000, 8192, &[0x21, 2, 0x10, 0xff, 0])])),
+            (3, record(0, &[file_name(5, "deleted", 1)])),
+        ];
+        for (number, record) in &records {
+            let offset = 4 * 4096 + number * 1024;
+            volume[offset..offset + 1024].copy_from_slice(record);
+        }
+
+        let mut read = HashMap::new();
+        read_table(&mut Cursor::new(volume), |number, record| {
+            read.insert(number, record);
+        }).unwrap();
+        assert_eq!(read.keys().copied().collect::<std::collections::BTreeSet<u64>>(), [0, 5, 6, 7].into());
+        let file = &read[&7];
+        assert_eq!(file.name, Some((6, String::from("big file.iso"))));
+        assert_eq!((file.size, file.directory, file.links), (Some((5000, 8192)), false, 1));
+
+        let mut dir_paths = HashMap::from([(ROOT_RECORD, Some(String::from("C:\\")))]);
+        assert_eq!(dir_path(6, &read, &mut dir_paths), Some(String::from("C:\\Users")));
+        assert_eq!(dir_path(9, &read, &mut dir_paths), None);
+        assert_eq!(le_signed(&[0x10, 0xff]), -240);
+        assert!(read_table(&mut Cursor::new(vec![0; 8192]), |_, _| {}).is_err());
+    }
+}
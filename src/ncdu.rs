@@ -0,0 +1,133 @@
+// The scanned tree in the JSON export format of ncdu, to browse it with `ncdu -f`. Directories are
+// arrays holding their own info first, then their files and subdirectories:
+//
+//   [1, 0, {"progname": "fatass", "progver": "0.1.0", "timestamp": 1700000000},
+//   [{"name": "/home/me"},
+//   {"name": "video.mkv", "asize": 1048576, "dsize": 1052672},
+//   [{"name": "src"}, ...]]]
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Component, Path};
+use std::time::SystemTime;
+
+use crate::hardlinks::{file_id, link_count, SeenLinks};
+use crate::output::json_escape;
+use crate::scanner::{measure_file, spinner, walk};
+use crate::size::allocated_size;
+use crate::time::unix_seconds;
+use crate::Scanner;
+
+#[derive(Debug, Clone)]
+pub struct ExportFile {
+    pub name: String,
+    pub apparent_size: u64,
+    pub disk_size: u64,
+    // Inode and link count of files reached through several hard links
+    pub link: Option<(u64, u64)>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ExportDir {
+    pub name: String,
+    pub files: Vec<ExportFile>,
+    pub dirs: BTreeMap<String, ExportDir>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Export {
+    pub root: ExportDir,
+    pub files: u64,
+    pub created: SystemTime,
+}
+
+impl ExportDir {
+    // The directory at a path relative to this one, created along the way
+    fn subdir(&mut self, relative: &Path) -> &mut ExportDir {
+        let mut dir = self;
+        for component in relative.components() {
+            let Component::Normal(name) = component else { continue };
+            let name = name.to_string_lossy().to_string();
+            dir = dir.dirs.entry(name.clone()).or_insert_with(|| ExportDir { name, ..ExportDir::default() });
+        }
+        dir
+    }
+}
+
+// Every file kept by the filters below the single search path, with the directories leading to them
+pub fn export_tree(scanner: &Scanner) -> Result<Export, String> {
+    let roots = scanner.roots();
+    let [search_path] = roots.as_slice() else {
+        return Err(String::from("an ncdu export holds a single tree, give it one search path"));
+    };
+    let root = Path::new(search_path);
+    if !root.is_dir() {
+        return Err(format!("{} is not a directory", search_path));
+    }
+
+    let filters = &scanner.filters;
+    let progress_bar = spinner(scanner.progress);
+    let seen_links = SeenLinks::new(scanner.hardlinks);
+    let root_name = fs::canonicalize(root).map(|path| path.display().to_string()).unwrap_or_else(|_| search_path.clone());
+    let mut export = Export {
+        root: ExportDir { name: root_name, ..ExportDir::default() },
+        files: 0,
+        created: SystemTime::now(),
+    };
+    for entry in walk(search_path, filters, &scanner.errors) {
+        let Ok(relative) = entry.path().strip_prefix(root) else { continue };
+        if entry.file_type().is_dir() {
+            export.root.subdir(relative);
+            continue;
+        }
+        progress_bar.inc(1);
+        if !filters.keeps_file(root, entry.path()) {
+            continue;
+        }
+        let Some(metadata) = scanner.errors.walk_metadata(&entry).filter(|metadata| seen_links.first_visit(metadata)) else { continue };
+        if !filters.keeps_entry(&measure_file(entry.path(), &metadata, scanner.disk_usage)) {
+            continue;
+        }
+
+        let link = match (file_id(&metadata), link_count(&metadata)) {
+            (Some((_, inode)), Some(links)) if links > 1 => Some((inode, links)),
+            _ => None,
+        };
+        let parent = relative.parent().unwrap_or(Path::new(""));
+        export.root.subdir(parent).files.push(ExportFile {
+            name: entry.file_name().to_string_lossy().to_string(),
+            apparent_size: metadata.len(),
+            disk_size: allocated_size(entry.path(), &metadata),
+            link,
+        });
+        export.files += 1;
+    }
+    progress_bar.finish();
+    Ok(export)
+}
+
+fn write_dir(out: &mut dyn Write, dir: &ExportDir) -> io::Result<()> {
+    write!(out, ",\n[{{\"name\": {}}}", json_escape(&dir.name))?;
+    for file in &dir.files {
+        write!(out, ",\n{{\"name\": {}, \"asize\": {}, \"dsize\": {}", json_escape(&file.name), file.apparent_size, file.disk_size)?;
+        if let Some((inode, links)) = file.link {
+            write!(out, ", \"ino\": {}, \"hlnkc\": true, \"nlink\": {}", inode, links)?;
+        }
+        write!(out, "}}")?;
+    }
+    for subdir in dir.dirs.values() {
+        write_dir(out, subdir)?;
+    }
+    write!(out, "]")
+}
+
+pub fn write_export(out: &mut dyn Write, export: &Export) -> io::Result<()> {
+    write!(
+        out,
+        "[1, 0, {{\"progname\": \"fatass\", \"progver\": \"{}\", \"timestamp\": {}}}",
+        env!("CARGO_PKG_VERSION"),
+        unix_seconds(export.created)
+    )?;
+    write_dir(out, &export.root)?;
+    writeln!(out, "]")
+}
@@ -0,0 +1,86 @@
+// A summary posted to a webhook when a scan goes over a limit. curl does the posting, HTTPS works
+// without a TLS stack of our own. The "text" field is what Slack shows, other receivers get the
+// details too:
+//
+//   {"text": "fatass on web-01: The fattest file, /srv/dump.sql, takes 12 GB, over the 10 GB limit",
+//   "host": "web-01", "paths": ["/srv"], "breaches": ["The fattest file, ..."], "total_bytes": 12884901888,
+//   "files": 4096, "top": [{"path": "/srv/dump.sql", "kind": "file", "size": 12884901888, ...}]}
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+use crate::output::{entry_object, json_escape};
+use crate::FileEntry;
+
+#[derive(Debug)]
+pub struct Notification<'a> {
+    // What went over which limit, one sentence each
+    pub breaches: &'a [String],
+    pub paths: &'a [String],
+    pub total_bytes: u64,
+    pub files: usize,
+    // The fattest entries, biggest first
+    pub top: &'a [FileEntry],
+}
+
+impl Notification<'_> {
+    pub fn to_json(&self) -> String {
+        let host = hostname();
+        let text = format!("fatass on {}: {}", host, self.breaches.join(", "));
+        let paths: Vec<String> = self.paths.iter().map(|path| json_escape(path)).collect();
+        let breaches: Vec<String> = self.breaches.iter().map(|breach| json_escape(breach)).collect();
+        let top: Vec<String> = self.top.iter().map(|file_entry| entry_object(file_entry, "")).collect();
+        format!(
+            "{{\"text\": {}, \"host\": {}, \"paths\": [{}], \"breaches\": [{}], \"total_bytes\": {}, \"files\": {}, \"top\": [{}]}}",
+            json_escape(&text),
+            json_escape(&host),
+            paths.join(", "),
+            breaches.join(", "),
+            self.total_bytes,
+            self.files,
+            top.join(", ")
+        )
+    }
+}
+
+#[cfg(unix)]
+pub fn hostname() -> String {
+    let mut buffer = [0u8; 256];
+    // SAFETY: the buffer outlives the call and its length is given, the name may lack its NUL when truncated
+    let result = unsafe { libc::gethostname(buffer.as_mut_ptr() as *mut libc::c_char, buffer.len()) };
+    if result != 0 {
+        return String::from("unknown");
+    }
+    let len = buffer.iter().position(|&byte| byte == 0).unwrap_or(buffer.len());
+    String::from_utf8_lossy(&buffer[..len]).to_string()
+}
+
+#[cfg(not(unix))]
+pub fn hostname() -> String {
+    std::env::var("COMPUTERNAME").unwrap_or_else(|_| String::from("unknown"))
+}
+
+// POST the body as JSON, a response outside of 2xx is an error
+pub fn post(url: &str, body: &str) -> io::Result<()> {
+    let mut child = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--max-time", "30"])
+        .args(["--header", "Content-Type: application/json", "--data-binary", "@-", "--url"])
+        .arg(url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| match err.kind() {
+            io::ErrorKind::NotFound => io::Error::new(io::ErrorKind::NotFound, "curl is needed to post to webhooks and wasn't found"),
+            _ => err,
+        })?;
+    // Dropping stdin once written ends the body
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(body.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(String::from_utf8_lossy(&output.stderr).trim().to_string()))
+    }
+}
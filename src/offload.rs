@@ -0,0 +1,106 @@
+// Moving the results to another volume for fatass offload. Each file is copied under the
+// destination at the same path relative to the search path it was found under, the copy checked
+// against the original before the original goes, so a bad disk or a full archive never loses data
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::checksum::{hash_file, HashAlgorithm};
+use crate::FileEntry;
+
+// What an offload did, failures are kept to be summarized once it's over
+#[derive(Debug, Default)]
+pub struct OffloadReport {
+    pub moved: usize,
+    pub bytes: u64,
+    pub failures: Vec<(String, io::Error)>,
+}
+
+impl OffloadReport {
+    pub fn record(&mut self, entry: &FileEntry, result: io::Result<()>) {
+        match result {
+            Ok(()) => {
+                self.moved += 1;
+                self.bytes += entry.size;
+            }
+            Err(err) => self.failures.push((entry.path.clone(), err)),
+        }
+    }
+}
+
+// Where the file goes under the destination: its path below the deepest search path holding it,
+// its name alone when it was a search path itself
+pub fn destination_of(path: &Path, roots: &[String], destination: &Path) -> PathBuf {
+    let relative = roots.iter()
+        .filter_map(|root| path.strip_prefix(root).ok())
+        .filter(|relative| !relative.as_os_str().is_empty())
+        .min_by_key(|relative| relative.components().count());
+    match relative {
+        Some(relative) => destination.join(relative),
+        None => destination.join(path.file_name().unwrap_or(path.as_os_str())),
+    }
+}
+
+// Copy the file to the target, check the copy, then remove the original and leave a symlink to
+// the copy in its place when asked. A copy that doesn't match is removed again
+pub fn offload_file(path: &Path, target: &Path, leave_symlink: bool) -> io::Result<()> {
+    if target.symlink_metadata().is_ok() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", target.display())));
+    }
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::copy(path, target)?;
+    let verified = verify_copy(path, target);
+    if verified.is_err() {
+        let _ = fs::remove_file(target);
+        return verified;
+    }
+    // A relative target would point below the directory of the link
+    let absolute = fs::canonicalize(target)?;
+    fs::remove_file(path)?;
+    if leave_symlink {
+        symlink(&absolute, path)?;
+    }
+    Ok(())
+}
+
+// The copy holds the same bytes and keeps the time the original was last written
+pub(crate) fn verify_copy(path: &Path, target: &Path) -> io::Result<()> {
+    if hash_file(path, HashAlgorithm::Blake3)? != hash_file(target, HashAlgorithm::Blake3)? {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "the copy doesn't match the original"));
+    }
+    let modified = fs::metadata(path)?.modified()?;
+    fs::File::options().write(true).open(target)?.set_modified(modified)
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+// Needs Developer Mode or the symlink privilege
+#[cfg(windows)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::windows::fs::symlink_file(target, link)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn symlink(_target: &Path, _link: &Path) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "symlinks aren't supported here"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_paths_relative_to_the_search_paths() {
+        let roots = [String::from("/srv"), String::from("/srv/media"), String::from("./logs"), String::from("/data/big.iso")];
+        let archive = Path::new("/mnt/archive");
+        assert_eq!(destination_of(Path::new("/srv/media/2019/a.mov"), &roots, archive), Path::new("/mnt/archive/2019/a.mov"));
+        assert_eq!(destination_of(Path::new("/srv/db/dump.sql"), &roots, archive), Path::new("/mnt/archive/db/dump.sql"));
+        assert_eq!(destination_of(Path::new("./logs/app/old.log"), &roots, archive), Path::new("/mnt/archive/app/old.log"));
+        assert_eq!(destination_of(Path::new("/data/big.iso"), &roots, archive), Path::new("/mnt/archive/big.iso"));
+    }
+}
@@ -0,0 +1,1266 @@
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::time::SystemTime;
+use tabled::{
+    builder::Builder,
+    settings::{
+        formatting::AlignmentStrategy,
+        object::{Columns, Rows}, Alignment, Style,
+        style::BorderColor,
+        themes::Colorization, Color
+    },
+    Table,
+};
+
+use crate::archive::ArchivePeek;
+use crate::compressible::Compressible;
+use crate::docker::StorageUsage;
+use crate::dupes::DuplicateGroup;
+use crate::errors::ScanError;
+use crate::groups::{Group, GroupKey};
+use crate::inodes::DirCount;
+use crate::markup::{write_html, write_markdown, Markup};
+use crate::owners::{format_mode, Names};
+use crate::paths::{shorten_middle, PathShower, PathStyle};
+use crate::size::{format_size, parse_size};
+use crate::snapshot::Change;
+use crate::estimate::{Estimate, Interval};
+use crate::stats::SizeStats;
+use crate::template::Template;
+use crate::time::format_time;
+use crate::volumes::Volume;
+use crate::FileEntry;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    // One JSON object per line, for log pipelines
+    Ndjson,
+    Csv,
+    // Tab separated lines without headers, for scripts
+    Plain,
+    Markdown,
+    // A standalone page with sortable columns
+    Html,
+    // The whole scanned tree for ncdu, written by ncdu::write_export, the other reports fall back to JSON
+    Ncdu,
+    // An SVG picture of the directory sizes, written by treemap::write_treemap
+    Treemap,
+}
+
+impl OutputFormat {
+    pub fn parse(value: &str) -> Option<OutputFormat> {
+        match value {
+            "table" => Some(OutputFormat::Table),
+            "json" => Some(OutputFormat::Json),
+            "ndjson" => Some(OutputFormat::Ndjson),
+            "csv" => Some(OutputFormat::Csv),
+            "plain" => Some(OutputFormat::Plain),
+            "markdown" => Some(OutputFormat::Markdown),
+            "html" => Some(OutputFormat::Html),
+            "ncdu" => Some(OutputFormat::Ncdu),
+            "treemap" => Some(OutputFormat::Treemap),
+            _ => None,
+        }
+    }
+
+    // Machine readable formats keep stdout for the results only
+    pub fn status_to_stderr(&self) -> bool {
+        *self != OutputFormat::Table
+    }
+}
+
+// Whether to color anything, auto leaves it to NO_COLOR, CLICOLOR and whether stdout is a terminal
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ColorChoice {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn parse(value: &str) -> Option<ColorChoice> {
+        match value {
+            "auto" => Some(ColorChoice::Auto),
+            "always" => Some(ColorChoice::Always),
+            "never" => Some(ColorChoice::Never),
+            _ => None,
+        }
+    }
+}
+
+// Table colors, mono turns them off for terminals or logs that don't render them well
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Theme {
+    #[default]
+    Default,
+    // Darker colors readable on a light background
+    Light,
+    Mono,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ColumnRole {
+    Path,
+    Size,
+    Other,
+}
+
+struct Palette {
+    border: Color,
+    path: Color,
+    size: Color,
+    other: Color,
+    // Rows worth a look, like the new entries of a watch
+    highlight: Color,
+}
+
+impl Palette {
+    fn color(&self, role: ColumnRole) -> Color {
+        match role {
+            ColumnRole::Path => self.path.clone(),
+            ColumnRole::Size => self.size.clone(),
+            ColumnRole::Other => self.other.clone(),
+        }
+    }
+}
+
+// The colors rows can take after their size
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RowColor {
+    Red,
+    Yellow,
+    Green,
+    Blue,
+    Magenta,
+    Cyan,
+}
+
+pub const ROW_COLORS: [&str; 6] = ["red", "yellow", "green", "blue", "magenta", "cyan"];
+
+impl RowColor {
+    pub fn parse(value: &str) -> Option<RowColor> {
+        match value {
+            "red" => Some(RowColor::Red),
+            "yellow" => Some(RowColor::Yellow),
+            "green" => Some(RowColor::Green),
+            "blue" => Some(RowColor::Blue),
+            "magenta" => Some(RowColor::Magenta),
+            "cyan" => Some(RowColor::Cyan),
+            _ => None,
+        }
+    }
+
+    // Bright on the default theme, plain on the light one where bright colors fade out
+    fn color(&self, bright: bool) -> Color {
+        match (self, bright) {
+            (RowColor::Red, true) => Color::FG_BRIGHT_RED,
+            (RowColor::Red, false) => Color::FG_RED,
+            (RowColor::Yellow, true) => Color::FG_BRIGHT_YELLOW,
+            (RowColor::Yellow, false) => Color::FG_YELLOW,
+            (RowColor::Green, true) => Color::FG_BRIGHT_GREEN,
+            (RowColor::Green, false) => Color::FG_GREEN,
+            (RowColor::Blue, true) => Color::FG_BRIGHT_BLUE,
+            (RowColor::Blue, false) => Color::FG_BLUE,
+            (RowColor::Magenta, true) => Color::FG_BRIGHT_MAGENTA,
+            (RowColor::Magenta, false) => Color::FG_MAGENTA,
+            (RowColor::Cyan, true) => Color::FG_BRIGHT_CYAN,
+            (RowColor::Cyan, false) => Color::FG_CYAN,
+        }
+    }
+}
+
+// Rows of this size or more take the color, over the colors of the theme
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorThreshold {
+    pub min_size: u64,
+    pub color: RowColor,
+}
+
+// "10G=red,1G=yellow", biggest threshold first so a row takes the first one it reaches
+pub fn parse_color_thresholds(value: &str) -> Result<Vec<ColorThreshold>, String> {
+    let mut thresholds = Vec::new();
+    for item in value.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+        let (size, color) = item.split_once('=').ok_or_else(|| format!("'{}' should be SIZE=COLOR", item))?;
+        let min_size = parse_size(size).ok_or_else(|| format!("invalid size '{}'", size.trim()))?;
+        let color = RowColor::parse(color.trim())
+            .ok_or_else(|| format!("unknown color '{}', expected one of: {}", color.trim(), ROW_COLORS.join(", ")))?;
+        thresholds.push(ColorThreshold { min_size, color });
+    }
+    thresholds.sort_by_key(|threshold| Reverse(threshold.min_size));
+    Ok(thresholds)
+}
+
+impl Theme {
+    pub fn parse(value: &str) -> Option<Theme> {
+        match value {
+            "default" => Some(Theme::Default),
+            "light" => Some(Theme::Light),
+            "mono" => Some(Theme::Mono),
+            _ => None,
+        }
+    }
+
+    fn palette(&self) -> Option<Palette> {
+        match self {
+            Theme::Default => Some(Palette {
+                border: Color::FG_GREEN,
+                path: Color::FG_CYAN,
+                size: Color::FG_BRIGHT_RED,
+                other: Color::FG_YELLOW,
+                highlight: Color::BOLD | Color::FG_BRIGHT_GREEN,
+            }),
+            Theme::Light => Some(Palette {
+                border: Color::FG_BLUE,
+                path: Color::FG_BLACK,
+                size: Color::FG_RED,
+                other: Color::FG_MAGENTA,
+                highlight: Color::BOLD | Color::FG_GREEN,
+            }),
+            Theme::Mono => None,
+        }
+    }
+}
+
+// The columns of the results and how tables look
+#[derive(Debug, Clone, Default)]
+pub struct OutputOptions {
+    pub theme: Theme,
+    // In the order they are shown, path and size first unless --columns places them
+    pub columns: Vec<Column>,
+    // End plain results with a NUL instead of a line break and leave the size out, like find -print0
+    pub print0: bool,
+    // Write plain results after a template instead, like find -printf
+    pub template: Option<Template>,
+    // The total the percent columns are shares of, none to leave them empty
+    pub percent_of: Option<u64>,
+    // Row colors by size, the theme colors the rows below every threshold
+    pub color_thresholds: Vec<ColorThreshold>,
+    pub path_style: PathStyle,
+    // Show the home directory as ~ in the reports read by people
+    pub tilde: bool,
+    // The width of the terminal, tables wider than it get their paths cut in the middle
+    pub table_width: Option<usize>,
+    // Whether the scan behind the results was cut short and what it couldn't read, held by the JSON
+    // document along with the results, none for results complete by nature like snapshot diffs
+    pub completeness: Option<Completeness>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Completeness {
+    pub partial: bool,
+    pub errors: Vec<ScanError>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Column {
+    Path,
+    Size,
+    // The share of the scanned total, and the running sum of the shares, only meaningful when
+    // entries don't contain each other
+    Percent,
+    CumulativePercent,
+    ApparentSize,
+    Links,
+    Mode,
+    Owner,
+    Group,
+    Modified,
+    Accessed,
+    // Sniffed from the first bytes of the files
+    ContentType,
+    // Checksum of the content with the --hash algorithm
+    Checksum,
+    // The extended attributes worth showing, user.* tags mostly
+    Xattrs,
+    // A bar as long as the entry's share of the largest one shown, left out of structured outputs
+    Bar,
+}
+
+impl Column {
+    pub fn parse(value: &str) -> Option<Column> {
+        match value {
+            "path" => Some(Column::Path),
+            "size" => Some(Column::Size),
+            "percent" => Some(Column::Percent),
+            "cumulative" => Some(Column::CumulativePercent),
+            "apparent" => Some(Column::ApparentSize),
+            "links" => Some(Column::Links),
+            "mode" => Some(Column::Mode),
+            "owner" => Some(Column::Owner),
+            "group" => Some(Column::Group),
+            "mtime" => Some(Column::Modified),
+            "atime" => Some(Column::Accessed),
+            "type" => Some(Column::ContentType),
+            "checksum" => Some(Column::Checksum),
+            "xattrs" => Some(Column::Xattrs),
+            "bar" => Some(Column::Bar),
+            _ => None,
+        }
+    }
+
+    // The field name in JSON and CSV
+    fn key(&self) -> &'static str {
+        match self {
+            Column::Path => "path",
+            Column::Size => "size",
+            Column::Percent => "percent",
+            Column::CumulativePercent => "cumulative_percent",
+            Column::ApparentSize => "apparent_size",
+            Column::Links => "links",
+            Column::Mode => "mode",
+            Column::Owner => "owner",
+            Column::Group => "group",
+            Column::Modified => "modified",
+            Column::Accessed => "accessed",
+            Column::ContentType => "content_type",
+            Column::Checksum => "checksum",
+            Column::Xattrs => "xattrs",
+            Column::Bar => "bar",
+        }
+    }
+
+    // "apparent_size" becomes "Apparent size"
+    fn header(&self) -> String {
+        let key = self.key();
+        key[..1].to_uppercase() + &key[1..].replace('_', " ")
+    }
+
+    fn role(&self) -> ColumnRole {
+        match self {
+            Column::Path => ColumnRole::Path,
+            Column::Size | Column::ApparentSize | Column::Bar => ColumnRole::Size,
+            _ => ColumnRole::Other,
+        }
+    }
+
+    fn is_number(&self) -> bool {
+        matches!(self, Column::Size | Column::Percent | Column::CumulativePercent | Column::ApparentSize | Column::Links)
+    }
+}
+
+// The value of an optional column, missing when the entry doesn't have it
+enum ColumnValue {
+    Bytes(Option<u64>),
+    Count(Option<u64>),
+    Time(Option<SystemTime>),
+    Percent(Option<f64>),
+    Text(Option<String>),
+}
+
+impl ColumnValue {
+    fn to_table(&self) -> String {
+        match self {
+            ColumnValue::Bytes(Some(bytes)) => format_size(*bytes),
+            ColumnValue::Count(Some(count)) => count.to_string(),
+            ColumnValue::Time(Some(time)) => format_time(*time),
+            ColumnValue::Percent(Some(percent)) => format!("{:.1}%", percent),
+            ColumnValue::Text(Some(text)) => text.clone(),
+            _ => String::from("-"),
+        }
+    }
+
+    fn to_json(&self) -> String {
+        match self {
+            ColumnValue::Bytes(Some(number)) | ColumnValue::Count(Some(number)) => number.to_string(),
+            ColumnValue::Time(Some(time)) => json_escape(&format_time(*time)),
+            ColumnValue::Percent(Some(percent)) => format!("{:.2}", percent),
+            ColumnValue::Text(Some(text)) => json_escape(text),
+            _ => String::from("null"),
+        }
+    }
+
+    fn to_csv(&self) -> String {
+        match self {
+            ColumnValue::Bytes(Some(number)) | ColumnValue::Count(Some(number)) => number.to_string(),
+            ColumnValue::Time(Some(time)) => format_time(*time),
+            ColumnValue::Percent(Some(percent)) => format!("{:.2}", percent),
+            ColumnValue::Text(Some(text)) => csv_escape(text),
+            _ => String::new(),
+        }
+    }
+}
+
+impl OutputOptions {
+    // The value of a column for an entry, cumulative is the size of the entries shown so far, this
+    // one included, largest the size of the biggest one
+    fn value(&self, column: Column, file_entry: &FileEntry, cumulative: u64, largest: u64, names: &mut Names) -> ColumnValue {
+        let percent = |size: u64| self.percent_of.filter(|total| *total > 0).map(|total| size as f64 * 100.0 / total as f64);
+        match column {
+            Column::Path => ColumnValue::Text(Some(file_entry.path.clone())),
+            Column::Size => ColumnValue::Bytes(Some(file_entry.size)),
+            Column::Percent => ColumnValue::Percent(percent(file_entry.size)),
+            Column::CumulativePercent => ColumnValue::Percent(percent(cumulative)),
+            Column::ApparentSize => ColumnValue::Bytes(file_entry.apparent_size),
+            Column::Links => ColumnValue::Count(file_entry.links),
+            Column::Mode => ColumnValue::Text(file_entry.mode.map(format_mode)),
+            Column::Owner => ColumnValue::Text(file_entry.uid.map(|uid| names.user(uid))),
+            Column::Group => ColumnValue::Text(file_entry.gid.map(|gid| names.group(gid))),
+            Column::Modified => ColumnValue::Time(file_entry.modified),
+            Column::Accessed => ColumnValue::Time(file_entry.accessed),
+            Column::ContentType => ColumnValue::Text(file_entry.content_type.map(String::from)),
+            Column::Checksum => ColumnValue::Text(file_entry.checksum.clone()),
+            Column::Xattrs => ColumnValue::Text(file_entry.xattrs.clone()),
+            Column::Bar => ColumnValue::Text(Some(size_bar(file_entry.size, largest))),
+        }
+    }
+
+    fn position(&self, column: Column) -> Option<usize> {
+        self.columns.iter().position(|shown| *shown == column)
+    }
+}
+
+pub fn json_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+// An array with an object per line, or the bare lines for NDJSON
+// One object per line for NDJSON, the results of a document for JSON, every report alike
+fn write_objects(out: &mut dyn Write, objects: &[String], options: &OutputOptions, ndjson: bool) -> io::Result<()> {
+    if ndjson {
+        return objects.iter().try_for_each(|object| writeln!(out, "{}", object));
+    }
+    write_document(out, objects, options.completeness.as_ref().unwrap_or(&Completeness::default()))
+}
+
+pub(crate) fn entry_object(file_entry: &FileEntry, extra: &str) -> String {
+    format!(
+        "{{\"path\": {}, \"kind\": {}, \"size\": {}, \"size_human\": {}{}}}",
+        json_escape(&file_entry.path),
+        json_escape(file_entry.kind.as_str()),
+        file_entry.size,
+        json_escape(&file_entry.get_str_size()),
+        extra
+    )
+}
+
+// A file kept by the filters, written as soon as it's found when streaming NDJSON, before the results
+pub fn write_candidate(out: &mut dyn Write, file_entry: &FileEntry) -> io::Result<()> {
+    writeln!(out, "{}", entry_object(file_entry, ", \"candidate\": true"))
+}
+
+// New entries are the ones missing from the previous round of a watch, none outside of watches
+fn write_json(out: &mut dyn Write, files: &[FileEntry], options: &OutputOptions, new_paths: Option<&HashSet<String>>, ndjson: bool) -> io::Result<()> {
+    let mut cumulative: u64 = 0;
+    let mut names = Names::default();
+    let objects: Vec<String> = files.iter().map(|file_entry| {
+        cumulative += file_entry.size;
+        let mut fields: Vec<String> = options.columns.iter().filter_map(|column| match column {
+            Column::Path => Some(format!("\"path\": {}, \"kind\": {}", json_escape(&file_entry.path), json_escape(file_entry.kind.as_str()))),
+            Column::Size => Some(format!("\"size\": {}, \"size_human\": {}", file_entry.size, json_escape(&file_entry.get_str_size()))),
+            Column::Bar => None,
+            _ => Some(format!("\"{}\": {}", column.key(), options.value(*column, file_entry, cumulative, 0, &mut names).to_json())),
+        }).collect();
+        if let Some(new_paths) = new_paths {
+            fields.push(format!("\"new\": {}", new_paths.contains(&file_entry.path)));
+        }
+        if let Some(archive) = &file_entry.archive {
+            fields.push(format!("\"archive\": {}", archive_json(archive)));
+        }
+        format!("{{{}}}", fields.join(", "))
+    }).collect();
+
+    write_objects(out, &objects, options, ndjson)
+}
+
+// {"partial": false, "errors": [...], "results": [...]}, for scripts to tell complete results from
+// those missing what couldn't be read or wasn't reached
+fn write_document(out: &mut dyn Write, objects: &[String], completeness: &Completeness) -> io::Result<()> {
+    let errors: Vec<String> = completeness.errors.iter().map(|error| {
+        format!(
+            "{{\"path\": {}, \"kind\": {}, \"message\": {}, \"transient\": {}}}",
+            json_escape(&error.path),
+            json_escape(&error.kind),
+            json_escape(&error.message),
+            error.transient
+        )
+    }).collect();
+    let list = |objects: &[String]| if objects.is_empty() {
+        String::from("[]")
+    } else {
+        let indented: Vec<String> = objects.iter().map(|object| format!("    {}", object)).collect();
+        format!("[\n{}\n  ]", indented.join(",\n"))
+    };
+    writeln!(out, "{{\n  \"partial\": {},\n  \"errors\": {},\n  \"results\": {}\n}}", completeness.partial, list(&errors), list(objects))
+}
+
+fn archive_json(archive: &ArchivePeek) -> String {
+    let largest: Vec<String> = archive.largest.iter()
+        .map(|member| format!("{{\"path\": {}, \"size\": {}}}", json_escape(&member.path), member.size))
+        .collect();
+    format!("{{\"format\": {}, \"members\": {}, \"largest\": [{}]}}", json_escape(archive.format), archive.members, largest.join(", "))
+}
+
+// Quote a CSV field only when it holds a separator, a quote or a line break
+fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn write_csv(out: &mut dyn Write, files: &[FileEntry], options: &OutputOptions, new_paths: Option<&HashSet<String>>) -> io::Result<()> {
+    let columns: Vec<Column> = options.columns.iter().copied().filter(|column| *column != Column::Bar).collect();
+    let mut headers: Vec<&str> = columns.iter().map(|column| match column {
+        Column::Size => "size_bytes,size_human",
+        _ => column.key(),
+    }).collect();
+    if new_paths.is_some() {
+        headers.push("new");
+    }
+    writeln!(out, "{}", headers.join(","))?;
+    let mut cumulative: u64 = 0;
+    let mut names = Names::default();
+    for file_entry in files {
+        cumulative += file_entry.size;
+        let mut fields: Vec<String> = columns.iter().map(|column| match column {
+            Column::Size => format!("{},{}", file_entry.size, csv_escape(&file_entry.get_str_size())),
+            _ => options.value(*column, file_entry, cumulative, 0, &mut names).to_csv(),
+        }).collect();
+        if let Some(new_paths) = new_paths {
+            fields.push(new_paths.contains(&file_entry.path).to_string());
+        }
+        writeln!(out, "{}", fields.join(","))?;
+    }
+    Ok(())
+}
+
+// "size<TAB>path" lines, bare NUL terminated paths to feed xargs -0 or the lines of a template
+fn write_plain(out: &mut dyn Write, files: &[FileEntry], options: &OutputOptions) -> io::Result<()> {
+    let mut names = Names::default();
+    for file_entry in files {
+        if let Some(template) = &options.template {
+            write!(out, "{}", template.render(file_entry, options.percent_of, &mut names))?;
+        } else if options.print0 {
+            write!(out, "{}\0", file_entry.path)?;
+        } else {
+            writeln!(out, "{}\t{}", file_entry.size, file_entry.path)?;
+        }
+    }
+    Ok(())
+}
+
+// Widest bar of the bar column, in characters
+const BAR_WIDTH: usize = 20;
+const BAR_EIGHTHS: [char; 8] = [' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+
+// A bar of full blocks then a partial one, in eighths of a character, any size at all gets a sliver
+fn size_bar(size: u64, largest: u64) -> String {
+    if largest == 0 {
+        return String::new();
+    }
+    let eighths = ((size as u128 * (BAR_WIDTH * 8) as u128).div_ceil(largest as u128)) as usize;
+    let mut bar = "█".repeat(eighths / 8);
+    if !eighths.is_multiple_of(8) {
+        bar.push(BAR_EIGHTHS[eighths % 8]);
+    }
+    bar
+}
+
+// The cells of the results table, new entries marked in front of their path when asked to
+fn results_markup(files: &[FileEntry], options: &OutputOptions, new_paths: Option<&HashSet<String>>, mark_new: bool) -> Markup {
+    let is_new = |file_entry: &FileEntry| new_paths.is_some_and(|new_paths| new_paths.contains(&file_entry.path));
+    let headers = options.columns.iter().map(Column::header).collect();
+    let numbers = options.columns.iter().enumerate().filter(|(_, column)| column.is_number()).map(|(index, _)| index);
+    let mut markup = Markup::new("fatass results", headers).right_aligned(numbers);
+
+    let largest = files.iter().map(|file_entry| file_entry.size).max().unwrap_or(0);
+    let mut cumulative: u64 = 0;
+    let mut names = Names::default();
+    for file_entry in files {
+        cumulative += file_entry.size;
+        let record = options.columns.iter().map(|column| match column {
+            Column::Path if mark_new && is_new(file_entry) => format!("+ {}", file_entry.path),
+            Column::Size => file_entry.get_str_size(),
+            _ => options.value(*column, file_entry, cumulative, largest, &mut names).to_table(),
+        }).collect();
+        markup.rows.push(record);
+    }
+    markup
+}
+
+fn builder(markup: &Markup) -> Builder {
+    let mut builder = Builder::default();
+    builder.push_record(markup.headers.clone());
+    for row in &markup.rows {
+        builder.push_record(row.clone());
+    }
+    builder
+}
+
+fn write_table(out: &mut dyn Write, files: &[FileEntry], options: &OutputOptions, new_paths: Option<&HashSet<String>>) -> io::Result<()> {
+    let is_new = |file_entry: &FileEntry| new_paths.is_some_and(|new_paths| new_paths.contains(&file_entry.path));
+    let palette = options.theme.palette();
+    // Without colors to highlight them, new entries are marked in front of their path
+    let mut markup = results_markup(files, options, new_paths, palette.is_none());
+    if let (Some(table_width), Some(path_column)) = (options.table_width, options.position(Column::Path)) {
+        fit_paths(&mut markup, path_column, table_width);
+    }
+
+    // Numbers are right aligned, sizes colored like the size column
+    let roles: Vec<ColumnRole> = options.columns.iter().map(Column::role).collect();
+    let mut table = styled_table(builder(&markup), options.theme, &roles, options.position(Column::Size));
+    for &column in &markup.right_aligned {
+        table.modify(Columns::single(column), Alignment::right());
+    }
+    if let Some(palette) = &palette {
+        for (index, file_entry) in files.iter().enumerate() {
+            let threshold = options.color_thresholds.iter().find(|threshold| file_entry.size >= threshold.min_size);
+            if let Some(threshold) = threshold {
+                table.modify(Rows::single(index + 1), threshold.color.color(options.theme == Theme::Default));
+            }
+            if is_new(file_entry) {
+                table.modify(Rows::single(index + 1), palette.highlight.clone());
+            }
+        }
+    }
+    writeln!(out, "{}", table)?;
+    for file_entry in files {
+        if let Some(archive) = &file_entry.archive {
+            write_archive_table(out, &file_entry.path, archive, options)?;
+        }
+    }
+    Ok(())
+}
+
+const ARCHIVE_INDENT: &str = "  ";
+
+// The largest members of an archive under the results, indented below a line naming it
+fn write_archive_table(out: &mut dyn Write, path: &str, archive: &ArchivePeek, options: &OutputOptions) -> io::Result<()> {
+    // Repositories hold blobs, the content of files at some commit
+    let (noun, header) = if archive.format == "git" { ("blob", "Blob") } else { ("file", "Member") };
+    let files = format!("{} {}{}", archive.members, noun, if archive.members == 1 { "" } else { "s" });
+    let shown = if archive.members > archive.largest.len() as u64 { format!(", the largest {}", archive.largest.len()) } else { String::new() };
+    writeln!(out, "\n{} ({}, {}{}):", path, archive.format, files, shown)?;
+    let headers = [header, "Size"].map(String::from).to_vec();
+    let mut markup = Markup::new("fatass archive", headers).right_aligned([1]);
+    for member in &archive.largest {
+        markup.rows.push(vec![member.path.clone(), format_size(member.size)]);
+    }
+    if let Some(table_width) = options.table_width {
+        fit_paths(&mut markup, 0, table_width.saturating_sub(ARCHIVE_INDENT.len()));
+    }
+    let table = styled_table(builder(&markup), options.theme, &[ColumnRole::Path, ColumnRole::Size], Some(1));
+    for line in table.to_string().lines() {
+        writeln!(out, "{}{}", ARCHIVE_INDENT, line)?;
+    }
+    Ok(())
+}
+
+// The rounded look shared by every table, colored after the theme, with its size column right aligned
+fn styled_table(builder: Builder, theme: Theme, roles: &[ColumnRole], size_column: Option<usize>) -> Table {
+    let mut table = builder.build();
+    table.with(Style::rounded());
+    if let Some(size_column) = size_column {
+        table.modify(Columns::single(size_column), Alignment::right());
+    }
+    if let Some(palette) = theme.palette() {
+        let colors: Vec<Color> = roles.iter().map(|role| palette.color(*role)).collect();
+        table
+            .with(BorderColor::filled(palette.border.clone()))
+            .with(Colorization::columns(colors))
+            .with(Colorization::exact([palette.border], Rows::first()));
+    }
+    table
+}
+
+// Narrowest the paths get cut to, below it the table had better wrap
+const MIN_PATH_WIDTH: usize = 24;
+
+// Cut the paths so the table fits the width, the other columns are kept whole
+fn fit_paths(markup: &mut Markup, path_column: usize, table_width: usize) {
+    let column_width = |column: usize| {
+        let cells = std::iter::once(&markup.headers).chain(&markup.rows);
+        cells.map(|row| row[column].lines().map(|line| line.chars().count()).max().unwrap_or(0)).max().unwrap_or(0)
+    };
+    let columns = markup.headers.len();
+    // "│ " before every cell, " " after it and the closing "│"
+    let borders = 3 * columns + 1;
+    let others: usize = (0..columns).filter(|column| *column != path_column).map(column_width).sum();
+    let path_width = table_width.saturating_sub(borders + others).max(MIN_PATH_WIDTH);
+    for row in &mut markup.rows {
+        row[path_column] = shorten_middle(&row[path_column], path_width);
+    }
+}
+
+// The entries with their paths the way they are shown, the home directory only turns into ~ for people
+fn shown_entries(files: &[FileEntry], options: &OutputOptions, output_format: OutputFormat) -> (Vec<FileEntry>, PathShower) {
+    let for_people = matches!(output_format, OutputFormat::Table | OutputFormat::Markdown | OutputFormat::Html);
+    let shower = PathShower::new(options.path_style, options.tilde && for_people);
+    let files = files.iter().map(|file_entry| FileEntry { path: shower.show(&file_entry.path), ..file_entry.clone() }).collect();
+    (files, shower)
+}
+
+pub fn write_results(out: &mut dyn Write, files: &[FileEntry], output_format: OutputFormat, options: &OutputOptions) -> io::Result<()> {
+    let (files, _) = shown_entries(files, options, output_format);
+    let files = files.as_slice();
+    match output_format {
+        OutputFormat::Table => write_table(out, files, options, None),
+        OutputFormat::Json | OutputFormat::Ndjson | OutputFormat::Ncdu | OutputFormat::Treemap => write_json(out, files, options, None, output_format == OutputFormat::Ndjson),
+        OutputFormat::Csv => write_csv(out, files, options, None),
+        OutputFormat::Plain => write_plain(out, files, options),
+        OutputFormat::Markdown => write_markdown(out, &results_markup(files, options, None, false)),
+        OutputFormat::Html => write_html(out, &results_markup(files, options, None, false)),
+    }
+}
+
+// Same as write_results, telling which entries are new since the previous round of a watch
+pub fn write_watch(out: &mut dyn Write, files: &[FileEntry], new_paths: &HashSet<String>, output_format: OutputFormat, options: &OutputOptions) -> io::Result<()> {
+    let (files, shower) = shown_entries(files, options, output_format);
+    let files = files.as_slice();
+    let new_paths: HashSet<String> = new_paths.iter().map(|path| shower.show(path)).collect();
+    let new_paths = &new_paths;
+    match output_format {
+        OutputFormat::Table => write_table(out, files, options, Some(new_paths)),
+        OutputFormat::Json | OutputFormat::Ndjson | OutputFormat::Ncdu | OutputFormat::Treemap => write_json(out, files, options, Some(new_paths), output_format == OutputFormat::Ndjson),
+        OutputFormat::Csv => write_csv(out, files, options, Some(new_paths)),
+        // Every round is whole, what's new doesn't matter to a script
+        OutputFormat::Plain => write_plain(out, files, options),
+        OutputFormat::Markdown => write_markdown(out, &results_markup(files, options, Some(new_paths), true)),
+        OutputFormat::Html => write_html(out, &results_markup(files, options, Some(new_paths), true)),
+    }
+}
+
+// Widest bar of the size histogram, in characters
+const HISTOGRAM_WIDTH: usize = 40;
+
+// The header of --fs-usage, how the scan and the biggest result compare with the space of the
+// filesystems scanned
+pub fn write_volumes(out: &mut dyn Write, volumes: &[Volume], scanned_bytes: u64, shown: &[FileEntry]) -> io::Result<()> {
+    let share = |part: u64, whole: u64| if whole == 0 { 0.0 } else { part as f64 * 100.0 / whole as f64 };
+    let mount_width = volumes.iter().map(|volume| volume.mount.display().to_string().chars().count()).max().unwrap_or(0).max(18);
+    writeln!(out, "Filesystems:")?;
+    for volume in volumes {
+        let mount = volume.mount.display().to_string();
+        writeln!(out, "  {:<mount_width$} {} used of {}, {} free", mount, format_size(volume.used), format_size(volume.total), format_size(volume.free))?;
+    }
+    let (used, free) = volumes.iter().fold((0, 0), |(used, free), volume| (used + volume.used, free + volume.free));
+    writeln!(out, "  {:<mount_width$} {}, {:.1}% of the used space", "Scanned", format_size(scanned_bytes), share(scanned_bytes, used))?;
+    if let Some(biggest) = shown.iter().max_by_key(|file_entry| file_entry.size) {
+        writeln!(out, "  {:<mount_width$} {}, {:.1}% of the free space", "Biggest result", format_size(biggest.size), share(biggest.size, free))?;
+    }
+    writeln!(out)
+}
+
+// The footer of --stats, the shown entries are the top of the scanned ones
+pub fn write_stats(out: &mut dyn Write, stats: &SizeStats, shown: &[FileEntry]) -> io::Result<()> {
+    let shown_bytes: u64 = shown.iter().map(|file_entry| file_entry.size).sum();
+    let share = if stats.total() == 0 { 0.0 } else { shown_bytes as f64 * 100.0 / stats.total() as f64 };
+    writeln!(out, "\nStats:")?;
+    writeln!(out, "  {:<18} {}", "Files scanned", stats.files())?;
+    writeln!(out, "  {:<18} {}", "Total size", format_size(stats.total()))?;
+    writeln!(out, "  {:<18} {}, {:.1}% of the total", format!("Top {}", shown.len()), format_size(shown_bytes), share)?;
+    writeln!(out, "  {:<18} {}", "Mean size", format_size(stats.mean()))?;
+    writeln!(out, "  {:<18} {}", "Median size", format_size(stats.median()))?;
+    writeln!(out, "  {:<18} {}", "95th percentile", format_size(stats.percentile(95)))?;
+
+    writeln!(out, "\nSizes:")?;
+    let histogram = stats.histogram();
+    let most = histogram.iter().map(|bucket| bucket.files).max().unwrap_or(0).max(1);
+    let labels: Vec<String> = histogram.iter().map(|bucket| match bucket.max {
+        Some(max) if bucket.min == 0 => format!("< {}", format_size(max)),
+        Some(max) => format!("{} - {}", format_size(bucket.min), format_size(max)),
+        None => format!(">= {}", format_size(bucket.min)),
+    }).collect();
+    let label_width = labels.iter().map(|label| label.chars().count()).max().unwrap_or(0).max(18);
+    for (bucket, label) in histogram.iter().zip(labels) {
+        // Any file at all gets at least one mark
+        let width = (bucket.files * HISTOGRAM_WIDTH).div_ceil(most);
+        let line = format!("  {:<label_width$} {:>8} {}", label, bucket.files, "#".repeat(width));
+        writeln!(out, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+// The report of --estimate, every figure with its 95% confidence interval
+pub fn write_estimate(out: &mut dyn Write, estimate: &Estimate) -> io::Result<()> {
+    let count = |interval: &Interval| format!("{:.0} ± {:.0}", interval.value, interval.margin);
+    let bytes = &estimate.bytes;
+    let share = if bytes.value == 0.0 { 0.0 } else { bytes.margin * 100.0 / bytes.value };
+    writeln!(out, "Estimate from {} probes reading {} directories in {:.1?}, 95% confidence:", estimate.probes, estimate.dirs_read, estimate.elapsed)?;
+    writeln!(out, "  {:<18} {} ± {} ({:.1}%)", "Total size", format_size(bytes.value as u64), format_size(bytes.margin as u64), share)?;
+    writeln!(out, "  {:<18} {}", "Files", count(&estimate.files))?;
+    writeln!(out, "  {:<18} {}", "Directories", count(&estimate.dirs))?;
+    if estimate.files.value >= 1.0 {
+        writeln!(out, "  {:<18} {}", "Mean size", format_size((bytes.value / estimate.files.value) as u64))?;
+    }
+
+    writeln!(out, "\nSizes:")?;
+    let most = estimate.buckets.iter().map(|(_, _, files)| files.value).fold(0.0, f64::max).max(1.0);
+    let labels: Vec<String> = estimate.buckets.iter().map(|(min, max, _)| match max {
+        Some(max) if *min == 0 => format!("< {}", format_size(*max)),
+        Some(max) => format!("{} - {}", format_size(*min), format_size(*max)),
+        None => format!(">= {}", format_size(*min)),
+    }).collect();
+    let label_width = labels.iter().map(|label| label.chars().count()).max().unwrap_or(0).max(18);
+    let counts: Vec<String> = estimate.buckets.iter().map(|(_, _, files)| count(files)).collect();
+    let count_width = counts.iter().map(|count| count.chars().count()).max().unwrap_or(0);
+    for (((_, _, files), label), count) in estimate.buckets.iter().zip(labels).zip(counts) {
+        let width = (files.value * HISTOGRAM_WIDTH as f64 / most).ceil() as usize;
+        let line = format!("  {:<label_width$} {:>count_width$} {}", label, count, "#".repeat(width));
+        writeln!(out, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+fn write_dupes_json(out: &mut dyn Write, groups: &[DuplicateGroup], options: &OutputOptions, ndjson: bool) -> io::Result<()> {
+    let objects: Vec<String> = groups.iter().map(|group| {
+        let paths: Vec<String> = group.paths.iter().map(|path| json_escape(path)).collect();
+        format!(
+            "{{\"size\": {}, \"size_human\": {}, \"wasted\": {}, \"paths\": [{}]}}",
+            group.size,
+            json_escape(&format_size(group.size)),
+            group.wasted(),
+            paths.join(", ")
+        )
+    }).collect();
+
+    write_objects(out, &objects, options, ndjson)
+}
+
+// One row per copy, the group number ties them together
+fn write_dupes_csv(out: &mut dyn Write, groups: &[DuplicateGroup]) -> io::Result<()> {
+    writeln!(out, "group,path,size_bytes,size_human")?;
+    for (index, group) in groups.iter().enumerate() {
+        for path in &group.paths {
+            writeln!(out, "{},{},{},{}", index + 1, csv_escape(path), group.size, csv_escape(&format_size(group.size)))?;
+        }
+    }
+    Ok(())
+}
+
+// "group<TAB>size<TAB>path" lines, one per copy
+fn write_dupes_plain(out: &mut dyn Write, groups: &[DuplicateGroup]) -> io::Result<()> {
+    for (index, group) in groups.iter().enumerate() {
+        for path in &group.paths {
+            writeln!(out, "{}\t{}\t{}", index + 1, group.size, path)?;
+        }
+    }
+    Ok(())
+}
+
+fn dupes_markup(groups: &[DuplicateGroup]) -> Markup {
+    let headers = ["Paths", "Size", "Copies", "Wasted"].map(String::from).to_vec();
+    let mut markup = Markup::new("fatass duplicates", headers).right_aligned([1, 2, 3]);
+    for group in groups {
+        markup.rows.push(vec![
+            group.paths.join("\n"),
+            format_size(group.size),
+            group.paths.len().to_string(),
+            format_size(group.wasted()),
+        ]);
+    }
+    markup
+}
+
+fn write_dupes_table(out: &mut dyn Write, groups: &[DuplicateGroup], theme: Theme) -> io::Result<()> {
+    let roles = [ColumnRole::Path, ColumnRole::Size, ColumnRole::Other, ColumnRole::Size];
+    let mut table = styled_table(builder(&dupes_markup(groups)), theme, &roles, Some(1));
+    // Lines between rows keep the groups apart
+    table.with(Style::modern_rounded());
+    if let Some(palette) = theme.palette() {
+        table.with(BorderColor::filled(palette.border));
+    }
+    writeln!(out, "{}", table)
+}
+
+pub fn write_dupes(out: &mut dyn Write, groups: &[DuplicateGroup], output_format: OutputFormat, options: &OutputOptions) -> io::Result<()> {
+    match output_format {
+        OutputFormat::Table => write_dupes_table(out, groups, options.theme),
+        OutputFormat::Json | OutputFormat::Ndjson | OutputFormat::Ncdu | OutputFormat::Treemap => write_dupes_json(out, groups, options, output_format == OutputFormat::Ndjson),
+        OutputFormat::Csv => write_dupes_csv(out, groups),
+        OutputFormat::Plain => write_dupes_plain(out, groups),
+        OutputFormat::Markdown => write_markdown(out, &dupes_markup(groups)),
+        OutputFormat::Html => write_html(out, &dupes_markup(groups)),
+    }
+}
+
+fn write_groups_json(out: &mut dyn Write, groups: &[Group], options: &OutputOptions, ndjson: bool) -> io::Result<()> {
+    let objects: Vec<String> = groups.iter().map(|group| {
+        let top: Vec<String> = group.top.iter()
+            .map(|file_entry| format!("{{\"path\": {}, \"size\": {}}}", json_escape(&file_entry.path), file_entry.size))
+            .collect();
+        format!(
+            "{{\"group\": {}, \"size\": {}, \"size_human\": {}, \"files\": {}, \"top\": [{}]}}",
+            json_escape(&group.name),
+            group.size,
+            json_escape(&format_size(group.size)),
+            group.files,
+            top.join(", ")
+        )
+    }).collect();
+
+    write_objects(out, &objects, options, ndjson)
+}
+
+// One row per top file, the group columns repeated on each
+fn write_groups_csv(out: &mut dyn Write, groups: &[Group]) -> io::Result<()> {
+    writeln!(out, "group,size_bytes,size_human,files,top_path,top_size_bytes")?;
+    for group in groups {
+        for file_entry in &group.top {
+            writeln!(
+                out,
+                "{},{},{},{},{},{}",
+                csv_escape(&group.name),
+                group.size,
+                csv_escape(&format_size(group.size)),
+                group.files,
+                csv_escape(&file_entry.path),
+                file_entry.size
+            )?;
+        }
+    }
+    Ok(())
+}
+
+// "size<TAB>files<TAB>group" lines
+fn write_groups_plain(out: &mut dyn Write, groups: &[Group]) -> io::Result<()> {
+    for group in groups {
+        writeln!(out, "{}\t{}\t{}", group.size, group.files, group.name)?;
+    }
+    Ok(())
+}
+
+fn groups_markup(groups: &[Group], key: GroupKey) -> Markup {
+    let [largest_files, largest_sizes] = if groups.iter().any(|group| group.top.len() > 1) {
+        ["Largest files", "Largest sizes"]
+    } else {
+        ["Largest file", "Largest size"]
+    };
+    let headers = [key.label(), "Size", "Files", largest_files, largest_sizes].map(String::from).to_vec();
+    let mut markup = Markup::new("fatass groups", headers).right_aligned([1, 2, 4]);
+    for group in groups {
+        let paths: Vec<&str> = group.top.iter().map(|file_entry| file_entry.path.as_str()).collect();
+        let sizes: Vec<String> = group.top.iter().map(|file_entry| file_entry.get_str_size()).collect();
+        markup.rows.push(vec![
+            group.name.clone(),
+            format_size(group.size),
+            group.files.to_string(),
+            paths.join("\n"),
+            sizes.join("\n"),
+        ]);
+    }
+    markup
+}
+
+fn write_groups_table(out: &mut dyn Write, groups: &[Group], key: GroupKey, theme: Theme) -> io::Result<()> {
+    let roles = [ColumnRole::Path, ColumnRole::Size, ColumnRole::Other, ColumnRole::Path, ColumnRole::Size];
+    let mut table = styled_table(builder(&groups_markup(groups, key)), theme, &roles, Some(1));
+    // Several sizes in a cell line up by their own width, not the widest one
+    table.modify(Columns::single(2), Alignment::right())
+        .modify(Columns::single(4), Alignment::right())
+        .modify(Columns::single(4), AlignmentStrategy::PerLine);
+    writeln!(out, "{}", table)
+}
+
+pub fn write_groups(out: &mut dyn Write, groups: &[Group], key: GroupKey, output_format: OutputFormat, options: &OutputOptions) -> io::Result<()> {
+    match output_format {
+        OutputFormat::Table => write_groups_table(out, groups, key, options.theme),
+        OutputFormat::Json | OutputFormat::Ndjson | OutputFormat::Ncdu | OutputFormat::Treemap => write_groups_json(out, groups, options, output_format == OutputFormat::Ndjson),
+        OutputFormat::Csv => write_groups_csv(out, groups),
+        OutputFormat::Plain => write_groups_plain(out, groups),
+        OutputFormat::Markdown => write_markdown(out, &groups_markup(groups, key)),
+        OutputFormat::Html => write_html(out, &groups_markup(groups, key)),
+    }
+}
+
+fn write_inodes_json(out: &mut dyn Write, counts: &[DirCount], options: &OutputOptions, ndjson: bool) -> io::Result<()> {
+    let objects: Vec<String> = counts.iter()
+        .map(|count| format!("{{\"path\": {}, \"entries\": {}, \"direct\": {}}}", json_escape(&count.path), count.entries, count.direct))
+        .collect();
+
+    write_objects(out, &objects, options, ndjson)
+}
+
+fn write_inodes_csv(out: &mut dyn Write, counts: &[DirCount]) -> io::Result<()> {
+    writeln!(out, "path,entries,direct")?;
+    for count in counts {
+        writeln!(out, "{},{},{}", csv_escape(&count.path), count.entries, count.direct)?;
+    }
+    Ok(())
+}
+
+// "entries<TAB>direct<TAB>path" lines
+fn write_inodes_plain(out: &mut dyn Write, counts: &[DirCount]) -> io::Result<()> {
+    for count in counts {
+        writeln!(out, "{}\t{}\t{}", count.entries, count.direct, count.path)?;
+    }
+    Ok(())
+}
+
+fn inodes_markup(counts: &[DirCount]) -> Markup {
+    let headers = ["Directory", "Entries", "Direct"].map(String::from).to_vec();
+    let mut markup = Markup::new("fatass inodes", headers).right_aligned([1, 2]);
+    for count in counts {
+        markup.rows.push(vec![count.path.clone(), count.entries.to_string(), count.direct.to_string()]);
+    }
+    markup
+}
+
+fn write_inodes_table(out: &mut dyn Write, counts: &[DirCount], theme: Theme) -> io::Result<()> {
+    let roles = [ColumnRole::Path, ColumnRole::Size, ColumnRole::Other];
+    let mut table = styled_table(builder(&inodes_markup(counts)), theme, &roles, Some(1));
+    table.modify(Columns::single(2), Alignment::right());
+    writeln!(out, "{}", table)
+}
+
+pub fn write_inodes(out: &mut dyn Write, counts: &[DirCount], output_format: OutputFormat, options: &OutputOptions) -> io::Result<()> {
+    match output_format {
+        OutputFormat::Table => write_inodes_table(out, counts, options.theme),
+        OutputFormat::Json | OutputFormat::Ndjson | OutputFormat::Ncdu | OutputFormat::Treemap => write_inodes_json(out, counts, options, output_format == OutputFormat::Ndjson),
+        OutputFormat::Csv => write_inodes_csv(out, counts),
+        OutputFormat::Plain => write_inodes_plain(out, counts),
+        OutputFormat::Markdown => write_markdown(out, &inodes_markup(counts)),
+        OutputFormat::Html => write_html(out, &inodes_markup(counts)),
+    }
+}
+
+fn write_compressible_json(out: &mut dyn Write, candidates: &[Compressible], options: &OutputOptions, ndjson: bool) -> io::Result<()> {
+    let objects: Vec<String> = candidates.iter()
+        .map(|candidate| format!(
+            "{{\"path\": {}, \"size\": {}, \"estimate\": {}, \"savings\": {}, \"ratio\": {:.2}}}",
+            json_escape(&candidate.path),
+            candidate.size,
+            candidate.estimate,
+            candidate.savings(),
+            candidate.ratio()
+        ))
+        .collect();
+
+    write_objects(out, &objects, options, ndjson)
+}
+
+fn write_compressible_csv(out: &mut dyn Write, candidates: &[Compressible]) -> io::Result<()> {
+    writeln!(out, "path,size_bytes,estimate_bytes,savings_bytes,savings_human,ratio")?;
+    for candidate in candidates {
+        writeln!(
+            out,
+            "{},{},{},{},{},{:.2}",
+            csv_escape(&candidate.path),
+            candidate.size,
+            candidate.estimate,
+            candidate.savings(),
+            csv_escape(&format_size(candidate.savings())),
+            candidate.ratio()
+        )?;
+    }
+    Ok(())
+}
+
+// "savings<TAB>size<TAB>path" lines
+fn write_compressible_plain(out: &mut dyn Write, candidates: &[Compressible]) -> io::Result<()> {
+    for candidate in candidates {
+        writeln!(out, "{}\t{}\t{}", candidate.savings(), candidate.size, candidate.path)?;
+    }
+    Ok(())
+}
+
+fn compressible_markup(candidates: &[Compressible]) -> Markup {
+    let headers = ["Path", "Size", "Compressed", "Savings", "Ratio"].map(String::from).to_vec();
+    let mut markup = Markup::new("fatass compressible", headers).right_aligned([1, 2, 3, 4]);
+    for candidate in candidates {
+        markup.rows.push(vec![
+            candidate.path.clone(),
+            format_size(candidate.size),
+            format_size(candidate.estimate),
+            format_size(candidate.savings()),
+            format!("{:.1}x", candidate.ratio()),
+        ]);
+    }
+    markup
+}
+
+fn write_compressible_table(out: &mut dyn Write, candidates: &[Compressible], theme: Theme) -> io::Result<()> {
+    let roles = [ColumnRole::Path, ColumnRole::Size, ColumnRole::Other, ColumnRole::Size, ColumnRole::Other];
+    let mut table = styled_table(builder(&compressible_markup(candidates)), theme, &roles, Some(1));
+    table.modify(Columns::new(2..5), Alignment::right());
+    writeln!(out, "{}", table)
+}
+
+pub fn write_compressible(out: &mut dyn Write, candidates: &[Compressible], output_format: OutputFormat, options: &OutputOptions) -> io::Result<()> {
+    match output_format {
+        OutputFormat::Table => write_compressible_table(out, candidates, options.theme),
+        OutputFormat::Json | OutputFormat::Ndjson | OutputFormat::Ncdu | OutputFormat::Treemap => write_compressible_json(out, candidates, options, output_format == OutputFormat::Ndjson),
+        OutputFormat::Csv => write_compressible_csv(out, candidates),
+        OutputFormat::Plain => write_compressible_plain(out, candidates),
+        OutputFormat::Markdown => write_markdown(out, &compressible_markup(candidates)),
+        OutputFormat::Html => write_html(out, &compressible_markup(candidates)),
+    }
+}
+
+fn write_docker_json(out: &mut dyn Write, usage: &[StorageUsage], options: &OutputOptions, ndjson: bool) -> io::Result<()> {
+    let objects: Vec<String> = usage.iter()
+        .map(|row| format!(
+            "{{\"kind\": \"{}\", \"name\": {}, \"id\": {}, \"size\": {}, \"unique\": {}}}",
+            row.kind.as_str(),
+            json_escape(&row.name),
+            json_escape(&row.id),
+            row.size,
+            row.unique
+        ))
+        .collect();
+
+    write_objects(out, &objects, options, ndjson)
+}
+
+fn write_docker_csv(out: &mut dyn Write, usage: &[StorageUsage]) -> io::Result<()> {
+    writeln!(out, "kind,name,id,size_bytes,unique_bytes,unique_human")?;
+    for row in usage {
+        writeln!(
+            out,
+            "{},{},{},{},{},{}",
+            row.kind.as_str(),
+            csv_escape(&row.name),
+            row.id,
+            row.size,
+            row.unique,
+            csv_escape(&format_size(row.unique))
+        )?;
+    }
+    Ok(())
+}
+
+// "unique<TAB>size<TAB>kind<TAB>name" lines
+fn write_docker_plain(out: &mut dyn Write, usage: &[StorageUsage]) -> io::Result<()> {
+    for row in usage {
+        writeln!(out, "{}\t{}\t{}\t{}", row.unique, row.size, row.kind.as_str(), row.name)?;
+    }
+    Ok(())
+}
+
+fn docker_markup(usage: &[StorageUsage]) -> Markup {
+    let headers = ["Kind", "Name", "ID", "Size", "Unique"].map(String::from).to_vec();
+    let mut markup = Markup::new("fatass docker", headers).right_aligned([3, 4]);
+    for row in usage {
+        markup.rows.push(vec![
+            row.kind.as_str().to_string(),
+            row.name.clone(),
+            row.id.clone(),
+            format_size(row.size),
+            format_size(row.unique),
+        ]);
+    }
+    markup
+}
+
+fn write_docker_table(out: &mut dyn Write, usage: &[StorageUsage], theme: Theme) -> io::Result<()> {
+    let roles = [ColumnRole::Other, ColumnRole::Path, ColumnRole::Other, ColumnRole::Other, ColumnRole::Size];
+    let mut table = styled_table(builder(&docker_markup(usage)), theme, &roles, Some(4));
+    table.modify(Columns::single(3), Alignment::right());
+    writeln!(out, "{}", table)
+}
+
+pub fn write_docker(out: &mut dyn Write, usage: &[StorageUsage], output_format: OutputFormat, options: &OutputOptions) -> io::Result<()> {
+    match output_format {
+        OutputFormat::Table => write_docker_table(out, usage, options.theme),
+        OutputFormat::Json | OutputFormat::Ndjson | OutputFormat::Ncdu | OutputFormat::Treemap => write_docker_json(out, usage, options, output_format == OutputFormat::Ndjson),
+        OutputFormat::Csv => write_docker_csv(out, usage),
+        OutputFormat::Plain => write_docker_plain(out, usage),
+        OutputFormat::Markdown => write_markdown(out, &docker_markup(usage)),
+        OutputFormat::Html => write_html(out, &docker_markup(usage)),
+    }
+}
+
+// Signed size, "+1.20 GB" or "-300.00 KB"
+fn format_delta(delta: i128) -> String {
+    let sign = if delta < 0 { "-" } else { "+" };
+    format!("{}{}", sign, format_size(delta.unsigned_abs().min(u64::MAX as u128) as u64))
+}
+
+fn optional_size(size: Option<u64>, format: impl Fn(u64) -> String, missing: &str) -> String {
+    size.map(format).unwrap_or_else(|| missing.to_string())
+}
+
+fn write_diff_json(out: &mut dyn Write, changes: &[Change], options: &OutputOptions, ndjson: bool) -> io::Result<()> {
+    let objects: Vec<String> = changes.iter().map(|change| {
+        format!(
+            "{{\"path\": {}, \"kind\": \"{}\", \"change\": \"{}\", \"old_size_bytes\": {}, \"new_size_bytes\": {}, \"delta_bytes\": {}, \"delta_human\": {}}}",
+            json_escape(&change.path),
+            change.kind.as_str(),
+            change.change_kind().as_str(),
+            optional_size(change.old_size, |size| size.to_string(), "null"),
+            optional_size(change.new_size, |size| size.to_string(), "null"),
+            change.delta(),
+            json_escape(&format_delta(change.delta()))
+        )
+    }).collect();
+
+    write_objects(out, &objects, options, ndjson)
+}
+
+fn write_diff_csv(out: &mut dyn Write, changes: &[Change]) -> io::Result<()> {
+    writeln!(out, "path,kind,change,old_size_bytes,new_size_bytes,delta_bytes,delta_human")?;
+    for change in changes {
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{}",
+            csv_escape(&change.path),
+            change.kind.as_str(),
+            change.change_kind().as_str(),
+            optional_size(change.old_size, |size| size.to_string(), ""),
+            optional_size(change.new_size, |size| size.to_string(), ""),
+            change.delta(),
+            csv_escape(&format_delta(change.delta()))
+        )?;
+    }
+    Ok(())
+}
+
+// "delta<TAB>change<TAB>path" lines, deltas signed
+fn write_diff_plain(out: &mut dyn Write, changes: &[Change]) -> io::Result<()> {
+    for change in changes {
+        writeln!(out, "{:+}\t{}\t{}", change.delta(), change.change_kind().as_str(), change.path)?;
+    }
+    Ok(())
+}
+
+fn diff_markup(changes: &[Change]) -> Markup {
+    let headers = ["Path", "Delta", "Change", "Before", "After"].map(String::from).to_vec();
+    let mut markup = Markup::new("fatass changes", headers).right_aligned([1, 3, 4]);
+    for change in changes {
+        markup.rows.push(vec![
+            change.path.clone(),
+            format_delta(change.delta()),
+            change.change_kind().as_str().to_string(),
+            optional_size(change.old_size, format_size, "-"),
+            optional_size(change.new_size, format_size, "-"),
+        ]);
+    }
+    markup
+}
+
+fn write_diff_table(out: &mut dyn Write, changes: &[Change], theme: Theme) -> io::Result<()> {
+    let roles = [ColumnRole::Path, ColumnRole::Size, ColumnRole::Other, ColumnRole::Other, ColumnRole::Other];
+    let mut table = styled_table(builder(&diff_markup(changes)), theme, &roles, Some(1));
+    table.modify(Columns::new(3..5), Alignment::right());
+    writeln!(out, "{}", table)
+}
+
+pub fn write_diff(out: &mut dyn Write, changes: &[Change], output_format: OutputFormat, options: &OutputOptions) -> io::Result<()> {
+    match output_format {
+        OutputFormat::Table => write_diff_table(out, changes, options.theme),
+        OutputFormat::Json | OutputFormat::Ndjson | OutputFormat::Ncdu | OutputFormat::Treemap => write_diff_json(out, changes, options, output_format == OutputFormat::Ndjson),
+        OutputFormat::Csv => write_diff_csv(out, changes),
+        OutputFormat::Plain => write_diff_plain(out, changes),
+        OutputFormat::Markdown => write_markdown(out, &diff_markup(changes)),
+        OutputFormat::Html => write_html(out, &diff_markup(changes)),
+    }
+}
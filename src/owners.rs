@@ -0,0 +1,104 @@
+// Who owns files and what they allow, from the Unix uid, gid and mode bits. Other platforms don't
+// expose them through the std metadata, there every entry has none.
+use std::collections::HashMap;
+use std::fs::Metadata;
+
+// (uid, gid, mode) of the entry, mode including the file type bits
+#[cfg(unix)]
+pub(crate) fn ownership(metadata: &Metadata) -> (Option<u32>, Option<u32>, Option<u32>) {
+    use std::os::unix::fs::MetadataExt;
+    (Some(metadata.uid()), Some(metadata.gid()), Some(metadata.mode()))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn ownership(_metadata: &Metadata) -> (Option<u32>, Option<u32>, Option<u32>) {
+    (None, None, None)
+}
+
+#[cfg(unix)]
+fn user_name(uid: u32) -> Option<String> {
+    use std::ffi::CStr;
+    let mut buffer = vec![0 as libc::c_char; 4096];
+    let mut result: *mut libc::passwd = std::ptr::null_mut();
+    // SAFETY: getpwuid_r only writes into the zeroed passwd and the buffer we own, the name it
+    // points to lives in that buffer and is copied out before it goes away
+    unsafe {
+        let mut passwd: libc::passwd = std::mem::zeroed();
+        let status = libc::getpwuid_r(uid, &mut passwd, buffer.as_mut_ptr(), buffer.len(), &mut result);
+        if status != 0 || result.is_null() || passwd.pw_name.is_null() {
+            return None;
+        }
+        Some(CStr::from_ptr(passwd.pw_name).to_string_lossy().into_owned())
+    }
+}
+
+#[cfg(unix)]
+fn group_name(gid: u32) -> Option<String> {
+    use std::ffi::CStr;
+    let mut buffer = vec![0 as libc::c_char; 4096];
+    let mut result: *mut libc::group = std::ptr::null_mut();
+    // SAFETY: same as user_name, with getgrgid_r and a group
+    unsafe {
+        let mut group: libc::group = std::mem::zeroed();
+        let status = libc::getgrgid_r(gid, &mut group, buffer.as_mut_ptr(), buffer.len(), &mut result);
+        if status != 0 || result.is_null() || group.gr_name.is_null() {
+            return None;
+        }
+        Some(CStr::from_ptr(group.gr_name).to_string_lossy().into_owned())
+    }
+}
+
+#[cfg(not(unix))]
+fn user_name(_uid: u32) -> Option<String> {
+    None
+}
+
+#[cfg(not(unix))]
+fn group_name(_gid: u32) -> Option<String> {
+    None
+}
+
+// Names of users and groups, looked up once each, the number stands in for unknown ones
+#[derive(Debug, Default)]
+pub(crate) struct Names {
+    users: HashMap<u32, String>,
+    groups: HashMap<u32, String>,
+}
+
+impl Names {
+    pub(crate) fn user(&mut self, uid: u32) -> String {
+        self.users.entry(uid).or_insert_with(|| user_name(uid).unwrap_or_else(|| uid.to_string())).clone()
+    }
+
+    pub(crate) fn group(&mut self, gid: u32) -> String {
+        self.groups.entry(gid).or_insert_with(|| group_name(gid).unwrap_or_else(|| gid.to_string())).clone()
+    }
+}
+
+// "drwxr-xr-x" like ls -l, setuid, setgid and sticky bits included
+pub fn format_mode(mode: u32) -> String {
+    let kind = match mode & 0o170_000 {
+        0o040_000 => 'd',
+        0o120_000 => 'l',
+        0o010_000 => 'p',
+        0o140_000 => 's',
+        0o020_000 => 'c',
+        0o060_000 => 'b',
+        _ => '-',
+    };
+    let mut text = String::from(kind);
+    // Read, write and execute for the owner, group and others, with the special bit of each
+    for (shift, special, marks) in [(6, 0o4000, ['s', 'S']), (3, 0o2000, ['s', 'S']), (0, 0o1000, ['t', 'T'])] {
+        let bits = (mode >> shift) & 0o7;
+        text.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        text.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        let execute = bits & 0o1 != 0;
+        text.push(match (mode & special != 0, execute) {
+            (true, true) => marks[0],
+            (true, false) => marks[1],
+            (false, true) => 'x',
+            (false, false) => '-',
+        });
+    }
+    text
+}
@@ -0,0 +1,38 @@
+// Tables taller than the terminal go through $PAGER, less by default, so the fattest 500 files can
+// be scrolled rather than running off the top of the screen. Without LESS set, less is told to keep
+// the colors (R), to quit by itself when the text fits after all (F) and to leave it on screen (X)
+use std::env;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+const DEFAULT_PAGER: &str = "less";
+const DEFAULT_LESS: &str = "FRX";
+
+// Whether the text has more lines than stdout, a terminal of unknown height never pages
+pub fn taller_than_terminal(text: &[u8]) -> bool {
+    let Some((rows, _)) = console::Term::stdout().size_checked() else {
+        return false;
+    };
+    // The prompt takes the last row back
+    text.iter().filter(|byte| **byte == b'\n').count() >= rows as usize
+}
+
+// Write the text through the pager and wait for it to quit. An error means it couldn't be started
+// and nothing was shown, the text is then left for the caller to print
+pub fn page(text: &[u8]) -> io::Result<()> {
+    let pager = env::var("PAGER").ok().filter(|pager| !pager.trim().is_empty()).unwrap_or_else(|| DEFAULT_PAGER.to_string());
+    let mut words = pager.split_whitespace();
+    let program = words.next().unwrap_or(DEFAULT_PAGER);
+    let mut command = Command::new(program);
+    command.args(words).stdin(Stdio::piped());
+    if env::var_os("LESS").is_none() {
+        command.env("LESS", DEFAULT_LESS);
+    }
+    let mut child = command.spawn()?;
+    // Quitting the pager before the end closes the pipe, the rest isn't wanted then
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(text);
+    }
+    child.wait()?;
+    Ok(())
+}
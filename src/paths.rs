@@ -0,0 +1,77 @@
+// How the paths of the results are shown: as the walk found them under the search paths, relative
+// to the current directory or absolute, with the home directory written ~ for people to read, and
+// cut in the middle when a table would be wider than the terminal
+use std::env;
+use std::path::{self, Path, PathBuf, MAIN_SEPARATOR};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum PathStyle {
+    // Joined to the search path they were found under, the way it was given
+    #[default]
+    AsGiven,
+    // Relative to the current directory, the paths outside of it stay absolute
+    Relative,
+    Absolute,
+}
+
+impl PathStyle {
+    pub fn parse(value: &str) -> Option<PathStyle> {
+        match value {
+            "relative" => Some(PathStyle::Relative),
+            "absolute" => Some(PathStyle::Absolute),
+            _ => None,
+        }
+    }
+}
+
+// Rewrites paths after a style, the directories it needs are looked up once
+pub(crate) struct PathShower {
+    style: PathStyle,
+    current_dir: Option<PathBuf>,
+    home: Option<PathBuf>,
+}
+
+impl PathShower {
+    pub(crate) fn new(style: PathStyle, tilde: bool) -> PathShower {
+        let current_dir = if style == PathStyle::AsGiven { None } else { env::current_dir().ok() };
+        let home = env::var_os(if cfg!(windows) { "USERPROFILE" } else { "HOME" })
+            .filter(|home| tilde && !home.is_empty())
+            .map(PathBuf::from);
+        PathShower { style, current_dir, home }
+    }
+
+    pub(crate) fn show(&self, path: &str) -> String {
+        let shown = match (self.style, &self.current_dir) {
+            (PathStyle::AsGiven, _) | (_, None) => path.to_string(),
+            (style, Some(current_dir)) => {
+                let absolute = path::absolute(path).unwrap_or_else(|_| PathBuf::from(path));
+                match absolute.strip_prefix(current_dir) {
+                    Ok(relative) if style == PathStyle::Relative && relative.as_os_str().is_empty() => String::from("."),
+                    Ok(relative) if style == PathStyle::Relative => relative.to_string_lossy().to_string(),
+                    _ => absolute.to_string_lossy().to_string(),
+                }
+            }
+        };
+        match self.home.as_deref().and_then(|home| Path::new(&shown).strip_prefix(home).ok()) {
+            Some(rest) if rest.as_os_str().is_empty() => String::from("~"),
+            Some(rest) => format!("~{}{}", MAIN_SEPARATOR, rest.to_string_lossy()),
+            None => shown,
+        }
+    }
+}
+
+// Cut the middle out of a path longer than width characters, keeping its start and its file name
+pub(crate) fn shorten_middle(path: &str, width: usize) -> String {
+    let chars: Vec<char> = path.chars().collect();
+    if chars.len() <= width || width < 3 {
+        return path.to_string();
+    }
+    // The end holds the name, it gets the bigger half
+    let kept = width - 1;
+    let head = kept / 2;
+    let tail = kept - head;
+    let mut shortened: String = chars[..head].iter().collect();
+    shortened.push('…');
+    shortened.extend(&chars[chars.len() - tail..]);
+    shortened
+}
@@ -0,0 +1,69 @@
+// Sets of paths a scan of a whole drive is better off skipping, for --preset. Pseudo filesystems
+// like /proc report made up sizes, snap and Time Machine mounts show files stored elsewhere a second
+// time, and the swap and hibernation files can't be freed by deleting them.
+//
+// System paths are absolute and only skipped where they really are, a ~/dev directory is left
+// alone. The Windows ones are skipped by name at any depth since every drive has its own
+use crate::glob::Glob;
+use crate::regex::Regex;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Preset {
+    // All of them, for scans of / or C:\ whatever the system
+    SystemScan,
+    Linux,
+    Macos,
+    Windows,
+}
+
+// Kernel and runtime pseudo filesystems, and the squashfs mounts of snaps
+const LINUX_PATHS: [&str; 6] = ["/proc", "/sys", "/dev", "/run", "/snap", "/var/lib/snapd/snap"];
+// The device filesystem, Time Machine local snapshots, the data volume / is a firmlinked view of,
+// and the swap files
+const MACOS_PATHS: [&str; 5] = ["/dev", "/Volumes/com.apple.TimeMachine.localsnapshots", "/.MobileBackups", "/System/Volumes/Data", "/private/var/vm"];
+// Time Machine backup disks, full of hard links to the same files
+const MACOS_NAMES: [&str; 1] = ["Backups.backupdb"];
+const WINDOWS_NAMES: [&str; 7] = ["$Recycle.Bin", "$RECYCLE.BIN", "RECYCLER", "System Volume Information", "pagefile.sys", "hiberfil.sys", "swapfile.sys"];
+
+impl Preset {
+    pub const NAMES: [&'static str; 4] = ["system-scan", "linux", "macos", "windows"];
+
+    pub fn parse(value: &str) -> Option<Preset> {
+        match value {
+            "system-scan" => Some(Preset::SystemScan),
+            "linux" => Some(Preset::Linux),
+            "macos" => Some(Preset::Macos),
+            "windows" => Some(Preset::Windows),
+            _ => None,
+        }
+    }
+
+    fn paths(&self) -> Vec<&'static str> {
+        match self {
+            Preset::SystemScan => LINUX_PATHS.iter().chain(&MACOS_PATHS).copied().collect(),
+            Preset::Linux => LINUX_PATHS.to_vec(),
+            Preset::Macos => MACOS_PATHS.to_vec(),
+            Preset::Windows => Vec::new(),
+        }
+    }
+
+    fn names(&self) -> Vec<&'static str> {
+        match self {
+            Preset::SystemScan => MACOS_NAMES.iter().chain(&WINDOWS_NAMES).copied().collect(),
+            Preset::Linux => Vec::new(),
+            Preset::Macos => MACOS_NAMES.to_vec(),
+            Preset::Windows => WINDOWS_NAMES.to_vec(),
+        }
+    }
+
+    // The paths as regexes on the full path, matching them and everything below
+    pub fn path_excludes(&self) -> Vec<Regex> {
+        self.paths().into_iter()
+            .map(|path| Regex::new(&format!("^{}(/|$)", path.replace('.', "\\."))).expect("preset paths are valid regexes"))
+            .collect()
+    }
+
+    pub fn name_excludes(&self) -> Vec<Glob> {
+        self.names().into_iter().map(|name| Glob::new(name).expect("preset names are valid globs")).collect()
+    }
+}
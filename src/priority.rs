@@ -0,0 +1,89 @@
+// Making way for the services of the machine being scanned: --nice lowers the CPU and disk
+// priority of fatass, --idle only lets it run when nothing else wants the CPU or the disks. Set
+// before any thread is started, the walkers inherit it
+use std::io;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Priority {
+    Nice,
+    Idle,
+}
+
+#[cfg(unix)]
+fn check(result: libc::c_int) -> io::Result<()> {
+    if result == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// Nice 10 and the lowest best-effort disk priority, or nice 19 and the idle disk class that only
+// gets the disk when nobody else uses it, what ionice -c 2 -n 7 and ionice -c 3 would set
+#[cfg(target_os = "linux")]
+pub fn lower(priority: Priority) -> io::Result<()> {
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+    const IOPRIO_CLASS_BE: libc::c_int = 2;
+    const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+    let (nice, ioprio) = match priority {
+        Priority::Nice => (10, IOPRIO_CLASS_BE << IOPRIO_CLASS_SHIFT | 7),
+        Priority::Idle => (19, IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT),
+    };
+    // SAFETY: both only take integers and act on the calling thread
+    unsafe {
+        check(libc::setpriority(libc::PRIO_PROCESS, 0, nice))?;
+        check(libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, ioprio) as libc::c_int)
+    }
+}
+
+// Nice 10 and the utility disk policy, or the background band where the CPU and the disks are
+// throttled whenever anything else needs them, like background QoS
+#[cfg(target_os = "macos")]
+pub fn lower(priority: Priority) -> io::Result<()> {
+    const IOPOL_TYPE_DISK: libc::c_int = 0;
+    const IOPOL_SCOPE_PROCESS: libc::c_int = 0;
+    const IOPOL_UTILITY: libc::c_int = 4;
+    extern "C" {
+        fn setiopolicy_np(iotype: libc::c_int, scope: libc::c_int, policy: libc::c_int) -> libc::c_int;
+    }
+    // SAFETY: both only take integers and act on the calling process
+    unsafe {
+        match priority {
+            Priority::Nice => {
+                check(libc::setpriority(libc::PRIO_PROCESS, 0, 10))?;
+                check(setiopolicy_np(IOPOL_TYPE_DISK, IOPOL_SCOPE_PROCESS, IOPOL_UTILITY))
+            }
+            Priority::Idle => check(libc::setpriority(libc::PRIO_DARWIN_PROCESS, 0, libc::PRIO_DARWIN_BG)),
+        }
+    }
+}
+
+// Nice 10 or 19, the disk priority is left to the system
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "macos"))))]
+pub fn lower(priority: Priority) -> io::Result<()> {
+    let nice = if priority == Priority::Nice { 10 } else { 19 };
+    // SAFETY: setpriority only takes integers
+    check(unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) })
+}
+
+// The below normal priority class, or background mode which lowers the disk and memory priority
+// along with the CPU one
+#[cfg(windows)]
+pub fn lower(priority: Priority) -> io::Result<()> {
+    use windows_sys::Win32::System::Threading::{GetCurrentProcess, SetPriorityClass, BELOW_NORMAL_PRIORITY_CLASS, PROCESS_MODE_BACKGROUND_BEGIN};
+
+    let class = match priority {
+        Priority::Nice => BELOW_NORMAL_PRIORITY_CLASS,
+        Priority::Idle => PROCESS_MODE_BACKGROUND_BEGIN,
+    };
+    // SAFETY: the pseudo handle of the current process needs no closing
+    if unsafe { SetPriorityClass(GetCurrentProcess(), class) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn lower(_priority: Priority) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "priorities can't be changed here"))
+}
@@ -0,0 +1,83 @@
+// Progress of a scan as JSON lines on stderr, for the programs wrapping fatass to draw their own
+// progress while they read the results on stdout:
+//
+//   {"event": "progress", "files": 18211, "bytes": 73400320, "dir": "/data/logs", "elapsed": 0.5}
+//   {"event": "done", "files": 40960, "bytes": 104857600, "dir": null, "elapsed": 1.173}
+//
+// The walkers report every file and directory here, an event is written at most every INTERVAL
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::interrupt;
+use crate::output::json_escape;
+use crate::throttle;
+
+const INTERVAL: Duration = Duration::from_millis(500);
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static FILES: AtomicU64 = AtomicU64::new(0);
+static BYTES: AtomicU64 = AtomicU64::new(0);
+// When the scan started and when the last event was written
+static CLOCK: Mutex<Option<(Instant, Instant)>> = Mutex::new(None);
+static CURRENT_DIR: Mutex<String> = Mutex::new(String::new());
+
+// Start counting for a scan, events are only written when enabled
+pub(crate) fn start(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+    FILES.store(0, Ordering::Relaxed);
+    BYTES.store(0, Ordering::Relaxed);
+    let now = Instant::now();
+    *CLOCK.lock().unwrap() = Some((now, now));
+    CURRENT_DIR.lock().unwrap().clear();
+}
+
+pub(crate) fn entered(dir: &Path) {
+    throttle::metadata();
+    if ENABLED.load(Ordering::Relaxed) {
+        *CURRENT_DIR.lock().unwrap() = dir.display().to_string();
+    }
+}
+
+pub(crate) fn scanned(bytes: u64) {
+    interrupt::scanned_file();
+    throttle::metadata();
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    FILES.fetch_add(1, Ordering::Relaxed);
+    BYTES.fetch_add(bytes, Ordering::Relaxed);
+    // Walking threads don't wait for each other to write, the next file will do
+    let Ok(mut clock) = CLOCK.try_lock() else { return };
+    let Some((started, last)) = clock.as_mut() else { return };
+    if last.elapsed() >= INTERVAL {
+        *last = Instant::now();
+        let started = *started;
+        drop(clock);
+        write_event("progress", Some(&CURRENT_DIR.lock().unwrap()), started);
+    }
+}
+
+// The last event of a scan, with the final counts
+pub(crate) fn finish() {
+    if !ENABLED.swap(false, Ordering::Relaxed) {
+        return;
+    }
+    let started = CLOCK.lock().unwrap().map_or_else(Instant::now, |(started, _)| started);
+    write_event("done", None, started);
+}
+
+fn write_event(event: &str, dir: Option<&str>, started: Instant) {
+    let line = format!(
+        "{{\"event\": \"{}\", \"files\": {}, \"bytes\": {}, \"dir\": {}, \"elapsed\": {:.3}}}",
+        event,
+        FILES.load(Ordering::Relaxed),
+        BYTES.load(Ordering::Relaxed),
+        dir.filter(|dir| !dir.is_empty()).map_or_else(|| String::from("null"), json_escape),
+        started.elapsed().as_secs_f64()
+    );
+    // Nobody left to read them is no reason to stop the scan
+    let _ = writeln!(io::stderr().lock(), "{}", line);
+}
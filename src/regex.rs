@@ -0,0 +1,446 @@
+// Small regular expression engine for --path-regex, compiled to a program run as a Pike VM so
+// matching stays linear in the path length whatever the pattern
+//
+// Supported syntax:
+//   .  [abc] [^a-z]       any character, classes and negated classes
+//   \d \w \s \D \W \S     digits, word characters, whitespace and their negations
+//   ^ $ \b                start, end and word boundary
+//   ( ) (?: ) |           groups and alternation
+//   * + ? {n} {n,} {n,m}  repetitions, a trailing '?' for lazy ones is accepted
+//
+// Like grep, a pattern matches anywhere in the text unless anchored.
+
+// Repetitions are unrolled, this keeps the program size reasonable
+const MAX_REPEAT: u32 = 1000;
+
+#[derive(Debug, Clone)]
+enum CharClass {
+    Any,
+    Literal(char),
+    Ranges { negated: bool, ranges: Vec<(char, char)> },
+}
+
+impl CharClass {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            CharClass::Any => c != '\n',
+            CharClass::Literal(literal) => c == *literal,
+            CharClass::Ranges { negated, ranges } => ranges.iter().any(|(low, high)| *low <= c && c <= *high) != *negated,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Assertion {
+    Start,
+    End,
+    WordBoundary,
+    NotWordBoundary,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Empty,
+    Char(CharClass),
+    Assert(Assertion),
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    Repeat { node: Box<Node>, min: u32, max: Option<u32> },
+}
+
+const DIGIT: &[(char, char)] = &[('0', '9')];
+const WORD: &[(char, char)] = &[('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')];
+const SPACE: &[(char, char)] = &[('\t', '\r'), (' ', ' ')];
+
+fn is_word(c: char) -> bool {
+    WORD.iter().any(|(low, high)| *low <= c && c <= *high)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn alternation(&mut self) -> Result<Node, String> {
+        let mut branches = vec![self.concatenation()?];
+        while self.eat('|') {
+            branches.push(self.concatenation()?);
+        }
+        Ok(if branches.len() == 1 { branches.pop().unwrap() } else { Node::Alternate(branches) })
+    }
+
+    fn concatenation(&mut self) -> Result<Node, String> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.atom()?;
+            nodes.push(self.repetition(atom)?);
+        }
+        Ok(match nodes.len() {
+            0 => Node::Empty,
+            1 => nodes.pop().unwrap(),
+            _ => Node::Concat(nodes),
+        })
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        let c = self.peek().ok_or("unexpected end of pattern")?;
+        self.pos += 1;
+        Ok(match c {
+            '.' => Node::Char(CharClass::Any),
+            '^' => Node::Assert(Assertion::Start),
+            '$' => Node::Assert(Assertion::End),
+            '(' => {
+                // Every group is non capturing here, "(?:" is only accepted for familiarity
+                if self.eat('?') && !self.eat(':') {
+                    return Err("only (?:...) groups are supported".to_string());
+                }
+                let inner = self.alternation()?;
+                if !self.eat(')') {
+                    return Err("unclosed group".to_string());
+                }
+                inner
+            }
+            '[' => Node::Char(self.class()?),
+            '\\' => self.escape()?,
+            '*' | '+' | '?' => return Err(format!("nothing to repeat before '{}'", c)),
+            c => Node::Char(CharClass::Literal(c)),
+        })
+    }
+
+    fn escape(&mut self) -> Result<Node, String> {
+        let c = self.peek().ok_or("trailing backslash")?;
+        self.pos += 1;
+        let ranges = |negated: bool, ranges: &[(char, char)]| Node::Char(CharClass::Ranges { negated, ranges: ranges.to_vec() });
+        Ok(match c {
+            'd' => ranges(false, DIGIT),
+            'D' => ranges(true, DIGIT),
+            'w' => ranges(false, WORD),
+            'W' => ranges(true, WORD),
+            's' => ranges(false, SPACE),
+            'S' => ranges(true, SPACE),
+            'b' => Node::Assert(Assertion::WordBoundary),
+            'B' => Node::Assert(Assertion::NotWordBoundary),
+            c => Node::Char(CharClass::Literal(escaped_char(c))),
+        })
+    }
+
+    fn class(&mut self) -> Result<CharClass, String> {
+        let negated = self.eat('^');
+        let mut ranges = Vec::new();
+        // A ']' right after the opening bracket is a literal
+        let mut first = true;
+        loop {
+            let c = self.peek().ok_or("unclosed character class")?;
+            self.pos += 1;
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+
+            let low = if c == '\\' {
+                let escaped = self.peek().ok_or("unclosed character class")?;
+                self.pos += 1;
+                match escaped {
+                    'd' => { ranges.extend_from_slice(DIGIT); continue; }
+                    'w' => { ranges.extend_from_slice(WORD); continue; }
+                    's' => { ranges.extend_from_slice(SPACE); continue; }
+                    'D' | 'W' | 'S' => return Err(format!("\\{} is not supported inside a class", escaped)),
+                    escaped => escaped_char(escaped),
+                }
+            } else {
+                c
+            };
+
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|c| *c != ']') {
+                self.pos += 1;
+                let mut high = self.peek().ok_or("unclosed character class")?;
+                self.pos += 1;
+                if high == '\\' {
+                    high = escaped_char(self.peek().ok_or("unclosed character class")?);
+                    self.pos += 1;
+                }
+                if high < low {
+                    return Err(format!("invalid range {}-{}", low, high));
+                }
+                ranges.push((low, high));
+            } else {
+                ranges.push((low, low));
+            }
+        }
+        Ok(CharClass::Ranges { negated, ranges })
+    }
+
+    fn repetition(&mut self, atom: Node) -> Result<Node, String> {
+        let mut node = atom;
+        loop {
+            let (min, max) = match self.peek() {
+                Some('*') => {
+                    self.pos += 1;
+                    (0, None)
+                }
+                Some('+') => {
+                    self.pos += 1;
+                    (1, None)
+                }
+                Some('?') => {
+                    self.pos += 1;
+                    (0, Some(1))
+                }
+                // counts() moves past the braces itself
+                Some('{') => match self.counts() {
+                    Some(counts) => counts,
+                    // Not a valid repetition, e.g. "a{", the brace is a literal
+                    None => return Ok(node),
+                },
+                _ => return Ok(node),
+            };
+            // Lazy and greedy repetitions match the same texts, only the matched span differs
+            self.eat('?');
+
+            if max.is_some_and(|max| max < min) || min > MAX_REPEAT || max.is_some_and(|max| max > MAX_REPEAT) {
+                return Err(format!("invalid repetition {{{},{}}}", min, max.map(|max| max.to_string()).unwrap_or_default()));
+            }
+            node = Node::Repeat { node: Box::new(node), min, max };
+        }
+    }
+
+    // Parse "{n}", "{n,}" or "{n,m}" and move past it, none when it isn't one
+    fn counts(&mut self) -> Option<(u32, Option<u32>)> {
+        let rest: String = self.chars[self.pos..].iter().collect();
+        let end = rest.find('}')?;
+        let body = &rest[1..end];
+        let counts = match body.split_once(',') {
+            None => {
+                let count = body.parse().ok()?;
+                (count, Some(count))
+            }
+            Some((min, "")) => (min.parse().ok()?, None),
+            Some((min, max)) => (min.parse().ok()?, Some(max.parse().ok()?)),
+        };
+        self.pos += rest[..=end].chars().count();
+        Some(counts)
+    }
+}
+
+fn escaped_char(c: char) -> char {
+    match c {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        c => c,
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Inst {
+    Char(CharClass),
+    Assert(Assertion),
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+fn compile(node: &Node, program: &mut Vec<Inst>) {
+    match node {
+        Node::Empty => {}
+        Node::Char(class) => program.push(Inst::Char(class.clone())),
+        Node::Assert(assertion) => program.push(Inst::Assert(*assertion)),
+        Node::Concat(nodes) => nodes.iter().for_each(|node| compile(node, program)),
+        Node::Alternate(branches) => {
+            // Each branch but the last is preceded by a split to the next one and ends jumping past them all
+            let mut jumps = Vec::new();
+            for (index, branch) in branches.iter().enumerate() {
+                if index + 1 < branches.len() {
+                    let split = program.len();
+                    program.push(Inst::Split(split + 1, 0));
+                    compile(branch, program);
+                    jumps.push(program.len());
+                    program.push(Inst::Jump(0));
+                    let next = program.len();
+                    program[split] = Inst::Split(split + 1, next);
+                } else {
+                    compile(branch, program);
+                }
+            }
+            let end = program.len();
+            for jump in jumps {
+                program[jump] = Inst::Jump(end);
+            }
+        }
+        Node::Repeat { node, min, max } => {
+            for _ in 0..*min {
+                compile(node, program);
+            }
+            match max {
+                None => {
+                    let split = program.len();
+                    program.push(Inst::Split(split + 1, 0));
+                    compile(node, program);
+                    program.push(Inst::Jump(split));
+                    let end = program.len();
+                    program[split] = Inst::Split(split + 1, end);
+                }
+                Some(max) => {
+                    let mut splits = Vec::new();
+                    for _ in *min..*max {
+                        splits.push(program.len());
+                        program.push(Inst::Split(0, 0));
+                        compile(node, program);
+                    }
+                    let end = program.len();
+                    for split in splits {
+                        program[split] = Inst::Split(split + 1, end);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Regex {
+    program: Vec<Inst>,
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Regex, String> {
+        let mut parser = Parser { chars: pattern.chars().collect(), pos: 0 };
+        let node = parser.alternation().map_err(|err| format!("invalid regex '{}': {}", pattern, err))?;
+        if parser.pos < parser.chars.len() {
+            return Err(format!("invalid regex '{}': unmatched ')'", pattern));
+        }
+
+        let mut program = Vec::new();
+        compile(&node, &mut program);
+        program.push(Inst::Match);
+        Ok(Regex { program })
+    }
+
+    // Whether the pattern matches anywhere in text
+    pub fn is_match(&self, text: &str) -> bool {
+        let chars: Vec<char> = text.chars().collect();
+        let mut current: Vec<usize> = Vec::new();
+        let mut next: Vec<usize> = Vec::new();
+        // The position each instruction was last added at, so every thread is only added once per step
+        let mut added: Vec<usize> = vec![usize::MAX; self.program.len()];
+
+        for pos in 0..=chars.len() {
+            // A new attempt starts at every position since the pattern isn't anchored
+            if self.add_thread(&mut current, &mut added, 0, pos, &chars) {
+                return true;
+            }
+            let Some(&c) = chars.get(pos) else { break };
+
+            for &pc in &current {
+                if let Inst::Char(class) = &self.program[pc] {
+                    if class.matches(c) && self.add_thread(&mut next, &mut added, pc + 1, pos + 1, &chars) {
+                        return true;
+                    }
+                }
+            }
+            std::mem::swap(&mut current, &mut next);
+            next.clear();
+        }
+        false
+    }
+
+    // Follow jumps, splits and assertions from pc, returns true as soon as a match is reached
+    fn add_thread(&self, threads: &mut Vec<usize>, added: &mut [usize], pc: usize, pos: usize, chars: &[char]) -> bool {
+        if added[pc] == pos {
+            return false;
+        }
+        added[pc] = pos;
+
+        match &self.program[pc] {
+            Inst::Match => true,
+            Inst::Jump(target) => self.add_thread(threads, added, *target, pos, chars),
+            Inst::Split(first, second) => {
+                self.add_thread(threads, added, *first, pos, chars) || self.add_thread(threads, added, *second, pos, chars)
+            }
+            Inst::Assert(assertion) => {
+                let before = pos.checked_sub(1).and_then(|i| chars.get(i)).is_some_and(|c| is_word(*c));
+                let after = chars.get(pos).is_some_and(|c| is_word(*c));
+                let holds = match assertion {
+                    Assertion::Start => pos == 0,
+                    Assertion::End => pos == chars.len(),
+                    Assertion::WordBoundary => before != after,
+                    Assertion::NotWordBoundary => before == after,
+                };
+                holds && self.add_thread(threads, added, pc + 1, pos, chars)
+            }
+            Inst::Char(_) => {
+                threads.push(pc);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        Regex::new(pattern).unwrap().is_match(text)
+    }
+
+    #[test]
+    fn matches_anywhere_unless_anchored() {
+        assert!(matches("log", "/var/log/syslog"));
+        assert!(!matches("^log", "/var/log/syslog"));
+        assert!(matches(r"\.log$", "/var/log/app.log"));
+        assert!(!matches(r"\.log$", "/var/log/app.log.1"));
+    }
+
+    #[test]
+    fn counted_repetitions_and_classes() {
+        assert!(matches(r"backup-\d{4}-\d{2}", "/srv/backup-2023-11/db.tar"));
+        assert!(!matches(r"backup-\d{4}-\d{2}", "/srv/backup-23-11/db.tar"));
+        assert!(matches(r"^/home/[a-z]+/\.cache/", "/home/alice/.cache/x"));
+        assert!(matches("a{2,3}b", "xaaab"));
+        assert!(!matches("^a{2,3}b", "ab"));
+        assert!(matches("[^/]*\\.iso$", "/data/ubuntu.iso"));
+    }
+
+    #[test]
+    fn groups_alternation_and_boundaries() {
+        assert!(matches(r"\.(mp4|mkv)$", "/videos/a.mkv"));
+        assert!(!matches(r"\.(mp4|mkv)$", "/videos/a.avi"));
+        assert!(matches(r"(?:ab)+c", "ababc"));
+        assert!(matches(r"\bcore\b", "/tmp/core"));
+        assert!(!matches(r"\bcore\b", "/tmp/hardcore"));
+        assert!(matches("a|", "xyz"));
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        assert!(Regex::new("(abc").is_err());
+        assert!(Regex::new("abc)").is_err());
+        assert!(Regex::new("[a-").is_err());
+        assert!(Regex::new("*a").is_err());
+        assert!(Regex::new("a{3,2}").is_err());
+    }
+
+    #[test]
+    fn pathological_patterns_stay_fast() {
+        let text = "a".repeat(5000);
+        assert!(!matches("(a*)*b", &text));
+    }
+}
@@ -0,0 +1,230 @@
+// Scans of another machine over SSH. find runs there and streams back the metadata of every file,
+// the filters, the ranking and the rendering happen here so nothing needs installing on the
+// server. -printf takes GNU find, which any Linux server has. S3 buckets are listed the same way
+// by the aws CLI, see s3.rs, and FTP and SFTP servers by lftp, see ftp.rs
+use std::fmt;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::errors::ScanErrors;
+use crate::ftp::FtpPath;
+use crate::interrupt::interrupted;
+use crate::s3::S3Prefix;
+use crate::source::{FileSource, ListOptions};
+use crate::FileEntry;
+
+// Type, size, 512 byte blocks, mtime, atime, uid, gid, mode, links, device and inode then the path,
+// NUL terminated since a path may hold anything else
+const FIND_FORMAT: &str = "%y\\t%s\\t%b\\t%T@\\t%A@\\t%U\\t%G\\t%m\\t%n\\t%D\\t%i\\t%p\\0";
+const FIELDS: usize = 12;
+// What ssh exits with when it couldn't connect, and a shell when the command isn't there
+const SSH_FAILED: i32 = 255;
+const NOT_FOUND: i32 = 127;
+
+// A path of another machine
+#[derive(Debug, Clone, PartialEq)]
+pub struct SshPath {
+    // What ssh connects to, user@host or a Host of ~/.ssh/config
+    pub host: String,
+    pub path: String,
+}
+
+impl SshPath {
+    // "user@host:/data", the home directory when nothing follows the colon
+    pub fn parse(value: &str) -> Option<SshPath> {
+        let (host, path) = value.split_once(':')?;
+        if host.is_empty() || host.starts_with('-') {
+            return None;
+        }
+        // find would take a path like "-delete" for an expression
+        let path = match path {
+            "" => String::from("."),
+            path if path.starts_with('-') => format!("./{}", path),
+            path => path.to_string(),
+        };
+        Some(SshPath { host: host.to_string(), path })
+    }
+}
+
+impl fmt::Display for SshPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.host, self.path)
+    }
+}
+
+impl FileSource for SshPath {
+    fn path(&self) -> String {
+        self.path.clone()
+    }
+
+    fn how(&self) -> String {
+        format!("on {} over SSH", self.host)
+    }
+
+    fn list(&self, options: &ListOptions, errors: &ScanErrors, visit: &mut dyn FnMut(FileEntry, Option<(u64, u64)>)) -> io::Result<()> {
+        stream_find(&self.host, &self.path, options, errors, visit)
+    }
+}
+
+// The source of a --remote value: an S3, FTP or SFTP URL, or HOST:PATH for SSH
+pub fn parse(value: &str) -> Option<Arc<dyn FileSource>> {
+    if value.starts_with("s3://") {
+        return S3Prefix::parse(value).map(|source| Arc::new(source) as Arc<dyn FileSource>);
+    }
+    if let Some(source) = FtpPath::parse(value) {
+        return Some(Arc::new(source));
+    }
+    SshPath::parse(value).map(|source| Arc::new(source) as Arc<dyn FileSource>)
+}
+
+// Single quoted for the remote shell
+fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+// The find command line, in the C locale so its error messages can be parsed
+fn find_command(path: &str, options: &ListOptions) -> String {
+    let mut words = vec![String::from("LC_ALL=C find")];
+    words.push(String::from(if options.follow_symlinks { "-L" } else { "-H" }));
+    words.push(shell_quote(path));
+    if options.one_file_system {
+        words.push(String::from("-xdev"));
+    }
+    if let Some(max_depth) = options.max_depth {
+        words.push(format!("-maxdepth {}", max_depth));
+    }
+    words.push(String::from("! -type d -printf"));
+    words.push(shell_quote(FIND_FORMAT));
+    words.join(" ")
+}
+
+// The type bits of the mode, find only prints the permissions
+pub(crate) fn type_bits(kind: &str) -> u32 {
+    match kind {
+        "l" => 0o120_000,
+        "p" => 0o010_000,
+        "s" => 0o140_000,
+        "c" => 0o020_000,
+        "b" => 0o060_000,
+        _ => 0o100_000,
+    }
+}
+
+// Seconds since the epoch with a fraction, as %T@ prints them
+fn epoch_time(seconds: &str) -> Option<SystemTime> {
+    let seconds: f64 = seconds.parse().ok()?;
+    UNIX_EPOCH.checked_add(Duration::try_from_secs_f64(seconds).ok()?)
+}
+
+// The file of a find record, with its device and inode
+fn parse_record(record: &[u8], disk_usage: bool) -> Option<(FileEntry, Option<(u64, u64)>)> {
+    let record = String::from_utf8_lossy(record);
+    let fields: Vec<&str> = record.splitn(FIELDS, '\t').collect();
+    let [kind, size, blocks, modified, accessed, uid, gid, mode, links, device, inode, path] = fields[..] else {
+        return None;
+    };
+    let size: u64 = size.parse().ok()?;
+    let mut file_entry = if disk_usage {
+        FileEntry::new(path.to_string(), blocks.parse::<u64>().ok()? * 512).with_apparent_size(size)
+    } else {
+        FileEntry::new(path.to_string(), size)
+    };
+    file_entry.modified = epoch_time(modified);
+    file_entry.accessed = epoch_time(accessed);
+    file_entry.uid = uid.parse().ok();
+    file_entry.gid = gid.parse().ok();
+    file_entry.mode = u32::from_str_radix(mode, 8).ok().map(|mode| mode | type_bits(kind));
+    file_entry.links = links.parse().ok();
+    Some((file_entry, device.parse().ok().zip(inode.parse().ok())))
+}
+
+// The path and the reason of a "find: 'path': Permission denied" message
+fn find_error(line: &str) -> Option<(&str, String)> {
+    let (quoted, kind) = line.strip_prefix("find: ")?.rsplit_once(": ")?;
+    let path = quoted.strip_prefix('\'')?.strip_suffix('\'')?;
+    Some((path, kind.to_lowercase()))
+}
+
+// Run find on the remote machine and hand over every file it lists. What find couldn't read goes
+// to the errors, what ssh has to say to stderr
+fn stream_find(host: &str, path: &str, options: &ListOptions, errors: &ScanErrors, visit: &mut dyn FnMut(FileEntry, Option<(u64, u64)>)) -> io::Result<()> {
+    let mut child = Command::new("ssh")
+        .args(["-T", "--", host, &find_command(path, options)])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let stderr = child.stderr.take().expect("stderr is piped");
+
+    let (mut listed, errors_before) = (0, errors.count());
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                match find_error(&line) {
+                    Some((path, kind)) => errors.record_kind(Path::new(path), kind),
+                    None => eprintln!("{}", line),
+                }
+            }
+        });
+        let mut stdout = BufReader::with_capacity(64 * 1024, stdout);
+        let mut record = Vec::new();
+        while !interrupted() {
+            record.clear();
+            if stdout.read_until(0, &mut record)? == 0 {
+                break;
+            }
+            if record.last() == Some(&0) {
+                record.pop();
+            }
+            if let Some((file_entry, id)) = parse_record(&record, options.disk_usage) {
+                listed += 1;
+                visit(file_entry, id);
+            }
+        }
+        // Stop the listing, which lets the stderr reader finish too
+        if interrupted() {
+            let _ = child.kill();
+        }
+        io::Result::Ok(())
+    })?;
+
+    // find exits with 1 when some paths couldn't be read, which the errors tell already. Failing
+    // without listing nor telling anything is what a find without -printf does
+    let told = errors.count() > errors_before;
+    match child.wait()?.code() {
+        Some(SSH_FAILED) => Err(io::Error::other(format!("ssh couldn't connect to {}", host))),
+        Some(NOT_FOUND) => Err(io::Error::other(format!("find isn't installed on {}", host))),
+        Some(code) if code != 0 && listed == 0 && !told && !interrupted() => Err(io::Error::other(format!("find failed on {}, -printf takes GNU find", host))),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_find_records() {
+        let record = b"f\t5000\t16\t1700000000.5\t1700000100.0000000000\t1000\t100\t644\t2\t2049\t131\t/data/a\tb.log";
+        let (file_entry, id) = parse_record(record, true).unwrap();
+        assert_eq!(file_entry.path, "/data/a\tb.log");
+        assert_eq!((file_entry.size, file_entry.apparent_size), (8192, Some(5000)));
+        assert_eq!(file_entry.modified, Some(UNIX_EPOCH + Duration::from_millis(1_700_000_000_500)));
+        assert_eq!(file_entry.mode, Some(0o100_644));
+        assert_eq!(id, Some((2049, 131)));
+        assert!(parse_record(b"f\tnope\t0", false).is_none());
+
+        assert_eq!(SshPath::parse("admin@db1:"), Some(SshPath { host: String::from("admin@db1"), path: String::from(".") }));
+        assert_eq!(SshPath::parse("-oProxyCommand=x:/"), None);
+        assert_eq!(SshPath::parse("db1:-delete").unwrap().path, "./-delete");
+        assert!(find_command(&SshPath::parse("db1:-delete").unwrap().path, &ListOptions::default()).starts_with("LC_ALL=C find -H './-delete' "));
+        assert_eq!(parse("sftp://nas/share").unwrap().to_string(), "sftp://nas/share");
+        assert_eq!(parse("s3://logs/2024").unwrap().how(), "with the aws CLI");
+        assert_eq!(find_error("find: '/root/x': Permission denied"), Some(("/root/x", String::from("permission denied"))));
+    }
+}
@@ -0,0 +1,137 @@
+// Listings of S3 buckets for fatass s3. The aws CLI makes the requests, so its credentials,
+// profiles and endpoints all apply, and streams back the keys with their size and modification
+// time. Keys become paths below s3://bucket, the prefixes up to each slash its directories
+use std::fmt;
+use std::io::{self, BufRead, BufReader};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::errors::ScanErrors;
+use crate::interrupt::interrupted;
+use crate::source::{FileSource, ListOptions};
+use crate::time::parse_date;
+use crate::FileEntry;
+
+// The objects of a bucket whose keys start with the prefix
+#[derive(Debug, Clone, PartialEq)]
+pub struct S3Prefix {
+    pub bucket: String,
+    pub prefix: String,
+}
+
+impl S3Prefix {
+    // "s3://bucket/prefix", the whole bucket without a prefix
+    pub fn parse(value: &str) -> Option<S3Prefix> {
+        let location = value.strip_prefix("s3://")?;
+        let (bucket, prefix) = location.split_once('/').unwrap_or((location, ""));
+        if bucket.is_empty() {
+            return None;
+        }
+        Some(S3Prefix { bucket: bucket.to_string(), prefix: prefix.to_string() })
+    }
+}
+
+impl fmt::Display for S3Prefix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "s3://{}/{}", self.bucket, self.prefix)
+    }
+}
+
+impl FileSource for S3Prefix {
+    fn path(&self) -> String {
+        self.to_string()
+    }
+
+    // A key prefix may end in the middle of a name, its directory is the part up to the last slash
+    fn root(&self) -> PathBuf {
+        match self.prefix.rsplit_once('/') {
+            Some((dir, _)) => PathBuf::from(format!("s3://{}/{}", self.bucket, dir)),
+            None => PathBuf::from(format!("s3://{}", self.bucket)),
+        }
+    }
+
+    fn how(&self) -> String {
+        String::from("with the aws CLI")
+    }
+
+    fn list(&self, _: &ListOptions, _: &ScanErrors, visit: &mut dyn FnMut(FileEntry, Option<(u64, u64)>)) -> io::Result<()> {
+        stream_objects(&self.bucket, &self.prefix, |file_entry| visit(file_entry, None))
+    }
+}
+
+// The object of a line of "aws s3 ls", e.g. "2024-05-01 10:22:33      52301 logs/app.log" with
+// the time in the local timezone
+fn parse_line(bucket: &str, line: &str) -> Option<FileEntry> {
+    let (date, rest) = line.split_once(' ')?;
+    let (time, rest) = rest.split_once(' ')?;
+    let (size, key) = rest.trim_start().split_once(' ')?;
+    // Keys ending with a slash are the folders of the console, not files
+    if key.is_empty() || key.ends_with('/') {
+        return None;
+    }
+    let mut file_entry = FileEntry::new(format!("s3://{}/{}", bucket, key), size.parse().ok()?);
+    file_entry.modified = parse_date(&format!("{} {}", date, time));
+    Some(file_entry)
+}
+
+// List every object whose key starts with the prefix and hand them over as they come. What aws
+// says last on stderr is the error when it fails
+fn stream_objects(bucket: &str, prefix: &str, mut visit: impl FnMut(FileEntry)) -> io::Result<()> {
+    let url = format!("s3://{}/{}", bucket, prefix);
+    let mut child = match Command::new("aws")
+        .args(["s3", "ls", "--recursive", &url])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Err(io::Error::other("the aws CLI isn't installed")),
+        spawned => spawned?,
+    };
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let stderr = child.stderr.take().expect("stderr is piped");
+
+    let mut message = None;
+    thread::scope(|scope| {
+        let told = scope.spawn(|| BufReader::new(stderr).lines().map_while(Result::ok).filter(|line| !line.trim().is_empty()).last());
+        for line in BufReader::with_capacity(64 * 1024, stdout).lines() {
+            if interrupted() {
+                // Stop the listing, which lets the stderr reader finish too
+                let _ = child.kill();
+                break;
+            }
+            if let Some(file_entry) = parse_line(bucket, &line?) {
+                visit(file_entry);
+            }
+        }
+        message = told.join().unwrap_or_default();
+        io::Result::Ok(())
+    })?;
+
+    // aws exits with 1 without a word when no key starts with the prefix, an empty listing
+    match (child.wait()?.success(), message) {
+        (false, Some(message)) if !interrupted() => Err(io::Error::other(message.trim().to_string())),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_listing_lines() {
+        let file_entry = parse_line("logs", "2024-05-01 10:22:33      52301 app/2024 05.log").unwrap();
+        assert_eq!((file_entry.path.as_str(), file_entry.size), ("s3://logs/app/2024 05.log", 52301));
+        assert_eq!(file_entry.modified, parse_date("2024-05-01 10:22:33"));
+        assert!(parse_line("logs", "2024-05-01 10:22:33          0 app/").is_none());
+        assert!(parse_line("logs", "                           PRE app/").is_none());
+
+        let bucket = S3Prefix::parse("s3://logs/2024/app-").unwrap();
+        assert_eq!(bucket, S3Prefix { bucket: String::from("logs"), prefix: String::from("2024/app-") });
+        assert_eq!((bucket.path(), bucket.root()), (String::from("s3://logs/2024/app-"), PathBuf::from("s3://logs/2024")));
+        assert_eq!(S3Prefix::parse("s3://logs").unwrap().root(), PathBuf::from("s3://logs"));
+        assert_eq!(S3Prefix::parse("s3:///key"), None);
+    }
+}
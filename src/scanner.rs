@@ -0,0 +1,1149 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, Metadata};
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use indicatif::{ProgressBar, ProgressStyle};
+use walkdir::{DirEntry, WalkDir};
+
+use crate::cache::{walk_cached, ScanCache};
+use crate::filetypes::normalize_extension;
+use crate::errors::ScanErrors;
+use crate::extents::{self, SeenExtents};
+use crate::filter::Filters;
+use crate::git::{store_of, GitStores};
+use crate::glob::Glob;
+use crate::hardlinks::{file_id, HardlinkMode, SeenLinks};
+use crate::ignore::Ignores;
+use crate::interrupt::{self, interrupted, Limit};
+use crate::macos::{self, SeenClones};
+use crate::progress;
+use crate::regex::Regex;
+use crate::source::{FileSource, ListOptions};
+use crate::size::{allocated_size, format_size};
+use crate::stats::SizeStats;
+use crate::topn::{Rank, TopN};
+use crate::{trace, verbose};
+use crate::windows;
+use crate::xattrs;
+use crate::FileEntry;
+
+// Directories already walked, by (device, inode), so that when following symlinks a loop like
+// a -> b -> a ends, and a directory linked from several places is only counted once
+#[derive(Debug, Default)]
+struct VisitedDirs {
+    enabled: bool,
+    seen: Mutex<HashSet<(u64, u64)>>,
+}
+
+impl VisitedDirs {
+    fn new(enabled: bool) -> VisitedDirs {
+        VisitedDirs { enabled, seen: Mutex::new(HashSet::new()) }
+    }
+
+    fn first_visit(&self, dir: &Path) -> bool {
+        if !self.enabled {
+            return true;
+        }
+        match fs::metadata(dir).ok().as_ref().and_then(file_id).or_else(|| windows::file_id(dir)) {
+            Some(id) => self.seen.lock().unwrap().insert(id),
+            None => true,
+        }
+    }
+}
+
+// Walk the search path, never reading the directories the filters exclude
+pub(crate) fn walk<'a>(search_path: &'a str, filters: &'a Filters, errors: &'a ScanErrors) -> impl Iterator<Item = DirEntry> + 'a {
+    let root = Path::new(search_path);
+    // Ignore rules of the directories leading to the current entry, with their depth
+    let mut ignores: Vec<(usize, Ignores)> = Vec::new();
+    let visited = VisitedDirs::new(filters.follow_symlinks);
+
+    WalkDir::new(search_path)
+        .max_depth(filters.max_depth.unwrap_or(usize::MAX))
+        .follow_links(filters.follow_symlinks)
+        .same_file_system(filters.one_file_system)
+        .into_iter()
+        .filter_entry(move |e| {
+            while ignores.last().is_some_and(|(depth, _)| *depth >= e.depth()) {
+                ignores.pop();
+            }
+            let parent_ignores = ignores.last().map(|(_, i)| i.clone()).unwrap_or_default();
+            let is_dir = e.file_type().is_dir();
+
+            if e.depth() > 0 {
+                if is_dir && e.path_is_symlink() && !filters.follows_link(e.path()) {
+                    return false;
+                }
+                if !filters.keeps_visible(e.path(), is_dir, &parent_ignores) {
+                    return false;
+                }
+                if is_dir && !filters.keeps_dir(root, e.path()) {
+                    return false;
+                }
+            }
+            if is_dir && !visited.first_visit(e.path()) {
+                trace!("Skipping {}: already walked through a symlink", e.path().display());
+                return false;
+            }
+            if is_dir {
+                trace!("Reading {}", e.path().display());
+                progress::entered(e.path());
+            }
+            if is_dir && filters.reads_ignore_files() {
+                ignores.push((e.depth(), filters.ignores_for(e.path(), &parent_ignores)));
+            }
+            true
+        })
+        .filter_map(|e| errors.walk_ok(e))
+}
+
+// Past this many files a tree is scanned in streaming mode unless told otherwise
+pub(crate) const STREAMING_THRESHOLD: usize = 100_000;
+
+// A directory left to read, with the ignore rules applying to its content
+struct WorkItem {
+    dir: PathBuf,
+    ignores: Ignores,
+    // Index of the search path it's below
+    root_index: usize,
+    // 0 for the search path itself
+    depth: usize,
+}
+
+struct WorkQueue {
+    state: Mutex<(Vec<WorkItem>, usize)>,
+    signal: Condvar,
+}
+
+impl WorkQueue {
+    fn new(roots: Vec<WorkItem>) -> WorkQueue {
+        WorkQueue {
+            state: Mutex::new((roots, 0)),
+            signal: Condvar::new(),
+        }
+    }
+
+    // Wait for a directory to read, returns none once the queue is empty and nobody can add to it anymore
+    fn pop(&self) -> Option<WorkItem> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(dir) = state.0.pop() {
+                state.1 += 1;
+                return Some(dir);
+            }
+            if state.1 == 0 {
+                return None;
+            }
+            state = self.signal.wait(state).unwrap();
+        }
+    }
+
+    fn push(&self, item: WorkItem) {
+        self.state.lock().unwrap().0.push(item);
+        self.signal.notify_one();
+    }
+
+    fn done(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.1 -= 1;
+        if state.1 == 0 && state.0.is_empty() {
+            self.signal.notify_all();
+        }
+    }
+}
+
+// The size scanned so far on spinners, then how many paths couldn't be read when some couldn't
+fn progress_note(scanned_bytes: Option<u64>, errors: &ScanErrors) -> String {
+    let mut notes: Vec<String> = scanned_bytes.map(format_size).into_iter().collect();
+    match errors.count() {
+        0 => {}
+        1 => notes.push(String::from("1 unreadable path")),
+        count => notes.push(format!("{} unreadable paths", count)),
+    }
+    if notes.is_empty() { String::new() } else { format!("({})", notes.join(", ")) }
+}
+
+pub(crate) fn spinner(enabled: bool) -> ProgressBar {
+    if !enabled {
+        return ProgressBar::hidden();
+    }
+    let progress_bar = ProgressBar::new_spinner();
+    progress_bar.set_style(ProgressStyle::with_template("[{elapsed_precise}] {spinner:.cyan} {pos} files {msg}")
+        .unwrap());
+    progress_bar.enable_steady_tick(std::time::Duration::from_millis(100));
+    progress_bar
+}
+
+// A bar sized in bytes, with the throughput and the time left, for work that takes as long as the
+// data it goes through
+pub(crate) fn byte_bar(total_bytes: u64) -> ProgressBar {
+    let progress_bar = ProgressBar::new(total_bytes);
+    progress_bar.set_style(ProgressStyle::with_template("[{elapsed_precise}] {bar:50.cyan/blue} {binary_bytes:>10}/{binary_total_bytes:10} {binary_bytes_per_sec} ETA {eta} {msg}")
+        .unwrap()
+        .progress_chars("##-"));
+    progress_bar
+}
+
+// A file sized by its length, or by the space allocated for it when measuring disk usage
+pub(crate) fn measure_file(path: &Path, metadata: &Metadata, disk_usage: bool) -> FileEntry {
+    let display_path = windows::display_path(&path.to_string_lossy()).into_owned();
+    // Cloud placeholders take nothing on the disk whatever their length
+    let file_entry = if disk_usage || windows::is_placeholder(metadata) {
+        FileEntry::new(display_path, allocated_size(path, metadata)).with_apparent_size(metadata.len())
+    } else {
+        FileEntry::new(display_path, metadata.len())
+    };
+    file_entry.with_metadata(metadata)
+}
+
+// Drop search paths nested in, or equal to, another one so nothing gets counted twice
+fn dedup_roots(search_paths: &[String]) -> Vec<String> {
+    let canonical: Vec<PathBuf> = search_paths.iter()
+        .map(|path| fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path)))
+        .collect();
+
+    // By ancestors rather than pairs, path lists can hold many thousands of them
+    let all: HashSet<&Path> = canonical.iter().map(PathBuf::as_path).collect();
+    let mut kept: HashSet<&Path> = HashSet::new();
+    search_paths.iter().zip(&canonical)
+        // Of two identical paths only the first one is kept
+        .filter(|(_, path)| !path.ancestors().skip(1).any(|ancestor| all.contains(ancestor)) && kept.insert(path))
+        .map(|(path, _)| path.clone())
+        .collect()
+}
+
+// Builds and runs a scan, e.g. `Scanner::new("/data").path("/srv").count(10).min_size(1 << 30).scan()`
+#[derive(Debug, Clone)]
+pub struct Scanner {
+    pub(crate) search_paths: Vec<String>,
+    pub(crate) fatass_count: usize,
+    pub(crate) threads: usize,
+    pub(crate) dirs: bool,
+    // Which entries to keep, the fattest, the smallest non-empty ones or the stalest
+    pub(crate) rank: Rank,
+    // List the zero byte files and the directories without any entry instead
+    pub(crate) empty: bool,
+    // Count the files below .git directories toward a single entry for each of them
+    pub(crate) git_aware: bool,
+    // Where the files are listed from when they aren't walked here, another machine or a bucket
+    pub(crate) source: Option<Arc<dyn FileSource>>,
+    pub(crate) progress: bool,
+    // Size the progress bar by bytes rather than files
+    pub(crate) byte_progress: bool,
+    // Write the progress as JSON lines on stderr, for the programs wrapping fatass
+    pub(crate) json_progress: bool,
+    pub(crate) streaming: bool,
+    pub(crate) disk_usage: bool,
+    // Count the alternate data streams of files on Windows, into their size or as entries of their own
+    pub(crate) ads: bool,
+    pub(crate) ads_entries: bool,
+    // Count resource forks and the blocks APFS clones share once, on macOS
+    pub(crate) apfs: bool,
+    pub(crate) clones: Arc<SeenClones>,
+    // Size files by their extents on Linux, those shared by reflinks counted once
+    pub(crate) physical: bool,
+    pub(crate) extents: Arc<SeenExtents>,
+    // Add what the extended attributes take to the size of files
+    pub(crate) xattrs: bool,
+    pub(crate) hardlinks: HardlinkMode,
+    // Where the directory listings are cached between runs, none to always walk everything
+    pub(crate) cache: Option<PathBuf>,
+    pub(crate) refresh_cache: bool,
+    // Whether to keep the size of every kept file for the percentiles and histogram of the stats
+    pub(crate) stats: bool,
+    // Stop after going through this many files or after this long, with partial results
+    pub(crate) max_files: Option<u64>,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) filters: Filters,
+    // What the last walk couldn't read, shared by the clones of the scanner
+    pub(crate) errors: Arc<ScanErrors>,
+    // Whether the last walk was interrupted or stopped by a limit before its end, shared alike
+    pub(crate) cut_short: Arc<AtomicBool>,
+}
+
+impl Scanner {
+    pub fn new(search_path: impl Into<String>) -> Scanner {
+        Scanner {
+            search_paths: vec![search_path.into()],
+            fatass_count: 100,
+            threads: 1,
+            dirs: false,
+            rank: Rank::Size,
+            empty: false,
+            git_aware: false,
+            source: None,
+            progress: false,
+            byte_progress: false,
+            json_progress: false,
+            streaming: false,
+            disk_usage: false,
+            ads: false,
+            ads_entries: false,
+            apfs: false,
+            clones: Arc::default(),
+            physical: false,
+            extents: Arc::default(),
+            xattrs: false,
+            hardlinks: HardlinkMode::All,
+            cache: None,
+            refresh_cache: false,
+            stats: false,
+            max_files: None,
+            timeout: None,
+            filters: Filters::default(),
+            errors: Arc::default(),
+            cut_short: Arc::default(),
+        }
+    }
+
+    // Add another search path, results of every path are merged
+    pub fn path(mut self, search_path: impl Into<String>) -> Scanner {
+        self.search_paths.push(search_path.into());
+        self
+    }
+
+    // How many entries to keep, the fattest ones
+    pub fn count(mut self, fatass_count: usize) -> Scanner {
+        self.fatass_count = fatass_count;
+        self
+    }
+
+    // Walk with a pool of threads when above 1
+    pub fn threads(mut self, threads: usize) -> Scanner {
+        self.threads = threads.max(1);
+        self
+    }
+
+    // Report directories by the total size of the files below them instead of files
+    pub fn dirs(mut self, dirs: bool) -> Scanner {
+        self.dirs = dirs;
+        self
+    }
+
+    // Keep the entries ranking best instead of the fattest: the smallest ones, smallest first and
+    // empty files still skipped, or the fattest ones that have been left alone the longest
+    pub fn rank(mut self, rank: Rank) -> Scanner {
+        self.rank = rank;
+        self
+    }
+
+    // Report the zero byte files and the empty directories below the search paths, only the
+    // directories when scanning directories
+    pub fn empty(mut self, empty: bool) -> Scanner {
+        self.empty = empty;
+        self
+    }
+
+    // Report the .git directories as single entries, sized by everything below them, instead of
+    // their objects and packs. Directory scans already do
+    pub fn git_aware(mut self, git_aware: bool) -> Scanner {
+        self.git_aware = git_aware;
+        self
+    }
+
+    // Scan the files a source lists instead of walking the search paths, e.g. a path of another
+    // machine over SSH or the objects of an S3 bucket
+    pub fn source(mut self, source: Option<Arc<dyn FileSource>>) -> Scanner {
+        if let Some(source) = &source {
+            self.search_paths = vec![source.path()];
+        }
+        self.source = source;
+        self
+    }
+
+    // Draw progress bars on stderr while scanning
+    pub fn progress(mut self, progress: bool) -> Scanner {
+        self.progress = progress;
+        self
+    }
+
+    // Count the progress bar in bytes, with the throughput and an ETA. The files are then sized
+    // while they are gathered, so the bar knows the total upfront
+    pub fn byte_progress(mut self, byte_progress: bool) -> Scanner {
+        self.byte_progress = byte_progress;
+        self
+    }
+
+    // Report the files and bytes scanned so far and the directory being read as JSON events on
+    // stderr, whether or not the bar is drawn
+    pub fn json_progress(mut self, json_progress: bool) -> Scanner {
+        self.json_progress = json_progress;
+        self
+    }
+
+    // Process files as they are found, without buffering them to size the progress bar
+    pub fn streaming(mut self, streaming: bool) -> Scanner {
+        self.streaming = streaming;
+        self
+    }
+
+    // Rank files by the space allocated for them rather than their length, sparse files get smaller
+    pub fn disk_usage(mut self, disk_usage: bool) -> Scanner {
+        self.disk_usage = disk_usage;
+        self
+    }
+
+    // Add the alternate data streams of NTFS files to their size
+    pub fn ads(mut self, ads: bool) -> Scanner {
+        self.ads = ads;
+        self
+    }
+
+    // With ads, list each stream as an entry of its own, e.g. "setup.exe:Zone.Identifier", after
+    // its file instead. Directory sizes count them either way
+    pub fn ads_entries(mut self, ads_entries: bool) -> Scanner {
+        self.ads_entries = ads_entries;
+        self
+    }
+
+    // Add the resource forks of files to their size, and with disk_usage only count the blocks of a
+    // clone not shared with the first file of its family met
+    pub fn apfs(mut self, apfs: bool) -> Scanner {
+        self.apfs = apfs;
+        self
+    }
+
+    // Size files by the extents the filesystem maps for them, the first file met with an extent
+    // shared with others counting it. Files whose extents aren't mapped count their allocated size
+    pub fn physical(mut self, physical: bool) -> Scanner {
+        self.physical = physical;
+        self
+    }
+
+    // Count the names and values of the extended attributes of files in their size
+    pub fn xattrs(mut self, xattrs: bool) -> Scanner {
+        self.xattrs = xattrs;
+        self
+    }
+
+    // Whether a file reached through several hard links counts for each of them
+    pub fn count_hardlinks(mut self, hardlinks: HardlinkMode) -> Scanner {
+        self.hardlinks = hardlinks;
+        self
+    }
+
+    // Keep the directory listings in a cache file so the next scans only read the changed ones
+    pub fn cache(mut self, cache: Option<PathBuf>) -> Scanner {
+        self.cache = cache;
+        self
+    }
+
+    // List every directory again, still updating the cache
+    pub fn refresh_cache(mut self, refresh_cache: bool) -> Scanner {
+        self.refresh_cache = refresh_cache;
+        self
+    }
+
+    // Gather the full statistics of every kept file, read back with Scan::stats
+    pub fn stats(mut self, stats: bool) -> Scanner {
+        self.stats = stats;
+        self
+    }
+
+    // How many times to try again the metadata operations failing with a transient error
+    pub fn retries(self, retries: u32) -> Scanner {
+        self.errors.set_retries(retries);
+        self
+    }
+
+    // Stop after going through this many files, read back with Scan::limit_reached
+    pub fn max_files(mut self, max_files: Option<u64>) -> Scanner {
+        self.max_files = max_files;
+        self
+    }
+
+    // Stop once the scan has run this long
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Scanner {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn min_size(mut self, min_size: u64) -> Scanner {
+        self.filters.min_size = min_size;
+        self
+    }
+
+    pub fn exclude(mut self, glob: Glob) -> Scanner {
+        self.filters.excludes.push(glob);
+        self
+    }
+
+    pub fn include(mut self, glob: Glob) -> Scanner {
+        self.filters.includes.push(glob);
+        self
+    }
+
+    // Only keep files with this extension, given with or without the dot, repeatable
+    pub fn extension(mut self, extension: &str) -> Scanner {
+        let extension = normalize_extension(extension);
+        if !extension.is_empty() && !self.filters.extensions.contains(&extension) {
+            self.filters.extensions.push(extension);
+        }
+        self
+    }
+
+    // Only keep files whose full path matches, any of them when given several times
+    pub fn path_regex(mut self, regex: Regex) -> Scanner {
+        self.filters.path_regexes.push(regex);
+        self
+    }
+
+    // Skip files and directories whose full path matches
+    pub fn path_regex_exclude(mut self, regex: Regex) -> Scanner {
+        self.filters.path_regex_excludes.push(regex);
+        self
+    }
+
+    pub fn respect_gitignore(mut self, respect_gitignore: bool) -> Scanner {
+        self.filters.respect_gitignore = respect_gitignore;
+        self
+    }
+
+    // Skip what the .fatassignore files list
+    pub fn fatassignore(mut self, fatassignore: bool) -> Scanner {
+        self.filters.fatassignore = fatassignore;
+        self
+    }
+
+    pub fn hidden(mut self, hidden: bool) -> Scanner {
+        self.filters.hidden = hidden;
+        self
+    }
+
+    // How deep below the search paths to look, 1 only keeps their direct content
+    pub fn max_depth(mut self, max_depth: Option<usize>) -> Scanner {
+        self.filters.max_depth = max_depth;
+        self
+    }
+
+    // Only keep files last modified before this time
+    pub fn modified_before(mut self, modified_before: Option<SystemTime>) -> Scanner {
+        self.filters.modified_before = modified_before;
+        self
+    }
+
+    // Only keep files last modified after this time
+    pub fn modified_after(mut self, modified_after: Option<SystemTime>) -> Scanner {
+        self.filters.modified_after = modified_after;
+        self
+    }
+
+    // Stay on the filesystem of each search path, skipping whatever is mounted below it
+    pub fn one_file_system(mut self, one_file_system: bool) -> Scanner {
+        self.filters.one_file_system = one_file_system;
+        self
+    }
+
+    // Walk into symlinked directories, each directory is still only walked once
+    pub fn follow_symlinks(mut self, follow_symlinks: bool) -> Scanner {
+        self.filters.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    // With follow_symlinks, still don't walk into the junctions of Windows
+    pub fn skip_junctions(mut self, skip_junctions: bool) -> Scanner {
+        self.filters.skip_junctions = skip_junctions;
+        self
+    }
+
+    // The search paths that will actually be walked, without the nested ones
+    pub fn roots(&self) -> Vec<String> {
+        dedup_roots(&self.search_paths)
+    }
+
+    // The paths the last scan, or the last walk of another command, couldn't read
+    pub fn errors(&self) -> &ScanErrors {
+        &self.errors
+    }
+
+    // Whether the results of the last scan are partial, for the reports built from its files
+    pub fn cut_short(&self) -> bool {
+        self.cut_short.load(Ordering::Relaxed)
+    }
+
+    pub fn is_parallel(&self) -> bool {
+        !self.dirs && self.threads > 1
+    }
+
+    // Parallel walks read every directory anyway, the cache is for single threaded scans. Streaming
+    // is for trees too big to hold, their listings included
+    fn uses_cache(&self) -> bool {
+        self.cache.is_some() && !self.is_parallel() && !self.streaming
+    }
+
+    // Where the kept entries are selected, by their rank
+    fn top_n(&self) -> TopN {
+        TopN::ranked(self.fatass_count, self.rank.clone())
+    }
+
+    // The file with its extended attributes, its resource fork and its alternate data streams in
+    // its size, unless the streams are entries of their own
+    pub(crate) fn measure(&self, path: &Path, metadata: &Metadata) -> FileEntry {
+        let mut file_entry = measure_file(path, metadata, self.disk_usage);
+        if self.physical {
+            file_entry.size = extents::physical_size(path, metadata, &self.extents).unwrap_or_else(|| allocated_size(path, metadata));
+            file_entry.apparent_size.get_or_insert(metadata.len());
+        }
+        if self.xattrs {
+            let attributes_size = xattrs::attributes_size(path);
+            file_entry.size += attributes_size;
+            if let Some(apparent_size) = &mut file_entry.apparent_size {
+                *apparent_size += attributes_size;
+            }
+        }
+        if self.apfs {
+            let attributes = macos::apfs_attributes(path);
+            if let Some((clone_id, private_size)) = attributes.clone.filter(|_| self.disk_usage) {
+                if !self.clones.first_visit(clone_id) {
+                    file_entry.size = private_size;
+                }
+            }
+            file_entry.size += if self.disk_usage { attributes.fork_allocated } else { attributes.fork_length };
+            if let Some(apparent_size) = &mut file_entry.apparent_size {
+                *apparent_size += attributes.fork_length;
+            }
+        }
+        if !self.ads || self.ads_entries {
+            return file_entry;
+        }
+        for (_, length, allocated) in windows::alternate_streams(path) {
+            file_entry.size += if self.disk_usage { allocated } else { length };
+            if let Some(apparent_size) = &mut file_entry.apparent_size {
+                *apparent_size += length;
+            }
+        }
+        file_entry
+    }
+
+    // The alternate data streams of a measured file when they're entries of their own
+    pub(crate) fn stream_entries(&self, file_entry: &FileEntry) -> Vec<FileEntry> {
+        if !self.ads || !self.ads_entries {
+            return Vec::new();
+        }
+        windows::alternate_streams(Path::new(&file_entry.path)).into_iter()
+            .map(|(name, length, allocated)| FileEntry {
+                path: format!("{}:{}", file_entry.path, name),
+                size: if self.disk_usage { allocated } else { length },
+                apparent_size: self.disk_usage.then_some(length),
+                ..file_entry.clone()
+            })
+            .collect()
+    }
+
+    // The file to the entries, or to the .git directory it's below when git aware
+    fn keep_file(&self, file_entry: FileEntry, biggest_files: &mut TopN, git_stores: &mut GitStores, on_candidate: &(dyn Fn(&FileEntry) + Sync)) {
+        match store_of(Path::new(&file_entry.path)).filter(|_| self.git_aware) {
+            Some(store) => git_stores.add(store, &file_entry),
+            None => {
+                on_candidate(&file_entry);
+                biggest_files.push(file_entry);
+            }
+        }
+    }
+
+    // The .git directories once all of their files are counted
+    fn keep_git_stores(&self, git_stores: GitStores, biggest_files: &mut TopN, on_candidate: &(dyn Fn(&FileEntry) + Sync)) {
+        for store_entry in git_stores.into_entries(self.disk_usage) {
+            on_candidate(&store_entry);
+            biggest_files.push(store_entry);
+        }
+    }
+
+    pub fn scan(&self) -> Scan {
+        self.scan_with(&|_| {})
+    }
+
+    // Same as scan, calling on_candidate with every file kept by the filters as it's found, from
+    // the walking threads of parallel scans. Directory scans only know their sizes at the end
+    pub fn scan_with(&self, on_candidate: &(dyn Fn(&FileEntry) + Sync)) -> Scan {
+        self.errors.clear();
+        self.clones.clear();
+        self.extents.clear();
+        let mut stats = SizeStats::new(self.stats);
+        let walk_start = Instant::now();
+        let how = match (&self.source, &self.cache) {
+            (Some(source), _) => source.how(),
+            (None, Some(_)) if self.uses_cache() => String::from("single threaded, with the cache"),
+            _ if self.is_parallel() => format!("with {} threads", self.threads),
+            _ => String::from("single threaded"),
+        };
+        verbose!("Scanning {} {}", self.roots().join(", "), how);
+        interrupt::limit_scan(self.max_files, self.timeout);
+        progress::start(self.json_progress);
+        let entries = match &self.cache {
+            _ if self.source.is_some() => self.scan_source(&mut stats, on_candidate),
+            // Empty directories are the ones neither listings nor sizes keep track of
+            _ if self.empty => self.scan_empty(&mut stats, on_candidate),
+            Some(cache_path) if self.uses_cache() => self.scan_cached(cache_path, &mut stats, on_candidate),
+            _ if self.dirs => self.scan_dirs(&mut stats),
+            _ if self.is_parallel() => self.scan_parallel(&mut stats, on_candidate),
+            _ => self.scan_files(&mut stats, on_candidate),
+        };
+        stats.finish();
+        progress::finish();
+        let limit = interrupt::end_scan();
+        verbose!(
+            "Walked {} matching files ({}) in {:?}, kept {} entries, {} unreadable paths",
+            stats.files(), format_size(stats.total()), walk_start.elapsed(), entries.len(), self.errors.count()
+        );
+        if self.physical {
+            let (shared_bytes, compressed_files) = self.extents.found();
+            verbose!("Counted {} of shared extents once, {} files have compressed extents", format_size(shared_bytes), compressed_files);
+        }
+        let interrupted = limit.is_some() || interrupted();
+        self.cut_short.store(interrupted, Ordering::Relaxed);
+        Scan { entries: entries.into_iter(), stats, interrupted, limit }
+    }
+
+    // Buffer the first files so the progress bar knows how many to expect, past STREAMING_THRESHOLD
+    // the tree is considered huge and entries are processed as they arrive with a spinner instead
+    fn scan_files(&self, stats: &mut SizeStats, on_candidate: &(dyn Fn(&FileEntry) + Sync)) -> Vec<FileEntry> {
+        let filters = &self.filters;
+        let roots = self.roots();
+        let mut candidates = roots.iter().flat_map(|root| {
+            walk(root, filters, &self.errors)
+                .filter(|e| !e.file_type().is_dir())
+                .filter(|e| filters.keeps_file(Path::new(root), e.path()))
+        });
+
+        // Listing entries only needs the directory reads, no metadata calls, unless the bar counts
+        // bytes and needs their sizes
+        let mut buffered: Vec<(DirEntry, Option<walkdir::Result<Metadata>>)> = Vec::new();
+        if self.progress && !self.streaming {
+            let gathered = candidates.by_ref().take_while(|_| !interrupted()).take(STREAMING_THRESHOLD + 1);
+            buffered.extend(gathered.map(|entry| {
+                let metadata = self.byte_progress.then(|| entry.metadata());
+                (entry, metadata)
+            }));
+        }
+        let streaming = self.streaming || buffered.len() > STREAMING_THRESHOLD;
+
+        let progress_bar = if !self.progress {
+            ProgressBar::hidden()
+        } else if streaming {
+            spinner(true)
+        } else if self.byte_progress {
+            let total_bytes = buffered.iter().filter_map(|(_, metadata)| metadata.as_ref()?.as_ref().ok()).map(Metadata::len).sum();
+            byte_bar(total_bytes)
+        } else {
+            let progress_bar = ProgressBar::new(buffered.len() as u64);
+            progress_bar.set_style(ProgressStyle::with_template("[{elapsed_precise}] {bar:50.cyan/blue} {pos:>7}/{len:7} {msg}")
+                .unwrap()
+                .progress_chars("##-"));
+            progress_bar
+        };
+
+        let mut biggest_files = self.top_n();
+        let mut git_stores = GitStores::default();
+        let mut scanned_bytes: u64 = 0;
+        let seen_links = SeenLinks::new(self.hardlinks);
+        // Buffered files are measured even after an interrupt or a timeout, they are all there is
+        // to show, only --max-files stops them
+        let buffered_len = buffered.len();
+        let rest = candidates.map(|entry| (entry, None));
+        for (i, (entry, metadata)) in buffered.into_iter().chain(rest).enumerate() {
+            if interrupted() && (i >= buffered_len || interrupt::limit_reached() == Some(Limit::Files)) {
+                break;
+            }
+            let metadata = match metadata {
+                Some(Ok(metadata)) => Some(metadata),
+                _ => self.errors.walk_metadata(&entry),
+            };
+            let length = metadata.as_ref().map_or(0, Metadata::len);
+            let file_entry = metadata
+                .filter(|metadata| seen_links.first_visit(metadata))
+                .map(|metadata| self.measure(entry.path(), &metadata));
+            let size = file_entry.as_ref().map(|e| e.size).unwrap_or(0);
+            let stream_entries = file_entry.as_ref().map(|file_entry| self.stream_entries(file_entry)).unwrap_or_default();
+            for file_entry in file_entry.into_iter().chain(stream_entries).filter(|file_entry| filters.keeps_entry(file_entry)) {
+                stats.record(file_entry.size);
+                self.keep_file(file_entry, &mut biggest_files, &mut git_stores, on_candidate);
+            }
+
+            scanned_bytes += size;
+            if i % 256 == 0 {
+                progress_bar.set_message(progress_note(streaming.then_some(scanned_bytes), &self.errors));
+            }
+            progress_bar.inc(if self.byte_progress && !streaming { length } else { 1 });
+            progress::scanned(size);
+        }
+        progress_bar.set_message(progress_note(streaming.then_some(scanned_bytes), &self.errors));
+        progress_bar.finish();
+
+        self.keep_git_stores(git_stores, &mut biggest_files, on_candidate);
+        biggest_files.into_sorted_vec()
+    }
+
+    // Walk the tree with a pool of threads, each keeping its own biggest files, then merge them
+    // Parallel walks can't know the file count upfront, so the progress only counts scanned files
+    fn scan_parallel(&self, stats: &mut SizeStats, on_candidate: &(dyn Fn(&FileEntry) + Sync)) -> Vec<FileEntry> {
+        let filters = &self.filters;
+        let roots: Vec<PathBuf> = self.roots().into_iter().map(PathBuf::from).collect();
+        let progress_bar = spinner(self.progress);
+        let seen_links = SeenLinks::new(self.hardlinks);
+        let visited = VisitedDirs::new(filters.follow_symlinks);
+
+        // Search paths pointing to files don't need a worker
+        let mut biggest_files = self.top_n();
+        let mut root_dirs: Vec<WorkItem> = Vec::new();
+        for (index, root) in roots.iter().enumerate() {
+            if root.is_dir() {
+                visited.first_visit(root);
+                if filters.max_depth != Some(0) {
+                    let ignores = filters.ignores_for(root, &Ignores::default());
+                    root_dirs.push(WorkItem { dir: root.clone(), ignores, root_index: index, depth: 0 });
+                }
+                continue;
+            }
+            let mut size = 0;
+            if let Some(metadata) = self.errors.retry(root, || fs::metadata(root)).filter(|metadata| seen_links.first_visit(metadata)) {
+                let file_entry = self.measure(root, &metadata);
+                size = file_entry.size;
+                let stream_entries = self.stream_entries(&file_entry);
+                for file_entry in iter::once(file_entry).chain(stream_entries).filter(|file_entry| filters.keeps_entry(file_entry)) {
+                    stats.record(file_entry.size);
+                    on_candidate(&file_entry);
+                    biggest_files.push(file_entry);
+                }
+            }
+            progress_bar.inc(1);
+            progress::scanned(size);
+        }
+        let queue = WorkQueue::new(root_dirs);
+        // Devices of the search paths, where the walk stays with --one-file-system
+        let root_devices: Vec<Option<u64>> = roots.iter()
+            .map(|root| fs::metadata(root).ok().as_ref().and_then(file_id).map(|(dev, _)| dev))
+            .collect();
+
+        let per_thread: Vec<(TopN, SizeStats, GitStores)> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..self.threads).map(|_| {
+                scope.spawn(|| {
+                    let mut biggest_files = self.top_n();
+                    let mut thread_stats = SizeStats::new(self.stats);
+                    let mut git_stores = GitStores::default();
+                    while let Some(WorkItem { dir, ignores, root_index: index, depth }) = queue.pop() {
+                        // Drain the queue without reading anything more
+                        if interrupted() {
+                            queue.done();
+                            continue;
+                        }
+                        let root = roots[index].as_path();
+                        trace!("Reading {}", dir.display());
+                        progress::entered(&dir);
+                        if let Some(entries) = self.errors.retry(&dir, || fs::read_dir(&dir)) {
+                            for entry in entries.filter_map(|e| self.errors.io_ok(&dir, e)) {
+                                // Limits stop the scan within a directory too
+                                if interrupted() {
+                                    break;
+                                }
+                                let path = entry.path();
+                                let Some(mut file_type) = self.errors.io_ok(&path, entry.file_type()) else { continue };
+                                // Following a symlink means looking at its target, dangling ones are skipped
+                                let mut target_metadata = None;
+                                if file_type.is_symlink() && filters.follows_link(&path) {
+                                    let Ok(metadata) = fs::metadata(&path) else { continue };
+                                    file_type = metadata.file_type();
+                                    target_metadata = Some(metadata);
+                                }
+                                if !filters.keeps_visible(&path, file_type.is_dir(), &ignores) {
+                                    continue;
+                                }
+                                if file_type.is_dir() {
+                                    if filters.one_file_system {
+                                        let metadata = target_metadata.or_else(|| entry.metadata().ok());
+                                        let device = metadata.as_ref().and_then(file_id).map(|(dev, _)| dev);
+                                        if device.is_some() && device != root_devices[index] {
+                                            continue;
+                                        }
+                                    }
+                                    // The content of a directory at the max depth would be past it
+                                    let within_depth = filters.max_depth.is_none_or(|max_depth| depth + 1 < max_depth);
+                                    if within_depth && filters.keeps_dir(root, &path) && visited.first_visit(&path) {
+                                        let child_ignores = filters.ignores_for(&path, &ignores);
+                                        queue.push(WorkItem { dir: path, ignores: child_ignores, root_index: index, depth: depth + 1 });
+                                    }
+                                    continue;
+                                }
+                                if !filters.keeps_file(root, &path) {
+                                    continue;
+                                }
+
+                                let metadata = target_metadata.or_else(|| self.errors.retry(&path, || entry.metadata()));
+                                let mut size = 0;
+                                if let Some(metadata) = metadata.filter(|metadata| seen_links.first_visit(metadata)) {
+                                    let file_entry = self.measure(&path, &metadata);
+                                    size = file_entry.size;
+                                    let stream_entries = self.stream_entries(&file_entry);
+                                    for file_entry in iter::once(file_entry).chain(stream_entries).filter(|file_entry| filters.keeps_entry(file_entry)) {
+                                        thread_stats.record(file_entry.size);
+                                        self.keep_file(file_entry, &mut biggest_files, &mut git_stores, on_candidate);
+                                    }
+                                }
+                                progress_bar.inc(1);
+                                progress::scanned(size);
+                            }
+                        }
+                        progress_bar.set_message(progress_note(None, &self.errors));
+                        queue.done();
+                    }
+                    (biggest_files, thread_stats, git_stores)
+                })
+            }).collect();
+
+            workers.into_iter().map(|worker| worker.join().unwrap()).collect()
+        });
+        progress_bar.finish();
+
+        let mut git_stores = GitStores::default();
+        for (thread_files, thread_stats, thread_stores) in per_thread {
+            biggest_files.merge(thread_files);
+            stats.merge(thread_stats);
+            git_stores.merge(thread_stores);
+        }
+        self.keep_git_stores(git_stores, &mut biggest_files, on_candidate);
+        biggest_files.into_sorted_vec()
+    }
+
+    // Files or directories alike, with the listings of the unchanged directories taken from the cache
+    fn scan_cached(&self, cache_path: &Path, stats: &mut SizeStats, on_candidate: &(dyn Fn(&FileEntry) + Sync)) -> Vec<FileEntry> {
+        let filters = &self.filters;
+        let progress_bar = spinner(self.progress);
+        let seen_links = SeenLinks::new(self.hardlinks);
+        let mut cache = ScanCache::load(cache_path);
+
+        let mut biggest_files = self.top_n();
+        let mut git_stores = GitStores::default();
+        let mut dir_sizes: HashMap<PathBuf, (u64, u64)> = HashMap::new();
+        for search_path in self.roots() {
+            let root = Path::new(&search_path);
+            walk_cached(&search_path, filters, &mut cache, self.refresh_cache, &self.errors, |path, metadata| {
+                progress_bar.inc(1);
+                if !seen_links.first_visit(metadata) {
+                    progress::scanned(0);
+                    return;
+                }
+                let file_entry = self.measure(path, metadata);
+                progress::scanned(file_entry.size);
+                if !filters.keeps_entry(&file_entry) {
+                    return;
+                }
+                stats.record(file_entry.size);
+                if self.dirs {
+                    add_to_dirs(&mut dir_sizes, root, path, &file_entry);
+                } else {
+                    self.keep_file(file_entry, &mut biggest_files, &mut git_stores, on_candidate);
+                }
+            });
+        }
+        progress_bar.finish();
+        // A cache that can't be written only costs the next scan its speed, an interrupted scan
+        // would leave out the directories it didn't get to
+        if !interrupted() {
+            let _ = cache.save(cache_path);
+        }
+
+        if self.dirs {
+            self.biggest_dirs(dir_sizes)
+        } else {
+            self.keep_git_stores(git_stores, &mut biggest_files, on_candidate);
+            biggest_files.into_sorted_vec()
+        }
+    }
+
+    // Sum every file size into all of its parent directories up to the search path
+    fn scan_dirs(&self, stats: &mut SizeStats) -> Vec<FileEntry> {
+        let filters = &self.filters;
+        let progress_bar = spinner(self.progress);
+        let seen_links = SeenLinks::new(self.hardlinks);
+
+        // Sizes and apparent sizes, the latter only differ when measuring disk usage
+        let mut dir_sizes: HashMap<PathBuf, (u64, u64)> = HashMap::new();
+        for search_path in self.roots() {
+            let root = Path::new(&search_path);
+            for entry in walk(&search_path, filters, &self.errors)
+                .filter(|e| !e.file_type().is_dir())
+                .filter(|e| filters.keeps_file(root, e.path()))
+            {
+                if interrupted() {
+                    break;
+                }
+                let file_entry = self.errors.walk_metadata(&entry)
+                    .filter(|metadata| seen_links.first_visit(metadata))
+                    .map(|metadata| self.measure(entry.path(), &metadata));
+                progress::scanned(file_entry.as_ref().map_or(0, |file_entry| file_entry.size));
+                let stream_entries = file_entry.as_ref().map(|file_entry| self.stream_entries(file_entry)).unwrap_or_default();
+                for file_entry in file_entry.into_iter().chain(stream_entries).filter(|file_entry| filters.keeps_entry(file_entry)) {
+                    stats.record(file_entry.size);
+                    add_to_dirs(&mut dir_sizes, root, entry.path(), &file_entry);
+                }
+                progress_bar.inc(1);
+                if progress_bar.position().is_multiple_of(256) {
+                    progress_bar.set_message(progress_note(None, &self.errors));
+                }
+            }
+        }
+        progress_bar.finish();
+        self.biggest_dirs(dir_sizes)
+    }
+
+    // The files a source lists, through the filters the walk would apply. Directories are the
+    // prefixes of the paths, key prefixes for buckets
+    fn scan_source(&self, stats: &mut SizeStats, on_candidate: &(dyn Fn(&FileEntry) + Sync)) -> Vec<FileEntry> {
+        let Some(source) = &self.source else { return Vec::new() };
+        let filters = &self.filters;
+        let options = ListOptions {
+            follow_symlinks: filters.follow_symlinks,
+            one_file_system: filters.one_file_system,
+            max_depth: filters.max_depth,
+            disk_usage: self.disk_usage,
+        };
+        let root = source.root();
+        let root = root.as_path();
+        let progress_bar = spinner(self.progress);
+        let seen_links = SeenLinks::new(self.hardlinks);
+
+        let mut biggest_files = self.top_n();
+        let mut dir_sizes: HashMap<PathBuf, (u64, u64)> = HashMap::new();
+        let mut scanned_bytes: u64 = 0;
+        let listed = source.list(&options, &self.errors, &mut |file_entry, id| {
+            let path = PathBuf::from(&file_entry.path);
+            scanned_bytes += file_entry.size;
+            progress::scanned(file_entry.size);
+            progress_bar.inc(1);
+            if progress_bar.position().is_multiple_of(256) {
+                progress_bar.set_message(progress_note(Some(scanned_bytes), &self.errors));
+            }
+            if !seen_links.first_visit_stat(file_entry.links, id) || !filters.keeps_listed(root, &path) || !filters.keeps_entry(&file_entry) {
+                return;
+            }
+            stats.record(file_entry.size);
+            if self.dirs {
+                add_to_dirs(&mut dir_sizes, root, &path, &file_entry);
+            } else {
+                on_candidate(&file_entry);
+                biggest_files.push(file_entry);
+            }
+        });
+        // The connection failing is an error of the whole search path
+        if let Err(err) = listed {
+            self.errors.record_kind(Path::new(&source.to_string()), err.to_string());
+        }
+        progress_bar.set_message(progress_note(Some(scanned_bytes), &self.errors));
+        progress_bar.finish();
+
+        if self.dirs { self.biggest_dirs(dir_sizes) } else { biggest_files.into_sorted_vec() }
+    }
+
+    // Zero byte files and directories without a single entry, hidden ones included, the search
+    // paths themselves aren't reported
+    fn scan_empty(&self, stats: &mut SizeStats, on_candidate: &(dyn Fn(&FileEntry) + Sync)) -> Vec<FileEntry> {
+        let filters = &self.filters;
+        let progress_bar = spinner(self.progress);
+
+        let mut empty_entries = self.top_n();
+        for search_path in self.roots() {
+            let root = Path::new(&search_path);
+            for entry in walk(&search_path, filters, &self.errors) {
+                if interrupted() {
+                    break;
+                }
+                let is_dir = entry.file_type().is_dir();
+                if !is_dir && (self.dirs || !filters.keeps_file(root, entry.path())) {
+                    continue;
+                }
+                let Some(metadata) = self.errors.walk_metadata(&entry) else { continue };
+                progress_bar.inc(1);
+                progress::scanned(if is_dir { 0 } else { metadata.len() });
+                let empty = if is_dir {
+                    entry.depth() > 0 && self.errors.retry(entry.path(), || fs::read_dir(entry.path())).is_some_and(|mut entries| entries.next().is_none())
+                } else {
+                    metadata.len() == 0
+                };
+                if !empty || !filters.keeps_age(metadata.modified().ok()) {
+                    continue;
+                }
+                let empty_entry = if is_dir {
+                    FileEntry::new_dir(entry.path().display().to_string(), 0).with_metadata(&metadata)
+                } else {
+                    measure_file(entry.path(), &metadata, self.disk_usage)
+                };
+                stats.record(0);
+                on_candidate(&empty_entry);
+                empty_entries.push(empty_entry);
+            }
+        }
+        progress_bar.finish();
+        empty_entries.into_sorted_vec()
+    }
+
+    fn biggest_dirs(&self, dir_sizes: HashMap<PathBuf, (u64, u64)>) -> Vec<FileEntry> {
+        let mut biggest_dirs = self.top_n();
+        for (dir, (size, apparent_size)) in dir_sizes {
+            let dir_entry = FileEntry::new_dir(dir.display().to_string(), size);
+            biggest_dirs.push(if self.disk_usage { dir_entry.with_apparent_size(apparent_size) } else { dir_entry });
+        }
+        // Only the kept directories need their own timestamps, which aren't here for remote ones
+        if self.source.is_some() {
+            return biggest_dirs.into_sorted_vec();
+        }
+        biggest_dirs.into_sorted_vec()
+            .into_iter()
+            .map(|dir| match fs::metadata(&dir.path) {
+                Ok(metadata) => dir.with_metadata(&metadata),
+                Err(_) => dir,
+            })
+            .collect()
+    }
+}
+
+// Add the file to the sizes and apparent sizes of every directory from its parent up to the root
+fn add_to_dirs(dir_sizes: &mut HashMap<PathBuf, (u64, u64)>, root: &Path, path: &Path, file_entry: &FileEntry) {
+    let (size, apparent_size) = (file_entry.size, file_entry.apparent_size.unwrap_or(file_entry.size));
+    for dir in path.ancestors().skip(1).take_while(|dir| dir.starts_with(root)) {
+        let sizes = dir_sizes.entry(dir.to_path_buf()).or_insert((0, 0));
+        sizes.0 += size;
+        sizes.1 += apparent_size;
+    }
+}
+
+// The fattest entries found by a scan, biggest first
+#[derive(Debug)]
+pub struct Scan {
+    entries: std::vec::IntoIter<FileEntry>,
+    stats: SizeStats,
+    interrupted: bool,
+    limit: Option<Limit>,
+}
+
+impl Scan {
+    // Statistics of every file the scan kept, only the count and total unless the scanner was asked for them
+    pub fn stats(&self) -> &SizeStats {
+        &self.stats
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.stats.total()
+    }
+
+    // Whether Ctrl-C or a limit stopped the scan early, the entries are then the fattest found until then
+    pub fn interrupted(&self) -> bool {
+        self.interrupted
+    }
+
+    // The limit that stopped the scan, none when it went through everything or Ctrl-C stopped it
+    pub fn limit_reached(&self) -> Option<Limit> {
+        self.limit
+    }
+}
+
+impl Iterator for Scan {
+    type Item = FileEntry;
+
+    fn next(&mut self) -> Option<FileEntry> {
+        self.entries.next()
+    }
+}
@@ -0,0 +1,160 @@
+// Scripts carrying out an action on the results, written to be reviewed and run later under change
+// control rather than run by fatass. Every path is single quoted for the shell reading it, which
+// keeps spaces, globs, dollars and newlines literal
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::time::format_time;
+use crate::{format_size, EntryKind, FileEntry};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScriptAction {
+    Remove,
+    Trash,
+    // Gzip files, tar and gzip directories
+    Compress,
+    // Into a directory, never over an existing entry
+    Move,
+}
+
+impl ScriptAction {
+    pub const NAMES: [&'static str; 4] = ["rm", "trash", "compress", "mv"];
+
+    pub fn parse(value: &str) -> Option<ScriptAction> {
+        match value {
+            "rm" => Some(ScriptAction::Remove),
+            "trash" => Some(ScriptAction::Trash),
+            "compress" => Some(ScriptAction::Compress),
+            "mv" => Some(ScriptAction::Move),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScriptShell {
+    Sh,
+    PowerShell,
+}
+
+impl ScriptShell {
+    // PowerShell for .ps1 files, sh for anything else
+    pub fn for_path(path: &Path) -> ScriptShell {
+        match path.extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("ps1") => ScriptShell::PowerShell,
+            _ => ScriptShell::Sh,
+        }
+    }
+
+    // Single quotes keep everything literal but themselves: sh closes the string around an escaped
+    // one, PowerShell doubles them, curly ones included since it takes those as quotes too
+    pub fn quote(self, value: &str) -> String {
+        let mut quoted = String::from("'");
+        for c in value.chars() {
+            match (self, c) {
+                (ScriptShell::Sh, '\'') => quoted.push_str("'\\''"),
+                (ScriptShell::PowerShell, '\'' | '\u{2018}' | '\u{2019}' | '\u{201a}' | '\u{201b}') => {
+                    quoted.push(c);
+                    quoted.push(c);
+                }
+                _ => quoted.push(c),
+            }
+        }
+        quoted.push('\'');
+        quoted
+    }
+}
+
+// The command carrying out the action on the entry, moves need the destination directory
+fn command(entry: &FileEntry, action: ScriptAction, shell: ScriptShell, destination: Option<&str>) -> String {
+    let path = shell.quote(&entry.path);
+    let is_dir = entry.kind == EntryKind::Directory;
+    let name = Path::new(&entry.path).file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let target = shell.quote(&Path::new(destination.unwrap_or(".")).join(&name).to_string_lossy());
+    match (shell, action) {
+        (ScriptShell::Sh, ScriptAction::Remove) => format!("rm {} -- {}", if is_dir { "-rf" } else { "-f" }, path),
+        (ScriptShell::Sh, ScriptAction::Trash) if cfg!(target_os = "macos") => format!("trash {}", path),
+        (ScriptShell::Sh, ScriptAction::Trash) => format!("gio trash -- {}", path),
+        (ScriptShell::Sh, ScriptAction::Compress) if is_dir => {
+            format!("tar -czf {} -- {} && rm -rf -- {}", shell.quote(&format!("{}.tar.gz", entry.path)), path, path)
+        }
+        (ScriptShell::Sh, ScriptAction::Compress) => format!("gzip -- {}", path),
+        // Moving to the full target fails rather than renaming when the directory doesn't exist
+        (ScriptShell::Sh, ScriptAction::Move) => format!("mv -n -- {} {}", path, target),
+        (ScriptShell::PowerShell, ScriptAction::Remove) => format!("Remove-Item -LiteralPath {} -Force{}", path, if is_dir { " -Recurse" } else { "" }),
+        (ScriptShell::PowerShell, ScriptAction::Trash) => {
+            let method = if is_dir { "DeleteDirectory" } else { "DeleteFile" };
+            format!("[Microsoft.VisualBasic.FileIO.FileSystem]::{}({}, 'OnlyErrorDialogs', 'SendToRecycleBin')", method, path)
+        }
+        (ScriptShell::PowerShell, ScriptAction::Compress) => format!(
+            "Compress-Archive -LiteralPath {} -DestinationPath {}; Remove-Item -LiteralPath {} -Force{}",
+            path, shell.quote(&format!("{}.zip", entry.path)), path, if is_dir { " -Recurse" } else { "" }
+        ),
+        (ScriptShell::PowerShell, ScriptAction::Move) => format!("Move-Item -LiteralPath {} -Destination {}", path, target),
+    }
+}
+
+// The script, a header saying what it is then a command per entry with its size
+pub fn write_script(out: &mut dyn Write, entries: &[FileEntry], action: ScriptAction, shell: ScriptShell, destination: Option<&str>) -> io::Result<()> {
+    let total: u64 = entries.iter().map(|entry| entry.size).sum();
+    if shell == ScriptShell::Sh {
+        writeln!(out, "#!/bin/sh")?;
+    }
+    writeln!(out, "# Written by fatass on {}, {} entries taking {}", format_time(SystemTime::now()), entries.len(), format_size(total))?;
+    writeln!(out, "# Review it before running it, it stops at the first command that fails")?;
+    match shell {
+        ScriptShell::Sh => writeln!(out, "set -e")?,
+        ScriptShell::PowerShell => {
+            writeln!(out, "$ErrorActionPreference = 'Stop'")?;
+            if action == ScriptAction::Trash {
+                writeln!(out, "Add-Type -AssemblyName Microsoft.VisualBasic")?;
+            }
+        }
+    }
+    writeln!(out)?;
+    for entry in entries {
+        writeln!(out, "{}  # {}", command(entry, action, shell, destination), entry.get_str_size())?;
+    }
+    Ok(())
+}
+
+// Write the script to the file, the shell kind following its extension, executable on Unix
+pub fn create_script(path: &Path, entries: &[FileEntry], action: ScriptAction, destination: Option<&str>) -> io::Result<()> {
+    let mut writer = io::BufWriter::new(fs::File::create(path)?);
+    write_script(&mut writer, entries, action, ScriptShell::for_path(path), destination)?;
+    writer.flush()?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_paths_for_each_shell() {
+        assert_eq!(ScriptShell::Sh.quote("it's $HOME/*.log"), "'it'\\''s $HOME/*.log'");
+        assert_eq!(ScriptShell::PowerShell.quote("it's \u{2019}$env:TEMP\u{2019}"), "'it''s \u{2019}\u{2019}$env:TEMP\u{2019}\u{2019}'");
+        assert_eq!(ScriptShell::for_path(Path::new("cleanup.PS1")), ScriptShell::PowerShell);
+        assert_eq!(ScriptShell::for_path(Path::new("rm.sh")), ScriptShell::Sh);
+
+        let file = FileEntry::new(String::from("/var/log/old app.log"), 10);
+        let dir = FileEntry::new_dir(String::from("/srv/cache"), 10);
+        assert_eq!(command(&file, ScriptAction::Remove, ScriptShell::Sh, None), "rm -f -- '/var/log/old app.log'");
+        assert_eq!(command(&dir, ScriptAction::Compress, ScriptShell::Sh, None), "tar -czf '/srv/cache.tar.gz' -- '/srv/cache' && rm -rf -- '/srv/cache'");
+        assert_eq!(command(&file, ScriptAction::Move, ScriptShell::Sh, Some("/mnt/archive")), "mv -n -- '/var/log/old app.log' '/mnt/archive/old app.log'");
+        assert_eq!(command(&dir, ScriptAction::Remove, ScriptShell::PowerShell, None), "Remove-Item -LiteralPath '/srv/cache' -Force -Recurse");
+
+        let mut script = Vec::new();
+        write_script(&mut script, &[file], ScriptAction::Compress, ScriptShell::Sh, None).unwrap();
+        let script = String::from_utf8(script).unwrap();
+        assert!(script.starts_with("#!/bin/sh\n"));
+        assert!(script.contains("\nset -e\n\ngzip -- '/var/log/old app.log'  # "));
+    }
+}
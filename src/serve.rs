@@ -0,0 +1,169 @@
+// The HTML report of fatass serve: a page per directory with its subdirectories by recursive size
+// and the fattest files below it, linked to each other to drill down. Requests are answered one at
+// a time, a report is read by a few people, not served to the world
+//
+//   GET /                  the directory holding every file
+//   GET /?dir=%2Fdata%2Fa  the directory /data/a
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::markup::{write_html_page, Markup};
+use crate::time::format_time;
+use crate::tree::{dir_name, DirTree, TOP};
+use crate::{format_size, FileEntry};
+
+#[derive(Debug)]
+pub struct Report {
+    // Biggest first
+    files: Vec<FileEntry>,
+    tree: DirTree,
+    // How many files each page lists
+    count: usize,
+}
+
+impl Report {
+    pub fn new(mut files: Vec<FileEntry>, count: usize) -> Report {
+        files.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+        let tree = DirTree::new(&files);
+        Report { files, tree, count }
+    }
+
+    pub fn files(&self) -> usize {
+        self.files.len()
+    }
+
+    fn label(dir: &str) -> &str {
+        if dir == TOP { "every search path" } else { dir }
+    }
+
+    // The page of a directory, none for directories the report doesn't know
+    fn page(&self, dir: &str, out: &mut dyn Write) -> io::Result<bool> {
+        let Some(node) = self.tree.get(dir) else { return Ok(false) };
+
+        let share = |size: u64| if node.size > 0 { format!("{:.1}%", size as f64 * 100.0 / node.size as f64) } else { String::from("-") };
+        let headers = ["Directory", "Size", "Files", "Share"].map(String::from).to_vec();
+        let mut subdirs = Markup::new("Directories", headers).right_aligned([1, 2, 3]);
+        for (child, child_node) in self.tree.children(dir) {
+            subdirs.rows.push(vec![dir_name(child), format_size(child_node.size), child_node.files.to_string(), share(child_node.size)]);
+            subdirs.links.push(Some(dir_link(child)));
+        }
+
+        let headers = ["Path", "Size", "Share", "Modified"].map(String::from).to_vec();
+        let mut fattest = Markup::new("Fattest files", headers).right_aligned([1, 2]);
+        let below = self.files.iter().filter(|file_entry| dir == TOP || Path::new(&file_entry.path).starts_with(dir));
+        for file_entry in below.take(self.count) {
+            let modified = file_entry.modified.map(format_time).unwrap_or_else(|| String::from("-"));
+            fattest.rows.push(vec![file_entry.path.clone(), file_entry.get_str_size(), share(file_entry.size), modified]);
+        }
+
+        // The trail goes from the first page down to this one
+        let home = self.tree.home();
+        let mut trail = Vec::new();
+        let mut above = self.tree.parent(dir).filter(|_| dir != home);
+        while let Some(parent) = above {
+            trail.push((Report::label(&parent).to_string(), dir_link(&parent)));
+            above = self.tree.parent(&parent).filter(|_| parent != home);
+        }
+        trail.reverse();
+
+        let title = format!("{}, {} in {} files", Report::label(dir), format_size(node.size), node.files);
+        write_html_page(out, &title, &trail, &[subdirs, fattest])?;
+        Ok(true)
+    }
+}
+
+fn dir_link(dir: &str) -> String {
+    format!("/?dir={}", percent_encode(dir))
+}
+
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let hex = value.get(index + 1..index + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[index], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            (b'+', _) => {
+                decoded.push(b' ');
+                index += 1;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+fn respond(stream: &mut TcpStream, status: &str, body: &[u8], head_only: bool) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        body.len()
+    )?;
+    if !head_only {
+        stream.write_all(body)?;
+    }
+    stream.flush()
+}
+
+fn handle(mut stream: TcpStream, report: &Report) -> io::Result<()> {
+    // A client that stops halfway mustn't hold up the next ones
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // The headers tell nothing the report needs, they are read so the client sees its request taken
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && header.trim_end() != "" {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let head_only = method == "HEAD";
+    if method != "GET" && !head_only {
+        return respond(&mut stream, "405 Method Not Allowed", b"Only GET and HEAD are answered\n", false);
+    }
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    if path != "/" {
+        return respond(&mut stream, "404 Not Found", b"Not found\n", head_only);
+    }
+    let dir = query.split('&')
+        .find_map(|pair| pair.strip_prefix("dir="))
+        .map(percent_decode)
+        .unwrap_or_else(|| report.tree.home().to_string());
+
+    let mut body = Vec::new();
+    if report.page(&dir, &mut body)? {
+        respond(&mut stream, "200 OK", &body, head_only)
+    } else {
+        respond(&mut stream, "404 Not Found", b"No scanned files below this directory\n", head_only)
+    }
+}
+
+// Answer requests until the process is stopped, a failed one doesn't stop the others
+pub fn serve(listener: &TcpListener, report: &Report) {
+    for stream in listener.incoming().flatten() {
+        let _ = handle(stream, report);
+    }
+}
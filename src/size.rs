@@ -0,0 +1,144 @@
+// Parsing and formatting of human readable sizes, and measuring the space files take on disk
+use std::fs::Metadata;
+use std::path::Path;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+// Parse a human size like "500M", "2GiB", "1.5 GB" or "1024" into bytes
+// Single letters and *iB suffixes are powers of 1024, *B suffixes are powers of 1000
+pub fn parse_size(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let split = value.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse().ok()?;
+
+    let multiplier: f64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1.0,
+        "k" | "kib" => 1024.0,
+        "m" | "mib" => 1024f64.powi(2),
+        "g" | "gib" => 1024f64.powi(3),
+        "t" | "tib" => 1024f64.powi(4),
+        "p" | "pib" => 1024f64.powi(5),
+        "kb" => 1e3,
+        "mb" => 1e6,
+        "gb" => 1e9,
+        "tb" => 1e12,
+        "pb" => 1e15,
+        _ => return None,
+    };
+
+    Some((number * multiplier).round() as u64)
+}
+
+// How sizes are shown, the historical default divides by 1024 but keeps the KB, MB labels
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum UnitStyle {
+    #[default]
+    Legacy,
+    // Powers of 1024 labelled KiB, MiB
+    Binary,
+    // Powers of 1000 labelled kB, MB
+    Si,
+    // The exact count, with thousands separators
+    Bytes,
+}
+
+impl UnitStyle {
+    pub fn parse(value: &str) -> Option<UnitStyle> {
+        match value {
+            "legacy" => Some(UnitStyle::Legacy),
+            // iec was the name of binary before it got the right labels
+            "binary" | "iec" => Some(UnitStyle::Binary),
+            "si" => Some(UnitStyle::Si),
+            "bytes" => Some(UnitStyle::Bytes),
+            _ => None,
+        }
+    }
+
+    // Bytes has no units, its round numbers are the decimal ones
+    pub(crate) fn base(&self) -> f64 {
+        match self {
+            UnitStyle::Si | UnitStyle::Bytes => 1000.0,
+            _ => 1024.0,
+        }
+    }
+
+    fn labels(&self) -> [&'static str; 8] {
+        match self {
+            UnitStyle::Legacy => ["KB", "MB", "GB", "TB", "PB", "EB", "ZB", "YB"],
+            UnitStyle::Binary => ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB", "ZiB", "YiB"],
+            UnitStyle::Si | UnitStyle::Bytes => ["kB", "MB", "GB", "TB", "PB", "EB", "ZB", "YB"],
+        }
+    }
+}
+
+// Process wide like the colors of the `colored` crate, sizes are formatted from everywhere
+static UNIT_STYLE: AtomicU8 = AtomicU8::new(0);
+
+pub fn set_unit_style(style: UnitStyle) {
+    UNIT_STYLE.store(style as u8, Ordering::Relaxed);
+}
+
+pub fn unit_style() -> UnitStyle {
+    match UNIT_STYLE.load(Ordering::Relaxed) {
+        1 => UnitStyle::Binary,
+        2 => UnitStyle::Si,
+        3 => UnitStyle::Bytes,
+        _ => UnitStyle::Legacy,
+    }
+}
+
+// "1,234,567", digits grouped by three
+fn group_thousands(value: u64) -> String {
+    let digits = value.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+pub fn format_size(size: u64) -> String {
+    let style = unit_style();
+    if style == UnitStyle::Bytes {
+        return format!("{} Bytes", group_thousands(size));
+    }
+    let mut size = size as f64;
+    let mut suffix = String::from("Bytes");
+
+    for unit in style.labels() {
+        if size < style.base() {
+            break;
+        }
+        size /= style.base();
+        suffix = unit.to_string();
+    }
+
+    let size_str = if size.fract() == 0.0 {
+        format!("{:.0}", size)
+    } else {
+        format!("{:.2}", size)
+    };
+
+    format!("{} {}", size_str, suffix)
+}
+
+// Space actually allocated for a file, below its length when sparse or compressed
+#[cfg(unix)]
+pub fn allocated_size(_path: &Path, metadata: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    // st_blocks is always in 512 bytes units, whatever the filesystem block size
+    metadata.blocks() * 512
+}
+
+#[cfg(windows)]
+pub fn allocated_size(path: &Path, metadata: &Metadata) -> u64 {
+    crate::windows::compressed_size(path).unwrap_or_else(|| metadata.len())
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn allocated_size(_path: &Path, metadata: &Metadata) -> u64 {
+    metadata.len()
+}
@@ -0,0 +1,161 @@
+// Scan results saved to a file so a later scan can tell what changed since. The format is plain
+// text, a header then one "kind<TAB>size<TAB>path" line per entry:
+//
+//   # fatass snapshot 1
+//   # created 1700000000
+//   file	1048576	/home/me/video.mkv
+//
+// Backslashes, tabs and newlines in paths are escaped so every entry stays on its line
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::time::{from_unix_seconds, unix_seconds};
+use crate::{EntryKind, FileEntry};
+
+const HEADER: &str = "# fatass snapshot 1";
+
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub created: Option<SystemTime>,
+    pub entries: Vec<FileEntry>,
+}
+
+fn escape_path(path: &str) -> String {
+    path.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n")
+}
+
+fn unescape_path(path: &str) -> String {
+    let mut unescaped = String::with_capacity(path.len());
+    let mut chars = path.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+impl Snapshot {
+    pub fn new(entries: Vec<FileEntry>) -> Snapshot {
+        Snapshot { created: Some(SystemTime::now()), entries }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "{}", HEADER)?;
+        if let Some(created) = self.created {
+            writeln!(out, "# created {}", unix_seconds(created))?;
+        }
+        for entry in &self.entries {
+            writeln!(out, "{}\t{}\t{}", entry.kind.as_str(), entry.size, escape_path(&entry.path))?;
+        }
+        out.flush()
+    }
+
+    pub fn load(path: &Path) -> Result<Snapshot, String> {
+        let content = fs::read_to_string(path).map_err(|err| format!("could not read {}: {}", path.display(), err))?;
+        let mut lines = content.lines().enumerate();
+        if lines.next().map(|(_, line)| line) != Some(HEADER) {
+            return Err(format!("{} is not a fatass snapshot", path.display()));
+        }
+
+        let mut snapshot = Snapshot { created: None, entries: Vec::new() };
+        for (index, line) in lines {
+            if let Some(comment) = line.strip_prefix('#') {
+                if let Some(seconds) = comment.trim().strip_prefix("created ").and_then(|value| value.parse().ok()) {
+                    snapshot.created = Some(from_unix_seconds(seconds));
+                }
+                continue;
+            }
+            let invalid = || format!("{}: line {}: expected kind, size and path separated by tabs", path.display(), index + 1);
+            let mut fields = line.splitn(3, '\t');
+            let (Some(kind), Some(size), Some(entry_path)) = (fields.next(), fields.next(), fields.next()) else {
+                return Err(invalid());
+            };
+            let size: u64 = size.parse().map_err(|_| invalid())?;
+            let entry_path = unescape_path(entry_path);
+            snapshot.entries.push(match kind {
+                "file" => FileEntry::new(entry_path, size),
+                "dir" => FileEntry::new_dir(entry_path, size),
+                _ => return Err(invalid()),
+            });
+        }
+        Ok(snapshot)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChangeKind {
+    Grew,
+    Shrank,
+    Appeared,
+    Disappeared,
+}
+
+impl ChangeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeKind::Grew => "grew",
+            ChangeKind::Shrank => "shrank",
+            ChangeKind::Appeared => "new",
+            ChangeKind::Disappeared => "gone",
+        }
+    }
+}
+
+// An entry whose size differs between two snapshots, None where it didn't exist
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub path: String,
+    pub kind: EntryKind,
+    pub old_size: Option<u64>,
+    pub new_size: Option<u64>,
+}
+
+impl Change {
+    pub fn delta(&self) -> i128 {
+        self.new_size.unwrap_or(0) as i128 - self.old_size.unwrap_or(0) as i128
+    }
+
+    pub fn change_kind(&self) -> ChangeKind {
+        match (self.old_size, self.new_size) {
+            (None, _) => ChangeKind::Appeared,
+            (_, None) => ChangeKind::Disappeared,
+            _ if self.delta() < 0 => ChangeKind::Shrank,
+            _ => ChangeKind::Grew,
+        }
+    }
+}
+
+// Every entry that grew, shrank, appeared or disappeared, the biggest moves first
+pub fn diff(old: &[FileEntry], new: &[FileEntry]) -> Vec<Change> {
+    let old_sizes: HashMap<&str, &FileEntry> = old.iter().map(|entry| (entry.path.as_str(), entry)).collect();
+    let new_paths: HashMap<&str, &FileEntry> = new.iter().map(|entry| (entry.path.as_str(), entry)).collect();
+
+    let mut changes: Vec<Change> = new.iter()
+        .map(|entry| Change {
+            path: entry.path.clone(),
+            kind: entry.kind,
+            old_size: old_sizes.get(entry.path.as_str()).map(|old| old.size),
+            new_size: Some(entry.size),
+        })
+        .filter(|change| change.old_size != change.new_size)
+        .collect();
+    changes.extend(old.iter()
+        .filter(|entry| !new_paths.contains_key(entry.path.as_str()))
+        .map(|entry| Change { path: entry.path.clone(), kind: entry.kind, old_size: Some(entry.size), new_size: None }));
+
+    changes.sort_by(|a, b| Reverse(a.delta().abs()).cmp(&Reverse(b.delta().abs())).then_with(|| a.path.cmp(&b.path)));
+    changes
+}
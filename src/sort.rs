@@ -0,0 +1,41 @@
+use std::cmp::Reverse;
+
+use crate::FileEntry;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortKey {
+    Size,
+    Mtime,
+    Atime,
+    Name,
+    Ext,
+}
+
+impl SortKey {
+    pub fn parse(value: &str) -> Option<SortKey> {
+        match value {
+            "size" => Some(SortKey::Size),
+            "mtime" => Some(SortKey::Mtime),
+            "atime" => Some(SortKey::Atime),
+            "name" => Some(SortKey::Name),
+            "ext" => Some(SortKey::Ext),
+            _ => None,
+        }
+    }
+}
+
+// Order the final results, biggest first for sizes, oldest first for times and alphabetically otherwise
+// Entries missing a timestamp go last, ties keep the size order
+pub fn sort_entries(entries: &mut [FileEntry], key: SortKey, reverse: bool) {
+    match key {
+        SortKey::Size => entries.sort_by_key(|e| Reverse(e.size)),
+        SortKey::Mtime => entries.sort_by_key(|e| (e.modified.is_none(), e.modified)),
+        SortKey::Atime => entries.sort_by_key(|e| (e.accessed.is_none(), e.accessed)),
+        SortKey::Name => entries.sort_by_key(|e| e.name().to_lowercase()),
+        SortKey::Ext => entries.sort_by_key(|e| e.extension()),
+    }
+
+    if reverse {
+        entries.reverse();
+    }
+}
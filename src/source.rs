@@ -0,0 +1,38 @@
+// Where the files of a scan come from when they aren't walked here: another machine over SSH, an
+// FTP or SFTP server, an S3 bucket. A source only lists files, the filters, the ranking, the
+// grouping and the rendering are the same whatever it is, so adding one doesn't touch them
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::errors::ScanErrors;
+use crate::FileEntry;
+
+// What a source may use to list less, the filters apply to whatever it hands over anyway
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ListOptions {
+    pub follow_symlinks: bool,
+    pub one_file_system: bool,
+    pub max_depth: Option<usize>,
+    // Size files by the blocks they take rather than their length, where the source knows both
+    pub disk_usage: bool,
+}
+
+// Displayed as what the errors of the whole listing are reported for, e.g. admin@db1:/var/lib
+pub trait FileSource: fmt::Debug + fmt::Display + Send + Sync {
+    // The search path the listed paths start with
+    fn path(&self) -> String;
+
+    // The directory the listed paths are relative to, for the globs and the directory sizes
+    fn root(&self) -> PathBuf {
+        PathBuf::from(self.path())
+    }
+
+    // How the files are listed, for the verbose messages, e.g. "on db1 over SSH"
+    fn how(&self) -> String;
+
+    // Hand over every file that isn't a directory as it's listed, with its device and inode when
+    // the source knows them so hard links count once. The paths it couldn't read go to the errors,
+    // the whole listing failing is the Err
+    fn list(&self, options: &ListOptions, errors: &ScanErrors, visit: &mut dyn FnMut(FileEntry, Option<(u64, u64)>)) -> io::Result<()>;
+}
@@ -0,0 +1,458 @@
+// Every scanned file written to a SQLite database for ad-hoc queries. SQLite isn't linked, its file
+// format is simple enough to write directly: rows fill table b-tree leaves as they come, then the
+// interior pages and the schema on page 1 are written once the scan is done
+//
+//   CREATE TABLE schema_version (version INTEGER NOT NULL)
+//   CREATE TABLE files (path TEXT NOT NULL, size INTEGER NOT NULL, mtime INTEGER, owner TEXT, extension TEXT)
+//
+// The version only changes along with the tables, so queries can check what they read
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::owners::Names;
+use crate::time::unix_seconds;
+use crate::FileEntry;
+
+pub const SCHEMA_VERSION: i64 = 1;
+
+const SCHEMA: [(&str, &str); 2] = [
+    ("schema_version", "CREATE TABLE schema_version (version INTEGER NOT NULL)"),
+    ("files", "CREATE TABLE files (path TEXT NOT NULL, size INTEGER NOT NULL, mtime INTEGER, owner TEXT, extension TEXT)"),
+];
+
+const PAGE_SIZE: usize = 4096;
+// Page 1 starts with the database header, its b-tree comes after
+const HEADER_SIZE: usize = 100;
+const LEAF_PAGE: u8 = 0x0d;
+const INTERIOR_PAGE: u8 = 0x05;
+
+enum Value<'a> {
+    Null,
+    Integer(i64),
+    Text(&'a str),
+}
+
+// Big endian, 7 bits per byte with the high bit set on all but the last. Lengths and row ids here
+// stay far below the 9 byte form of huge values
+fn put_varint(out: &mut Vec<u8>, value: u64) {
+    let mut groups = vec![(value & 0x7f) as u8];
+    let mut rest = value >> 7;
+    while rest > 0 {
+        groups.push((rest & 0x7f) as u8 | 0x80);
+        rest >>= 7;
+    }
+    out.extend(groups.iter().rev());
+}
+
+fn varint_len(value: u64) -> usize {
+    let mut buffer = Vec::new();
+    put_varint(&mut buffer, value);
+    buffer.len()
+}
+
+// The serial type of an integer with how many bytes it's stored in
+fn integer_type(value: i64) -> (u64, usize) {
+    match value {
+        0 => (8, 0),
+        1 => (9, 0),
+        -0x80..=0x7f => (1, 1),
+        -0x8000..=0x7fff => (2, 2),
+        -0x80_0000..=0x7f_ffff => (3, 3),
+        -0x8000_0000..=0x7fff_ffff => (4, 4),
+        -0x8000_0000_0000..=0x7fff_ffff_ffff => (5, 6),
+        _ => (6, 8),
+    }
+}
+
+// A header of serial types, its own size first, then the values
+fn record(values: &[Value]) -> Vec<u8> {
+    let mut types = Vec::new();
+    let mut body = Vec::new();
+    for value in values {
+        match value {
+            Value::Null => put_varint(&mut types, 0),
+            Value::Integer(value) => {
+                let (serial_type, width) = integer_type(*value);
+                put_varint(&mut types, serial_type);
+                body.extend_from_slice(&value.to_be_bytes()[8 - width..]);
+            }
+            Value::Text(text) => {
+                put_varint(&mut types, text.len() as u64 * 2 + 13);
+                body.extend_from_slice(text.as_bytes());
+            }
+        }
+    }
+
+    let mut header_size = types.len() + 1;
+    while types.len() + varint_len(header_size as u64) != header_size {
+        header_size = types.len() + varint_len(header_size as u64);
+    }
+    let mut record = Vec::with_capacity(header_size + body.len());
+    put_varint(&mut record, header_size as u64);
+    record.extend(types);
+    record.extend(body);
+    record
+}
+
+// How much of a payload stays in its leaf cell, the rest goes to overflow pages
+fn local_payload(payload: usize) -> usize {
+    let most = PAGE_SIZE - 35;
+    if payload <= most {
+        return payload;
+    }
+    let least = (PAGE_SIZE - 12) * 32 / 255 - 23;
+    let local = least + (payload - least) % (PAGE_SIZE - 4);
+    if local <= most { local } else { least }
+}
+
+fn btree_page(kind: u8, header_start: usize, cells: &[Vec<u8>], right_most: Option<u32>) -> Vec<u8> {
+    let mut page = vec![0; PAGE_SIZE];
+    let header_len = if right_most.is_some() { 12 } else { 8 };
+    let mut content = PAGE_SIZE;
+    for (index, cell) in cells.iter().enumerate() {
+        content -= cell.len();
+        page[content..content + cell.len()].copy_from_slice(cell);
+        let pointer = header_start + header_len + index * 2;
+        page[pointer..pointer + 2].copy_from_slice(&(content as u16).to_be_bytes());
+    }
+    page[header_start] = kind;
+    page[header_start + 3..header_start + 5].copy_from_slice(&(cells.len() as u16).to_be_bytes());
+    page[header_start + 5..header_start + 7].copy_from_slice(&(content as u16).to_be_bytes());
+    if let Some(right_most) = right_most {
+        page[header_start + 8..header_start + 12].copy_from_slice(&right_most.to_be_bytes());
+    }
+    page
+}
+
+// Pages written one after the other, page 1 is left for the end
+struct Pages {
+    out: BufWriter<File>,
+    count: u32,
+}
+
+impl Pages {
+    fn push(&mut self, page: &[u8]) -> io::Result<u32> {
+        self.out.write_all(page)?;
+        self.count += 1;
+        Ok(self.count)
+    }
+}
+
+#[derive(Default)]
+struct Table {
+    // Cells of the leaf being filled and the space they take with their pointers
+    cells: Vec<Vec<u8>>,
+    used: usize,
+    // Page and largest row id of the full leaves, or of the interior pages while finishing
+    children: Vec<(u32, i64)>,
+    rows: i64,
+}
+
+impl Table {
+    fn push(&mut self, pages: &mut Pages, values: &[Value]) -> io::Result<()> {
+        let rowid = self.rows + 1;
+        let payload = record(values);
+        let local = local_payload(payload.len());
+
+        let mut cell = Vec::new();
+        put_varint(&mut cell, payload.len() as u64);
+        put_varint(&mut cell, rowid as u64);
+        cell.extend_from_slice(&payload[..local]);
+        if local < payload.len() {
+            // Overflow pages chain forward, they are written in order so each knows the next number
+            let chunks: Vec<&[u8]> = payload[local..].chunks(PAGE_SIZE - 4).collect();
+            cell.extend_from_slice(&(pages.count + 1).to_be_bytes());
+            for (index, chunk) in chunks.iter().enumerate() {
+                let next = if index + 1 < chunks.len() { pages.count + 2 } else { 0 };
+                let mut page = vec![0; PAGE_SIZE];
+                page[..4].copy_from_slice(&next.to_be_bytes());
+                page[4..4 + chunk.len()].copy_from_slice(chunk);
+                pages.push(&page)?;
+            }
+        }
+
+        if 8 + self.used + cell.len() + 2 > PAGE_SIZE {
+            self.flush(pages)?;
+        }
+        self.used += cell.len() + 2;
+        self.cells.push(cell);
+        self.rows = rowid;
+        Ok(())
+    }
+
+    fn flush(&mut self, pages: &mut Pages) -> io::Result<()> {
+        let page = pages.push(&btree_page(LEAF_PAGE, 0, &self.cells, None))?;
+        self.children.push((page, self.rows));
+        self.cells.clear();
+        self.used = 0;
+        Ok(())
+    }
+
+    // Write the interior levels over the leaves, returns the root page
+    fn finish(mut self, pages: &mut Pages) -> io::Result<u32> {
+        if self.children.is_empty() || !self.cells.is_empty() {
+            self.flush(pages)?;
+        }
+        while self.children.len() > 1 {
+            let mut level = Vec::new();
+            let mut group: Vec<(u32, i64)> = Vec::new();
+            let mut used = 0;
+            for child in self.children.drain(..) {
+                // The last child of a page is its right-most pointer, the others get a cell
+                if let Some(&(_, key)) = group.last() {
+                    let cell_len = 4 + varint_len(key as u64) + 2;
+                    if 12 + used + cell_len > PAGE_SIZE {
+                        level.push(write_interior(pages, &group)?);
+                        group.clear();
+                        used = 0;
+                    } else {
+                        used += cell_len;
+                    }
+                }
+                group.push(child);
+            }
+            level.push(write_interior(pages, &group)?);
+            self.children = level;
+        }
+        Ok(self.children[0].0)
+    }
+}
+
+fn write_interior(pages: &mut Pages, children: &[(u32, i64)]) -> io::Result<(u32, i64)> {
+    let (&(right_most, largest), rest) = children.split_last().expect("interior pages have children");
+    let cells: Vec<Vec<u8>> = rest.iter().map(|&(page, key)| {
+        let mut cell = page.to_be_bytes().to_vec();
+        put_varint(&mut cell, key as u64);
+        cell
+    }).collect();
+    let page = pages.push(&btree_page(INTERIOR_PAGE, 0, &cells, Some(right_most)))?;
+    Ok((page, largest))
+}
+
+pub struct SqliteExport {
+    pages: Pages,
+    version_root: u32,
+    files: Table,
+    names: Names,
+    // The first write error, the export stops there and finish reports it
+    error: Option<io::Error>,
+}
+
+impl SqliteExport {
+    pub fn create(path: &Path) -> io::Result<SqliteExport> {
+        let mut pages = Pages { out: BufWriter::new(File::create(path)?), count: 0 };
+        pages.push(&[0; PAGE_SIZE])?;
+        let mut version = Table::default();
+        version.push(&mut pages, &[Value::Integer(SCHEMA_VERSION)])?;
+        let version_root = version.finish(&mut pages)?;
+        Ok(SqliteExport { pages, version_root, files: Table::default(), names: Names::default(), error: None })
+    }
+
+    pub fn add(&mut self, file_entry: &FileEntry) {
+        if self.error.is_some() {
+            return;
+        }
+        let owner = file_entry.uid.map(|uid| self.names.user(uid));
+        let extension = file_entry.extension();
+        let values = [
+            Value::Text(&file_entry.path),
+            Value::Integer(file_entry.size as i64),
+            file_entry.modified.map_or(Value::Null, |modified| Value::Integer(unix_seconds(modified))),
+            owner.as_deref().map_or(Value::Null, Value::Text),
+            if extension.is_empty() { Value::Null } else { Value::Text(&extension) },
+        ];
+        if let Err(err) = self.files.push(&mut self.pages, &values) {
+            self.error = Some(err);
+        }
+    }
+
+    // Write the last pages and the schema, returns how many files were exported
+    pub fn finish(mut self) -> io::Result<i64> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+        let files = self.files.rows;
+        let files_root = self.files.finish(&mut self.pages)?;
+
+        let roots = [self.version_root, files_root];
+        let cells: Vec<Vec<u8>> = SCHEMA.iter().zip(roots).enumerate().map(|(index, (&(name, sql), root))| {
+            let payload = record(&[Value::Text("table"), Value::Text(name), Value::Text(name), Value::Integer(root as i64), Value::Text(sql)]);
+            let mut cell = Vec::new();
+            put_varint(&mut cell, payload.len() as u64);
+            put_varint(&mut cell, index as u64 + 1);
+            cell.extend(payload);
+            cell
+        }).collect();
+        let mut page = btree_page(LEAF_PAGE, HEADER_SIZE, &cells, None);
+        page[..HEADER_SIZE].copy_from_slice(&database_header(self.pages.count));
+
+        let mut file = self.pages.out.into_inner().map_err(|err| err.into_error())?;
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&page)?;
+        file.flush()?;
+        Ok(files)
+    }
+}
+
+fn database_header(page_count: u32) -> [u8; HEADER_SIZE] {
+    let mut header = [0; HEADER_SIZE];
+    header[..16].copy_from_slice(b"SQLite format 3\0");
+    header[16..18].copy_from_slice(&(PAGE_SIZE as u16).to_be_bytes());
+    // File format versions, no reserved bytes, then the fixed payload fractions
+    header[18..24].copy_from_slice(&[1, 1, 0, 64, 32, 32]);
+    // Change counter
+    header[24..28].copy_from_slice(&1u32.to_be_bytes());
+    header[28..32].copy_from_slice(&page_count.to_be_bytes());
+    // Schema cookie and schema format
+    header[40..44].copy_from_slice(&1u32.to_be_bytes());
+    header[44..48].copy_from_slice(&4u32.to_be_bytes());
+    // UTF-8 text
+    header[56..60].copy_from_slice(&1u32.to_be_bytes());
+    // The change counter the page count is valid for, then the SQLite version the format matches
+    header[92..96].copy_from_slice(&1u32.to_be_bytes());
+    header[96..100].copy_from_slice(&3_045_000u32.to_be_bytes());
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[derive(Debug, PartialEq)]
+    enum Field {
+        Null,
+        Integer(i64),
+        Text(String),
+    }
+
+    fn varint(data: &[u8], pos: &mut usize) -> u64 {
+        let mut value = 0;
+        loop {
+            let byte = data[*pos];
+            *pos += 1;
+            value = value << 7 | (byte & 0x7f) as u64;
+            if byte < 0x80 {
+                return value;
+            }
+        }
+    }
+
+    fn fields(record: &[u8]) -> Vec<Field> {
+        let mut pos = 0;
+        let header_size = varint(record, &mut pos) as usize;
+        let mut types = Vec::new();
+        while pos < header_size {
+            types.push(varint(record, &mut pos));
+        }
+        types.into_iter().map(|serial_type| match serial_type {
+            0 => Field::Null,
+            8 => Field::Integer(0),
+            9 => Field::Integer(1),
+            1..=6 => {
+                let width = [1, 2, 3, 4, 6, 8][serial_type as usize - 1];
+                let mut bytes = [if record[pos] & 0x80 == 0 { 0 } else { 0xff }; 8];
+                bytes[8 - width..].copy_from_slice(&record[pos..pos + width]);
+                pos += width;
+                Field::Integer(i64::from_be_bytes(bytes))
+            }
+            _ => {
+                let len = (serial_type as usize - 13) / 2;
+                pos += len;
+                Field::Text(String::from_utf8(record[pos - len..pos].to_vec()).unwrap())
+            }
+        }).collect()
+    }
+
+    // Every row of a table b-tree in order, with the overflow chains followed, returns the depth
+    fn read_table(data: &[u8], page_number: u32, rows: &mut Vec<(i64, Vec<Field>)>) -> usize {
+        let page = &data[(page_number as usize - 1) * PAGE_SIZE..page_number as usize * PAGE_SIZE];
+        let start = if page_number == 1 { HEADER_SIZE } else { 0 };
+        let cell_count = u16::from_be_bytes([page[start + 3], page[start + 4]]) as usize;
+        let header_len = if page[start] == INTERIOR_PAGE { 12 } else { 8 };
+        let cells = (0..cell_count).map(|index| {
+            let pointer = start + header_len + index * 2;
+            u16::from_be_bytes([page[pointer], page[pointer + 1]]) as usize
+        });
+
+        if page[start] == INTERIOR_PAGE {
+            let mut depths = Vec::new();
+            for cell in cells {
+                let child = u32::from_be_bytes(page[cell..cell + 4].try_into().unwrap());
+                depths.push(read_table(data, child, rows));
+                // The key is the largest row id on the left of it
+                assert_eq!(varint(page, &mut { cell + 4 }) as i64, rows.last().unwrap().0);
+            }
+            let right_most = u32::from_be_bytes(page[start + 8..start + 12].try_into().unwrap());
+            depths.push(read_table(data, right_most, rows));
+            assert!(depths.iter().all(|&depth| depth == depths[0]), "the leaves are all as deep");
+            return depths[0] + 1;
+        }
+
+        assert_eq!(page[start], LEAF_PAGE);
+        for mut pos in cells {
+            let payload_len = varint(page, &mut pos) as usize;
+            let rowid = varint(page, &mut pos) as i64;
+            let local = local_payload(payload_len);
+            let mut payload = page[pos..pos + local].to_vec();
+            let mut next = if local < payload_len { u32::from_be_bytes(page[pos + local..pos + local + 4].try_into().unwrap()) } else { 0 };
+            while next != 0 {
+                let overflow = &data[(next as usize - 1) * PAGE_SIZE..next as usize * PAGE_SIZE];
+                let len = (payload_len - payload.len()).min(PAGE_SIZE - 4);
+                payload.extend_from_slice(&overflow[4..4 + len]);
+                next = u32::from_be_bytes(overflow[..4].try_into().unwrap());
+            }
+            assert_eq!(payload.len(), payload_len);
+            rows.push((rowid, fields(&payload)));
+        }
+        1
+    }
+
+    // Enough long paths for the files table to need more than one interior page below its root,
+    // and a few longer than a page spilling into overflow chains
+    #[test]
+    fn writes_deep_trees_and_overflow_pages() {
+        let path = env::temp_dir().join(format!("fatass-sqlite-test-{}.db", std::process::id()));
+        let mut export = SqliteExport::create(&path).unwrap();
+        let long = |index: usize| match index {
+            10 => "o".repeat(PAGE_SIZE - 40),
+            500 => "o".repeat(PAGE_SIZE + 10),
+            1000 => "o".repeat(PAGE_SIZE * 5),
+            _ => "x".repeat(990),
+        };
+        let paths: Vec<String> = (0..2400).map(|index| format!("/data/{}/{}.log", long(index), index)).collect();
+        for (index, path) in paths.iter().enumerate() {
+            export.add(&FileEntry::new(path.clone(), index as u64 * 1000));
+        }
+        assert_eq!(export.finish().unwrap(), 2400);
+        let data = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(&data[..16], b"SQLite format 3\0");
+        assert_eq!(u32::from_be_bytes(data[28..32].try_into().unwrap()) as usize * PAGE_SIZE, data.len());
+        let mut schema = Vec::new();
+        assert_eq!(read_table(&data, 1, &mut schema), 1);
+        let root = |name: &str| match schema.iter().find(|(_, fields)| fields[1] == Field::Text(name.to_string())) {
+            Some((_, fields)) => match fields[3] {
+                Field::Integer(root) => root as u32,
+                _ => panic!("no root page for {}", name),
+            },
+            None => panic!("no table {}", name),
+        };
+
+        let mut versions = Vec::new();
+        read_table(&data, root("schema_version"), &mut versions);
+        assert_eq!(versions, [(1, vec![Field::Integer(SCHEMA_VERSION)])]);
+
+        let mut rows = Vec::new();
+        assert_eq!(read_table(&data, root("files"), &mut rows), 3);
+        assert_eq!(rows.len(), paths.len());
+        for (index, ((rowid, fields), path)) in rows.iter().zip(&paths).enumerate() {
+            assert_eq!(*rowid, index as i64 + 1);
+            let expected = [Field::Text(path.clone()), Field::Integer(index as i64 * 1000), Field::Null, Field::Null, Field::Text("log".to_string())];
+            assert_eq!(fields, &expected, "row {}", rowid);
+        }
+    }
+}
@@ -0,0 +1,94 @@
+// Aggregates over every file a scan kept, for the --stats footer and the percent columns. The
+// count and total are always there, the sizes themselves only when kept, 8 bytes a file, so the
+// median and percentiles are exact
+use crate::size::unit_style;
+
+#[derive(Debug, Clone, Default)]
+pub struct SizeStats {
+    keep_sizes: bool,
+    // Sorted once the scan is over
+    sizes: Vec<u64>,
+    files: usize,
+    total: u64,
+}
+
+// Files whose size falls in [min, max), the last bucket has no max
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bucket {
+    pub min: u64,
+    pub max: Option<u64>,
+    pub files: usize,
+}
+
+// Where the buckets of the histogram split: 1 KB, 100 KB, 1 MB, 100 MB and 1 GB in the current units
+pub fn bucket_bounds() -> [u64; 5] {
+    let base = unit_style().base() as u64;
+    [base, 100 * base, base.pow(2), 100 * base.pow(2), base.pow(3)]
+}
+
+impl SizeStats {
+    // Without the sizes kept, only files and total mean anything
+    pub fn new(keep_sizes: bool) -> SizeStats {
+        SizeStats { keep_sizes, ..SizeStats::default() }
+    }
+
+    pub fn record(&mut self, size: u64) {
+        if self.keep_sizes {
+            self.sizes.push(size);
+        }
+        self.files += 1;
+        self.total = self.total.saturating_add(size);
+    }
+
+    pub fn merge(&mut self, other: SizeStats) {
+        self.sizes.extend(other.sizes);
+        self.files += other.files;
+        self.total = self.total.saturating_add(other.total);
+    }
+
+    pub(crate) fn finish(&mut self) {
+        self.sizes.sort_unstable();
+    }
+
+    pub fn files(&self) -> usize {
+        self.files
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    pub fn mean(&self) -> u64 {
+        if self.files == 0 {
+            return 0;
+        }
+        self.total / self.files as u64
+    }
+
+    pub fn median(&self) -> u64 {
+        self.percentile(50)
+    }
+
+    // Nearest rank, the smallest size at least `percent` of the files don't exceed
+    pub fn percentile(&self, percent: usize) -> u64 {
+        if self.sizes.is_empty() {
+            return 0;
+        }
+        let rank = (percent.min(100) * self.sizes.len()).div_ceil(100).max(1);
+        self.sizes[rank - 1]
+    }
+
+    // Buckets at the bucket_bounds
+    pub fn histogram(&self) -> Vec<Bucket> {
+        let bounds = bucket_bounds();
+        let mut buckets: Vec<Bucket> = Vec::with_capacity(bounds.len() + 1);
+        let mut min = 0;
+        for max in bounds.into_iter().map(Some).chain([None]) {
+            let start = self.sizes.partition_point(|&size| size < min);
+            let end = max.map_or(self.sizes.len(), |max| self.sizes.partition_point(|&size| size < max));
+            buckets.push(Bucket { min, max, files: end - start });
+            min = max.unwrap_or_default();
+        }
+        buckets
+    }
+}
@@ -0,0 +1,240 @@
+// A line per entry written after a template, like find -printf, for scripts that want neither the
+// table nor a structured output:
+//
+//   {size:>10} {mtime} {path}
+//   {bytes}\t{owner}\t{path}\0
+//
+// Fields are named in braces, with an alignment and a width after a colon. \t, \n, \0 and \\ are
+// escapes and {{ and }} literal braces. Lines end with a line break unless the template ends with
+// its own \n or \0
+use std::path::Path;
+
+use crate::owners::{format_mode, Names};
+use crate::size::format_size;
+use crate::time::format_time;
+use crate::FileEntry;
+
+pub const FIELDS: [&str; 19] = [
+    "path", "name", "dir", "ext", "kind", "size", "bytes", "apparent", "apparent_bytes", "links",
+    "mode", "owner", "group", "uid", "gid", "mtime", "atime", "mtime_epoch", "percent",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Path,
+    // The last component of the path, and the directory holding it
+    Name,
+    Dir,
+    Extension,
+    Kind,
+    Size,
+    Bytes,
+    ApparentSize,
+    ApparentBytes,
+    Links,
+    Mode,
+    Owner,
+    Group,
+    Uid,
+    Gid,
+    Modified,
+    Accessed,
+    // Seconds since the Unix epoch, for sorting and comparing in scripts
+    ModifiedEpoch,
+    Percent,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Field> {
+        match name {
+            "path" => Some(Field::Path),
+            "name" => Some(Field::Name),
+            "dir" => Some(Field::Dir),
+            "ext" => Some(Field::Extension),
+            "kind" => Some(Field::Kind),
+            "size" => Some(Field::Size),
+            "bytes" => Some(Field::Bytes),
+            "apparent" => Some(Field::ApparentSize),
+            "apparent_bytes" => Some(Field::ApparentBytes),
+            "links" => Some(Field::Links),
+            "mode" => Some(Field::Mode),
+            "owner" => Some(Field::Owner),
+            "group" => Some(Field::Group),
+            "uid" => Some(Field::Uid),
+            "gid" => Some(Field::Gid),
+            "mtime" => Some(Field::Modified),
+            "atime" => Some(Field::Accessed),
+            "mtime_epoch" => Some(Field::ModifiedEpoch),
+            "percent" => Some(Field::Percent),
+            _ => None,
+        }
+    }
+
+    // Numbers line up on the right unless the template tells otherwise, like Rust's format!
+    fn is_number(&self) -> bool {
+        matches!(
+            self,
+            Field::Size | Field::Bytes | Field::ApparentSize | Field::ApparentBytes | Field::Links | Field::Uid | Field::Gid | Field::ModifiedEpoch | Field::Percent
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Field { field: Field, align: Option<Align>, width: usize },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    parts: Vec<Part>,
+    line_break: bool,
+}
+
+// "size:>10" into the field, its alignment and its width
+fn parse_field(spec: &str) -> Result<Part, String> {
+    let (name, format) = spec.split_once(':').unwrap_or((spec, ""));
+    let field = Field::parse(name.trim())
+        .ok_or_else(|| format!("unknown field '{}', expected one of: {}", name.trim(), FIELDS.join(", ")))?;
+    let (align, width) = match format.chars().next() {
+        Some('<') => (Some(Align::Left), &format[1..]),
+        Some('>') => (Some(Align::Right), &format[1..]),
+        Some('^') => (Some(Align::Center), &format[1..]),
+        _ => (None, format),
+    };
+    let width = if width.is_empty() { 0 } else { width.parse().map_err(|_| format!("invalid width '{}' in {{{}}}", width, spec))? };
+    Ok(Part::Field { field, align, width })
+}
+
+// The text and fields of a template, in order
+fn parse_parts(template: &str) -> Result<Vec<Part>, String> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('t') => text.push('\t'),
+                Some('n') => text.push('\n'),
+                Some('0') => text.push('\0'),
+                Some('\\') => text.push('\\'),
+                Some(other) => return Err(format!("unknown escape '\\{}', expected \\t, \\n, \\0 or \\\\", other)),
+                None => return Err("the template ends with a lone backslash".to_string()),
+            },
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let mut spec = String::new();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        closed = true;
+                        break;
+                    }
+                    spec.push(c);
+                }
+                if !closed {
+                    return Err(format!("unclosed field '{{{}'", spec));
+                }
+                if !text.is_empty() {
+                    parts.push(Part::Text(std::mem::take(&mut text)));
+                }
+                parts.push(parse_field(&spec)?);
+            }
+            '}' => return Err("unmatched '}', write }} for a brace".to_string()),
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        parts.push(Part::Text(text));
+    }
+    Ok(parts)
+}
+
+impl Template {
+    pub fn parse(template: &str) -> Result<Template, String> {
+        let parts = parse_parts(template)?;
+        let line_break = !matches!(parts.last(), Some(Part::Text(text)) if text.ends_with('\n') || text.ends_with('\0'));
+        if !parts.iter().any(|part| matches!(part, Part::Field { .. })) {
+            return Err("the template holds no field, like {path}".to_string());
+        }
+        Ok(Template { parts, line_break })
+    }
+
+    // A word of a command line, which may hold no field and ends without a line break
+    pub(crate) fn parse_word(word: &str) -> Result<Template, String> {
+        Ok(Template { parts: parse_parts(word)?, line_break: false })
+    }
+
+    // Whether a field needs the scanned total, only the percent does
+    pub fn uses_percent(&self) -> bool {
+        self.parts.iter().any(|part| matches!(part, Part::Field { field: Field::Percent, .. }))
+    }
+
+    // The line of an entry, fields it doesn't have are written -
+    pub(crate) fn render(&self, file_entry: &FileEntry, percent_of: Option<u64>, names: &mut Names) -> String {
+        let mut line = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => line.push_str(text),
+                Part::Field { field, align, width } => {
+                    let value = field_value(*field, file_entry, percent_of, names).unwrap_or_else(|| String::from("-"));
+                    let align = align.unwrap_or(if field.is_number() { Align::Right } else { Align::Left });
+                    let padding = width.saturating_sub(value.chars().count());
+                    let (before, after) = match align {
+                        Align::Left => (0, padding),
+                        Align::Right => (padding, 0),
+                        Align::Center => (padding / 2, padding - padding / 2),
+                    };
+                    line.extend(std::iter::repeat_n(' ', before));
+                    line.push_str(&value);
+                    line.extend(std::iter::repeat_n(' ', after));
+                }
+            }
+        }
+        if self.line_break {
+            line.push('\n');
+        }
+        line
+    }
+}
+
+fn field_value(field: Field, file_entry: &FileEntry, percent_of: Option<u64>, names: &mut Names) -> Option<String> {
+    let path = Path::new(&file_entry.path);
+    match field {
+        Field::Path => Some(file_entry.path.clone()),
+        Field::Name => Some(path.file_name().map_or_else(|| file_entry.path.clone(), |name| name.to_string_lossy().to_string())),
+        Field::Dir => path.parent().map(|dir| dir.to_string_lossy().to_string()),
+        Field::Extension => Some(file_entry.extension()).filter(|extension| !extension.is_empty()),
+        Field::Kind => Some(file_entry.kind.as_str().to_string()),
+        Field::Size => Some(file_entry.get_str_size()),
+        Field::Bytes => Some(file_entry.size.to_string()),
+        Field::ApparentSize => file_entry.apparent_size.map(format_size),
+        Field::ApparentBytes => file_entry.apparent_size.map(|size| size.to_string()),
+        Field::Links => file_entry.links.map(|links| links.to_string()),
+        Field::Mode => file_entry.mode.map(format_mode),
+        Field::Owner => file_entry.uid.map(|uid| names.user(uid)),
+        Field::Group => file_entry.gid.map(|gid| names.group(gid)),
+        Field::Uid => file_entry.uid.map(|uid| uid.to_string()),
+        Field::Gid => file_entry.gid.map(|gid| gid.to_string()),
+        Field::Modified => file_entry.modified.map(format_time),
+        Field::Accessed => file_entry.accessed.map(format_time),
+        Field::ModifiedEpoch => file_entry.modified
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|since| since.as_secs().to_string()),
+        Field::Percent => percent_of.filter(|total| *total > 0).map(|total| format!("{:.1}", file_entry.size as f64 * 100.0 / total as f64)),
+    }
+}
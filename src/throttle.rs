@@ -0,0 +1,69 @@
+// Keeping a scan from hogging the disks of a busy server with --io-limit: every read of a file and
+// every metadata operation take from a budget of bytes per second, and whoever gets ahead of it
+// waits. A metadata operation counts as the block it reads from the disk
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::size::parse_size;
+
+// What an inode or a directory block takes to read
+pub const METADATA_COST: u64 = 4096;
+
+// Bytes per second, 0 for no limit
+static RATE: AtomicU64 = AtomicU64::new(0);
+// When what was charged so far is paid off, unused time isn't saved up for later bursts
+static PAID_UNTIL: Mutex<Option<Instant>> = Mutex::new(None);
+
+pub fn set_limit(bytes_per_second: Option<u64>) {
+    RATE.store(bytes_per_second.unwrap_or(0), Ordering::Relaxed);
+}
+
+// "50MB/s" or "50MB", in bytes per second
+pub fn parse_rate(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let size = value.strip_suffix("/s").unwrap_or(value);
+    parse_size(size).filter(|rate| *rate > 0)
+}
+
+// How long the bytes take at the rate
+fn cost(bytes: u64, rate: u64) -> Duration {
+    Duration::from_secs_f64(bytes as f64 / rate as f64)
+}
+
+// Account for bytes read, sleeping until the budget allows them
+pub(crate) fn charge(bytes: u64) {
+    let rate = RATE.load(Ordering::Relaxed);
+    if rate == 0 {
+        return;
+    }
+    let now = Instant::now();
+    let start = {
+        let mut paid_until = PAID_UNTIL.lock().unwrap();
+        let start = paid_until.filter(|paid_until| *paid_until > now).unwrap_or(now);
+        *paid_until = Some(start + cost(bytes, rate));
+        start
+    };
+    if start > now {
+        thread::sleep(start - now);
+    }
+}
+
+pub(crate) fn metadata() {
+    charge(METADATA_COST);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_rates() {
+        assert_eq!(parse_rate("50MB/s"), Some(50_000_000));
+        assert_eq!(parse_rate("1MiB"), Some(1 << 20));
+        assert_eq!(parse_rate("0/s"), None);
+        assert_eq!(parse_rate("fast"), None);
+        assert_eq!(cost(METADATA_COST, 4096 * 1000), Duration::from_millis(1));
+    }
+}
@@ -0,0 +1,161 @@
+// Timestamps formatting and parsing, local time on Unix and UTC elsewhere
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Seconds since the epoch, negative before it
+pub fn unix_seconds(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as i64,
+        Err(err) => -(err.duration().as_secs() as i64),
+    }
+}
+
+// Days since the epoch to a (year, month, day) civil date, see http://howardhinnant.github.io/date_algorithms.html
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+// Civil date to days since the epoch, the inverse of civil_from_days
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 } as i64;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+pub fn from_unix_seconds(seconds: i64) -> SystemTime {
+    if seconds >= 0 {
+        UNIX_EPOCH + Duration::from_secs(seconds as u64)
+    } else {
+        UNIX_EPOCH - Duration::from_secs(seconds.unsigned_abs())
+    }
+}
+
+// Offset of the local timezone from UTC at the given time, in seconds
+#[cfg(unix)]
+fn local_offset(seconds: i64) -> i64 {
+    let time = seconds as libc::time_t;
+    // SAFETY: localtime_r only writes into the zeroed tm we own
+    unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&time, &mut tm).is_null() {
+            return 0;
+        }
+        tm.tm_gmtoff
+    }
+}
+
+#[cfg(not(unix))]
+fn local_offset(_seconds: i64) -> i64 {
+    0
+}
+
+// Local (year, month, day) and seconds into that day
+fn local_date(time: SystemTime) -> ((i64, u32, u32), i64) {
+    let seconds = unix_seconds(time);
+    let local = seconds + local_offset(seconds);
+    (civil_from_days(local.div_euclid(86_400)), local.rem_euclid(86_400))
+}
+
+// The local year of the time
+pub fn year(time: SystemTime) -> i64 {
+    local_date(time).0.0
+}
+
+// "2023-11-24 18:02" in local time
+pub fn format_time(time: SystemTime) -> String {
+    let ((year, month, day), seconds_of_day) = local_date(time);
+    format!("{:04}-{:02}-{:02} {:02}:{:02}", year, month, day, seconds_of_day / 3600, seconds_of_day % 3600 / 60)
+}
+
+// "2023-11-24T18:02:51" in local time, as trash info files expect
+pub fn format_time_iso(time: SystemTime) -> String {
+    let ((year, month, day), seconds_of_day) = local_date(time);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year, month, day, seconds_of_day / 3600, seconds_of_day % 3600 / 60, seconds_of_day % 60
+    )
+}
+
+// A human duration like "90d", "2w", "6mo" or "1y", months are 30 days and years 365
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number.parse().ok()?;
+
+    let unit_seconds: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "s" => 1,
+        "min" => 60,
+        "h" => 3600,
+        "d" => 86_400,
+        "w" => 7 * 86_400,
+        "mo" => 30 * 86_400,
+        "y" => 365 * 86_400,
+        _ => return None,
+    };
+    number.checked_mul(unit_seconds).map(Duration::from_secs)
+}
+
+// A wait between two scans, as parse_duration except a bare "m" is minutes as in "5m"
+pub fn parse_interval(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let interval = match value.strip_suffix(['m', 'M']) {
+        Some(number) if number.chars().all(|c| c.is_ascii_digit()) => parse_duration(&format!("{}min", number)),
+        _ => parse_duration(value),
+    };
+    interval.filter(|interval| !interval.is_zero())
+}
+
+// A local date like "2023-11-24", optionally with a time as in "2023-11-24 18:02" or "2023-11-24T18:02:51"
+pub fn parse_date(value: &str) -> Option<SystemTime> {
+    let value = value.trim();
+    let (date, time) = match value.split_once([' ', 'T']) {
+        Some((date, time)) => (date, Some(time)),
+        None => (value, None),
+    };
+
+    let mut date_parts = date.splitn(3, '-');
+    let year: i64 = date_parts.next()?.parse().ok()?;
+    let month: u32 = date_parts.next()?.parse().ok()?;
+    let day: u32 = date_parts.next()?.parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    let mut seconds_of_day: i64 = 0;
+    if let Some(time) = time {
+        let parts: Vec<i64> = time.split(':').map(|part| part.parse().ok()).collect::<Option<_>>()?;
+        let (hours, minutes, seconds) = match parts[..] {
+            [hours, minutes] => (hours, minutes, 0),
+            [hours, minutes, seconds] => (hours, minutes, seconds),
+            _ => return None,
+        };
+        if !(0..24).contains(&hours) || !(0..60).contains(&minutes) || !(0..60).contains(&seconds) {
+            return None;
+        }
+        seconds_of_day = hours * 3600 + minutes * 60 + seconds;
+    }
+
+    let local = days_from_civil(year, month, day) * 86_400 + seconds_of_day;
+    Some(from_unix_seconds(local - local_offset(local)))
+}
+
+// A point in time given either as a duration back from now or as a date
+pub fn parse_time_bound(value: &str, now: SystemTime) -> Option<SystemTime> {
+    match parse_duration(value) {
+        Some(duration) => now.checked_sub(duration),
+        None => parse_date(value),
+    }
+}
@@ -0,0 +1,230 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::expr::RankExpr;
+use crate::FileEntry;
+
+// What makes an entry worth keeping
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Rank {
+    #[default]
+    Size,
+    Smallest,
+    // Size times the time since the entry was last read or written, the fat files nobody touches
+    Stale,
+    // Whatever scores the highest by an expression of --rank-expr
+    Expr(Arc<RankExpr>),
+}
+
+impl Rank {
+    pub fn parse(value: &str) -> Option<Rank> {
+        match value {
+            "size" => Some(Rank::Size),
+            "stale" => Some(Rank::Stale),
+            _ => None,
+        }
+    }
+}
+
+// An entry with its score, the path only breaks ties so results don't depend on the walk order
+#[derive(Debug, Clone)]
+struct Ranked {
+    score: f64,
+    entry: FileEntry,
+}
+
+impl PartialEq for Ranked {
+    fn eq(&self, other: &Ranked) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Ranked {}
+
+impl PartialOrd for Ranked {
+    fn partial_cmp(&self, other: &Ranked) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Ranked {
+    fn cmp(&self, other: &Ranked) -> Ordering {
+        self.score.total_cmp(&other.score).then_with(|| other.entry.path.cmp(&self.entry.path))
+    }
+}
+
+// Keeps the `capacity` best ranked entries pushed into it, the fattest by default. The lowest kept
+// score sits on top of a min-heap
+#[derive(Debug, Clone)]
+pub struct TopN {
+    capacity: usize,
+    rank: Rank,
+    // What the ages of stale entries are counted from
+    now: SystemTime,
+    heap: BinaryHeap<Reverse<Ranked>>,
+}
+
+impl TopN {
+    pub fn new(capacity: usize) -> TopN {
+        TopN::ranked(capacity, Rank::Size)
+    }
+
+    pub fn ranked(capacity: usize, rank: Rank) -> TopN {
+        TopN {
+            capacity,
+            rank,
+            now: SystemTime::now(),
+            heap: BinaryHeap::with_capacity(capacity.saturating_add(1).min(4096)),
+        }
+    }
+
+    fn score(&self, entry: &FileEntry) -> f64 {
+        match &self.rank {
+            Rank::Size => entry.size as f64,
+            Rank::Smallest => -(entry.size as f64),
+            Rank::Stale => {
+                // Entries without timestamps can't be told stale
+                let touched = entry.modified.max(entry.accessed);
+                let age = touched.and_then(|touched| self.now.duration_since(touched).ok()).map_or(0, |age| age.as_secs());
+                entry.size as f64 * age as f64
+            }
+            Rank::Expr(expr) => expr.score(entry, self.now),
+        }
+    }
+
+    pub fn push(&mut self, entry: FileEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let entry = Ranked { score: self.score(&entry), entry };
+        self.push_ranked(entry);
+    }
+
+    fn push_ranked(&mut self, entry: Ranked) {
+        if self.heap.len() < self.capacity {
+            self.heap.push(Reverse(entry));
+        } else if let Some(mut lowest) = self.heap.peek_mut() {
+            // Replacing the top keeps the heap at capacity with a single sift
+            if entry > lowest.0 {
+                *lowest = Reverse(entry);
+            }
+        }
+    }
+
+    // Fold another selection in, as when merging per thread results
+    pub fn merge(&mut self, other: TopN) {
+        for Reverse(entry) in other.heap {
+            self.push_ranked(entry);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    // Best ranked first: biggest, smallest or stalest
+    pub fn into_sorted_vec(self) -> Vec<FileEntry> {
+        // Ascending order of Reverse is descending order of scores
+        self.heap.into_sorted_vec().into_iter().map(|Reverse(Ranked { entry, .. })| entry).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str, size: u64) -> FileEntry {
+        FileEntry::new(path.to_string(), size)
+    }
+
+    fn sizes(top: TopN) -> Vec<u64> {
+        top.into_sorted_vec().iter().map(|e| e.size).collect()
+    }
+
+    #[test]
+    fn keeps_the_biggest_in_descending_order() {
+        let mut top = TopN::new(3);
+        for (i, size) in [5, 1, 9, 3, 7, 2, 8].iter().enumerate() {
+            top.push(entry(&format!("f{}", i), *size));
+        }
+        assert_eq!(top.len(), 3);
+        assert_eq!(sizes(top), vec![9, 8, 7]);
+    }
+
+    #[test]
+    fn fewer_entries_than_capacity_are_all_kept() {
+        let mut top = TopN::new(10);
+        top.push(entry("a", 2));
+        top.push(entry("b", 4));
+        assert_eq!(sizes(top), vec![4, 2]);
+    }
+
+    #[test]
+    fn zero_capacity_keeps_nothing() {
+        let mut top = TopN::new(0);
+        top.push(entry("a", 2));
+        assert!(top.is_empty());
+        assert!(top.into_sorted_vec().is_empty());
+    }
+
+    #[test]
+    fn ties_are_broken_by_path() {
+        let mut top = TopN::new(2);
+        top.push(entry("c", 1));
+        top.push(entry("a", 1));
+        top.push(entry("b", 1));
+        let paths: Vec<String> = top.into_sorted_vec().into_iter().map(|e| e.path).collect();
+        assert_eq!(paths, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn smallest_keeps_the_smallest_in_ascending_order() {
+        let mut top = TopN::ranked(3, Rank::Smallest);
+        for (i, size) in [5, 1, 9, 3, 7, 2, 8].iter().enumerate() {
+            top.push(entry(&format!("f{}", i), *size));
+        }
+        assert_eq!(sizes(top), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn stale_weighs_size_by_age() {
+        let now = SystemTime::now();
+        let aged = |path: &str, size: u64, days: u64| FileEntry {
+            modified: Some(now - std::time::Duration::from_secs(days * 24 * 60 * 60)),
+            ..entry(path, size)
+        };
+        let mut top = TopN::ranked(2, Rank::Stale);
+        top.push(aged("fresh", 1000, 1));
+        top.push(aged("old", 100, 365));
+        top.push(aged("older", 10, 3650));
+        let paths: Vec<String> = top.into_sorted_vec().into_iter().map(|e| e.path).collect();
+        assert_eq!(paths, vec!["old", "older"]);
+    }
+
+    #[test]
+    fn merging_matches_a_single_selection() {
+        let sizes_in: Vec<u64> = (0..100).map(|i| (i * 37) % 101).collect();
+
+        let mut single = TopN::new(5);
+        let mut left = TopN::new(5);
+        let mut right = TopN::new(5);
+        for (i, size) in sizes_in.iter().enumerate() {
+            single.push(entry(&format!("f{}", i), *size));
+            if i % 2 == 0 {
+                left.push(entry(&format!("f{}", i), *size));
+            } else {
+                right.push(entry(&format!("f{}", i), *size));
+            }
+        }
+        left.merge(right);
+
+        assert_eq!(sizes(left), sizes(single));
+    }
+}
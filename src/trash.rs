@@ -0,0 +1,155 @@
+// Moving entries to the platform trash instead of unlinking them, so they can be restored
+use std::io;
+use std::path::Path;
+#[cfg(any(target_os = "macos", windows))]
+use std::process::Command;
+
+#[cfg(windows)]
+use crate::script::ScriptShell;
+
+#[cfg(any(target_os = "macos", windows))]
+fn run(command: &mut Command) -> io::Result<()> {
+    let output = command.output()?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(String::from_utf8_lossy(&output.stderr).trim().to_string()))
+    }
+}
+
+// Follows the freedesktop.org trash specification, entries on another device than the home trash
+// go to the $topdir/.Trash-$uid directory of their own mount
+#[cfg(all(unix, not(target_os = "macos")))]
+mod xdg {
+    use std::env;
+    use std::ffi::OsString;
+    use std::fs::{self, OpenOptions};
+    use std::io::{self, Write};
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+    use std::path::{Path, PathBuf};
+    use std::time::SystemTime;
+
+    use crate::time::format_time_iso;
+
+    fn home_trash() -> Option<PathBuf> {
+        let data_home = env::var_os("XDG_DATA_HOME")
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local").join("share")))?;
+        Some(data_home.join("Trash"))
+    }
+
+    // The highest ancestor still on the same device as path
+    fn mount_root(path: &Path, dev: u64) -> PathBuf {
+        let mut root = path.to_path_buf();
+        for ancestor in path.ancestors().skip(1) {
+            match fs::metadata(ancestor) {
+                Ok(metadata) if metadata.dev() == dev => root = ancestor.to_path_buf(),
+                _ => break,
+            }
+        }
+        root
+    }
+
+    fn trash_dir(path: &Path) -> io::Result<PathBuf> {
+        let dev = fs::symlink_metadata(path)?.dev();
+        if let Some(home_trash) = home_trash() {
+            fs::create_dir_all(&home_trash)?;
+            if fs::metadata(&home_trash)?.dev() == dev {
+                return Ok(home_trash);
+            }
+        }
+
+        // SAFETY: getuid has no preconditions and never fails
+        let uid = unsafe { libc::getuid() };
+        let trash = mount_root(path, dev).join(format!(".Trash-{}", uid));
+        fs::DirBuilder::new().recursive(true).mode(0o700).create(&trash)?;
+        Ok(trash)
+    }
+
+    // Percent encode everything but unreserved characters and separators, as the Path key expects
+    fn encode(path: &Path) -> String {
+        path.as_os_str().as_bytes().iter().map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => (*byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        }).collect()
+    }
+
+    pub(super) fn move_to_trash(path: &Path) -> io::Result<()> {
+        // Only the parent is resolved, a symlink is trashed itself and not its target
+        let name = path.file_name().ok_or_else(|| io::Error::other(format!("cannot trash {}", path.display())))?;
+        let parent = path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let path = fs::canonicalize(parent)?.join(name);
+
+        let trash = trash_dir(&path)?;
+        let files_dir = trash.join("files");
+        let info_dir = trash.join("info");
+        fs::create_dir_all(&files_dir)?;
+        fs::create_dir_all(&info_dir)?;
+
+        // Creating the info file first reserves the name, "name.2", "name.3" and so on when taken
+        let mut attempt: u32 = 1;
+        loop {
+            let mut trashed_name = OsString::from(name);
+            if attempt > 1 {
+                trashed_name.push(format!(".{}", attempt));
+            }
+            attempt += 1;
+
+            let mut info_name = trashed_name.clone();
+            info_name.push(".trashinfo");
+            let info_path = info_dir.join(info_name);
+            let mut info = match OpenOptions::new().write(true).create_new(true).open(&info_path) {
+                Ok(info) => info,
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err),
+            };
+            let trashed_path = files_dir.join(&trashed_name);
+            if fs::symlink_metadata(&trashed_path).is_ok() {
+                drop(info);
+                let _ = fs::remove_file(&info_path);
+                continue;
+            }
+
+            let written = write!(info, "[Trash Info]\nPath={}\nDeletionDate={}\n", encode(&path), format_time_iso(SystemTime::now()))
+                .and_then(|_| fs::rename(&path, &trashed_path));
+            if written.is_err() {
+                let _ = fs::remove_file(&info_path);
+            }
+            return written;
+        }
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+pub(crate) fn move_to_trash(path: &Path, _is_dir: bool) -> io::Result<()> {
+    xdg::move_to_trash(path)
+}
+
+// Finder keeps the "Put Back" information, which a plain move to ~/.Trash would lose
+#[cfg(target_os = "macos")]
+pub(crate) fn move_to_trash(path: &Path, _is_dir: bool) -> io::Result<()> {
+    let path = std::path::absolute(path)?;
+    let escaped = path.to_string_lossy().replace('\\', "\\\\").replace('"', "\\\"");
+    let script = format!("tell application \"Finder\" to delete POSIX file \"{}\"", escaped);
+    run(Command::new("osascript").arg("-e").arg(script))
+}
+
+#[cfg(windows)]
+pub(crate) fn move_to_trash(path: &Path, is_dir: bool) -> io::Result<()> {
+    let path = std::path::absolute(path)?;
+    // Curly quotes end a PowerShell string too, a name like Bob’s report.pdf would otherwise cut it short
+    let quoted = ScriptShell::PowerShell.quote(&path.to_string_lossy());
+    let method = if is_dir { "DeleteDirectory" } else { "DeleteFile" };
+    let script = format!(
+        "Add-Type -AssemblyName Microsoft.VisualBasic; [Microsoft.VisualBasic.FileIO.FileSystem]::{}({}, 'OnlyErrorDialogs', 'SendToRecycleBin')",
+        method, quoted
+    );
+    run(Command::new("powershell").args(["-NoProfile", "-NonInteractive", "-Command"]).arg(script))
+}
+
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn move_to_trash(_path: &Path, _is_dir: bool) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "no trash on this platform"))
+}
@@ -0,0 +1,99 @@
+// Scanned files summed up by directory, every directory holding the total of all the files below
+// it. The report server browses it and the treemap draws it
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+use crate::FileEntry;
+
+// The parent of every directory without one, so absolute and relative search paths share a top
+pub const TOP: &str = "";
+
+#[derive(Debug, Default)]
+pub struct DirNode {
+    pub size: u64,
+    pub files: u64,
+    // Files right in the directory, not in its subdirectories
+    pub own_size: u64,
+    pub own_files: u64,
+    pub children: BTreeSet<String>,
+}
+
+#[derive(Debug)]
+pub struct DirTree {
+    dirs: HashMap<String, DirNode>,
+    // The deepest directory holding every file
+    home: String,
+}
+
+impl DirTree {
+    pub fn new(files: &[FileEntry]) -> DirTree {
+        let mut dirs: HashMap<String, DirNode> = HashMap::new();
+        dirs.entry(TOP.to_string()).or_default();
+        for file_entry in files {
+            let mut child: Option<String> = None;
+            let mut dir = Path::new(&file_entry.path).parent();
+            loop {
+                let key = match dir.filter(|path| !path.as_os_str().is_empty()) {
+                    Some(path) => path.to_string_lossy().to_string(),
+                    None => TOP.to_string(),
+                };
+                let node = dirs.entry(key.clone()).or_default();
+                node.size += file_entry.size;
+                node.files += 1;
+                match child.take() {
+                    Some(child) => {
+                        node.children.insert(child);
+                    }
+                    None => {
+                        node.own_size += file_entry.size;
+                        node.own_files += 1;
+                    }
+                }
+                if key == TOP {
+                    break;
+                }
+                child = Some(key);
+                dir = dir.and_then(Path::parent);
+            }
+        }
+
+        // Skip the directories leading to the search paths, they hold nothing else
+        let mut home = TOP.to_string();
+        while let Some(node) = dirs.get(&home).filter(|node| node.own_files == 0 && node.children.len() == 1) {
+            home = node.children.iter().next().cloned().unwrap_or_default();
+        }
+        DirTree { dirs, home }
+    }
+
+    pub fn home(&self) -> &str {
+        &self.home
+    }
+
+    pub fn get(&self, dir: &str) -> Option<&DirNode> {
+        self.dirs.get(dir)
+    }
+
+    // The subdirectories of a directory, biggest first
+    pub fn children(&self, dir: &str) -> Vec<(&str, &DirNode)> {
+        let Some(node) = self.dirs.get(dir) else { return Vec::new() };
+        let mut children: Vec<(&str, &DirNode)> = node.children.iter()
+            .filter_map(|child| self.dirs.get(child).map(|child_node| (child.as_str(), child_node)))
+            .collect();
+        children.sort_by(|a, b| b.1.size.cmp(&a.1.size).then_with(|| a.0.cmp(b.0)));
+        children
+    }
+
+    // The parent the tree was built with, the top one past the root directories
+    pub fn parent(&self, dir: &str) -> Option<String> {
+        if dir == TOP {
+            return None;
+        }
+        let parent = Path::new(dir).parent().map(|path| path.to_string_lossy().to_string());
+        Some(parent.unwrap_or_else(|| TOP.to_string()))
+    }
+}
+
+// The last component of a directory, the whole path when it has none, like / or .
+pub fn dir_name(dir: &str) -> String {
+    Path::new(dir).file_name().map_or_else(|| dir.to_string(), |name| name.to_string_lossy().to_string())
+}
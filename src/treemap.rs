@@ -0,0 +1,176 @@
+// A treemap of the scanned directories as an SVG picture: every directory is a box sized by the
+// files below it, holding its subdirectories and a box for its own files, laid out with the
+// squarified algorithm so the boxes stay close to squares. Hovering a box shows its path and size
+use std::cmp::Reverse;
+use std::io::{self, Write};
+
+use crate::markup::html_escape;
+use crate::tree::{dir_name, DirTree, TOP};
+use crate::format_size;
+
+const WIDTH: f64 = 1280.0;
+const HEIGHT: f64 = 800.0;
+// Room for the title above the boxes and for the name atop each directory box
+const BANNER: f64 = 28.0;
+const HEADER: f64 = 15.0;
+const PADDING: f64 = 2.0;
+// Boxes smaller than this aren't drawn nor looked into
+const SMALLEST: f64 = 4.0;
+const MAX_DEPTH: usize = 8;
+// Roughly how wide a character of the labels is
+const CHAR_WIDTH: f64 = 6.5;
+
+#[derive(Debug, Clone, Copy)]
+struct Rect {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+enum Item<'a> {
+    Dir(&'a str),
+    // The files right in a directory, drawn as one box
+    OwnFiles(&'a str),
+}
+
+// How far the worst box of a row is from a square, with the row laid along a side of that length
+fn worst_ratio(areas: &[f64], side: f64) -> f64 {
+    let sum: f64 = areas.iter().sum();
+    let largest = areas.iter().cloned().fold(f64::MIN, f64::max);
+    let smallest = areas.iter().cloned().fold(f64::MAX, f64::min);
+    let side_squared = side * side;
+    f64::max(side_squared * largest / (sum * sum), sum * sum / (side_squared * smallest))
+}
+
+// Lay out sizes in a rectangle, biggest first, in rows along its shorter side, adding to a row as
+// long as that brings its boxes closer to squares
+fn squarify(sizes: &[u64], rect: Rect) -> Vec<Rect> {
+    let total: f64 = sizes.iter().map(|&size| size as f64).sum();
+    if total <= 0.0 {
+        return Vec::new();
+    }
+    let areas: Vec<f64> = sizes.iter().map(|&size| size as f64 * rect.width * rect.height / total).collect();
+    let mut placed = Vec::with_capacity(areas.len());
+    let mut free = rect;
+    let mut start = 0;
+    while start < areas.len() {
+        let side = free.width.min(free.height);
+        let mut end = start + 1;
+        while end < areas.len() && worst_ratio(&areas[start..=end], side) <= worst_ratio(&areas[start..end], side) {
+            end += 1;
+        }
+
+        let row_area: f64 = areas[start..end].iter().sum();
+        if free.width >= free.height {
+            // A column on the left
+            let column_width = if free.height > 0.0 { row_area / free.height } else { 0.0 };
+            let mut y = free.y;
+            for area in &areas[start..end] {
+                let height = if column_width > 0.0 { area / column_width } else { 0.0 };
+                placed.push(Rect { x: free.x, y, width: column_width, height });
+                y += height;
+            }
+            free.x += column_width;
+            free.width -= column_width;
+        } else {
+            // A row on top
+            let row_height = if free.width > 0.0 { row_area / free.width } else { 0.0 };
+            let mut x = free.x;
+            for area in &areas[start..end] {
+                let width = if row_height > 0.0 { area / row_height } else { 0.0 };
+                placed.push(Rect { x, y: free.y, width, height: row_height });
+                x += width;
+            }
+            free.y += row_height;
+            free.height -= row_height;
+        }
+        start = end;
+    }
+    placed
+}
+
+// The label cut to what fits in a width, none when not even a few characters do
+fn fit_label(label: &str, width: f64) -> Option<String> {
+    let fits = ((width - 4.0) / CHAR_WIDTH) as usize;
+    let length = label.chars().count();
+    if length <= fits {
+        Some(label.to_string())
+    } else if fits >= 4 {
+        Some(format!("{}…", label.chars().take(fits - 1).collect::<String>()))
+    } else {
+        None
+    }
+}
+
+fn fill(hue: f64, depth: usize, own_files: bool) -> String {
+    let lightness = (38 + depth * 7).min(80) + if own_files { 10 } else { 0 };
+    format!("hsl({:.0},55%,{}%)", hue, lightness.min(90))
+}
+
+fn write_box(out: &mut dyn Write, rect: Rect, color: &str, tooltip: &str, label: Option<&str>) -> io::Result<()> {
+    writeln!(
+        out,
+        "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\" stroke=\"#fff\" stroke-width=\"0.5\"><title>{}</title></rect>",
+        rect.x, rect.y, rect.width, rect.height, color, html_escape(tooltip)
+    )?;
+    if let Some(label) = label.filter(|_| rect.height >= HEADER).and_then(|label| fit_label(label, rect.width)) {
+        writeln!(out, "<text x=\"{:.1}\" y=\"{:.1}\">{}</text>", rect.x + 3.0, rect.y + 11.0, html_escape(&label))?;
+    }
+    Ok(())
+}
+
+fn write_dir(out: &mut dyn Write, tree: &DirTree, dir: &str, rect: Rect, depth: usize, hue: f64) -> io::Result<()> {
+    let Some(node) = tree.get(dir) else { return Ok(()) };
+    let tooltip = format!("{}\n{} in {} files", dir, format_size(node.size), node.files);
+    write_box(out, rect, &fill(hue, depth, false), &tooltip, Some(&dir_name(dir)))?;
+
+    let inside = Rect {
+        x: rect.x + PADDING,
+        y: rect.y + HEADER,
+        width: rect.width - 2.0 * PADDING,
+        height: rect.height - HEADER - PADDING,
+    };
+    if depth >= MAX_DEPTH || inside.width < SMALLEST || inside.height < SMALLEST {
+        return Ok(());
+    }
+
+    let mut items: Vec<(u64, Item)> = tree.children(dir).into_iter().map(|(child, child_node)| (child_node.size, Item::Dir(child))).collect();
+    if node.own_size > 0 {
+        items.push((node.own_size, Item::OwnFiles(dir)));
+        items.sort_by_key(|(size, _)| Reverse(*size));
+    }
+    let sizes: Vec<u64> = items.iter().map(|(size, _)| *size).collect();
+    for ((index, (size, item)), placed) in items.iter().enumerate().zip(squarify(&sizes, inside)) {
+        if placed.width < SMALLEST || placed.height < SMALLEST {
+            continue;
+        }
+        // The top level picks the colors, the boxes below keep them and get lighter
+        let hue = if depth == 0 { (index as f64 * 137.5) % 360.0 } else { hue };
+        match item {
+            Item::Dir(child) => write_dir(out, tree, child, placed, depth + 1, hue)?,
+            Item::OwnFiles(dir) => {
+                let own_files = tree.get(dir).map_or(0, |node| node.own_files);
+                let tooltip = format!("{} files right in {}\n{}", own_files, dir, format_size(*size));
+                write_box(out, placed, &fill(hue, depth + 1, true), &tooltip, Some("files"))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn write_treemap(out: &mut dyn Write, tree: &DirTree) -> io::Result<()> {
+    let home = tree.home();
+    let (size, files) = tree.get(home).map_or((0, 0), |node| (node.size, node.files));
+    let name = if home == TOP { "every search path" } else { home };
+    writeln!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\" font-family=\"sans-serif\" font-size=\"11\">",
+        WIDTH, HEIGHT, WIDTH, HEIGHT
+    )?;
+    writeln!(out, "<rect width=\"100%\" height=\"100%\" fill=\"#fff\"/>")?;
+    let title = format!("fatass treemap of {}, {} in {} files", name, format_size(size), files);
+    writeln!(out, "<text x=\"4\" y=\"19\" font-size=\"15\">{}</text>", html_escape(&title))?;
+    write_dir(out, tree, home, Rect { x: 0.0, y: BANNER, width: WIDTH, height: HEIGHT - BANNER }, 0, 210.0)?;
+    writeln!(out, "</svg>")
+}
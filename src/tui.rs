@@ -0,0 +1,311 @@
+// The --tui explorer, drawn with console's Term rather than ratatui: ratatui isn't among the crates
+// the build can use, and reading keys and redrawing a list of lines is all the explorer needs
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use colored::Colorize;
+use console::{Key, Term};
+
+use crate::desktop::{copy_to_clipboard, open_folder};
+use crate::hardlinks::SeenLinks;
+use crate::scanner::{measure_file, spinner, walk};
+use crate::{EntryKind, FileEntry, Scanner, TopN};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SortOrder {
+    SizeDesc,
+    SizeAsc,
+    Name,
+}
+
+impl SortOrder {
+    fn next(self) -> SortOrder {
+        match self {
+            SortOrder::SizeDesc => SortOrder::SizeAsc,
+            SortOrder::SizeAsc => SortOrder::Name,
+            SortOrder::Name => SortOrder::SizeDesc,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            SortOrder::SizeDesc => "size desc",
+            SortOrder::SizeAsc => "size asc",
+            SortOrder::Name => "name",
+        }
+    }
+}
+
+// Everything the explorer needs: the fattest children of every directory
+struct Tree {
+    children: HashMap<PathBuf, Vec<FileEntry>>,
+}
+
+impl Tree {
+    // Walk once, keeping only the fattest entries of each directory so huge trees stay cheap to browse
+    // With several search paths they are listed under a virtual top level with an empty path
+    fn build(scanner: &Scanner) -> Tree {
+        let progress_bar = spinner(scanner.progress);
+        let (fatass_count, filters) = (scanner.fatass_count, &scanner.filters);
+        let roots = scanner.roots();
+
+        let mut children: HashMap<PathBuf, TopN> = HashMap::new();
+        let mut dir_sizes: HashMap<PathBuf, u64> = HashMap::new();
+        let mut dirs: Vec<PathBuf> = Vec::new();
+        let seen_links = SeenLinks::new(scanner.hardlinks);
+
+        for search_path in &roots {
+            let root = Path::new(search_path);
+            for entry in walk(search_path, filters, &scanner.errors) {
+                if entry.file_type().is_dir() {
+                    dir_sizes.entry(entry.path().to_path_buf()).or_insert(0);
+                    if entry.depth() > 0 || roots.len() > 1 {
+                        dirs.push(entry.path().to_path_buf());
+                    }
+                    continue;
+                }
+                if !filters.keeps_file(root, entry.path()) {
+                    continue;
+                }
+
+                let Some(metadata) = scanner.errors.walk_metadata(&entry).filter(|metadata| seen_links.first_visit(metadata)) else { continue };
+                let file_entry = measure_file(entry.path(), &metadata, scanner.disk_usage);
+                let size = file_entry.size;
+                if !filters.keeps_entry(&file_entry) {
+                    continue;
+                }
+                for dir in entry.path().ancestors().skip(1).take_while(|dir| dir.starts_with(root)) {
+                    *dir_sizes.entry(dir.to_path_buf()).or_insert(0) += size;
+                }
+                let parent = if entry.depth() == 0 { Path::new("") } else { entry.path().parent().unwrap_or(root) };
+                children.entry(parent.to_path_buf())
+                    .or_insert_with(|| TopN::new(fatass_count))
+                    .push(file_entry);
+                progress_bar.inc(1);
+            }
+        }
+        progress_bar.finish_and_clear();
+
+        for dir in dirs {
+            let size = dir_sizes.get(&dir).copied().unwrap_or(0);
+            let parent = if roots.iter().any(|root| Path::new(root) == dir) {
+                PathBuf::new()
+            } else {
+                dir.parent().map(Path::to_path_buf).unwrap_or_default()
+            };
+            children.entry(parent)
+                .or_insert_with(|| TopN::new(fatass_count))
+                .push(FileEntry::new_dir(dir.display().to_string(), size));
+        }
+
+        Tree {
+            children: children.into_iter().map(|(dir, top)| (dir, top.into_sorted_vec())).collect(),
+        }
+    }
+
+    fn list(&self, dir: &Path) -> &[FileEntry] {
+        self.children.get(dir).map(|c| c.as_slice()).unwrap_or(&[])
+    }
+}
+
+struct Explorer {
+    tree: Tree,
+    current: PathBuf,
+    // Directories we came from and their selection, restored when going back up
+    history: Vec<(PathBuf, usize)>,
+    selected: usize,
+    offset: usize,
+    sort: SortOrder,
+    filter: String,
+    editing_filter: bool,
+    // What the last key did, shown in place of the footer until the next one
+    message: Option<String>,
+}
+
+impl Explorer {
+    fn visible(&self) -> Vec<&FileEntry> {
+        let needle = self.filter.to_lowercase();
+        let mut entries: Vec<&FileEntry> = self.tree.list(&self.current)
+            .iter()
+            .filter(|e| needle.is_empty() || e.name().to_lowercase().contains(&needle))
+            .collect();
+
+        match self.sort {
+            SortOrder::SizeDesc => entries.sort_by_key(|e| std::cmp::Reverse(e.size)),
+            SortOrder::SizeAsc => entries.sort_by_key(|e| e.size),
+            SortOrder::Name => entries.sort_by_key(|e| e.name().to_lowercase()),
+        }
+        entries
+    }
+
+    fn enter(&mut self) {
+        let target = match self.visible().get(self.selected) {
+            Some(entry) if entry.kind == EntryKind::Directory => PathBuf::from(&entry.path),
+            _ => return,
+        };
+        self.history.push((std::mem::replace(&mut self.current, target), self.selected));
+        self.selected = 0;
+        self.offset = 0;
+        self.filter.clear();
+    }
+
+    fn leave(&mut self) {
+        if let Some((parent, selected)) = self.history.pop() {
+            self.current = parent;
+            self.selected = selected;
+            self.offset = 0;
+            self.filter.clear();
+        }
+    }
+
+    fn render(&mut self, term: &Term) -> io::Result<()> {
+        let (rows, cols) = term.size();
+        let (rows, cols) = (rows as usize, cols as usize);
+        let list_height = rows.saturating_sub(3).max(1);
+        let entries = self.visible();
+
+        let selected = self.selected.min(entries.len().saturating_sub(1));
+        let mut offset = self.offset;
+        if selected < offset {
+            offset = selected;
+        } else if selected >= offset + list_height {
+            offset = selected + 1 - list_height;
+        }
+
+        let location = if self.current.as_os_str().is_empty() { "(all paths)".to_string() } else { self.current.display().to_string() };
+        let mut header = format!(" fatass  {}  [sort: {}]", location, self.sort.label());
+        if !self.filter.is_empty() || self.editing_filter {
+            header.push_str(&format!("  [filter: {}{}]", self.filter, if self.editing_filter { "_" } else { "" }));
+        }
+
+        let mut screen = vec![truncate(&header, cols).green().bold().to_string(), "─".repeat(cols).green().to_string()];
+        for (i, entry) in entries.iter().enumerate().skip(offset).take(list_height) {
+            let suffix = if entry.kind == EntryKind::Directory { "/" } else { "" };
+            let line = truncate(&format!(" {:>10}  {}{}", entry.get_str_size(), entry.name(), suffix), cols);
+            let line = format!("{:<width$}", line, width = cols);
+            screen.push(if i == selected {
+                line.reversed().to_string()
+            } else if entry.kind == EntryKind::Directory {
+                line.cyan().to_string()
+            } else {
+                line
+            });
+        }
+        if entries.is_empty() {
+            screen.push(" (empty)".dimmed().to_string());
+        }
+        while screen.len() < rows.saturating_sub(1) {
+            screen.push(String::new());
+        }
+        let footer = match &self.message {
+            Some(message) => format!(" {}", message),
+            None => String::from(" ↑↓ move  ⏎/→ open  ←/⌫ up  s sort  / filter  o show in folder  y copy path  q quit"),
+        };
+        screen.push(truncate(&footer, cols).dimmed().to_string());
+
+        self.selected = selected;
+        self.offset = offset;
+        term.clear_screen()?;
+        term.write_str(&screen.join("\n"))
+    }
+
+    // Open the folder of the selected entry or copy its path, telling how it went
+    fn hand_over(&mut self, copy: bool) {
+        let Some(path) = self.visible().get(self.selected).map(|entry| entry.path.clone()) else { return };
+        let message = if copy {
+            copy_to_clipboard(&path).map(|by| format!("Copied {} with {}", path, by)).unwrap_or_else(|err| format!("Could not copy the path: {}", err))
+        } else {
+            open_folder(Path::new(&path)).map(|_| format!("Opened the folder of {}", path)).unwrap_or_else(|err| format!("Could not open the folder: {}", err))
+        };
+        self.message = Some(message);
+    }
+
+    fn handle_key(&mut self, key: Key, page: usize) -> bool {
+        self.message = None;
+        if self.editing_filter {
+            match key {
+                Key::Enter => self.editing_filter = false,
+                Key::Escape => {
+                    self.editing_filter = false;
+                    self.filter.clear();
+                }
+                Key::Backspace => {
+                    self.filter.pop();
+                }
+                Key::Char(c) => self.filter.push(c),
+                _ => {}
+            }
+            self.selected = 0;
+            return true;
+        }
+
+        let last = self.visible().len().saturating_sub(1);
+        match key {
+            Key::Char('q') => return false,
+            Key::ArrowUp | Key::Char('k') => self.selected = self.selected.saturating_sub(1),
+            Key::ArrowDown | Key::Char('j') => self.selected = (self.selected + 1).min(last),
+            Key::PageUp => self.selected = self.selected.saturating_sub(page),
+            Key::PageDown => self.selected = (self.selected + page).min(last),
+            Key::Home => self.selected = 0,
+            Key::End => self.selected = last,
+            Key::Enter | Key::ArrowRight | Key::Char('l') => self.enter(),
+            Key::Backspace | Key::ArrowLeft | Key::Char('h') => self.leave(),
+            Key::Char('s') => {
+                self.sort = self.sort.next();
+                self.selected = 0;
+            }
+            Key::Char('/') => {
+                self.editing_filter = true;
+                self.filter.clear();
+            }
+            Key::Escape => self.filter.clear(),
+            Key::Char('o') => self.hand_over(false),
+            Key::Char('y') => self.hand_over(true),
+            _ => {}
+        }
+        true
+    }
+}
+
+
+fn truncate(line: &str, width: usize) -> String {
+    line.chars().take(width).collect()
+}
+
+// Browse the fattest entries of every directory, drilling down from the search path
+pub fn run(scanner: &Scanner) -> io::Result<()> {
+    let term = Term::stdout();
+    if !term.is_term() {
+        return Err(io::Error::other("--tui needs an interactive terminal"));
+    }
+
+    let roots = scanner.roots();
+    let root = if roots.len() == 1 { PathBuf::from(&roots[0]) } else { PathBuf::new() };
+    let mut explorer = Explorer {
+        tree: Tree::build(scanner),
+        current: root,
+        history: Vec::new(),
+        selected: 0,
+        offset: 0,
+        sort: SortOrder::SizeDesc,
+        filter: String::new(),
+        editing_filter: false,
+        message: None,
+    };
+
+    // Alternate screen so the shell scrollback is left untouched on exit
+    term.write_str("\x1b[?1049h")?;
+    term.hide_cursor()?;
+    let result = (|| {
+        loop {
+            explorer.render(&term)?;
+            let page = (term.size().0 as usize).saturating_sub(3).max(1);
+            if !explorer.handle_key(term.read_key()?, page) {
+                return Ok(());
+            }
+        }
+    })();
+    term.show_cursor()?;
+    term.write_str("\x1b[?1049l")?;
+    result
+}
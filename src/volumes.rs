@@ -0,0 +1,89 @@
+// The space of the filesystems the search paths are on, for the --fs-usage header. Sizes of the
+// results mean more next to what the disk holds and what's left of it
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Volume {
+    // Where the filesystem is mounted, the drive root on Windows
+    pub mount: PathBuf,
+    pub total: u64,
+    pub used: u64,
+    // What can still be written by the user, without the blocks kept for root
+    pub free: u64,
+}
+
+// The filesystems of the search paths, each once and in the order of the paths, those that can't be
+// asked left out
+pub fn volumes_of(search_paths: &[String]) -> Vec<Volume> {
+    let mut volumes: Vec<Volume> = Vec::new();
+    for search_path in search_paths {
+        let Ok(path) = Path::new(search_path).canonicalize() else { continue };
+        let mount = mount_point(&path);
+        if volumes.iter().any(|volume| volume.mount == mount) {
+            continue;
+        }
+        if let Some(volume) = volume(mount) {
+            volumes.push(volume);
+        }
+    }
+    volumes
+}
+
+// The last ancestor on the same device, where the filesystem is mounted
+#[cfg(unix)]
+fn mount_point(path: &Path) -> PathBuf {
+    use std::os::unix::fs::MetadataExt;
+
+    let device = |path: &Path| path.metadata().ok().map(|metadata| metadata.dev());
+    let own_device = device(path);
+    path.ancestors().take_while(|ancestor| device(ancestor) == own_device).last().unwrap_or(path).to_path_buf()
+}
+
+// The drive or share the path is on
+#[cfg(not(unix))]
+fn mount_point(path: &Path) -> PathBuf {
+    use std::path::Component;
+    path.components().take_while(|component| matches!(component, Component::Prefix(_) | Component::RootDir)).collect()
+}
+
+#[cfg(unix)]
+fn volume(mount: PathBuf) -> Option<Volume> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(mount.as_os_str().as_bytes()).ok()?;
+    // SAFETY: c_path is nul terminated and stats outlives the call, zeroed is a valid statvfs
+    let stats = unsafe {
+        let mut stats: libc::statvfs = std::mem::zeroed();
+        if libc::statvfs(c_path.as_ptr(), &mut stats) != 0 {
+            return None;
+        }
+        stats
+    };
+    let block = stats.f_frsize as u64;
+    Some(Volume {
+        mount,
+        total: stats.f_blocks as u64 * block,
+        used: (stats.f_blocks as u64).saturating_sub(stats.f_bfree as u64) * block,
+        free: stats.f_bavail as u64 * block,
+    })
+}
+
+#[cfg(windows)]
+fn volume(mount: PathBuf) -> Option<Volume> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let wide: Vec<u16> = mount.as_os_str().encode_wide().chain(std::iter::once(0)).collect();
+    let (mut available, mut total, mut total_free) = (0u64, 0u64, 0u64);
+    // SAFETY: wide is nul terminated and the three counts outlive the call
+    if unsafe { GetDiskFreeSpaceExW(wide.as_ptr(), &mut available, &mut total, &mut total_free) } == 0 {
+        return None;
+    }
+    Some(Volume { mount, total, used: total.saturating_sub(total_free), free: available })
+}
+
+#[cfg(not(any(unix, windows)))]
+fn volume(_mount: PathBuf) -> Option<Volume> {
+    None
+}
@@ -0,0 +1,273 @@
+// What Windows needs on top of the std library: the \\?\ form of paths past MAX_PATH for the
+// Win32 calls made directly, file ids so directories reached through several junctions count
+// once, the attributes telling junctions and cloud placeholders apart, and the alternate data
+// streams NTFS files may carry besides their content. The path, attribute and stream name logic
+// is plain string and bit work, only reading them from the system is Windows only
+use std::borrow::Cow;
+use std::fs::Metadata;
+use std::path::Path;
+
+// Longest path the Win32 calls take without the \\?\ prefix, the nul included
+#[cfg(any(windows, test))]
+const MAX_PATH: usize = 260;
+const VERBATIM: &str = "\\\\?\\";
+const VERBATIM_UNC: &str = "\\\\?\\UNC\\";
+// File attributes
+const FILE_ATTRIBUTE_OFFLINE: u32 = 0x1000;
+const FILE_ATTRIBUTE_RECALL_ON_OPEN: u32 = 0x40000;
+const FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS: u32 = 0x400000;
+// The reparse tag of junctions, volume mount points included
+const IO_REPARSE_TAG_MOUNT_POINT: u32 = 0xa000_0003;
+
+// The path as the user would type it, without the \\?\ prefix canonical paths come with
+pub(crate) fn display_path(path: &str) -> Cow<'_, str> {
+    if let Some(share) = path.strip_prefix(VERBATIM_UNC) {
+        Cow::Owned(format!("\\\\{}", share))
+    } else if let Some(rest) = path.strip_prefix(VERBATIM).filter(|rest| rest.as_bytes().get(1) == Some(&b':')) {
+        Cow::Borrowed(rest)
+    } else {
+        Cow::Borrowed(path)
+    }
+}
+
+// The \\?\ form of an absolute path too long for the Win32 calls, none when it fits or can't have
+// one. Verbatim paths skip the normalization of separators, so it's done here
+#[cfg(any(windows, test))]
+fn verbatim(path: &str) -> Option<String> {
+    if path.len() < MAX_PATH || path.starts_with(VERBATIM) {
+        return None;
+    }
+    let path = path.replace('/', "\\");
+    if let Some(share) = path.strip_prefix("\\\\") {
+        return Some(format!("{}{}", VERBATIM_UNC, share));
+    }
+    let bytes = path.as_bytes();
+    (bytes.len() > 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && bytes[2] == b'\\').then(|| format!("{}{}", VERBATIM, path))
+}
+
+// Cloud files whose content isn't on the disk, as OneDrive leaves them until they're opened
+fn placeholder_attributes(attributes: u32) -> bool {
+    attributes & (FILE_ATTRIBUTE_OFFLINE | FILE_ATTRIBUTE_RECALL_ON_OPEN | FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS) != 0
+}
+
+// The name of an alternate data stream from what FindFirstStreamW tells, e.g. Zone.Identifier for
+// ":Zone.Identifier:$DATA", none for "::$DATA" which is the content of the file
+#[cfg(any(windows, test))]
+fn stream_name(raw: &str) -> Option<&str> {
+    let name = raw.strip_prefix(':')?.strip_suffix(":$DATA")?;
+    (!name.is_empty()).then_some(name)
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::ffi::c_void;
+    use std::fs::{self, Metadata};
+    use std::os::windows::ffi::OsStrExt;
+    use std::os::windows::fs::MetadataExt;
+    use std::path::Path;
+    use std::ptr;
+
+    use windows_sys::Win32::Foundation::{CloseHandle, GetLastError, INVALID_HANDLE_VALUE, NO_ERROR};
+    use windows_sys::Win32::Storage::FileSystem::{
+        CreateFileW, FindClose, FindFirstFileW, FindFirstStreamW, FindNextStreamW, FindStreamInfoStandard, GetCompressedFileSizeW,
+        GetFileInformationByHandle, BY_HANDLE_FILE_INFORMATION, FILE_FLAG_BACKUP_SEMANTICS, FILE_SHARE_DELETE, FILE_SHARE_READ,
+        FILE_SHARE_WRITE, INVALID_FILE_SIZE, OPEN_EXISTING, WIN32_FIND_DATAW, WIN32_FIND_STREAM_DATA,
+    };
+
+    use super::{long_path, stream_name};
+
+    const FILE_ATTRIBUTE_REPARSE_POINT: u32 = 0x400;
+
+    fn wide(path: &Path) -> Vec<u16> {
+        long_path(path).as_os_str().encode_wide().chain(std::iter::once(0)).collect()
+    }
+
+    pub(super) fn attributes(metadata: &Metadata) -> u32 {
+        metadata.file_attributes()
+    }
+
+    pub(super) fn reparse_tag(path: &Path) -> Option<u32> {
+        let attributes = fs::symlink_metadata(path).ok()?.file_attributes();
+        if attributes & FILE_ATTRIBUTE_REPARSE_POINT == 0 {
+            return None;
+        }
+        let wide = wide(path);
+        // SAFETY: wide is nul terminated and data outlives the call, the handle is closed right after
+        unsafe {
+            let mut data: WIN32_FIND_DATAW = std::mem::zeroed();
+            let handle = FindFirstFileW(wide.as_ptr(), &mut data);
+            if handle == INVALID_HANDLE_VALUE {
+                return None;
+            }
+            FindClose(handle);
+            // The reserved field holds the tag of reparse points
+            Some(data.dwReserved0)
+        }
+    }
+
+    pub(super) fn file_id(path: &Path) -> Option<(u64, u64)> {
+        let wide = wide(path);
+        // SAFETY: wide is nul terminated and info outlives the call, the handle is closed right after.
+        // Backup semantics are what lets CreateFileW open directories
+        unsafe {
+            let handle = CreateFileW(wide.as_ptr(), 0, FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE, ptr::null(), OPEN_EXISTING, FILE_FLAG_BACKUP_SEMANTICS, 0);
+            if handle == INVALID_HANDLE_VALUE {
+                return None;
+            }
+            let mut info: BY_HANDLE_FILE_INFORMATION = std::mem::zeroed();
+            let read = GetFileInformationByHandle(handle, &mut info);
+            CloseHandle(handle);
+            (read != 0).then(|| (u64::from(info.dwVolumeSerialNumber), u64::from(info.nFileIndexHigh) << 32 | u64::from(info.nFileIndexLow)))
+        }
+    }
+
+    pub(super) fn compressed_size(path: &Path) -> Option<u64> {
+        let wide = wide(path);
+        let mut high: u32 = 0;
+        // SAFETY: wide is nul terminated and high outlives the call
+        let low = unsafe { GetCompressedFileSizeW(wide.as_ptr(), &mut high) };
+        // INVALID_FILE_SIZE is also a valid low word, only the last error tells them apart
+        if low == INVALID_FILE_SIZE && unsafe { GetLastError() } != NO_ERROR {
+            return None;
+        }
+        Some(u64::from(high) << 32 | u64::from(low))
+    }
+
+    pub(super) fn streams(path: &Path) -> Vec<(String, u64)> {
+        let wide = wide(path);
+        let mut streams = Vec::new();
+        // SAFETY: wide is nul terminated and data outlives the calls, the handle is closed right after
+        unsafe {
+            let mut data: WIN32_FIND_STREAM_DATA = std::mem::zeroed();
+            let handle = FindFirstStreamW(wide.as_ptr(), FindStreamInfoStandard, &mut data as *mut _ as *mut c_void, 0);
+            if handle == INVALID_HANDLE_VALUE {
+                return streams;
+            }
+            loop {
+                let length = data.cStreamName.iter().position(|&c| c == 0).unwrap_or(data.cStreamName.len());
+                let raw = String::from_utf16_lossy(&data.cStreamName[..length]);
+                if let Some(name) = stream_name(&raw) {
+                    streams.push((name.to_string(), data.StreamSize as u64));
+                }
+                if FindNextStreamW(handle, &mut data as *mut _ as *mut c_void) == 0 {
+                    break;
+                }
+            }
+            FindClose(handle);
+        }
+        streams
+    }
+}
+
+// The path the Win32 calls take, in the \\?\ form past MAX_PATH
+#[cfg(windows)]
+pub(crate) fn long_path(path: &Path) -> Cow<'_, Path> {
+    match path.to_str().and_then(verbatim) {
+        Some(verbatim) => Cow::Owned(verbatim.into()),
+        None => Cow::Borrowed(path),
+    }
+}
+
+#[cfg(windows)]
+fn attributes(metadata: &Metadata) -> u32 {
+    sys::attributes(metadata)
+}
+
+#[cfg(not(windows))]
+fn attributes(_metadata: &Metadata) -> u32 {
+    0
+}
+
+#[cfg(windows)]
+fn reparse_tag(path: &Path) -> Option<u32> {
+    sys::reparse_tag(path)
+}
+
+#[cfg(not(windows))]
+fn reparse_tag(_path: &Path) -> Option<u32> {
+    None
+}
+
+// Whether the content of the file is elsewhere, its length then isn't what it takes on the disk
+pub(crate) fn is_placeholder(metadata: &Metadata) -> bool {
+    placeholder_attributes(attributes(metadata))
+}
+
+// Junctions and volume mount points, unlike symlinks they mostly point to folders walked already,
+// e.g. "Application Data" to AppData\Roaming
+pub(crate) fn is_junction(path: &Path) -> bool {
+    reparse_tag(path) == Some(IO_REPARSE_TAG_MOUNT_POINT)
+}
+
+// The (volume serial, file index) pair identifying a file whatever the path it's reached from,
+// std doesn't tell them on Windows
+#[cfg(windows)]
+pub(crate) fn file_id(path: &Path) -> Option<(u64, u64)> {
+    sys::file_id(path)
+}
+
+#[cfg(not(windows))]
+pub(crate) fn file_id(_path: &Path) -> Option<(u64, u64)> {
+    None
+}
+
+// The space the file takes once compressed or sparse, none when Windows can't tell
+#[cfg(windows)]
+pub(crate) fn compressed_size(path: &Path) -> Option<u64> {
+    sys::compressed_size(path)
+}
+
+// The alternate data streams of a file with their length and the space they take, e.g. the
+// Zone.Identifier browsers tag downloads with, or whatever was hidden there
+#[cfg(windows)]
+pub(crate) fn alternate_streams(path: &Path) -> Vec<(String, u64, u64)> {
+    let display = path.display();
+    sys::streams(path).into_iter()
+        .map(|(name, length)| {
+            let allocated = sys::compressed_size(Path::new(&format!("{}:{}", display, name))).unwrap_or(length);
+            (name, length, allocated)
+        })
+        .collect()
+}
+
+#[cfg(not(windows))]
+pub(crate) fn alternate_streams(_path: &Path) -> Vec<(String, u64, u64)> {
+    Vec::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_long_paths() {
+        let long = format!("C:/Users/me/{}", "deep\\".repeat(60));
+        assert_eq!(verbatim(&long), Some(format!("\\\\?\\C:\\Users\\me\\{}", "deep\\".repeat(60))));
+        let share = format!("\\\\nas\\share\\{}", "deep\\".repeat(60));
+        assert_eq!(verbatim(&share), Some(format!("\\\\?\\UNC\\nas\\share\\{}", "deep\\".repeat(60))));
+        assert_eq!(verbatim("C:\\short"), None);
+        assert_eq!(verbatim(&format!("relative\\{}", "deep\\".repeat(60))), None);
+
+        assert_eq!(display_path("\\\\?\\C:\\Users"), "C:\\Users");
+        assert_eq!(display_path("\\\\?\\UNC\\nas\\share\\x"), "\\\\nas\\share\\x");
+        assert_eq!(display_path("\\\\?\\Volume{1234}\\x"), "\\\\?\\Volume{1234}\\x");
+        assert_eq!(display_path("/home/me"), "/home/me");
+    }
+
+    #[test]
+    fn tells_placeholders_apart() {
+        // A OneDrive file only in the cloud, one kept on the device, and a plain archived file
+        assert!(placeholder_attributes(0x0040_0020));
+        assert!(!placeholder_attributes(0x0008_0020));
+        assert!(!placeholder_attributes(0x20));
+        assert!(placeholder_attributes(FILE_ATTRIBUTE_OFFLINE));
+    }
+
+    #[test]
+    fn reads_stream_names() {
+        assert_eq!(stream_name(":Zone.Identifier:$DATA"), Some("Zone.Identifier"));
+        assert_eq!(stream_name(":com.dropbox.attrs:$DATA"), Some("com.dropbox.attrs"));
+        assert_eq!(stream_name("::$DATA"), None);
+        assert_eq!(stream_name(":$I30:$INDEX_ALLOCATION"), None);
+    }
+}
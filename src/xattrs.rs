@@ -0,0 +1,125 @@
+// Extended attributes, the metadata filesystems keep beside the content: tags, download origins,
+// Finder info, SELinux labels. They mostly take a few bytes, but some tools keep whole thumbnails
+// or checksums there and it adds up over millions of files. Linux and macOS list them the same way
+// but for the flag saying not to follow symlinks
+use std::path::Path;
+
+use crate::FileEntry;
+
+// Namespaces the system keeps for itself, labels and ACLs rather than what users or apps tagged
+const SYSTEM_NAMESPACES: [&str; 3] = ["system.", "security.", "trusted."];
+
+// The names of a listxattr buffer, each ends with a nul
+fn parse_names(buffer: &[u8]) -> Vec<String> {
+    buffer.split(|byte| *byte == 0).filter(|name| !name.is_empty()).map(|name| String::from_utf8_lossy(name).into_owned()).collect()
+}
+
+// Whether the attribute is worth showing, user.* tags, com.apple.* ones on macOS
+fn is_notable(name: &str) -> bool {
+    !SYSTEM_NAMESPACES.iter().any(|namespace| name.starts_with(namespace))
+}
+
+// What the attributes take, their names and values, the filesystems store both
+fn storage(attributes: &[(String, u64)]) -> u64 {
+    attributes.iter().map(|(name, length)| name.len() as u64 + length).sum()
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use std::ffi::CStr;
+
+    pub(super) fn list(path: &CStr, buffer: &mut [u8]) -> isize {
+        // SAFETY: path is nul terminated and the buffer is as long as told
+        unsafe { libc::llistxattr(path.as_ptr(), buffer.as_mut_ptr().cast(), buffer.len()) }
+    }
+
+    pub(super) fn value_length(path: &CStr, name: &CStr) -> isize {
+        // SAFETY: both strings are nul terminated, a null buffer only asks for the length
+        unsafe { libc::lgetxattr(path.as_ptr(), name.as_ptr(), std::ptr::null_mut(), 0) }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod sys {
+    use std::ffi::CStr;
+
+    pub(super) fn list(path: &CStr, buffer: &mut [u8]) -> isize {
+        // SAFETY: path is nul terminated and the buffer is as long as told
+        unsafe { libc::listxattr(path.as_ptr(), buffer.as_mut_ptr().cast(), buffer.len(), libc::XATTR_NOFOLLOW) }
+    }
+
+    pub(super) fn value_length(path: &CStr, name: &CStr) -> isize {
+        // SAFETY: both strings are nul terminated, a null buffer only asks for the length
+        unsafe { libc::getxattr(path.as_ptr(), name.as_ptr(), std::ptr::null_mut(), 0, 0, libc::XATTR_NOFOLLOW) }
+    }
+}
+
+// The attributes of the path with the length of their values, none when it has none or they
+// can't be read
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn attributes(path: &Path) -> Vec<(String, u64)> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let Ok(c_path) = CString::new(path.as_os_str().as_bytes()) else { return Vec::new() };
+    // Asked for the length first, the list may grow in between so it's asked again when too short
+    let mut buffer = Vec::new();
+    loop {
+        let length = sys::list(&c_path, &mut []);
+        if length <= 0 {
+            return Vec::new();
+        }
+        buffer.resize(length as usize, 0);
+        let read = sys::list(&c_path, &mut buffer);
+        if read >= 0 {
+            buffer.truncate(read as usize);
+            break;
+        }
+        if std::io::Error::last_os_error().raw_os_error() != Some(libc::ERANGE) {
+            return Vec::new();
+        }
+    }
+    parse_names(&buffer).into_iter()
+        .filter_map(|name| {
+            let c_name = CString::new(name.as_bytes()).ok()?;
+            let length = u64::try_from(sys::value_length(&c_path, &c_name)).ok()?;
+            Some((name, length))
+        })
+        .collect()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn attributes(_path: &Path) -> Vec<(String, u64)> {
+    Vec::new()
+}
+
+// The bytes the extended attributes of the path take
+pub(crate) fn attributes_size(path: &Path) -> u64 {
+    storage(&attributes(path))
+}
+
+// Fill in the notable attributes of the entries shown, comma separated, the entries without any
+// are left without
+pub fn list_entries(entries: &mut [FileEntry]) {
+    for entry in entries.iter_mut() {
+        let names: Vec<String> = attributes(Path::new(&entry.path)).into_iter().map(|(name, _)| name).filter(|name| is_notable(name)).collect();
+        entry.xattrs = (!names.is_empty()).then(|| names.join(", "));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_attribute_names() {
+        let names = parse_names(b"user.xdg.origin.url\0security.selinux\0user.checksum.sha256\0");
+        assert_eq!(names, ["user.xdg.origin.url", "security.selinux", "user.checksum.sha256"]);
+        let notable: Vec<&String> = names.iter().filter(|name| is_notable(name)).collect();
+        assert_eq!(notable, ["user.xdg.origin.url", "user.checksum.sha256"]);
+        assert!(is_notable("com.apple.metadata:_kMDItemUserTags"));
+        assert_eq!(parse_names(b""), Vec::<String>::new());
+
+        assert_eq!(storage(&[(String::from("user.tag"), 4), (String::from("user.origin"), 120)]), 8 + 4 + 11 + 120);
+    }
+}